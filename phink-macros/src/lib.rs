@@ -0,0 +1,200 @@
+//! Assertions and attributes for the properties of ink! contracts fuzzed by
+//! Phink. Depend on it behind the `phink` feature of the contract:
+//!
+//! ```toml
+//! [dependencies]
+//! phink = { package = "phink-macros", git = "https://github.com/kevin-valerio/phink", optional = true }
+//!
+//! [features]
+//! phink = ["dep:phink"]
+//! ```
+//!
+//! The assertions panic with a single-line JSON violation, prefixed with
+//! `VIOLATION_PREFIX`, which Phink parses out of the debug buffer of the
+//! contract to display the condition and the values that broke it.
+//!
+//! The attributes are expanded by `phink instrument` in the forked contract,
+//! before `#[ink::contract]` sees them, since an attribute within the module
+//! of a contract can't add messages to it. Compiled outside of Phink, they
+//! leave their item untouched.
+
+use proc_macro::TokenStream;
+use quote::{
+    quote,
+    ToTokens,
+};
+use syn::{
+    parse::{
+        Parse,
+        ParseStream,
+    },
+    parse_macro_input,
+    Expr,
+    Token,
+};
+
+/// Start of the panic message of a violated `phink_assert!` or
+/// `phink_assert_eq_dbg!`
+const VIOLATION_PREFIX: &str = "phink-violation: ";
+
+/// `condition` or `left, right`, then an optional message with its arguments
+struct Assertion {
+    exprs: Vec<Expr>,
+    message: Option<proc_macro2::TokenStream>,
+}
+
+impl Assertion {
+    fn parse_with(input: ParseStream, operands: usize) -> syn::Result<Self> {
+        let mut exprs = Vec::new();
+        for i in 0..operands {
+            if i > 0 {
+                input.parse::<Token![,]>()?;
+            }
+            exprs.push(input.parse()?);
+        }
+        let message =
+            if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+                Some(input.parse()?)
+            } else {
+                None
+            };
+        Ok(Self { exprs, message })
+    }
+
+    /// `, "message": "<formatted message>"` if there's one
+    fn message(&self) -> (String, proc_macro2::TokenStream) {
+        match &self.message {
+            Some(message) => {
+                (
+                    ",\"message\":{:?}".into(),
+                    quote! { , ink::prelude::format!(#message) },
+                )
+            }
+            None => (String::new(), quote! {}),
+        }
+    }
+}
+
+struct Condition(Assertion);
+
+impl Parse for Condition {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Assertion::parse_with(input, 1).map(Self)
+    }
+}
+
+struct Equality(Assertion);
+
+impl Parse for Equality {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Assertion::parse_with(input, 2).map(Self)
+    }
+}
+
+/// JSON string literal of `text`, escaped at compile time
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '{' => escaped.push_str("{{"),
+            '}' => escaped.push_str("}}"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Like `assert!`, with a violation Phink can parse:
+///
+/// ```ignore
+/// phink_assert!(self.total_supply >= self.balance_of(owner), "supply of {}", owner);
+/// ```
+#[proc_macro]
+pub fn phink_assert(input: TokenStream) -> TokenStream {
+    let Condition(assertion) = parse_macro_input!(input as Condition);
+    let condition = &assertion.exprs[0];
+    let (message_field, message) = assertion.message();
+    let format = format!(
+        "{}{{{{\"kind\":\"assert\",\"condition\":{}{}}}}}",
+        VIOLATION_PREFIX,
+        json_string(&condition.to_token_stream().to_string()),
+        message_field
+    );
+    quote! {
+        if !(#condition) {
+            panic!(#format #message);
+        }
+    }
+    .into()
+}
+
+/// Like `assert_eq!`, with a violation Phink can parse, carrying the `Debug`
+/// of both values:
+///
+/// ```ignore
+/// phink_assert_eq_dbg!(self.total_supply, sum_of_balances);
+/// ```
+#[proc_macro]
+pub fn phink_assert_eq_dbg(input: TokenStream) -> TokenStream {
+    let Equality(assertion) = parse_macro_input!(input as Equality);
+    let (left, right) = (&assertion.exprs[0], &assertion.exprs[1]);
+    let (message_field, message) = assertion.message();
+    let condition = format!("{} == {}", left.to_token_stream(), right.to_token_stream());
+    let format = format!(
+        "{}{{{{\"kind\":\"assert_eq\",\"condition\":{},\"left\":{{:?}},\"right\":{{:?}}{}}}}}",
+        VIOLATION_PREFIX,
+        json_string(&condition),
+        message_field
+    );
+    quote! {
+        match (&(#left), &(#right)) {
+            (left, right) => {
+                if !(*left == *right) {
+                    panic!(
+                        #format,
+                        ink::prelude::format!("{:?}", left),
+                        ink::prelude::format!("{:?}", right)
+                        #message
+                    );
+                }
+            }
+        }
+    }
+    .into()
+}
+
+/// Marks an `impl` block of the contract whose methods are all invariants.
+/// `phink instrument` makes it an `#[ink(impl)]` block behind the `phink`
+/// feature, and each of its methods a public `#[ink(message)]` prefixed with
+/// `phink_`:
+///
+/// ```ignore
+/// #[cfg_attr(feature = "phink", phink::invariant)]
+/// impl DomainNameService {
+///     fn dangerous_number_is_never_set(&self) {
+///         phink_assert!(self.dangerous_number != 69);
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn invariant(_: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+/// Precondition of a message, expanded by `phink instrument`: the message
+/// reverts if it doesn't hold
+#[proc_macro_attribute]
+pub fn requires(_: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+/// Postcondition of a message, expanded by `phink instrument`: `result` is
+/// the value returned by the message, and a violation is a finding
+#[proc_macro_attribute]
+pub fn ensures(_: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
@@ -0,0 +1,243 @@
+use crate::{
+    cli::{
+        config::Configuration,
+        triage::Classification,
+    },
+    instrumenter::instrumentation::{
+        ContractBuilder,
+        ContractInstrumenter,
+        Instrumenter,
+    },
+};
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        self,
+        Command,
+    },
+};
+use tracing::{
+    info,
+    warn,
+};
+
+/// Under `output_dir`, one campaign directory per tested commit
+pub const BISECT_DIR: &str = "bisect";
+
+/// First commit raising the finding
+pub struct Culprit {
+    pub commit: String,
+    /// Subject line of the commit
+    pub subject: String,
+    /// Description of the finding, e.g. `invariant `phink_assert_x` violated`
+    pub finding: String,
+}
+
+/// Finds the commit of the contract history where a seed starts raising its
+/// finding: each tested commit is checked out into a worktree of its own,
+/// instrumented, built, and the seed replayed with `phink execute`
+pub struct Bisector {
+    config: Configuration,
+    /// Arguments given to `phink` before the `execute` command, e.g.
+    /// `--config`
+    global_args: Vec<String>,
+    seed: PathBuf,
+    repo: PathBuf,
+    /// Root directory of the contract, relative to `repo`
+    contract_dir: PathBuf,
+    /// Last commit known not to raise the finding, if any
+    good: Option<String>,
+    bad: String,
+}
+
+impl Bisector {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        config: Configuration,
+        global_args: Vec<String>,
+        seed: PathBuf,
+        repo: PathBuf,
+        contract_dir: PathBuf,
+        good: Option<String>,
+        bad: String,
+    ) -> Self {
+        Self {
+            config,
+            global_args,
+            seed,
+            repo,
+            contract_dir,
+            good,
+            bad,
+        }
+    }
+
+    pub fn run(&self) -> Result<Culprit, String> {
+        let seed = fs::canonicalize(&self.seed)
+            .map_err(|e| format!("🙅 Can't find {}: {}", self.seed.display(), e))?;
+        let range = match &self.good {
+            Some(good) => format!("{}..{}", good, self.bad),
+            None => self.bad.clone(),
+        };
+        let mut commits: Vec<String> =
+            git(&self.repo, &["rev-list", "--reverse", &range])?
+                .lines()
+                .map(String::from)
+                .collect();
+        let bad = commits
+            .last()
+            .cloned()
+            .ok_or_else(|| format!("🙅 No commit in `{}`", range))?;
+
+        let worktree = env::temp_dir().join(format!("phink_bisect_{}", process::id()));
+        let worktree_arg = worktree.display().to_string();
+        git(
+            &self.repo,
+            &["worktree", "add", "--detach", &worktree_arg, &bad],
+        )?;
+        let culprit = self.search(&worktree, &seed, &mut commits);
+        let _ = git(
+            &self.repo,
+            &["worktree", "remove", "--force", &worktree_arg],
+        );
+        culprit
+    }
+
+    fn search(
+        &self,
+        worktree: &Path,
+        seed: &Path,
+        commits: &mut Vec<String>,
+    ) -> Result<Culprit, String> {
+        let bad = commits.last().cloned().unwrap_or_default();
+        let finding = match self.replay(worktree, seed, &bad)? {
+            Classification::Bug { description, .. } => description,
+            other => {
+                return Err(format!(
+                    "🙅 The seed doesn't raise any finding at {}: {}",
+                    bad,
+                    other.label()
+                ))
+            }
+        };
+
+        let first = first_breaking(commits, |commit| {
+            match self.replay(worktree, seed, commit) {
+                Ok(Classification::Bug { description, .. }) => {
+                    Some(description == finding)
+                }
+                Ok(Classification::HarnessFailure(reason)) => {
+                    warn!("⚠️ Skipping {}, the harness failed: {}", commit, reason);
+                    None
+                }
+                Ok(_) => Some(false),
+                Err(e) => {
+                    warn!("⚠️ Skipping {}: {}", commit, e);
+                    None
+                }
+            }
+        });
+        let commit = commits[first].clone();
+        Ok(Culprit {
+            subject: git(&self.repo, &["log", "-1", "--format=%s", &commit])?,
+            commit,
+            finding,
+        })
+    }
+
+    /// Replay the seed on the contract as of `commit`
+    fn replay(
+        &self,
+        worktree: &Path,
+        seed: &Path,
+        commit: &str,
+    ) -> Result<Classification, String> {
+        info!("🔎 Testing {}", commit);
+        git(worktree, &["checkout", "--force", "--detach", commit])?;
+
+        let sources = worktree.join(&self.contract_dir);
+        let mut instrumenter = Instrumenter::new(sources.clone());
+        let built = instrumenter
+            .instrument()
+            .and_then(|instrumenter| instrumenter.build());
+        let classification = built.map(|_| {
+            Classification::replay(
+                &self.global_args,
+                seed,
+                &instrumenter.contract_dir,
+                &self.config.output_dir().join(BISECT_DIR).join(commit),
+            )
+        });
+        // Only the fork, if the instrumentation got that far
+        if instrumenter.contract_dir != sources {
+            let _ = fs::remove_dir_all(&instrumenter.contract_dir);
+        }
+        classification
+    }
+}
+
+/// Index of the first of `commits` for which `breaks` holds, the last one
+/// breaking. The commits `breaks` can't test, returning `None`, are left out.
+fn first_breaking(
+    commits: &mut Vec<String>,
+    mut breaks: impl FnMut(&str) -> Option<bool>,
+) -> usize {
+    // Every commit before `low` passes, the one at `high` breaks
+    let (mut low, mut high) = (0, commits.len() - 1);
+    while low < high {
+        let mid = (low + high) / 2;
+        match breaks(&commits[mid]) {
+            Some(true) => high = mid,
+            Some(false) => low = mid + 1,
+            None => {
+                commits.remove(mid);
+                high -= 1;
+            }
+        }
+    }
+    high
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("🙅 Can't run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "🙅 `git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_breaking_commit_skips_the_untestable_ones() {
+        let mut commits: Vec<String> = ["a", "b", "c", "d", "e", "f"]
+            .iter()
+            .map(|commit| commit.to_string())
+            .collect();
+        let first = first_breaking(&mut commits, |commit| {
+            match commit {
+                "c" => None,
+                "a" | "b" => Some(false),
+                _ => Some(true),
+            }
+        });
+        assert_eq!(commits[first], "d");
+        assert!(!commits.contains(&"c".to_string()));
+    }
+}
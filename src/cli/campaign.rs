@@ -0,0 +1,320 @@
+use crate::{
+    cli::{
+        dashboard::{
+            AflWorker,
+            Dashboard,
+        },
+        output::CoverageSummary,
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::{
+        finding::{
+            Finding,
+            FindingsDb,
+            FINDINGS_DIR,
+            FINDING_METADATA,
+            FINDING_SEED,
+        },
+        fuzz::CORPUS_DIR,
+        parser::parse_selectors,
+        stats::{
+            RetiredStats,
+            StatsTracker,
+        },
+    },
+};
+use prettytable::{
+    Cell,
+    Row,
+    Table,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashSet,
+    },
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Share of the corpus exercising one message
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SelectorShare {
+    pub label: String,
+    /// Inputs calling the message at least once
+    pub inputs: usize,
+    pub calls: usize,
+}
+
+/// Finding whose trace is the shortest or the longest to replay
+#[derive(Debug, PartialEq, Eq)]
+pub struct FindingLength {
+    pub id: String,
+    pub messages: usize,
+    pub seed: PathBuf,
+}
+
+/// Summary of a past or running campaign, only read from the files of its
+/// `output_dir` and of AFL++
+#[derive(Debug)]
+pub struct CampaignStats {
+    pub output_dir: PathBuf,
+    pub total_execs: u64,
+    /// `None` without the instrumented contract, the instrumented points
+    /// being counted in its sources
+    pub coverage: Option<CoverageSummary>,
+    pub unique_findings: usize,
+    pub corpus_size: usize,
+    pub selectors: Vec<SelectorShare>,
+    pub shortest: Option<FindingLength>,
+    pub longest: Option<FindingLength>,
+    /// Identifiers of the findings of each swarm, with `swarm`
+    pub swarms: BTreeMap<String, Vec<String>>,
+}
+
+impl CampaignStats {
+    pub fn collect(ziggy: &ZiggyConfig) -> Self {
+        let output_dir = ziggy.config.output_dir();
+        let afl_dir = ziggy.afl_dir();
+
+        let workers: Vec<AflWorker> = Self::subdirs(&afl_dir)
+            .filter_map(|dir| {
+                let stats = fs::read_to_string(dir.join("fuzzer_stats")).ok()?;
+                Some(AflWorker::parse(
+                    &dir.file_name()?.to_string_lossy(),
+                    &stats,
+                ))
+            })
+            .collect();
+        // Without AFL++, e.g. after `phink run`, only our own stats are left
+        let total_execs = if workers.is_empty() {
+            StatsTracker::all(&output_dir)
+                .into_iter()
+                .map(|(_, _, stats)| stats.execs)
+                .sum::<u64>()
+                + RetiredStats::load(&output_dir).execs
+        } else {
+            workers.iter().map(|worker| worker.execs_done).sum()
+        };
+
+        // Every instance syncs the queue of the others, hence the deduplication
+        let mut inputs: HashSet<Vec<u8>> = Self::files(&output_dir.join(CORPUS_DIR))
+            .chain(
+                Self::subdirs(&afl_dir).flat_map(|dir| Self::files(&dir.join("queue"))),
+            )
+            .filter_map(|path| fs::read(path).ok())
+            .collect();
+        inputs.retain(|input| !input.is_empty());
+
+        let coverage = CoverageSummary::new(ziggy);
+        let findings = Self::findings(&output_dir);
+        let length = |finding: &Finding| {
+            FindingLength {
+                id: finding.reference().to_string(),
+                messages: finding.messages.len(),
+                seed: finding.path(&output_dir).join(FINDING_SEED),
+            }
+        };
+
+        let mut swarms: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for finding in &findings {
+            if let Some(swarm) = &finding.swarm {
+                swarms
+                    .entry(swarm.clone())
+                    .or_default()
+                    .push(finding.reference().to_string());
+            }
+        }
+
+        Self {
+            total_execs,
+            coverage: (coverage.instrumented_points > 0).then_some(coverage),
+            unique_findings: FindingsDb::load(&output_dir).findings.len(),
+            corpus_size: inputs.len(),
+            selectors: Self::selectors(ziggy, &inputs),
+            shortest: findings
+                .iter()
+                .min_by_key(|finding| finding.messages.len())
+                .map(length),
+            longest: findings
+                .iter()
+                .max_by_key(|finding| finding.messages.len())
+                .map(length),
+            swarms,
+            output_dir,
+        }
+    }
+
+    pub fn display(&self) {
+        println!("📈 Campaign of {}\n", self.output_dir.display());
+
+        let mut table = Table::new();
+        let mut row = |name: &str, value: String| {
+            table.add_row(Row::new(vec![Cell::new(name), Cell::new(&value)]));
+        };
+        row("Total execs", self.total_execs.to_string());
+        row(
+            "Coverage",
+            match &self.coverage {
+                Some(coverage) => {
+                    format!(
+                        "{:.1}% ({} / {} points)",
+                        coverage.coverage_percent,
+                        coverage.covered_points,
+                        coverage.instrumented_points
+                    )
+                }
+                None => "unknown, pass the instrumented contract".to_string(),
+            },
+        );
+        row("Unique findings", self.unique_findings.to_string());
+        row("Corpus", format!("{} inputs", self.corpus_size));
+        for (name, finding) in [
+            ("Shortest reproducer", &self.shortest),
+            ("Longest reproducer", &self.longest),
+        ] {
+            if let Some(finding) = finding {
+                row(
+                    name,
+                    format!(
+                        "{} ({} messages), {}",
+                        finding.id,
+                        finding.messages,
+                        finding.seed.display()
+                    ),
+                );
+            }
+        }
+        table.printstd();
+
+        if !self.swarms.is_empty() {
+            println!("\n🐝 Findings per swarm");
+            let mut table = Table::new();
+            table.add_row(Row::new(vec![Cell::new("Swarm"), Cell::new("Findings")]));
+            for (swarm, ids) in &self.swarms {
+                table.add_row(Row::new(vec![
+                    Cell::new(swarm),
+                    Cell::new(&ids.join(", ")),
+                ]));
+            }
+            table.printstd();
+        }
+
+        if self.selectors.is_empty() {
+            return;
+        }
+        println!("\n🧬 Corpus composition");
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Message"),
+            Cell::new("Inputs"),
+            Cell::new("Calls"),
+        ]));
+        for share in &self.selectors {
+            table.add_row(Row::new(vec![
+                Cell::new(&share.label),
+                Cell::new(&format!(
+                    "{} ({:.1}%)",
+                    share.inputs,
+                    share.inputs as f64 * 100.0 / self.corpus_size.max(1) as f64
+                )),
+                Cell::new(&share.calls.to_string()),
+            ]));
+        }
+        table.printstd();
+    }
+
+    fn selectors(ziggy: &ZiggyConfig, inputs: &HashSet<Vec<u8>>) -> Vec<SelectorShare> {
+        let labels = Dashboard::labels(&ziggy.contract_path);
+        let mut shares: BTreeMap<String, SelectorShare> = BTreeMap::new();
+
+        for input in inputs {
+            let selectors = parse_selectors(input, &ziggy.config);
+            let unique: HashSet<&[u8; 4]> = selectors.iter().collect();
+            for selector in &selectors {
+                let selector = hex::encode(selector);
+                let share = shares.entry(selector.clone()).or_insert_with(|| {
+                    SelectorShare {
+                        label: labels.get(&selector).cloned().unwrap_or(selector),
+                        ..Default::default()
+                    }
+                });
+                share.calls += 1;
+            }
+            for selector in unique {
+                if let Some(share) = shares.get_mut(&hex::encode(selector)) {
+                    share.inputs += 1;
+                }
+            }
+        }
+
+        let mut shares: Vec<SelectorShare> = shares.into_values().collect();
+        shares.sort_by_key(|share| std::cmp::Reverse(share.inputs));
+        shares
+    }
+
+    fn findings(output_dir: &Path) -> Vec<Finding> {
+        Self::subdirs(&output_dir.join(FINDINGS_DIR))
+            .filter_map(|dir| fs::read_to_string(dir.join(FINDING_METADATA)).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect()
+    }
+
+    fn subdirs(dir: &Path) -> impl Iterator<Item = PathBuf> {
+        Self::entries(dir).filter(|path| path.is_dir())
+    }
+
+    fn files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+        Self::entries(dir).filter(|path| path.is_file())
+    }
+
+    fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::config::Configuration,
+        fuzzer::parser::DELIMITER,
+    };
+
+    #[test]
+    fn test_corpus_composition_by_selector() {
+        let ziggy = ZiggyConfig::new(Configuration::default(), PathBuf::new());
+        let transfer = [0u8, 0, 0, 0, 0x84, 0xa1, 0x5d, 0xa1, 1, 2];
+        let flip = [0u8, 0, 0, 0, 0x63, 0x3a, 0xa5, 0x51];
+        let inputs: HashSet<Vec<u8>> = [
+            [&transfer[..], &DELIMITER, &transfer, &DELIMITER, &flip].concat(),
+            transfer.to_vec(),
+        ]
+        .into_iter()
+        .collect();
+
+        let shares = CampaignStats::selectors(&ziggy, &inputs);
+        assert_eq!(
+            shares,
+            vec![
+                SelectorShare {
+                    label: "84a15da1".into(),
+                    inputs: 2,
+                    calls: 3,
+                },
+                SelectorShare {
+                    label: "633aa551".into(),
+                    inputs: 1,
+                    calls: 1,
+                },
+            ]
+        );
+    }
+}
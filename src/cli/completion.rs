@@ -0,0 +1,138 @@
+use crate::{
+    contract::payload::PayloadCrafter,
+    instrumenter::instrumentation::Instrumenter,
+};
+use clap::Command;
+use clap_complete::{
+    engine::CompletionCandidate,
+    env::{
+        Bash,
+        Elvish,
+        EnvCompleter,
+        Fish,
+        Powershell,
+        Zsh,
+    },
+    Shell,
+};
+use std::{
+    env,
+    ffi::{
+        OsStr,
+        OsString,
+    },
+    fs,
+    io,
+    path::PathBuf,
+};
+
+/// Environment variable under which the shell asks `phink` to complete its
+/// command line, see `clap_complete::CompleteEnv`
+pub const COMPLETE_ENV: &str = "COMPLETE";
+/// Options of `generate-seed` followed by a value, i.e. not by the contract
+const VALUED_OPTIONS: [&str; 6] = [
+    "--message",
+    "--value",
+    "--origin",
+    "--append",
+    "--config",
+    "--profile",
+];
+
+/// Print the completion script of `shell`. It calls `phink` back to complete
+/// each argument, so that the message names are read from the metadata of
+/// the contract on the command line.
+pub fn write_registration(
+    shell: Shell,
+    command: &Command,
+    out: &mut dyn io::Write,
+) -> io::Result<()> {
+    let name = command.get_name();
+    let completer = env::current_exe()?.display().to_string();
+    let registration: &dyn EnvCompleter = match shell {
+        Shell::Bash => &Bash,
+        Shell::Elvish => &Elvish,
+        Shell::Fish => &Fish,
+        Shell::PowerShell => &Powershell,
+        Shell::Zsh => &Zsh,
+        _ => return Err(io::Error::other(format!("🙅 Unsupported shell {}", shell))),
+    };
+    registration.write_registration(COMPLETE_ENV, name, name, &completer, out)
+}
+
+/// Complete `generate-seed --message` with the messages of the contract being
+/// completed, the invariants excepted, along with their selector
+pub fn complete_message(current: &OsStr) -> Vec<CompletionCandidate> {
+    let words: Vec<OsString> = env::args_os().collect();
+    let Some(specs) = Instrumenter::new(contract_path(&words))
+        .find()
+        .ok()
+        .and_then(|ink| fs::read_to_string(ink.specs_path).ok())
+    else {
+        return Vec::new();
+    };
+
+    let current = current.to_string_lossy();
+    PayloadCrafter::extract_messages(&specs)
+        .into_iter()
+        .filter(|(label, _)| {
+            !PayloadCrafter::is_invariant(label) && label.starts_with(current.as_ref())
+        })
+        .map(|(label, selector)| {
+            CompletionCandidate::new(label)
+                .help(Some(format!("0x{}", hex::encode(selector)).into()))
+        })
+        .collect()
+}
+
+/// Contract of the `generate-seed` being completed, i.e. its first positional
+/// argument, or the current directory if it isn't typed yet
+fn contract_path(words: &[OsString]) -> PathBuf {
+    let mut arguments = words
+        .iter()
+        .skip_while(|word| *word != "generate-seed")
+        .skip(1);
+    let mut in_args = false;
+    while let Some(word) = arguments.next() {
+        let word = word.to_string_lossy();
+        if VALUED_OPTIONS.contains(&word.as_ref()) {
+            in_args = false;
+            arguments.next();
+        } else if word.starts_with('-') {
+            // `--args` takes every value up to the next option
+            in_args = word == "--args";
+        } else if !in_args {
+            return PathBuf::from(word.as_ref());
+        }
+    }
+    PathBuf::from(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_path_is_the_first_positional_argument() {
+        let words = |line: &str| -> Vec<OsString> {
+            line.split_whitespace().map(OsString::from).collect()
+        };
+
+        assert_eq!(
+            contract_path(&words(
+                "phink -- phink generate-seed sample/dns --message re"
+            )),
+            PathBuf::from("sample/dns")
+        );
+        assert_eq!(
+            contract_path(&words(
+                "phink -- phink generate-seed --args 1 2 --origin 3 sample/dns --message"
+            )),
+            PathBuf::from("sample/dns")
+        );
+        assert_eq!(
+            contract_path(&words("phink -- phink generate-seed --message")),
+            PathBuf::from(".")
+        );
+    }
+}
@@ -4,13 +4,26 @@ use crate::{
         EnableOriginFuzzing,
     },
     contract::{
+        payload::{
+            PayloadCrafter,
+            DEFAULT_PHINK_PREFIX,
+        },
         remote::{
             BalanceOf,
             ContractBridge,
         },
         runtime::Runtime,
     },
-    fuzzer::fuzz::MAX_MESSAGES_PER_EXEC,
+    cover::{
+        coverage::DEFAULT_FLUSH_EVERY,
+        progress::DEFAULT_PROGRESS_INTERVAL_SECS,
+    },
+    fuzzer::{
+        checkpoint::DEFAULT_CHECKPOINT_INTERVAL_SECS,
+        events::DEFAULT_PLATEAU_SECS,
+        fuzz::MAX_MESSAGES_PER_EXEC,
+        stats::DEFAULT_STATS_INTERVAL_SECS,
+    },
 };
 use frame_support::weights::Weight;
 use serde_derive::{
@@ -19,11 +32,28 @@ use serde_derive::{
 };
 use sp_core::crypto::AccountId32;
 use std::{
+    collections::BTreeMap,
+    env,
     fs,
     path::PathBuf,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+/// Prefix of the environment variables overriding the top-level keys of the
+/// configuration file, e.g. `PHINK_CORES=4` for `cores`
+pub const ENV_PREFIX: &str = "PHINK_";
+/// Used by the CLI to hand the whole configuration over to the fuzzing child
+/// process, so it isn't an override
+pub const START_FUZZING_ENV: &str = "PHINK_START_FUZZING_WITH_CONFIG";
+pub const DEFAULT_OUTPUT_DIR: &str = "./output/phink";
+/// Names of the origins `1` to `6`, see `Configuration::name_of`. Unlike
+/// `//Alice` and the other dev keys, these accounts aren't derived from any
+/// seed: `CALLER_1` is `[1; 32]`, `CALLER_2` is `[2; 32]` and so on.
+pub const ORIGIN_NAMES: [&str; 6] = [
+    "CALLER_1", "CALLER_2", "CALLER_3", "CALLER_4", "CALLER_5", "CALLER_6",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct Configuration {
     /// Number of cores to use for Ziggy
     pub cores: Option<u8>,
@@ -38,13 +68,23 @@ pub struct Configuration {
     /// Fuzz the origin. If `false`, the fuzzer will execute each message with
     /// the same account.
     pub fuzz_origin: bool,
+    /// Accounts the fuzzed origin byte is mapped onto, instead of the
+    /// `[origin; 32]` accounts. Each of them is endowed in the genesis.
+    pub origins: Option<Vec<OriginAccount>>,
     /// The gas limit enforced when executing the constructor
     pub default_gas_limit: Option<Weight>,
     /// The maximum amount of balance that can be charged from the caller to
     /// pay for the storage consumed.
     pub storage_deposit_limit: Option<String>,
+    /// If `true`, each message comes with its own storage deposit limit,
+    /// picked by the fuzzer among `storage_deposit_limit`, no limit at all,
+    /// and tight limits. This adds one byte per message to the input. A
+    /// message exhausting its limit while it had none is reported.
+    pub fuzz_storage_deposit_limit: Option<bool>,
     /// The `value` being transferred to the new account during the contract
-    /// instantiation
+    /// instantiation, i.e the endowment required by some constructors. Can
+    /// also be written `instantiation_value`.
+    #[serde(alias = "instantiation_value")]
     pub instantiate_initial_value: Option<String>,
     /// In the case where you wouldn't have any default constructor in you
     /// smart contract, i.e `new()` (without parameters), then you would
@@ -53,6 +93,235 @@ pub struct Configuration {
     /// involved the four first bytes of the constructor' selector,
     /// followed by the payload.
     pub constructor_payload: Option<String>,
+    /// If set, any message returning an `Err(...)` (i.e. reverting) in more
+    /// than `err_ratio_threshold` percent of its executions is reported at the
+    /// end of the campaign, and by `phink stats`, summed over the stats of
+    /// every process. This helps spotting messages that are effectively
+    /// unreachable for the fuzzer, or permanently broken.
+    pub err_ratio_threshold: Option<u8>,
+    /// If `false`, `run` and `execute` won't stop at the first bug. Findings
+    /// are recorded to disk and the execution continues. When fuzzing, Phink
+    /// always halts on a bug, since this is how AFL spots crashes.
+    pub halt_on_bug: Option<bool>,
+    /// Print a single line per finding instead of the stacktrace, the decoded
+    /// trace and the `cargo contract` commands, e.g. to replay a large corpus
+    /// with `run --terse`
+    pub terse_findings: Option<bool>,
+    /// Labels of the only messages the fuzzer calls, all of them if not set.
+    /// The invariants are called whatever this list is.
+    pub allowed_messages: Option<Vec<String>>,
+    /// Labels of messages the fuzzer never calls, e.g. an admin-only
+    /// `terminate` that would make every following message revert
+    pub denied_messages: Option<Vec<String>>,
+    /// Invariants checked after each input, all of them if not set. Each
+    /// entry is a group of `invariant_groups` or a pattern over the name of
+    /// the invariant, with or without its `phink_` prefix, `*` matching any
+    /// characters, e.g. `["balance_*", "ownership"]`.
+    pub invariants: Option<Vec<String>>,
+    /// Named lists of patterns, as in `invariants`, to select several
+    /// invariants at once
+    pub invariant_groups: Option<BTreeMap<String, Vec<String>>>,
+    /// Built-in `no_arithmetic_panic` invariant: the arithmetic overflows of a
+    /// non-privileged caller are reported as breaking it, even without any
+    /// `phink_` invariant. Those of a privileged caller, see `is_privileged`,
+    /// are still reported as plain traps.
+    pub no_arithmetic_panic: Option<bool>,
+    /// Properties over the whole sequence of messages of an input, checked
+    /// along with the invariants
+    pub sequence_properties: Option<Vec<SequenceProperty>>,
+    /// Stop `fuzz` after about that many executions, spread over the `cores`
+    /// instances of AFL++. No limit if not set.
+    pub max_executions: Option<u64>,
+    /// Keep up to this many end-states of the inputs reaching new coverage,
+    /// and start the following inputs from one of them or from the genesis,
+    /// to explore states deeper than `max_messages_per_exec` messages.
+    /// Disabled if not set.
+    pub stateful_snapshots: Option<usize>,
+    /// Swarm testing: with several `cores`, each secondary AFL++ worker only
+    /// calls a random subset of the messages, of the origins and of the
+    /// values, the main one keeping everything. `phink stats` tells which
+    /// swarm found what.
+    pub swarm: Option<bool>,
+    /// Report the messages that ran without reverting as coverage, and run
+    /// the `rare` power schedule of AFL++, so that it keeps favoring inputs
+    /// calling the messages seldom reached rather than only the cheapest ones
+    pub schedule_rare_selectors: Option<bool>,
+    /// Append the account IDs and hashes returned or emitted by the contract,
+    /// which it also stores, to `harvested.dict`. AFL++ can't reload a
+    /// dictionary while running, so it's only given along with the dictionary
+    /// of selectors on the next start
+    pub harvest_storage_values: Option<bool>,
+    /// Let the inputs contain the `TRANSFER_SELECTOR` pseudo-message, which
+    /// transfers its value to the contract between two messages without
+    /// calling it, e.g. to exercise the invariants over
+    /// `self.env().balance()`
+    pub fuzz_transfers: Option<bool>,
+    /// Write the initial corpus as SCALE-encoded lists of `FuzzCall`s, i.e.
+    /// seeds of version 2, instead of messages separated by `********`. Both
+    /// are read whatever this says.
+    pub scale_inputs: Option<bool>,
+    /// Optional notifications, sent whenever a new unique bug is found
+    pub notify: Option<NotifyConfig>,
+    /// Share the corpus with other machines fuzzing the same contract
+    pub sync: Option<SyncConfig>,
+    /// Persist the coverage of every input while fuzzing, so that `coverage`
+    /// can be generated without running all the seeds first. This costs a
+    /// file write per execution, expect a noticeable drop of execs/sec.
+    pub save_coverage_during_fuzzing: Option<bool>,
+    /// Every `coverage_progress_interval_secs`, each fuzzer appends its
+    /// number of unique coverage points, corpus size and executions to
+    /// `coverage_progress.jsonl`. Set it to `0` to disable it.
+    pub coverage_progress_interval_secs: Option<u64>,
+    /// Append a `plateau` event to `events.jsonl` once the campaign didn't
+    /// reach any new edge for this many seconds, as read by `fuzz` from the
+    /// `fuzzer_stats` of AFL++. Set it to `0` to disable it.
+    pub plateau_after_secs: Option<u64>,
+    /// Don't call the invariants after an input that didn't change the state,
+    /// e.g. when every message failed or reverted. An invariant broken right
+    /// after the instantiation is then caught by the first input that mutates
+    /// the state. Off by default: it computes the state root before and after
+    /// each input, which only pays off when the invariants cost more.
+    pub skip_unchanged_invariants: Option<bool>,
+    /// Before reporting a violated invariant, remove the messages of the
+    /// sequence one at a time, replaying it from the genesis, and report the
+    /// smallest sequence still violating it. Its origins are then renamed to
+    /// `CALLER_1`, `CALLER_2` and so on, by order of appearance, if it still
+    /// does.
+    pub minimize_findings: Option<bool>,
+    /// The coverage is buffered in memory, and written to the coverage file
+    /// every `coverage_flush_every` inputs, when a bug is found, and once all
+    /// the seeds got executed
+    pub coverage_flush_every: Option<usize>,
+    /// Every `stats_interval_secs`, the execs/sec, ratio of rejected inputs,
+    /// messages per input, time spent in the invariants and calls of each
+    /// message are written to `stats/<pid>.json`. Set it to `0` to disable
+    /// it, `phink dashboard` then only shows what AFL++ reports. A file not
+    /// updated for three intervals is the one of an exited process: `fuzz`
+    /// sums it into `retired_stats.json` and removes it.
+    pub stats_interval_secs: Option<u64>,
+    /// Every `checkpoint_interval_secs`, each fuzzer process saves what it
+    /// only keeps in memory, e.g. its stats or the end-states of
+    /// `stateful_snapshots`, to `checkpoints/<worker>.json`. `fuzz --resume` restores
+    /// them. Set it to `0` to disable it.
+    pub checkpoint_interval_secs: Option<u64>,
+    /// Restore the checkpoints and let AFL++ resume its queue, set by `fuzz
+    /// --resume`
+    pub resume: Option<bool>,
+    /// Parameters of the embedded runtime, the defaults are used for any
+    /// missing one
+    pub runtime: Option<RuntimeConfig>,
+    /// Fuzz a contract already deployed on a live chain, starting from its
+    /// current state, instead of instantiating a new one
+    pub fork: Option<ForkConfig>,
+    /// Mocked functions of the chain extensions used by the contract, since
+    /// the embedded runtime doesn't provide any
+    pub chain_extension_mocks: Option<Vec<ChainExtensionMock>>,
+    /// Instrumented `.wasm` of the next version of the contract. If set, the
+    /// inputs can contain the `UPGRADE_SELECTOR` pseudo-message, which
+    /// switches the contract to this code in the middle of the sequence, just
+    /// like `set_code_hash` would. The invariants are then checked against
+    /// the migrated state, so both versions must expose the same messages.
+    pub upgrade_wasm_path: Option<PathBuf>,
+    /// `.wasm` of another build of the contract, e.g. before a patch. It is
+    /// instantiated next to the fuzzed one with the same constructor, and
+    /// receives the same messages: any difference in their results, events
+    /// or storage is reported as a divergence.
+    pub differential_wasm_path: Option<PathBuf>,
+    /// Storage snapshot merged into the genesis after the instantiation,
+    /// either a raw chain spec (e.g a chopsticks export) or a flat
+    /// `{ "0x<key>": "0x<value>" }` JSON of the contract's storage
+    pub storage_dump_path: Option<PathBuf>,
+    /// Directory of the campaign: corpus, dictionary, coverage, findings and
+    /// stats. Give each campaign its own to run several from the same
+    /// working directory. `./output/phink` by default.
+    pub output_dir: Option<PathBuf>,
+    /// Named sets of keys overriding the ones above, e.g. `[profile.ci]`,
+    /// selected with `--profile ci`. They are already merged once loaded,
+    /// hence not handed over to the fuzzing processes.
+    #[serde(default, skip_serializing)]
+    pub profile: Option<toml::Table>,
+}
+
+/// Once the message `after` succeeded, the message `then_fails` must fail or
+/// revert for the rest of the sequence, e.g. no `transfer` after a `pause`
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SequenceProperty {
+    pub after: String,
+    pub then_fails: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OriginAccount {
+    /// SS58 address of the account
+    pub address: AccountId32,
+    /// Name shown next to the account in the traces, e.g. `attacker`
+    pub role: Option<String>,
+    /// Deploy and instantiate the contract with this account, instead of
+    /// `deployer_address`. It also sends every message if `fuzz_origin` is
+    /// disabled.
+    pub deployer: Option<bool>,
+    /// Whether the account administrates the contract, whose arithmetic
+    /// panics don't break `no_arithmetic_panic`
+    pub privileged: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChainExtensionMock {
+    /// `(extension id << 16) | function id`, as called by ink!
+    pub id: u32,
+    /// Hex of the SCALE-encoded output returned to the contract
+    pub output: Option<String>,
+    /// If set, the output is `fuzzed_len` bytes taken from the input instead
+    /// of `output`, so that the fuzzer picks the answers
+    pub fuzzed_len: Option<usize>,
+    /// Status code returned to the contract, `0` by default
+    pub status: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ForkConfig {
+    /// HTTP(S) JSON-RPC endpoint of a node of the chain
+    pub rpc_url: String,
+    /// SS58 address of the deployed contract
+    pub contract_address: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeConfig {
+    /// Minimum balance an account must hold to exist
+    pub existential_deposit: Option<u64>,
+    /// Maximum size of the WASM blob, in bytes
+    pub max_code_len: Option<u32>,
+    /// Maximum number of 64KiB memory pages the contract can use
+    pub memory_pages: Option<u32>,
+    /// Storage deposit charged per byte, as a string like
+    /// `storage_deposit_limit`
+    pub deposit_per_byte: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// URL receiving a JSON `POST` for each new unique finding
+    pub webhook_url: Option<String>,
+    /// Minimum number of seconds between two notifications
+    pub min_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+    /// Directory accepting a `PUT` of each entry, and listing them on a `GET`
+    pub url: String,
+    /// Seconds between two syncs, `DEFAULT_SYNC_INTERVAL_SECS` by default
+    pub interval_secs: Option<u64>,
+    /// Value of the `Authorization` header, e.g. `Bearer ...`
+    pub authorization: Option<String>,
 }
 
 impl Default for Configuration {
@@ -61,13 +330,50 @@ impl Default for Configuration {
             cores: Some(1),
             use_honggfuzz: false,
             fuzz_origin: false,
+            origins: None,
             deployer_address: ContractBridge::DEFAULT_DEPLOYER.into(),
             max_messages_per_exec: MAX_MESSAGES_PER_EXEC.into(),
             report_path: Some(PathBuf::from("output/coverage_report")),
             default_gas_limit: Option::from(ContractBridge::DEFAULT_GAS_LIMIT),
             storage_deposit_limit: None,
+            fuzz_storage_deposit_limit: None,
             instantiate_initial_value: None,
             constructor_payload: None,
+            err_ratio_threshold: None,
+            halt_on_bug: Some(true),
+            terse_findings: Some(false),
+            allowed_messages: None,
+            denied_messages: None,
+            invariants: None,
+            invariant_groups: None,
+            no_arithmetic_panic: None,
+            sequence_properties: None,
+            max_executions: None,
+            stateful_snapshots: None,
+            swarm: None,
+            schedule_rare_selectors: None,
+            harvest_storage_values: None,
+            fuzz_transfers: None,
+            scale_inputs: None,
+            notify: None,
+            sync: None,
+            save_coverage_during_fuzzing: Some(false),
+            coverage_progress_interval_secs: Some(DEFAULT_PROGRESS_INTERVAL_SECS),
+            plateau_after_secs: Some(DEFAULT_PLATEAU_SECS),
+            skip_unchanged_invariants: Some(false),
+            minimize_findings: Some(true),
+            coverage_flush_every: Some(DEFAULT_FLUSH_EVERY),
+            stats_interval_secs: Some(DEFAULT_STATS_INTERVAL_SECS),
+            checkpoint_interval_secs: Some(DEFAULT_CHECKPOINT_INTERVAL_SECS),
+            resume: None,
+            runtime: None,
+            fork: None,
+            chain_extension_mocks: None,
+            upgrade_wasm_path: None,
+            differential_wasm_path: None,
+            storage_dump_path: None,
+            output_dir: None,
+            profile: None,
         }
     }
 }
@@ -86,28 +392,557 @@ impl Configuration {
         }
     }
 
-    pub fn load_config(file_path: &PathBuf) -> Configuration {
-        let config_str = fs::read_to_string(file_path).unwrap_or_else(|err| {
-            panic!("🚫 Can't read config: {}", err);
-        });
+    /// Accounts of `origins`, empty if not set
+    pub fn origin_pool(&self) -> &[OriginAccount] {
+        self.origins.as_deref().unwrap_or_default()
+    }
+
+    /// Account sending the messages of `origin`, i.e. the pool account it
+    /// points to, or `[origin; 32]` without `origins`
+    pub fn account_of(&self, origin: u8) -> AccountId32 {
+        match self.origin_pool() {
+            [] => AccountId32::new([origin; 32]),
+            pool => pool[usize::from(origin) % pool.len()].address.clone(),
+        }
+    }
+
+    /// Role of `origin` in `origins`, if any
+    pub fn role_of(&self, origin: u8) -> Option<&str> {
+        match self.origin_pool() {
+            [] => None,
+            pool => pool[usize::from(origin) % pool.len()].role.as_deref(),
+        }
+    }
+
+    /// Name of the first accounts without `origins`, `[1; 32]` being
+    /// `CALLER_1`, the default deployer, then `CALLER_2` and so on. Else, its
+    /// role if any.
+    pub fn name_of(&self, origin: u8) -> Option<&str> {
+        match self.origin_pool() {
+            [] => {
+                usize::from(origin)
+                    .checked_sub(1)
+                    .and_then(|index| ORIGIN_NAMES.get(index).copied())
+            }
+            _ => self.role_of(origin),
+        }
+    }
+
+    /// Account deploying the contract: the `deployer` of `origins`, then
+    /// `deployer_address`, then `DEFAULT_DEPLOYER`
+    pub fn deployer(&self) -> AccountId32 {
+        self.origin_pool()
+            .iter()
+            .find(|account| account.deployer.unwrap_or(false))
+            .map(|account| account.address.clone())
+            .or_else(|| self.deployer_address.clone())
+            .unwrap_or(ContractBridge::DEFAULT_DEPLOYER)
+    }
+
+    /// Whether the deployer is told apart from the other callers, i.e. the
+    /// origins are fuzzed and some of them aren't the deployer. Otherwise, it
+    /// may send every message, as by default.
+    pub fn is_deployer_distinct(&self) -> bool {
+        let deployer = self.deployer();
+        self.fuzz_origin
+            && match self.origin_pool() {
+                [] => true,
+                pool => pool.iter().any(|account| account.address != deployer),
+            }
+    }
+
+    /// Whether `origin` is a `privileged` account of `origins`, or the
+    /// deployer when it is told apart from the other callers
+    pub fn is_privileged(&self, origin: u8) -> bool {
+        let account = self.account_of(origin);
+        (self.is_deployer_distinct() && account == self.deployer())
+            || self.origin_pool().iter().any(|pooled| {
+                pooled.address == account && pooled.privileged.unwrap_or(false)
+            })
+    }
+
+    pub fn should_check_arithmetic_panics(&self) -> bool {
+        self.no_arithmetic_panic.unwrap_or(false)
+    }
+
+    pub fn should_fuzz_storage_deposit_limit(&self) -> bool {
+        self.fuzz_storage_deposit_limit.unwrap_or(false)
+    }
+
+    pub fn should_fuzz_transfers(&self) -> bool {
+        self.fuzz_transfers.unwrap_or(false)
+    }
+
+    pub fn should_save_coverage(&self) -> bool {
+        cfg!(not(fuzzing)) || self.save_coverage_during_fuzzing.unwrap_or(false)
+    }
+
+    pub fn should_skip_unchanged_invariants(&self) -> bool {
+        self.skip_unchanged_invariants.unwrap_or(false)
+    }
+
+    pub fn should_minimize_findings(&self) -> bool {
+        self.minimize_findings.unwrap_or(true)
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        self.output_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT_DIR))
+    }
+
+    pub fn should_report_tersely(&self) -> bool {
+        self.terse_findings.unwrap_or(false)
+    }
+
+    pub fn should_halt_on_bug(&self) -> bool {
+        // We always halt while fuzzing, otherwise AFL would never see the crash
+        cfg!(fuzzing) || self.halt_on_bug.unwrap_or(true)
+    }
+
+    /// Whether the fuzzer may call the message labelled `label`, according
+    /// to `allowed_messages` and `denied_messages`. A message of a trait
+    /// matches both its label, e.g. `PSP22::transfer`, and its name.
+    pub fn is_message_fuzzed(&self, label: &str) -> bool {
+        let matches = |message: &String| {
+            message == label || message == PayloadCrafter::message_name(label)
+        };
+        self.allowed_messages
+            .as_ref()
+            .map_or(true, |allowed| allowed.iter().any(matches))
+            && !self
+                .denied_messages
+                .as_ref()
+                .map_or(false, |denied| denied.iter().any(matches))
+    }
+
+    /// Whether the invariant labelled `label` is checked, according to
+    /// `invariants` and `invariant_groups`
+    pub fn is_invariant_checked(&self, label: &str) -> bool {
+        let Some(selected) = &self.invariants else {
+            return true;
+        };
+        let name = PayloadCrafter::message_name(label);
+        let names = [
+            label,
+            name,
+            name.strip_prefix(DEFAULT_PHINK_PREFIX).unwrap_or(name),
+        ];
+        let groups = self.invariant_groups.as_ref();
+        selected
+            .iter()
+            .flat_map(|entry| {
+                match groups.and_then(|groups| groups.get(entry)) {
+                    Some(patterns) => patterns.iter().collect::<Vec<_>>(),
+                    None => vec![entry],
+                }
+            })
+            .any(|pattern| names.iter().any(|name| Self::glob(pattern, name)))
+    }
+
+    /// Whether `text` matches `pattern`, where `*` matches any characters
+    fn glob(pattern: &str, text: &str) -> bool {
+        match pattern.split_once('*') {
+            None => pattern == text,
+            Some((prefix, rest)) => {
+                let Some(text) = text.strip_prefix(prefix) else {
+                    return false;
+                };
+                (0..=text.len())
+                    .filter(|i| text.is_char_boundary(*i))
+                    .any(|i| Self::glob(rest, &text[i..]))
+            }
+        }
+    }
+
+    pub fn load_config(
+        file_path: &PathBuf,
+        profile: Option<&str>,
+    ) -> Result<Configuration, String> {
+        let config_str = fs::read_to_string(file_path)
+            .map_err(|err| format!("🚫 Can't read config: {}", err))?;
 
-        let config: Configuration = toml::from_str(&config_str).unwrap_or_else(|err| {
-            panic!("❌ Can't parse config: {}", err);
-        });
+        // `toml` already reports the line and column of syntax errors, unknown
+        // keys and mistyped values
+        let mut config: Configuration = toml::from_str(&config_str)
+            .map_err(|err| format!("❌ Can't parse config: {}", err))?;
 
-        if config.storage_deposit_limit.is_some()
-            && Option::is_none(&Self::parse_balance(config.storage_deposit_limit.clone()))
+        let overrides = Self::env_overrides(env::vars());
+        if profile.is_some() || !overrides.is_empty() {
+            config = Self::with_overrides(&config_str, profile, overrides).map_err(
+                |err| {
+                    format!(
+                        "❌ Can't apply the profile or the `{}*` environment variables: {}",
+                        ENV_PREFIX, err
+                    )
+                },
+            )?;
+        }
+
+        if let Err(errors) = config.validate(&config_str) {
+            return Err(format!(
+                "❌ Invalid config {}:\n{}",
+                file_path.display(),
+                errors.join("\n")
+            ));
+        }
+
+        Ok(config)
+    }
+
+    /// `PHINK_*` variables among `vars`, by configuration key
+    fn env_overrides(
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Vec<(String, String)> {
+        vars.filter(|(name, _)| name != START_FUZZING_ENV)
+            .filter_map(|(name, value)| {
+                name.strip_prefix(ENV_PREFIX)
+                    .map(|key| (key.to_lowercase(), value))
+            })
+            .collect()
+    }
+
+    /// Parse `source` with the keys of `profile` merged in, then each
+    /// override replacing its key. An override is read as a TOML value, e.g.
+    /// `4` or `true`, and as a plain string if that doesn't fit the key, e.g.
+    /// for `PHINK_STORAGE_DEPOSIT_LIMIT=1000`.
+    fn with_overrides(
+        source: &str,
+        profile: Option<&str>,
+        overrides: Vec<(String, String)>,
+    ) -> Result<Configuration, String> {
+        let mut table: toml::Table = toml::from_str(source).map_err(|e| e.to_string())?;
+
+        let mut profiles = match table.remove("profile") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            _ => toml::Table::new(),
+        };
+        if let Some(name) = profile {
+            match profiles.remove(name) {
+                Some(toml::Value::Table(keys)) => Self::merge(&mut table, keys),
+                _ => {
+                    return Err(format!(
+                        "no `[profile.{}]`, available: {:?}",
+                        name,
+                        profiles.keys().collect::<Vec<_>>()
+                    ))
+                }
+            }
+        }
+
+        for (key, raw) in overrides {
+            let typed = toml::from_str::<toml::Table>(&format!("value = {}", raw))
+                .ok()
+                .and_then(|parsed| parsed.get("value").cloned());
+
+            if let Some(typed) = typed {
+                table.insert(key.clone(), typed);
+                if toml::Value::Table(table.clone())
+                    .try_into::<Configuration>()
+                    .is_ok()
+                {
+                    continue;
+                }
+            }
+            table.insert(key, toml::Value::String(raw));
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    /// Write `overlay` over `base`, keeping the keys of `base`'s sub-tables
+    /// that `overlay` doesn't set, e.g. `default_gas_limit.proof_size`
+    fn merge(base: &mut toml::Table, overlay: toml::Table) {
+        for (key, value) in overlay {
+            match value {
+                toml::Value::Table(overlay)
+                    if base.get(&key).map_or(false, toml::Value::is_table) =>
+                {
+                    if let Some(toml::Value::Table(base)) = base.get_mut(&key) {
+                        Self::merge(base, overlay);
+                    }
+                }
+                value => {
+                    base.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Check what the deserialization can't, e.g. hex payloads or
+    /// contradictory options. Each error points to the line of `source`
+    /// defining the faulty key, when found.
+    pub fn validate(&self, source: &str) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut error = |key: &str, message: &str| {
+            errors.push(match Self::line_of(source, key) {
+                Some(line) => format!("  line {}: `{}` {}", line, key, message),
+                None => format!("  `{}` {}", key, message),
+            });
+        };
+
+        let deposit_per_byte = self
+            .runtime
+            .as_ref()
+            .and_then(|runtime| runtime.deposit_per_byte.clone());
+        let balances = [
+            ("storage_deposit_limit", &self.storage_deposit_limit),
+            ("instantiate_initial_value", &self.instantiate_initial_value),
+            ("deposit_per_byte", &deposit_per_byte),
+        ];
+        for (key, value) in balances {
+            if value.is_some() && Self::parse_balance(value).is_none() {
+                error(key, "must be a `u128` written as a string, e.g. \"1000\"");
+            }
+        }
+
+        if let Some(payload) = &self.constructor_payload {
+            match hex::decode(payload) {
+                Ok(payload) if payload.len() < 4 => {
+                    error(
+                        "constructor_payload",
+                        "must start with the 4 bytes of the constructor selector",
+                    )
+                }
+                Ok(_) => {}
+                Err(_) => error("constructor_payload", "must be hex, without `0x`"),
+            }
+        }
+
+        if let Some(gas) = self.default_gas_limit {
+            if gas.ref_time() == 0 || gas.proof_size() == 0 {
+                error(
+                    "default_gas_limit",
+                    "must have a non-zero `ref_time` and `proof_size`",
+                );
+            }
+        }
+
+        if self.cores == Some(0) {
+            error("cores", "must be at least 1");
+        }
+        if self.max_messages_per_exec == Some(0) {
+            error(
+                "max_messages_per_exec",
+                "must be at least 1, otherwise no message is ever executed",
+            );
+        }
+        if self.err_ratio_threshold.is_some_and(|ratio| ratio > 100) {
+            error(
+                "err_ratio_threshold",
+                "is a percentage, it can't exceed 100",
+            );
+        }
+
+        if self.invariants.as_ref().is_some_and(Vec::is_empty) {
+            error(
+                "invariants",
+                "must select at least one invariant, remove it to check all of them",
+            );
+        }
+
+        let pool = self.origin_pool();
+        if self.origins.is_some() && pool.is_empty() {
+            error("origins", "must list at least one account");
+        }
+        if pool.len() > usize::from(u8::MAX) + 1 {
+            error(
+                "origins",
+                "can't list more than 256 accounts, one per origin byte",
+            );
+        }
+        if pool
+            .iter()
+            .filter(|account| account.deployer.unwrap_or(false))
+            .count()
+            > 1
         {
-            panic!("❌ Cannot parse string to `u128` for `storage_deposit_limit`, check your configuration file");
+            error("deployer", "can only be set on one account of `origins`");
+        }
+
+        if let Some(fork) = &self.fork {
+            if !fork.rpc_url.starts_with("http://")
+                && !fork.rpc_url.starts_with("https://")
+            {
+                error("rpc_url", "must be an HTTP(S) endpoint");
+            }
+            if self.constructor_payload.is_some() {
+                error(
+                    "constructor_payload",
+                    "is ignored when `[fork]` is set, since the contract is already instantiated",
+                );
+            }
+            if Self::parse_balance(&self.instantiate_initial_value).unwrap_or(0) > 0 {
+                error(
+                    "instantiate_initial_value",
+                    "is ignored when `[fork]` is set, since the contract is already instantiated",
+                );
+            }
+        }
+
+        for mock in self.chain_extension_mocks.iter().flatten() {
+            if mock.output.is_some() && mock.fuzzed_len.is_some() {
+                error(
+                    "fuzzed_len",
+                    "can't be set along with `output` in the same chain extension mock",
+                );
+            }
+            if let Some(output) = &mock.output {
+                if hex::decode(output.trim_start_matches("0x")).is_err() {
+                    error("output", "of a chain extension mock must be hex");
+                }
+            }
+        }
+
+        for (key, path) in [
+            ("upgrade_wasm_path", &self.upgrade_wasm_path),
+            ("storage_dump_path", &self.storage_dump_path),
+        ] {
+            if path.as_ref().is_some_and(|path| !path.exists()) {
+                error(key, "points to a file that doesn't exist");
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
+    }
 
-        config
+    /// 1-based line where `key` is assigned, or where the `[key]` table starts
+    fn line_of(source: &str, key: &str) -> Option<usize> {
+        source
+            .lines()
+            .position(|line| {
+                let line = line.trim_start();
+                line.trim_start_matches('[')
+                    .starts_with(&format!("{}]", key))
+                    || line
+                        .strip_prefix(key)
+                        .is_some_and(|rest| rest.trim_start().starts_with('='))
+            })
+            .map(|index| index + 1)
     }
 
-    pub fn parse_balance(value: Option<String>) -> Option<BalanceOf<Runtime>> {
+    pub fn parse_balance(value: &Option<String>) -> Option<BalanceOf<Runtime>> {
         // Currently, TOML & Serde don't handle parsing `u128` 🤡
         // So we need to parse it as a `string`... to then revert it to `u128`
         // (which is `BalanceOf<T>`)
-        value.clone().and_then(|s| s.parse::<u128>().ok())
+        value.as_ref().and_then(|s| s.parse::<u128>().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = [
+            ("PHINK_CORES", "4"),
+            ("PHINK_STORAGE_DEPOSIT_LIMIT", "1000"),
+            (START_FUZZING_ENV, "{}"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let overrides = Configuration::env_overrides(vars.into_iter());
+        assert_eq!(overrides.len(), 2);
+
+        let config = Configuration::with_overrides(
+            "cores = 1\nuse_honggfuzz = false\nfuzz_origin = false\n",
+            None,
+            overrides,
+        )
+        .unwrap();
+        assert_eq!(config.cores, Some(4));
+        assert_eq!(config.storage_deposit_limit, Some("1000".into()));
+    }
+
+    #[test]
+    fn test_profile_is_merged_over_the_top_level_keys() {
+        let source = "cores = 1\nuse_honggfuzz = false\nfuzz_origin = false\n\n[default_gas_limit]\nref_time = 10\nproof_size = 20\n\n[profile.ci]\ncores = 4\nhalt_on_bug = false\n\n[profile.ci.default_gas_limit]\nref_time = 30\n";
+        let config: Configuration = toml::from_str(source).unwrap();
+        assert_eq!(config.cores, Some(1));
+
+        let config = Configuration::with_overrides(source, Some("ci"), vec![]).unwrap();
+        assert_eq!(config.cores, Some(4));
+        assert_eq!(config.halt_on_bug, Some(false));
+        assert_eq!(config.default_gas_limit, Some(Weight::from_parts(30, 20)));
+        assert_eq!(config.profile, None);
+
+        assert!(
+            Configuration::with_overrides(source, Some("overnight"), vec![])
+                .unwrap_err()
+                .contains("[\"ci\"]")
+        );
+    }
+
+    #[test]
+    fn test_origins_are_mapped_onto_the_pool() {
+        let source = "use_honggfuzz = false\nfuzz_origin = true\n\n[[origins]]\naddress = \"5C62Ck4UrFPiBtoCmeSrgF7x9yv9mn38446dhCpsi2mLHiFT\"\n\n[[origins]]\naddress = \"5C7LYpP2ZH3tpKbvVvwiVe54AapxErdPBbvkYhe6y9ZBkqWt\"\nrole = \"attacker\"\ndeployer = true\n";
+        let config: Configuration = toml::from_str(source).unwrap();
+        assert!(config.validate(source).is_ok());
+
+        assert_eq!(config.account_of(0), AccountId32::new([1; 32]));
+        assert_eq!(config.account_of(3), AccountId32::new([2; 32]));
+        assert_eq!(config.role_of(3), Some("attacker"));
+        assert_eq!(config.name_of(3), Some("attacker"));
+        assert_eq!(Configuration::default().name_of(2), Some("CALLER_2"));
+        assert_eq!(Configuration::default().name_of(0), None);
+        assert_eq!(config.deployer(), AccountId32::new([2; 32]));
+        assert!(config.is_privileged(3));
+        assert!(!config.is_privileged(0));
+        // By default, the deployer sends every message
+        let default = Configuration::default();
+        assert!(!default.is_deployer_distinct());
+        assert!(!default.is_privileged(1));
+        assert_eq!(
+            Configuration::default().account_of(3),
+            AccountId32::new([3; 32])
+        );
+    }
+
+    #[test]
+    fn test_invariants_are_selected_by_group_or_pattern() {
+        let config: Configuration = toml::from_str(
+            r#"
+            use_honggfuzz = false
+            fuzz_origin = false
+            invariants = ["balance_*", "ownership"]
+            [invariant_groups]
+            ownership = ["phink_owner_*", "admin_is_set"]
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.is_invariant_checked("phink_balance_matches_supply"));
+        assert!(config.is_invariant_checked("Invariants::phink_owner_never_zero"));
+        assert!(config.is_invariant_checked("phink_admin_is_set"));
+        assert!(!config.is_invariant_checked("phink_admin_is_set_once"));
+        assert!(!config.is_invariant_checked("phink_supply_is_capped"));
+        assert!(Configuration::default().is_invariant_checked("phink_supply_is_capped"));
+    }
+
+    #[test]
+    fn test_validate_points_to_the_faulty_lines() {
+        let source = "use_honggfuzz = false\nfuzz_origin = false\nconstructor_payload = \"9BAE9D5G\"\n\n[fork]\nrpc_url = \"wss://rpc.example.com\"\ncontract_address = \"\"\n";
+        let config: Configuration = toml::from_str(source).unwrap();
+
+        assert_eq!(
+            config.validate(source).unwrap_err(),
+            vec![
+                "  line 3: `constructor_payload` must be hex, without `0x`",
+                "  line 6: `rpc_url` must be an HTTP(S) endpoint",
+                "  line 3: `constructor_payload` is ignored when `[fork]` is set, since the contract is already instantiated",
+            ]
+        );
+        assert!(toml::from_str::<Configuration>(
+            "use_honggfuzz = false\nfuzz_origin = false\ncore = 2\n"
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,369 @@
+use crate::{
+    cli::{
+        output::CoverageSummary,
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::{
+        finding::FindingsDb,
+        stats::{
+            FuzzingStats,
+            StatsTracker,
+        },
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{
+            self,
+            Event,
+            KeyCode,
+            KeyEventKind,
+        },
+        execute,
+        terminal::{
+            disable_raw_mode,
+            enable_raw_mode,
+            EnterAlternateScreen,
+            LeaveAlternateScreen,
+        },
+    },
+    layout::{
+        Constraint,
+        Direction,
+        Layout,
+    },
+    widgets::{
+        Block,
+        Borders,
+        Gauge,
+        Paragraph,
+        Row,
+        Table,
+    },
+    Frame,
+    Terminal,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    fs,
+    io,
+    path::Path,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+const REFRESH_EVERY: Duration = Duration::from_secs(1);
+/// AFL++ rewrites `fuzzer_stats` every minute (`STATS_UPDATE_SEC`), an
+/// instance is considered dead past twice that
+const AFL_STALE_SECS: u64 = 120;
+
+/// One AFL++ instance, as read from its `fuzzer_stats`
+#[derive(Debug, Default, PartialEq)]
+pub struct AflWorker {
+    pub name: String,
+    pub last_update: u64,
+    pub execs_done: u64,
+    pub execs_per_sec: f64,
+    pub corpus_count: u64,
+    pub saved_crashes: u64,
+    /// Edges of the shared map covered by this instance
+    pub edges_found: u64,
+}
+
+impl AflWorker {
+    /// Parse the `key : value` lines of `fuzzer_stats`
+    pub fn parse(name: &str, fuzzer_stats: &str) -> Self {
+        let stats: HashMap<&str, &str> = fuzzer_stats
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        let number = |key: &str| stats.get(key).and_then(|v| v.parse().ok());
+
+        Self {
+            name: name.to_string(),
+            last_update: number("last_update").unwrap_or_default(),
+            execs_done: number("execs_done").unwrap_or_default(),
+            execs_per_sec: stats
+                .get("execs_per_sec")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+            corpus_count: number("corpus_count").unwrap_or_default(),
+            // `unique_crashes` before AFL++ 4
+            saved_crashes: number("saved_crashes")
+                .or_else(|| number("unique_crashes"))
+                .unwrap_or_default(),
+            edges_found: number("edges_found").unwrap_or_default(),
+        }
+    }
+
+    /// Every instance of `afl_dir` having written its `fuzzer_stats` yet
+    pub fn read_all(afl_dir: &Path) -> Vec<Self> {
+        fs::read_dir(afl_dir)
+            .map(|dir| {
+                dir.filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let stats =
+                            fs::read_to_string(entry.path().join("fuzzer_stats")).ok()?;
+                        Some(Self::parse(&entry.file_name().to_string_lossy(), &stats))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn is_alive(&self, now: u64) -> bool {
+        now.saturating_sub(self.last_update) <= AFL_STALE_SECS
+    }
+}
+
+/// Everything shown by one frame of the dashboard
+#[derive(Debug, Default)]
+pub struct CampaignState {
+    pub workers: Vec<AflWorker>,
+    pub now: u64,
+    pub coverage: Option<CoverageSummary>,
+    pub unique_findings: usize,
+    /// Calls of each message summed over the live fuzzer processes, by label
+    pub selector_hits: Vec<(String, u64)>,
+}
+
+/// Live view of a campaign, aggregating the `fuzzer_stats` of every AFL++
+/// instance along with the stats, coverage and findings written by Phink, so
+/// that `afl-whatsup` isn't needed. It only reads files, and can be started
+/// and stopped at any time in another terminal than `phink fuzz`.
+pub struct Dashboard {
+    ziggy: ZiggyConfig,
+    /// Label of each message, by hex-encoded selector
+    labels: HashMap<String, String>,
+}
+
+impl Dashboard {
+    pub fn new(ziggy: ZiggyConfig) -> Self {
+        let labels = Self::labels(&ziggy.contract_path);
+        Self { ziggy, labels }
+    }
+
+    /// Draw the dashboard until `q` or `Esc` is pressed
+    pub fn run(&self) -> io::Result<()> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+        let result = self.event_loop(&mut terminal);
+
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        result
+    }
+
+    fn event_loop(
+        &self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> io::Result<()> {
+        loop {
+            let state = self.state();
+            terminal.draw(|frame| Self::draw(frame, &state))?;
+
+            if event::poll(REFRESH_EVERY)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press
+                        && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn state(&self) -> CampaignState {
+        let output_dir = self.ziggy.config.output_dir();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let workers = AflWorker::read_all(&self.ziggy.afl_dir());
+
+        let coverage = CoverageSummary::new(&self.ziggy);
+
+        let mut hits: BTreeMap<String, u64> = BTreeMap::new();
+        for stats in self.live_stats(&output_dir) {
+            for (selector, count) in stats.selector_hits {
+                let label = self.labels.get(&selector).cloned().unwrap_or(selector);
+                *hits.entry(label).or_default() += count;
+            }
+        }
+        let mut selector_hits: Vec<(String, u64)> = hits.into_iter().collect();
+        selector_hits.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+
+        CampaignState {
+            workers,
+            now,
+            coverage: (coverage.instrumented_points > 0).then_some(coverage),
+            unique_findings: FindingsDb::load(&output_dir).findings.len(),
+            selector_hits,
+        }
+    }
+
+    fn draw(frame: &mut Frame, state: &CampaignState) {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(7),
+                Constraint::Length(3),
+                Constraint::Min(5),
+            ])
+            .split(frame.size());
+
+        let alive: Vec<&AflWorker> = state
+            .workers
+            .iter()
+            .filter(|worker| worker.is_alive(state.now))
+            .collect();
+        let summary = vec![
+            format!(
+                "Workers alive      {} / {}",
+                alive.len(),
+                state.workers.len()
+            ),
+            format!(
+                "Execs/s            {:.0}",
+                alive.iter().map(|w| w.execs_per_sec).sum::<f64>()
+            ),
+            format!(
+                "Total execs        {}",
+                state.workers.iter().map(|w| w.execs_done).sum::<u64>()
+            ),
+            format!(
+                "Corpus             {}",
+                state
+                    .workers
+                    .iter()
+                    .map(|w| w.corpus_count)
+                    .max()
+                    .unwrap_or(0)
+            ),
+            format!(
+                "Findings           {} unique, {} crashes saved by AFL++",
+                state.unique_findings,
+                state.workers.iter().map(|w| w.saved_crashes).sum::<u64>()
+            ),
+        ];
+        frame.render_widget(
+            Paragraph::new(summary.join("\n")).block(
+                Block::default()
+                    .title(" 🐙 Phink dashboard (q to quit) ")
+                    .borders(Borders::ALL),
+            ),
+            areas[0],
+        );
+
+        let coverage = Block::default().title(" Coverage ").borders(Borders::ALL);
+        match &state.coverage {
+            Some(summary) => {
+                frame.render_widget(
+                    Gauge::default()
+                        .block(coverage)
+                        .ratio((summary.coverage_percent / 100.0).clamp(0.0, 1.0))
+                        .label(format!(
+                            "{} / {} points ({:.1}%)",
+                            summary.covered_points,
+                            summary.instrumented_points,
+                            summary.coverage_percent
+                        )),
+                    areas[1],
+                )
+            }
+            None => {
+                frame.render_widget(
+                    Paragraph::new("No instrumented contract found").block(coverage),
+                    areas[1],
+                )
+            }
+        }
+
+        let rows = state
+            .selector_hits
+            .iter()
+            .map(|(label, hits)| Row::new(vec![label.clone(), hits.to_string()]));
+        frame.render_widget(
+            Table::new(
+                rows,
+                [Constraint::Percentage(70), Constraint::Percentage(30)],
+            )
+            .header(Row::new(vec!["Message", "Calls"]))
+            .block(Block::default().title(" Messages ").borders(Borders::ALL)),
+            areas[2],
+        );
+    }
+
+    /// Stats of the fuzzer processes still running
+    /// Our own stats are rewritten every `stats_interval_secs`, the file of a
+    /// process that exited stops being updated
+    fn live_stats(&self, output_dir: &Path) -> Vec<FuzzingStats> {
+        let interval_secs = self.ziggy.config.stats_interval_secs;
+        let Some(stale_after) = StatsTracker::stale_after(interval_secs) else {
+            return Vec::new();
+        };
+        StatsTracker::all(output_dir)
+            .into_iter()
+            .filter(|(_, age, _)| *age < stale_after)
+            .map(|(_, _, stats)| stats)
+            .collect()
+    }
+
+    /// Label of each message of the metadata, by selector without `0x`
+    pub fn labels(contract_path: &Path) -> HashMap<String, String> {
+        let specs: serde_json::Value = Instrumenter::new(contract_path.to_path_buf())
+            .find()
+            .ok()
+            .and_then(|finder| fs::read_to_string(finder.specs_path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        specs["spec"]["messages"]
+            .as_array()
+            .map(|messages| {
+                messages
+                    .iter()
+                    .filter_map(|m| {
+                        Some((
+                            m["selector"].as_str()?.trim_start_matches("0x").to_string(),
+                            m["label"].as_str()?.to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fuzzer_stats() {
+        let stats = "start_time        : 1720191000\nlast_update       : 1720191069\nexecs_done        : 2314\nexecs_per_sec     : 33.47\ncorpus_count      : 12\nsaved_crashes     : 1\nedges_found       : 87\n";
+        let worker = AflWorker::parse("mainaflfuzzer", stats);
+
+        assert_eq!(worker.execs_done, 2314);
+        assert_eq!(worker.execs_per_sec, 33.47);
+        assert_eq!(worker.corpus_count, 12);
+        assert_eq!(worker.saved_crashes, 1);
+        assert_eq!(worker.edges_found, 87);
+        assert!(worker.is_alive(1720191069 + AFL_STALE_SECS));
+        assert!(!worker.is_alive(1720191069 + AFL_STALE_SECS + 1));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::cli::config::ENV_PREFIX;
+use std::{
+    env,
+    fs,
+    io::{
+        self,
+        IsTerminal,
+    },
+    path::Path,
+    process::{
+        Command,
+        Stdio,
+    },
+};
+use tracing::info;
+
+/// Image built from `DOCKERFILE` if no other one is given
+pub const DEFAULT_IMAGE: &str = "phink-runner:latest";
+/// Volume keeping the build of Phink across the campaigns, since the one of
+/// the host can't be reused, e.g. on macOS
+const TARGET_VOLUME: &str = "phink-target";
+
+/// Toolchain of a campaign: AFL++, ziggy and `cargo-contract`. The sources of
+/// Phink are mounted at runtime, since `cargo ziggy` builds the harness from
+/// them.
+const DOCKERFILE: &str = r#"FROM rust:1.79-bookworm
+
+RUN apt-get update && apt-get install -y --no-install-recommends \
+    clang lld llvm-dev binutils-dev libunwind-dev && rm -rf /var/lib/apt/lists/*
+RUN rustup component add rust-src rustfmt llvm-tools-preview \
+    && rustup target add wasm32-unknown-unknown
+RUN cargo install --locked cargo-contract --version ^4 \
+    && cargo install --force ziggy cargo-afl honggfuzz grcov
+RUN cargo afl config --build --plugins --force
+
+# The core pattern and the CPU governor belong to the host's kernel
+ENV AFL_I_DONT_CARE_ABOUT_MISSING_CRASHES=1 AFL_SKIP_CPUFREQ=1
+"#;
+
+/// Commands that can run inside the container, all taking an instrumented
+/// contract
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DockerAction {
+    Fuzz,
+    Run,
+    Coverage,
+}
+
+impl DockerAction {
+    fn command(&self) -> &'static str {
+        match self {
+            DockerAction::Fuzz => "fuzz",
+            DockerAction::Run => "run",
+            DockerAction::Coverage => "coverage",
+        }
+    }
+}
+
+/// Runs a campaign inside a container, for the hosts where AFL++ can't run
+/// as-is, e.g. macOS or machines without root access. The working directory
+/// (i.e. the sources of Phink, `phink.toml` and `output_dir`) and the
+/// contract are mounted at the same paths, so that every path of the
+/// configuration still holds inside.
+pub struct DockerRunner {
+    image: String,
+    /// Whether `image` was given, and can then be pulled
+    custom_image: bool,
+    /// Arguments given to `phink` before the command, e.g. `--config`
+    global_args: Vec<String>,
+}
+
+impl DockerRunner {
+    pub fn new(image: Option<String>, global_args: Vec<String>) -> Self {
+        Self {
+            custom_image: image.is_some(),
+            image: image.unwrap_or_else(|| DEFAULT_IMAGE.to_string()),
+            global_args,
+        }
+    }
+
+    pub fn run(&self, action: DockerAction, contract_path: &Path) -> Result<(), String> {
+        self.ensure_image()?;
+
+        let working_dir = env::current_dir()
+            .map_err(|e| format!("🙅 Can't get the working directory: {}", e))?;
+        let contract_path = contract_path
+            .canonicalize()
+            .map_err(|e| format!("🙅 Can't find {}: {}", contract_path.display(), e))?;
+
+        let mut args = Self::mount_args(&working_dir, &contract_path);
+        if io::stdin().is_terminal() {
+            // AFL++ draws its UI in the terminal
+            args.push("-it".into());
+        }
+        args.push(self.image.clone());
+        args.extend(["cargo", "run", "--release", "--"].map(String::from));
+        args.extend(self.global_args.iter().cloned());
+        args.extend([
+            action.command().to_string(),
+            contract_path.display().to_string(),
+        ]);
+
+        info!("🐳 Running `phink {}` in {}", action.command(), self.image);
+        let status = Command::new("docker")
+            .arg("run")
+            .args(&args)
+            .status()
+            .map_err(|e| format!("🙅 Can't run `docker`, is it installed? {}", e))?;
+        if !status.success() {
+            return Err(format!("🙅 The container exited with {}", status));
+        }
+        Ok(())
+    }
+
+    fn mount_args(working_dir: &Path, contract_path: &Path) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "--rm".into(),
+            // AFL++ keeps its coverage map in shared memory
+            "--shm-size=2g".into(),
+            "-v".into(),
+            format!("{0}:{0}", working_dir.display()),
+            "-w".into(),
+            working_dir.display().to_string(),
+            "-v".into(),
+            format!("{}:/phink-target", TARGET_VOLUME),
+            "-e".into(),
+            "CARGO_TARGET_DIR=/phink-target".into(),
+        ];
+        if !contract_path.starts_with(working_dir) {
+            args.extend(["-v".into(), format!("{0}:{0}", contract_path.display())]);
+        }
+        // Forward the overrides of the configuration and the log filter
+        for (key, _) in env::vars() {
+            if key.starts_with(ENV_PREFIX) || key == "RUST_LOG" {
+                args.extend(["-e".into(), key]);
+            }
+        }
+        args
+    }
+
+    /// Use the image if it's already there, else pull it if it was given, or
+    /// build it from `DOCKERFILE`
+    fn ensure_image(&self) -> Result<(), String> {
+        let exists = Command::new("docker")
+            .args(["image", "inspect", &self.image])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("🙅 Can't run `docker`, is it installed? {}", e))?
+            .success();
+        if exists {
+            return Ok(());
+        }
+
+        if self.custom_image {
+            info!("🐳 Pulling {}", self.image);
+            let pulled = Command::new("docker")
+                .args(["pull", &self.image])
+                .status()
+                .map_err(|e| format!("🙅 `docker pull` failed: {}", e))?;
+            return pulled
+                .success()
+                .then_some(())
+                .ok_or_else(|| format!("🙅 Can't pull {}", self.image));
+        }
+
+        info!(
+            "🐳 Building {}, this takes a while the first time",
+            self.image
+        );
+        let context = env::temp_dir().join("phink-docker");
+        fs::create_dir_all(&context)
+            .and_then(|_| fs::write(context.join("Dockerfile"), DOCKERFILE))
+            .map_err(|e| format!("🙅 Can't write the Dockerfile: {}", e))?;
+        let built = Command::new("docker")
+            .args(["build", "-t", &self.image])
+            .arg(&context)
+            .status()
+            .map_err(|e| format!("🙅 `docker build` failed: {}", e))?;
+        built
+            .success()
+            .then_some(())
+            .ok_or_else(|| format!("🙅 Can't build {}", self.image))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_is_mounted_at_the_same_path() {
+        let args = DockerRunner::mount_args(
+            Path::new("/home/user/phink"),
+            Path::new("/tmp/ink_fuzzed_XqUCn"),
+        );
+        assert!(args.contains(&"/home/user/phink:/home/user/phink".to_string()));
+        assert!(args.contains(&"/tmp/ink_fuzzed_XqUCn:/tmp/ink_fuzzed_XqUCn".to_string()));
+
+        let args = DockerRunner::mount_args(
+            Path::new("/home/user/phink"),
+            Path::new("/home/user/phink/sample/dns"),
+        );
+        assert_eq!(args.iter().filter(|arg| *arg == "-v").count(), 2);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    process::Command,
+};
+
+/// Outcome of one prerequisite check
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// Version found, or why the check failed
+    pub details: String,
+    /// How to fix it, printed only if the check failed
+    pub fix: &'static str,
+}
+
+/// Checks that the environment can instrument, build and fuzz a contract,
+/// since most failures otherwise show up deep inside `cargo ziggy` or AFL++
+pub struct Doctor;
+
+impl Doctor {
+    pub fn checks() -> Vec<Check> {
+        vec![
+            Self::command(
+                "cargo-contract",
+                "cargo",
+                &["contract", "--version"],
+                "cargo install --force --locked cargo-contract",
+            ),
+            Self::command(
+                "rustfmt",
+                "rustfmt",
+                &["--version"],
+                "rustup component add rustfmt, it formats the instrumented sources",
+            ),
+            Self::installed(
+                "wasm32-unknown-unknown target",
+                &["target", "list", "--installed"],
+                "wasm32-unknown-unknown",
+                "rustup target add wasm32-unknown-unknown",
+            ),
+            Self::installed(
+                "rust-src component",
+                &["component", "list", "--installed"],
+                "rust-src",
+                "rustup component add rust-src",
+            ),
+            Self::command(
+                "cargo-ziggy",
+                "cargo",
+                &["ziggy", "--version"],
+                "cargo install --force ziggy",
+            ),
+            Self::command(
+                "cargo-afl",
+                "cargo",
+                &["afl", "--version"],
+                "cargo install --force afl",
+            ),
+            Self::core_pattern(),
+            Self::cpu_governor(),
+        ]
+    }
+
+    /// Print every check, returning whether they all passed
+    pub fn diagnose() -> bool {
+        let checks = Self::checks();
+        for check in &checks {
+            let status = if check.passed { "✅" } else { "❌" };
+            println!("{} {}: {}", status, check.name, check.details);
+            if !check.passed {
+                println!("   👉 {}", check.fix);
+            }
+        }
+
+        let passed = checks.iter().all(|check| check.passed);
+        if passed {
+            println!("\n🩺 Everything looks good, happy fuzzing!");
+        }
+        passed
+    }
+
+    fn command(
+        name: &'static str,
+        program: &str,
+        args: &[&str],
+        fix: &'static str,
+    ) -> Check {
+        let (passed, details) = match Command::new(program).args(args).output() {
+            Ok(output) if output.status.success() => {
+                (
+                    true,
+                    String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                )
+            }
+            Ok(output) => {
+                (
+                    false,
+                    format!(
+                        "`{} {}` failed: {}",
+                        program,
+                        args.join(" "),
+                        String::from_utf8_lossy(&output.stderr)
+                            .lines()
+                            .next()
+                            .unwrap_or_default()
+                            .trim()
+                    ),
+                )
+            }
+            Err(e) => (false, format!("`{}` not found: {}", program, e)),
+        };
+        Check {
+            name,
+            passed,
+            details,
+            fix,
+        }
+    }
+
+    /// Whether `rustup <args>` lists `expected`
+    fn installed(
+        name: &'static str,
+        args: &[&str],
+        expected: &str,
+        fix: &'static str,
+    ) -> Check {
+        let listed = Command::new("rustup")
+            .args(args)
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+
+        let (passed, details) = match listed {
+            Ok(listed) if listed.lines().any(|line| line.starts_with(expected)) => {
+                (true, "installed".to_string())
+            }
+            Ok(_) => (false, "missing".to_string()),
+            Err(e) => (false, format!("`rustup` not found: {}", e)),
+        };
+        Check {
+            name,
+            passed,
+            details,
+            fix,
+        }
+    }
+
+    /// AFL++ refuses to start if the crashes are piped to another program,
+    /// e.g. `apport` or `systemd-coredump`
+    fn core_pattern() -> Check {
+        let pattern = fs::read_to_string("/proc/sys/kernel/core_pattern");
+        let (passed, details) = match pattern {
+            Ok(pattern) => (!pattern.starts_with('|'), pattern.trim().to_string()),
+            // Not on Linux, AFL++ has its own requirements there
+            Err(_) => (true, "not applicable".to_string()),
+        };
+        Check {
+            name: "core_pattern",
+            passed,
+            details,
+            fix: "echo core | sudo tee /proc/sys/kernel/core_pattern",
+        }
+    }
+
+    /// AFL++ warns, and runs slower, unless the CPUs are set to `performance`
+    fn cpu_governor() -> Check {
+        let governor =
+            fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor");
+        let (passed, details) = match governor {
+            Ok(governor) => {
+                (
+                    governor.trim() == "performance",
+                    governor.trim().to_string(),
+                )
+            }
+            // Virtual machines usually don't expose any governor
+            Err(_) => (true, "not applicable".to_string()),
+        };
+        Check {
+            name: "CPU governor",
+            passed,
+            details,
+            fix: "echo performance | sudo tee /sys/devices/system/cpu/cpu*/cpufreq/scaling_governor, or set AFL_SKIP_CPUFREQ=1",
+        }
+    }
+}
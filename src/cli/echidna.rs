@@ -0,0 +1,139 @@
+use crate::cli::config::Configuration;
+use serde_derive::Deserialize;
+use std::{
+    fs,
+    path::Path,
+};
+use tracing::{
+    info,
+    warn,
+};
+
+/// Keys of an Echidna configuration that have a counterpart in Phink
+const MAPPED_KEYS: [&str; 6] = [
+    "testLimit",
+    "seqLen",
+    "sender",
+    "filterFunctions",
+    "filterBlacklist",
+    "workers",
+];
+
+/// The subset of an Echidna `config.yaml` overlapping with `Configuration`,
+/// for the teams bringing their EVM campaigns over
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EchidnaConfig {
+    /// Number of transactions to generate, mapped to `max_executions`
+    test_limit: Option<u64>,
+    /// Transactions per sequence, mapped to `max_messages_per_exec`
+    seq_len: Option<usize>,
+    /// Callers of the transactions. Their EVM addresses can't be kept, but
+    /// more than one enables `fuzz_origin`.
+    sender: Option<Vec<String>>,
+    /// `Contract.function` signatures, mapped to `denied_messages`, or to
+    /// `allowed_messages` if `filter_blacklist` is `false`
+    filter_functions: Option<Vec<String>>,
+    filter_blacklist: Option<bool>,
+    /// Parallel workers, mapped to `cores`
+    workers: Option<u8>,
+}
+
+impl EchidnaConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let yaml = fs::read_to_string(path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", path.display(), e))?;
+        Self::parse(&yaml).map_err(|e| {
+            format!(
+                "🙅 Can't parse {} as an Echidna config: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    fn parse(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        let keys: serde_yaml::Mapping = serde_yaml::from_str(yaml)?;
+        let ignored: Vec<&str> = keys
+            .keys()
+            .filter_map(|key| key.as_str())
+            .filter(|key| !MAPPED_KEYS.contains(key))
+            .collect();
+        if !ignored.is_empty() {
+            warn!(
+                "❓ Phink has no equivalent of these Echidna keys, they're ignored: {}",
+                ignored.join(", ")
+            );
+        }
+        serde_yaml::from_str(yaml)
+    }
+
+    /// Override the keys of `config` having an Echidna counterpart
+    pub fn apply(&self, config: &mut Configuration) {
+        if let Some(test_limit) = self.test_limit {
+            info!("🔁 testLimit: {} → max_executions", test_limit);
+            config.max_executions = Some(test_limit);
+        }
+        if let Some(seq_len) = self.seq_len {
+            info!("🔁 seqLen: {} → max_messages_per_exec", seq_len);
+            config.max_messages_per_exec = Some(seq_len);
+        }
+        if let Some(workers) = self.workers {
+            info!("🔁 workers: {} → cores", workers);
+            config.cores = Some(workers);
+        }
+        if let Some(senders) = &self.sender {
+            config.fuzz_origin = senders.len() > 1;
+            info!(
+                "🔁 sender: {} addresses → fuzz_origin = {}",
+                senders.len(),
+                config.fuzz_origin
+            );
+        }
+
+        if let Some(functions) = &self.filter_functions {
+            // `Token.transfer(address,uint256)` is the `transfer` message
+            let labels: Vec<String> = functions
+                .iter()
+                .map(|function| {
+                    let name = function.split('(').next().unwrap_or(function);
+                    name.rsplit('.').next().unwrap_or(name).to_string()
+                })
+                .collect();
+            if self.filter_blacklist.unwrap_or(true) {
+                info!("🔁 filterFunctions: {:?} → denied_messages", labels);
+                config.denied_messages = Some(labels);
+            } else {
+                info!("🔁 filterFunctions: {:?} → allowed_messages", labels);
+                config.allowed_messages = Some(labels);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echidna_config_is_mapped() {
+        let echidna = EchidnaConfig::parse(
+            "testLimit: 50000\nseqLen: 10\nsender: [\"0x10000\", \"0x20000\"]\n\
+            filterBlacklist: false\nfilterFunctions: [\"Token.transfer(address,uint256)\", \"mint\"]\n\
+            coverage: true\n",
+        )
+        .unwrap();
+        let mut config = Configuration::default();
+        echidna.apply(&mut config);
+
+        assert_eq!(config.max_executions, Some(50000));
+        assert_eq!(config.max_messages_per_exec, Some(10));
+        assert!(config.fuzz_origin);
+        assert_eq!(
+            config.allowed_messages,
+            Some(vec!["transfer".to_string(), "mint".to_string()])
+        );
+        assert!(config.is_message_fuzzed("mint"));
+        assert!(!config.is_message_fuzzed("burn"));
+    }
+}
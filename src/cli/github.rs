@@ -0,0 +1,190 @@
+use crate::{
+    cli::{
+        dashboard::Dashboard,
+        output::Summary,
+        ziggy::ZiggyConfig,
+    },
+    cover::map::CoverageMap,
+    fuzzer::finding::{
+        Finding,
+        FINDINGS_DIR,
+        FINDING_METADATA,
+    },
+};
+use regex::Regex;
+use std::{
+    env,
+    fmt::Write as _,
+    fs::{
+        self,
+        OpenOptions,
+    },
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use tracing::warn;
+
+/// Job summaries and annotations of GitHub Actions, so that the findings and
+/// the coverage show up on the pull request instead of only in the logs
+pub struct GithubActions;
+
+impl GithubActions {
+    pub fn enabled() -> bool {
+        env::var("GITHUB_ACTIONS").map_or(false, |value| value == "true")
+    }
+
+    /// Append `summary` to `$GITHUB_STEP_SUMMARY`, and annotate each finding,
+    /// on the definition of the invariant for the violated ones
+    pub fn publish(summary: &Summary, ziggy: &ZiggyConfig) {
+        if let Ok(path) = env::var("GITHUB_STEP_SUMMARY") {
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(Self::markdown(summary).as_bytes()));
+            if let Err(e) = written {
+                warn!("🙅 Can't write the job summary to {}: {}", path, e);
+            }
+        }
+
+        let labels = Dashboard::labels(&ziggy.contract_path);
+        let map = CoverageMap::load(&ziggy.contract_path).unwrap_or_default();
+        for known in &summary.findings {
+            let finding: Option<Finding> = fs::read_to_string(
+                ziggy
+                    .config
+                    .output_dir()
+                    .join(FINDINGS_DIR)
+                    .join(&known.id)
+                    .join(FINDING_METADATA),
+            )
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+
+            let location = finding
+                .and_then(|finding| finding.invariant)
+                .and_then(|selector| labels.get(&selector).cloned())
+                .and_then(|invariant| Self::definition(&map, &invariant));
+            println!(
+                "{}",
+                Self::annotation(
+                    &format!("Phink: {:?}", known.bug_type),
+                    &format!("{} ({})", known.description, known.reference()),
+                    location
+                        .as_ref()
+                        .map(|(file, line)| (file.as_path(), *line)),
+                )
+            );
+        }
+    }
+
+    fn markdown(summary: &Summary) -> String {
+        let mut markdown = String::new();
+        let _ = writeln!(markdown, "## 🐙 Phink `{}`\n", summary.command);
+        let coverage = &summary.coverage;
+        if coverage.instrumented_points > 0 {
+            let _ = writeln!(
+                markdown,
+                "**Coverage:** {:.1}% ({} / {} points)\n",
+                coverage.coverage_percent,
+                coverage.covered_points,
+                coverage.instrumented_points
+            );
+        }
+
+        if summary.findings.is_empty() {
+            let _ = writeln!(markdown, "No bug found 🎉\n");
+            return markdown;
+        }
+        let _ = writeln!(markdown, "| Finding | Bug | Description | Occurrences |");
+        let _ = writeln!(markdown, "|---|---|---|---|");
+        for finding in &summary.findings {
+            let _ = writeln!(
+                markdown,
+                "| `{}` | {:?} | {} | {} |",
+                finding.reference(),
+                finding.bug_type,
+                finding.description.replace('|', "\\|"),
+                finding.occurrences
+            );
+        }
+        markdown.push('\n');
+        markdown
+    }
+
+    /// `::error` workflow command, whose properties and message are escaped
+    /// as the runner expects
+    fn annotation(
+        title: &str,
+        message: &str,
+        location: Option<(&Path, usize)>,
+    ) -> String {
+        let escape = |data: &str| {
+            data.replace('%', "%25")
+                .replace('\r', "%0D")
+                .replace('\n', "%0A")
+        };
+        let property = |data: &str| escape(data).replace(':', "%3A").replace(',', "%2C");
+
+        let mut properties = vec![format!("title={}", property(title))];
+        if let Some((file, line)) = location {
+            properties.insert(0, format!("line={}", line));
+            properties
+                .insert(0, format!("file={}", property(&file.display().to_string())));
+        }
+        format!("::error {}::{}", properties.join(","), escape(message))
+    }
+
+    /// Where `fn <invariant>` is defined in the original sources, relative to
+    /// the workspace so that GitHub can attach the annotation to the diff
+    fn definition(map: &CoverageMap, invariant: &str) -> Option<(PathBuf, usize)> {
+        let definition =
+            Regex::new(&format!(r"\bfn\s+{}\b", regex::escape(invariant))).ok()?;
+        let (file, line) = map.files.iter().find_map(|(file, code)| {
+            let line = code.lines().position(|line| definition.is_match(line))?;
+            Some((file, line + 1))
+        })?;
+
+        let path = map
+            .source_dir
+            .as_ref()
+            .map_or_else(|| PathBuf::from(file), |source_dir| source_dir.join(file));
+        let relative = env::var("GITHUB_WORKSPACE")
+            .ok()
+            .and_then(|workspace| {
+                path.strip_prefix(workspace).ok().map(Path::to_path_buf)
+            })
+            .unwrap_or(path);
+        Some((relative, line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invariant_annotation() {
+        let mut map = CoverageMap::default();
+        map.files.insert(
+            "lib.rs".into(),
+            "impl Dns {\n    #[ink(message)]\n    pub fn phink_assert_dangerous_number(&self) {}\n}"
+                .into(),
+        );
+
+        let (file, line) =
+            GithubActions::definition(&map, "phink_assert_dangerous_number").unwrap();
+        assert_eq!((file.as_path(), line), (Path::new("lib.rs"), 3));
+        assert_eq!(
+            GithubActions::annotation(
+                "Phink: InvariantViolated",
+                "50% broken\nagain",
+                Some((&file, line))
+            ),
+            "::error file=lib.rs,line=3,title=Phink%3A InvariantViolated::50%25 broken%0Aagain"
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::instrumenter::invariants::INVARIANTS_FILE;
+use regex::Regex;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+pub const INVARIANTS_TEMPLATE: &str = "phink_invariants.rs.example";
+
+/// Commented configuration with sane defaults, trimmed down from the
+/// `phink.toml` of this repository
+const CONFIG_TEMPLATE: &str = r#"# Phink Configuration
+
+cores = 4 # set it to 1 for single-core
+max_messages_per_exec = 4 # maximum number of messages executed per input
+report_path = "output/phink/contract_coverage"
+use_honggfuzz = false # obligatory for MacOS users
+fuzz_origin = false # `true` to call the messages from several accounts
+# deployer_address = "5C62Ck4UrFPiBtoCmeSrgF7x9yv9mn38446dhCpsi2mLHiFT" # Alice (Origin(1))
+# constructor_payload = "9BAE9D5E..." # required if there's no `new()` without arguments, see `cargo contract encode`
+# storage_deposit_limit = "100000000000" # no limit if not set
+instantiate_initial_value = "0" # endowment transferred to the contract at instantiation
+save_coverage_during_fuzzing = false # `true` saves the coverage while fuzzing, at the cost of execs/sec
+halt_on_bug = true # set it to `false` to record findings and keep going during `run` and `execute`
+
+[default_gas_limit]
+ref_time = 100000000000
+proof_size = 3145728  # 3 * 1024 * 1024
+"#;
+
+/// Set up a contract for Phink: write a `phink.toml`, add the `phink`
+/// feature to its `Cargo.toml`, and drop a template of invariants next to it
+pub struct ProjectInit {
+    contract_path: PathBuf,
+}
+
+impl ProjectInit {
+    pub fn new(contract_path: PathBuf) -> Self {
+        Self { contract_path }
+    }
+
+    pub fn scaffold(&self) -> Result<(), String> {
+        self.write_config()?;
+        self.add_phink_feature()?;
+        self.write_invariants_template()
+    }
+
+    fn write_config(&self) -> Result<(), String> {
+        let path = self.contract_path.join("phink.toml");
+        if path.exists() {
+            println!("⏭️  {} already exists, keeping it", path.display());
+            return Ok(());
+        }
+        fs::write(&path, CONFIG_TEMPLATE)
+            .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))?;
+        println!("📝 Wrote {}", path.display());
+        Ok(())
+    }
+
+    fn add_phink_feature(&self) -> Result<(), String> {
+        let path = self.contract_path.join("Cargo.toml");
+        let manifest = fs::read_to_string(&path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", path.display(), e))?;
+
+        match Self::with_phink_feature(&manifest) {
+            Some(manifest) => {
+                fs::write(&path, manifest)
+                    .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))?;
+                println!("📦 Added the `phink` feature to {}", path.display());
+            }
+            None => println!("⏭️  The `phink` feature is already in {}", path.display()),
+        }
+        Ok(())
+    }
+
+    /// `manifest` with a `phink = []` feature, or `None` if it already has one
+    fn with_phink_feature(manifest: &str) -> Option<String> {
+        let parsed: toml::Value = toml::from_str(manifest).ok()?;
+        if parsed
+            .get("features")
+            .and_then(|f| f.get("phink"))
+            .is_some()
+        {
+            return None;
+        }
+
+        let features = Regex::new(r"(?m)^\[features\][^\n]*\n").unwrap();
+        Some(match features.find(manifest) {
+            Some(section) => {
+                format!(
+                    "{}phink = []\n{}",
+                    &manifest[..section.end()],
+                    &manifest[section.end()..]
+                )
+            }
+            None => format!("{}\n[features]\nphink = []\n", manifest.trim_end()),
+        })
+    }
+
+    fn write_invariants_template(&self) -> Result<(), String> {
+        let path = self.contract_path.join(INVARIANTS_TEMPLATE);
+        let storage = Self::storage_struct(&self.contract_path.join("lib.rs"))
+            .unwrap_or_else(|| "Contract".to_string());
+
+        fs::write(&path, Self::invariants_template(&storage))
+            .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))?;
+        println!(
+            "🧩 Wrote a template of invariants to {}, paste it into your `#[ink::contract]` module, \
+            or rename it to `{}` to keep it out of the contract",
+            path.display(),
+            INVARIANTS_FILE
+        );
+        Ok(())
+    }
+
+    /// Name of the `#[ink(storage)]` struct of the contract, if found
+    fn storage_struct(lib: &Path) -> Option<String> {
+        let code = fs::read_to_string(lib).ok()?;
+        Regex::new(r"#\[ink\(storage\)\][^{;]*?struct\s+(\w+)")
+            .unwrap()
+            .captures(&code)
+            .map(|captures| captures[1].to_string())
+    }
+
+    fn invariants_template(storage: &str) -> String {
+        format!(
+            r#"#[cfg(feature = "phink")]
+#[ink(impl)]
+impl {} {{
+    // Each message starting with `phink_` is an invariant, called after every
+    // fuzzed sequence. Assert here what must hold whatever the callers do.
+    #[ink(message)]
+    #[cfg(feature = "phink")]
+    pub fn phink_assert_example(&self) {{
+        assert!(true, "replace me with a property of your contract");
+    }}
+}}
+"#,
+            storage
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phink_feature_is_added_once() {
+        let manifest = "[package]\nname = \"dns\"\n\n[features]\ndefault = [\"std\"]\n";
+        let updated = ProjectInit::with_phink_feature(manifest).unwrap();
+        assert!(updated.contains("[features]\nphink = []\ndefault = [\"std\"]\n"));
+        assert!(ProjectInit::with_phink_feature(&updated).is_none());
+
+        let updated =
+            ProjectInit::with_phink_feature("[package]\nname = \"dns\"\n").unwrap();
+        assert!(updated.ends_with("\n[features]\nphink = []\n"));
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+/// Variable overriding the level picked from `--verbose` and `--quiet`, e.g.
+/// `RUST_LOG=phink::instrumenter=debug`
+const FILTER_ENV: &str = "RUST_LOG";
+
+/// How much Phink prints while it works. The findings, reports and JSON
+/// summaries are always printed, this only controls the progress messages.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Warnings and errors only
+    Quiet,
+    /// What Phink is doing, without the per-file or per-input details
+    Normal,
+    /// Everything, e.g. each copied file and each coverage flush
+    Verbose,
+}
+
+impl Verbosity {
+    /// `--verbose` wins, although clap already rejects both flags together
+    pub fn new(verbose: bool, quiet: bool) -> Self {
+        match (verbose, quiet) {
+            (true, _) => Verbosity::Verbose,
+            (_, true) => Verbosity::Quiet,
+            _ => Verbosity::Normal,
+        }
+    }
+
+    fn directive(self) -> String {
+        let level = match self {
+            Verbosity::Quiet => Level::WARN,
+            Verbosity::Normal => Level::INFO,
+            Verbosity::Verbose => Level::DEBUG,
+        };
+        level.to_string().to_lowercase()
+    }
+}
+
+/// Install the subscriber, writing to stderr so that stdout only carries the
+/// results. `RUST_LOG` always wins over `verbosity`.
+///
+/// `--verbose` is exported through `RUST_LOG`, so that the AFL++ children
+/// spawned by `cargo ziggy` log their details too.
+pub fn init(verbosity: Verbosity) {
+    let filter = EnvFilter::try_from_env(FILTER_ENV).unwrap_or_else(|_| {
+        if verbosity == Verbosity::Verbose {
+            env::set_var(FILTER_ENV, verbosity.directive());
+        }
+        EnvFilter::new(verbosity.directive())
+    });
+    install(filter);
+}
+
+/// Install the subscriber of a fuzzing child. Every input runs through it, so
+/// only the warnings are printed unless `RUST_LOG` asks for more.
+pub fn init_harness() {
+    install(
+        EnvFilter::try_from_env(FILTER_ENV)
+            .unwrap_or_else(|_| EnvFilter::new(Verbosity::Quiet.directive())),
+    );
+}
+
+fn install(filter: EnvFilter) {
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_directives() {
+        assert_eq!(Verbosity::new(true, false).directive(), "debug");
+        assert_eq!(Verbosity::new(false, true).directive(), "warn");
+        assert_eq!(Verbosity::new(false, false).directive(), "info");
+    }
+}
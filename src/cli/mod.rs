@@ -1,2 +1,16 @@
+pub mod bisect;
+pub mod campaign;
+pub mod completion;
 pub mod config;
+pub mod dashboard;
+pub mod docker;
+pub mod doctor;
+pub mod echidna;
+pub mod github;
+pub mod init;
+pub mod logging;
+pub mod mutate;
+pub mod output;
+pub mod triage;
+pub mod watch;
 pub mod ziggy;
@@ -0,0 +1,220 @@
+use crate::{
+    cli::config::Configuration,
+    fuzzer::{
+        finding::FindingsDb,
+        fuzz::CORPUS_DIR,
+    },
+    instrumenter::{
+        instrumentation::{
+            ContractBuilder,
+            ContractForker,
+            ContractInstrumenter,
+            Instrumenter,
+        },
+        mutation::{
+            Mutation,
+            Mutator,
+        },
+    },
+};
+use prettytable::{
+    row,
+    Table,
+};
+use std::{
+    collections::BTreeSet,
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Command,
+        Stdio,
+    },
+};
+use tracing::{
+    info,
+    warn,
+};
+use walkdir::WalkDir;
+
+/// Under `output_dir`, one campaign directory per mutant
+pub const MUTANTS_DIR: &str = "mutants";
+
+/// A mutant the invariants and the corpus didn't tell apart from the
+/// original contract
+pub struct Survivor {
+    pub file: PathBuf,
+    pub mutation: Mutation,
+}
+
+/// Scores the invariants: each mutant of the contract is instrumented, built,
+/// and its corpus replayed with `phink run`. A mutant is killed if it raises
+/// a finding the original contract doesn't.
+pub struct MutationTester {
+    config: Configuration,
+    contract_path: PathBuf,
+    /// Arguments given to `phink` before the `run` command, e.g. `--config`
+    global_args: Vec<String>,
+    corpus: PathBuf,
+    max_mutants: Option<usize>,
+}
+
+impl MutationTester {
+    pub fn new(
+        config: Configuration,
+        contract_path: PathBuf,
+        global_args: Vec<String>,
+        corpus: Option<PathBuf>,
+        max_mutants: Option<usize>,
+    ) -> Self {
+        let corpus = corpus.unwrap_or_else(|| config.output_dir().join(CORPUS_DIR));
+        Self {
+            config,
+            contract_path,
+            global_args,
+            corpus,
+            max_mutants,
+        }
+    }
+
+    pub fn run(&self) -> Result<(), String> {
+        let mutants_dir = self.config.output_dir().join(MUTANTS_DIR);
+        let baseline =
+            self.findings(&self.contract_path, &mutants_dir.join("baseline"))?;
+        info!(
+            "🧟 The original contract raises {} finding(s) with this corpus",
+            baseline.len()
+        );
+
+        let mutants: Vec<(PathBuf, String, usize)> = self
+            .sources()
+            .into_iter()
+            .filter_map(|file| fs::read_to_string(&file).ok().map(|code| (file, code)))
+            .flat_map(|(file, code)| {
+                (0..Mutator::count(&code))
+                    .map(move |index| (file.clone(), code.clone(), index))
+            })
+            .take(self.max_mutants.unwrap_or(usize::MAX))
+            .collect();
+
+        let (mut killed, mut stillborn) = (0, 0);
+        let mut survivors = Vec::new();
+        for (number, (file, code, index)) in mutants.iter().enumerate() {
+            let Some((mutated, mutation)) = Mutator::apply(code, *index) else {
+                continue;
+            };
+            let relative = file
+                .strip_prefix(&self.contract_path)
+                .unwrap_or(file)
+                .to_path_buf();
+            info!(
+                "🧟 Mutant {}/{}: {}:{} {}",
+                number + 1,
+                mutants.len(),
+                relative.display(),
+                mutation.line,
+                mutation.description
+            );
+
+            let fork = Instrumenter::new(self.contract_path.clone()).fork()?;
+            fs::write(fork.join(&relative), mutated).map_err(|e| {
+                format!("🙅 Can't write the mutant into {}: {}", fork.display(), e)
+            })?;
+            let findings = self.findings(&fork, &mutants_dir.join(number.to_string()));
+            let _ = fs::remove_dir_all(&fork);
+
+            match findings {
+                Ok(findings) if findings.difference(&baseline).next().is_some() => {
+                    info!("💀 Killed");
+                    killed += 1;
+                }
+                Ok(_) => {
+                    warn!("🧟 Survived");
+                    survivors.push(Survivor {
+                        file: relative,
+                        mutation,
+                    });
+                }
+                Err(e) => {
+                    info!("🪦 Stillborn, it doesn't build: {}", e);
+                    stillborn += 1;
+                }
+            }
+        }
+
+        Self::display(&survivors, killed, stillborn);
+        Ok(())
+    }
+
+    /// Keys of the findings raised by the contract of `contract_path` once
+    /// its corpus is replayed, in a campaign of its own under `campaign_dir`
+    fn findings(
+        &self,
+        contract_path: &Path,
+        campaign_dir: &Path,
+    ) -> Result<BTreeSet<String>, String> {
+        let mut instrumenter = Instrumenter::new(contract_path.to_path_buf());
+        instrumenter.instrument()?.build()?;
+
+        let _ = fs::remove_dir_all(campaign_dir);
+        let corpus_dir = campaign_dir.join(CORPUS_DIR);
+        fs::create_dir_all(&corpus_dir)
+            .map_err(|e| format!("🙅 Can't create {}: {}", corpus_dir.display(), e))?;
+        for entry in fs::read_dir(&self.corpus).into_iter().flatten().flatten() {
+            let _ = fs::copy(entry.path(), corpus_dir.join(entry.file_name()));
+        }
+
+        let status = Command::new(env::current_exe().map_err(|e| e.to_string())?)
+            .args(&self.global_args)
+            .arg("run")
+            .arg(&instrumenter.contract_dir)
+            .env("PHINK_OUTPUT_DIR", campaign_dir)
+            .env("PHINK_HALT_ON_BUG", "false")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        let _ = fs::remove_dir_all(&instrumenter.contract_dir);
+        status.map_err(|e| format!("🙅 Can't start `phink run`: {}", e))?;
+
+        Ok(FindingsDb::load(campaign_dir)
+            .findings
+            .into_keys()
+            .collect())
+    }
+
+    /// Rust sources of the contract, without the tests and the build
+    fn sources(&self) -> Vec<PathBuf> {
+        WalkDir::new(&self.contract_path)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.file_name() != "target" && entry.file_name() != "tests"
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "rs"))
+            .map(|entry| entry.into_path())
+            .collect()
+    }
+
+    fn display(survivors: &[Survivor], killed: usize, stillborn: usize) {
+        if !survivors.is_empty() {
+            let mut table = Table::new();
+            table.add_row(row!["Location", "Surviving mutation"]);
+            for survivor in survivors {
+                table.add_row(row![
+                    format!("{}:{}", survivor.file.display(), survivor.mutation.line),
+                    survivor.mutation.description
+                ]);
+            }
+            table.printstd();
+        }
+        println!(
+            "🧟 Mutation score: {}/{} mutants killed ({} didn't build)",
+            killed,
+            killed + survivors.len(),
+            stillborn
+        );
+    }
+}
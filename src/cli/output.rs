@@ -0,0 +1,256 @@
+use crate::{
+    cli::ziggy::ZiggyConfig,
+    fuzzer::{
+        finding::{
+            FindingsDb,
+            KnownFinding,
+        },
+        report::FindingsReport,
+    },
+};
+use serde_derive::Serialize;
+use std::path::PathBuf;
+
+/// `fuzz` and `run` exit with it when findings are known, so that CI can gate
+/// on the campaign
+pub const EXIT_FINDINGS: i32 = 1;
+/// Exit code when the configuration or the setup of the campaign is invalid
+pub const EXIT_SETUP_ERROR: i32 = 2;
+
+/// `json` prints a summary as the last line of stdout, so that scripts don't
+/// have to scrape the human-readable output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverageSummary {
+    pub instrumented_points: usize,
+    pub covered_points: usize,
+    pub coverage_percent: f64,
+}
+
+impl CoverageSummary {
+    pub fn new(config: &ZiggyConfig) -> Self {
+        let instrumented_points = FindingsReport::instrumented_points(config);
+        let covered_points = FindingsReport::covered_points(config).len();
+        Self {
+            instrumented_points,
+            covered_points,
+            coverage_percent: match instrumented_points {
+                0 => 0.0,
+                total => covered_points as f64 * 100.0 / total as f64,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// The seed didn't trigger any bug
+    Passed,
+    /// The seed triggered at least one bug, listed in `findings`
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub command: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<Verdict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_path: Option<PathBuf>,
+    pub coverage: CoverageSummary,
+    pub findings: Vec<KnownFinding>,
+}
+
+impl Summary {
+    /// Every known finding, along with the coverage reached so far
+    pub fn campaign(command: &'static str, config: &ZiggyConfig) -> Self {
+        Self {
+            command,
+            seed: None,
+            verdict: None,
+            report_path: None,
+            coverage: CoverageSummary::new(config),
+            findings: FindingsDb::load(&config.config.output_dir())
+                .findings
+                .into_values()
+                .collect(),
+        }
+    }
+
+    /// Only the findings hit since `before`, i.e during this campaign, so that
+    /// the findings of an earlier campaign don't fail this one
+    pub fn since(
+        command: &'static str,
+        before: &FindingsDb,
+        config: &ZiggyConfig,
+    ) -> Self {
+        Self {
+            command,
+            seed: None,
+            verdict: None,
+            report_path: None,
+            coverage: CoverageSummary::new(config),
+            findings: Self::hit_since(before, config),
+        }
+    }
+
+    /// Only the findings hit since `before`, i.e by the executed seed
+    pub fn seed(
+        seed: Option<PathBuf>,
+        before: &FindingsDb,
+        config: &ZiggyConfig,
+    ) -> Self {
+        let findings = Self::hit_since(before, config);
+        Self {
+            command: "execute",
+            seed,
+            verdict: Some(
+                if findings.is_empty() {
+                    Verdict::Passed
+                } else {
+                    Verdict::Failed
+                },
+            ),
+            report_path: None,
+            coverage: CoverageSummary::new(config),
+            findings,
+        }
+    }
+
+    /// The findings either new or hit again since `before`
+    fn hit_since(before: &FindingsDb, config: &ZiggyConfig) -> Vec<KnownFinding> {
+        FindingsDb::load(&config.config.output_dir())
+            .findings
+            .into_iter()
+            .filter(|(key, known)| {
+                before
+                    .findings
+                    .get(key)
+                    .map_or(true, |previous| previous.occurrences < known.occurrences)
+            })
+            .map(|(_, known)| known)
+            .collect()
+    }
+
+    /// `EXIT_FINDINGS` if the summary lists some finding and `error_on_finding`,
+    /// `0` otherwise
+    pub fn exit_code(&self, error_on_finding: bool) -> i32 {
+        if error_on_finding && !self.findings.is_empty() {
+            EXIT_FINDINGS
+        } else {
+            0
+        }
+    }
+
+    pub fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("🙅 Can't serialize the summary: {}", e),
+        }
+    }
+}
+
+/// Final summary of `run --terse`: the bugs hit since `before`, with how many
+/// times each, so that a large corpus replays into a short report
+pub fn replay_summary(before: &FindingsDb, after: &FindingsDb) -> String {
+    let hits: Vec<(&KnownFinding, u64, bool)> = after
+        .findings
+        .iter()
+        .filter_map(|(key, known)| {
+            let previous = before.findings.get(key);
+            let seen = previous.map_or(0, |previous| previous.occurrences);
+            (known.occurrences > seen)
+                .then(|| (known, known.occurrences - seen, previous.is_none()))
+        })
+        .collect();
+
+    if hits.is_empty() {
+        return "🧾 No finding while replaying the corpus".into();
+    }
+
+    let mut summary = format!(
+        "🧾 {} findings while replaying the corpus, {} unique bugs ({} new)",
+        hits.iter().map(|(_, count, _)| count).sum::<u64>(),
+        hits.len(),
+        hits.iter().filter(|(_, _, new)| *new).count()
+    );
+    for (known, count, _) in hits {
+        summary.push_str(&format!(
+            "\n   {} x{} {}",
+            known.reference(),
+            count,
+            known.description
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzer::bug::BugType;
+
+    fn known(id: &str, occurrences: u64) -> KnownFinding {
+        KnownFinding {
+            id: id.into(),
+            bug_id: Some(format!("PHINK-1a2b3c4d-{}", id)),
+            bug_type: BugType::ContractTrapped,
+            description: format!("contract trapped in `msg_{}`", id),
+            first_seen: 0,
+            last_seen: 0,
+            occurrences,
+        }
+    }
+
+    #[test]
+    fn test_exit_code_encodes_the_findings() {
+        let mut summary = Summary {
+            command: "run",
+            seed: None,
+            verdict: None,
+            report_path: None,
+            coverage: CoverageSummary {
+                instrumented_points: 10,
+                covered_points: 5,
+                coverage_percent: 50.0,
+            },
+            findings: vec![],
+        };
+        assert_eq!(summary.exit_code(true), 0);
+
+        summary.findings.push(known("1", 1));
+        assert_eq!(summary.exit_code(true), EXIT_FINDINGS);
+        assert_eq!(summary.exit_code(false), 0);
+    }
+
+    #[test]
+    fn test_replay_summary_counts_the_new_hits() {
+        let mut before = FindingsDb::default();
+        before.findings.insert("a".into(), known("1", 3));
+        before.findings.insert("b".into(), known("2", 1));
+        assert_eq!(
+            replay_summary(&before, &before),
+            "🧾 No finding while replaying the corpus"
+        );
+
+        let mut after = FindingsDb::default();
+        after.findings.insert("a".into(), known("1", 5));
+        after.findings.insert("b".into(), known("2", 1));
+        after.findings.insert("c".into(), known("3", 1));
+        assert_eq!(
+            replay_summary(&before, &after),
+            "🧾 3 findings while replaying the corpus, 2 unique bugs (1 new)\n   \
+             PHINK-1a2b3c4d-1 x2 contract trapped in `msg_1`\n   \
+             PHINK-1a2b3c4d-3 x1 contract trapped in `msg_3`"
+        );
+    }
+}
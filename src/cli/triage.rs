@@ -0,0 +1,259 @@
+use crate::{
+    cli::ziggy::ZiggyConfig,
+    fuzzer::{
+        bug::BugType,
+        finding::KnownFinding,
+        reproducer::Reproducer,
+    },
+};
+use prettytable::{
+    row,
+    Table,
+};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Command,
+        Stdio,
+    },
+};
+use tracing::{
+    info,
+    warn,
+};
+
+/// Under `output_dir`, the campaign the crashes are replayed in, along with
+/// the summary of the triage
+pub const TRIAGE_DIR: &str = "triage";
+pub const TRIAGE_SUMMARY: &str = "summary.txt";
+
+/// What replaying a crash of AFL++ gave
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Classification {
+    /// The crash raised this finding, by its bug ID and its description, e.g.
+    /// `invariant `phink_assert_x` violated`
+    Bug { id: String, description: String },
+    /// The crash doesn't contain any valid message
+    DecodeFailure,
+    /// The crash ran without raising any finding, e.g. with another version of
+    /// the contract or of the configuration
+    NotReproducible,
+    /// `phink execute` itself failed on the crash
+    HarnessFailure(String),
+}
+
+impl Classification {
+    /// Classification out of the output of `phink execute --output json`. A
+    /// crash raising several findings, e.g. a trap right after a broken
+    /// invariant, is classified by its invariant, else by its first bug ID.
+    pub fn from_summary(stdout: &str) -> Option<Self> {
+        let summary: Value = serde_json::from_str(stdout.lines().last()?).ok()?;
+        let findings: Vec<KnownFinding> =
+            serde_json::from_value(summary["findings"].clone()).ok()?;
+        let finding = findings.into_iter().min_by_key(|finding| {
+            (
+                finding.bug_type != BugType::InvariantViolated,
+                finding.reference().to_string(),
+            )
+        });
+        Some(match finding {
+            Some(finding) => {
+                Classification::Bug {
+                    id: finding.reference().to_string(),
+                    description: finding.description,
+                }
+            }
+            None => Classification::NotReproducible,
+        })
+    }
+
+    /// Execute `seed` on the built contract of `contract_path` with `phink
+    /// execute`, in the campaign of `output_dir`
+    pub fn replay(
+        global_args: &[String],
+        seed: &Path,
+        contract_path: &Path,
+        output_dir: &Path,
+    ) -> Self {
+        let output = env::current_exe().and_then(|phink| {
+            Command::new(phink)
+                .args(global_args)
+                .arg("--quiet")
+                .arg("execute")
+                .arg(seed)
+                .arg(contract_path)
+                .args(["--output", "json"])
+                .env("PHINK_OUTPUT_DIR", output_dir)
+                .env("PHINK_HALT_ON_BUG", "false")
+                .stderr(Stdio::null())
+                .output()
+        });
+        match output {
+            Ok(output) => {
+                Self::from_summary(&String::from_utf8_lossy(&output.stdout))
+                    .unwrap_or_else(|| {
+                        warn!("⚠️ `phink execute` failed on {}", seed.display());
+                        Classification::HarnessFailure(output.status.to_string())
+                    })
+            }
+            Err(e) => Classification::HarnessFailure(e.to_string()),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Classification::Bug { id, description } => {
+                format!("{} ({})", description, id)
+            }
+            Classification::DecodeFailure => "decode failure".into(),
+            Classification::NotReproducible => "not reproducible".into(),
+            Classification::HarnessFailure(reason) => {
+                format!("harness failure: {}", reason)
+            }
+        }
+    }
+}
+
+/// Replays every crash saved by AFL++ with `phink execute`, each in the same
+/// campaign of its own under `<output_dir>/triage`, then groups the crashes by
+/// the bug they reproduce
+pub struct Triage {
+    ziggy: ZiggyConfig,
+    /// Arguments given to `phink` before the `execute` command, e.g.
+    /// `--config`
+    global_args: Vec<String>,
+    crash_dir: PathBuf,
+}
+
+impl Triage {
+    pub fn new(ziggy: ZiggyConfig, global_args: Vec<String>, crash_dir: PathBuf) -> Self {
+        Self {
+            ziggy,
+            global_args,
+            crash_dir,
+        }
+    }
+
+    /// Classify every crash, print the summary table and write it to
+    /// `<output_dir>/triage/summary.txt`, returning its path
+    pub fn run(&self) -> Result<PathBuf, String> {
+        let crashes = self.crashes()?;
+        if crashes.is_empty() {
+            return Err(format!("🙅 No crash found in {}", self.crash_dir.display()));
+        }
+
+        let triage_dir = self.ziggy.config.output_dir().join(TRIAGE_DIR);
+        let _ = fs::remove_dir_all(&triage_dir);
+        fs::create_dir_all(&triage_dir)
+            .map_err(|e| format!("🙅 Can't create {}: {}", triage_dir.display(), e))?;
+        let reproducer = Reproducer::new(self.ziggy.clone())?;
+
+        let mut groups: BTreeMap<Classification, Vec<PathBuf>> = BTreeMap::new();
+        for (i, crash) in crashes.iter().enumerate() {
+            info!(
+                "🔎 Replaying crash {}/{}: {}",
+                i + 1,
+                crashes.len(),
+                crash.display()
+            );
+            let classification = self.classify(&reproducer, crash, &triage_dir);
+            groups
+                .entry(classification)
+                .or_default()
+                .push(crash.clone());
+        }
+
+        let table = Self::table(&groups);
+        table.printstd();
+        let summary = triage_dir.join(TRIAGE_SUMMARY);
+        fs::write(&summary, table.to_string())
+            .map_err(|e| format!("🙅 Can't write {}: {}", summary.display(), e))?;
+        Ok(summary)
+    }
+
+    /// Every file of `crash_dir` but the `README.txt` of AFL++
+    fn crashes(&self) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(&self.crash_dir)
+            .map_err(|e| format!("🙅 Can't read {}: {}", self.crash_dir.display(), e))?;
+        let mut crashes: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path.file_name().map_or(false, |name| name != "README.txt")
+            })
+            .collect();
+        crashes.sort();
+        Ok(crashes)
+    }
+
+    fn classify(
+        &self,
+        reproducer: &Reproducer,
+        crash: &Path,
+        triage_dir: &Path,
+    ) -> Classification {
+        let decodes =
+            fs::read(crash).map_or(false, |input| reproducer.describe(&input).is_ok());
+        if !decodes {
+            return Classification::DecodeFailure;
+        }
+
+        Classification::replay(
+            &self.global_args,
+            crash,
+            &self.ziggy.contract_path,
+            triage_dir,
+        )
+    }
+
+    fn table(groups: &BTreeMap<Classification, Vec<PathBuf>>) -> Table {
+        let mut table = Table::new();
+        table.add_row(row!["Classification", "Crashes", "First crash"]);
+        for (classification, crashes) in groups {
+            table.add_row(row![
+                classification.label(),
+                crashes.len(),
+                crashes[0].display()
+            ]);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crash_is_classified_from_the_summary() {
+        let stdout = "🌱 Seed of version 1\n{\"command\":\"execute\",\"verdict\":\"failed\",\"coverage\":{\"instrumented_points\":3,\"covered_points\":1,\"coverage_percent\":33.3},\"findings\":[{\"id\":\"0a1b\",\"bug_type\":\"InvariantViolated\",\"description\":\"invariant `phink_assert_x` violated\",\"first_seen\":1,\"last_seen\":1,\"occurrences\":1}]}";
+        assert_eq!(
+            Classification::from_summary(stdout),
+            Some(Classification::Bug {
+                id: "0a1b".into(),
+                description: "invariant `phink_assert_x` violated".into(),
+            })
+        );
+
+        let both = "{\"command\":\"execute\",\"findings\":[{\"id\":\"2c3d\",\"bug_type\":\"ContractTrapped\",\"description\":\"contract trapped\",\"first_seen\":1,\"last_seen\":1,\"occurrences\":1},{\"id\":\"0a1b\",\"bug_type\":\"InvariantViolated\",\"description\":\"invariant `phink_assert_x` violated\",\"first_seen\":1,\"last_seen\":1,\"occurrences\":1}]}";
+        assert_eq!(
+            Classification::from_summary(both),
+            Classification::from_summary(stdout)
+        );
+
+        let passed = "{\"command\":\"execute\",\"verdict\":\"passed\",\"findings\":[]}";
+        assert_eq!(
+            Classification::from_summary(passed),
+            Some(Classification::NotReproducible)
+        );
+        assert_eq!(Classification::from_summary("thread panicked"), None);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    cli::{
+        dashboard::Dashboard,
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::{
+        format,
+        fuzz::CORPUS_DIR,
+        parser::{
+            parse_selectors,
+            TRANSFER_SELECTOR,
+            UPGRADE_SELECTOR,
+        },
+    },
+    instrumenter::instrumentation::{
+        ContractBuilder,
+        ContractInstrumenter,
+        Instrumenter,
+    },
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    env,
+    fs,
+    os::unix::process::CommandExt,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Child,
+        Command,
+    },
+    thread,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
+use tracing::{
+    error,
+    info,
+    warn,
+};
+use walkdir::WalkDir;
+
+const POLL_EVERY: Duration = Duration::from_secs(1);
+
+/// Edit-fuzz-fix loop: every time a source of the contract changes, it is
+/// instrumented and built again, the corpus is migrated, and a short fuzzing
+/// campaign is restarted
+pub struct Watcher {
+    ziggy: ZiggyConfig,
+    /// Arguments given to `phink` before the `fuzz` command, e.g. `--config`
+    global_args: Vec<String>,
+    duration: Duration,
+}
+
+impl Watcher {
+    pub fn new(ziggy: ZiggyConfig, global_args: Vec<String>, duration: Duration) -> Self {
+        Self {
+            ziggy,
+            global_args,
+            duration,
+        }
+    }
+
+    pub fn run(&self) -> ! {
+        let mut instrumented: Option<PathBuf> = None;
+        loop {
+            let sources = Self::sources(&self.ziggy.contract_path);
+
+            match self.rebuild(instrumented.take()) {
+                Ok(contract_path) => {
+                    let migrated = ZiggyConfig::new(
+                        self.ziggy.config.clone(),
+                        contract_path.clone(),
+                    );
+                    let (kept, dropped) = Self::migrate_corpus(&migrated);
+                    info!(
+                        "🧬 Corpus migrated, {} inputs kept, {} dropped",
+                        kept, dropped
+                    );
+                    match self.fuzz(&contract_path) {
+                        Ok(child) => self.wait_for_change(&sources, Some(child)),
+                        Err(e) => {
+                            error!("🙅 Can't start `phink fuzz`: {}", e);
+                            self.wait_for_change(&sources, None)
+                        }
+                    }
+                    instrumented = Some(contract_path);
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    self.wait_for_change(&sources, None);
+                }
+            }
+        }
+    }
+
+    /// Instrument and build the contract in a new directory, removing the
+    /// one of the previous iteration
+    fn rebuild(&self, previous: Option<PathBuf>) -> Result<PathBuf, String> {
+        if let Some(previous) = previous {
+            let _ = fs::remove_dir_all(previous);
+        }
+        let mut instrumenter = Instrumenter::new(self.ziggy.contract_path.clone());
+        instrumenter.instrument()?.build()?;
+        info!(
+            "🤞 Contract instrumented and compiled into {}",
+            instrumenter.contract_dir.display()
+        );
+        Ok(instrumenter.contract_dir)
+    }
+
+    fn fuzz(&self, contract_path: &Path) -> std::io::Result<Child> {
+        Command::new(env::current_exe()?)
+            .args(&self.global_args)
+            .arg("fuzz")
+            .arg(contract_path)
+            // Own process group, so that AFL++ gets stopped along with `cargo ziggy`
+            .process_group(0)
+            .spawn()
+    }
+
+    /// Block until a source changes, stopping the campaign once it's done
+    /// or outdated
+    fn wait_for_change(
+        &self,
+        sources: &BTreeMap<PathBuf, SystemTime>,
+        child: Option<Child>,
+    ) {
+        let started = Instant::now();
+        let mut child = child;
+        loop {
+            thread::sleep(POLL_EVERY);
+
+            let changed = Self::sources(&self.ziggy.contract_path) != *sources;
+            if child.is_some() && (changed || started.elapsed() >= self.duration) {
+                Self::stop(child.take().unwrap());
+                if !changed {
+                    info!("👀 Campaign done, waiting for a change of the contract");
+                }
+            }
+            if changed {
+                info!("👀 The contract changed, starting over");
+                return;
+            }
+        }
+    }
+
+    fn stop(mut child: Child) {
+        let group = format!("-{}", child.id());
+        if Command::new("kill")
+            .args(["-INT", "--", &group])
+            .status()
+            .map_or(true, |status| !status.success())
+        {
+            warn!("🙅 Couldn't interrupt the campaign, killing it");
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+
+    /// Keep the inputs of the corpus and of the AFL++ queues calling only
+    /// messages the new metadata still has, and drop the others from the
+    /// corpus. Returns how many got kept and dropped.
+    fn migrate_corpus(ziggy: &ZiggyConfig) -> (usize, usize) {
+        let labels = Dashboard::labels(&ziggy.contract_path);
+        let corpus_dir = ziggy.config.output_dir().join(CORPUS_DIR);
+        let _ = fs::create_dir_all(&corpus_dir);
+        let queues = fs::read_dir(ziggy.afl_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join("queue"));
+
+        let mut inputs: HashMap<String, (PathBuf, Vec<u8>)> = HashMap::new();
+        for dir in [corpus_dir.clone()].into_iter().chain(queues) {
+            for path in Self::files(&dir) {
+                if let Ok(input) = fs::read(&path) {
+                    let name = hex::encode(&sp_io::hashing::blake2_256(&input)[..8]);
+                    inputs.entry(name).or_insert((path, input));
+                }
+            }
+        }
+
+        let (mut kept, mut dropped) = (0, 0);
+        for (name, (path, input)) in inputs {
+            let selectors = parse_selectors(&input, &ziggy.config);
+            // The indices of `FuzzCall`s point to a message whatever changed
+            let valid = format::scale_calls(&input).is_some()
+                || !selectors.is_empty()
+                    && selectors.iter().all(|selector| {
+                        *selector == UPGRADE_SELECTOR
+                            || *selector == TRANSFER_SELECTOR
+                            || labels.contains_key(&hex::encode(selector))
+                    });
+            if valid {
+                if !path.starts_with(&corpus_dir) {
+                    let _ =
+                        fs::write(corpus_dir.join(format!("migrated_{}", name)), input);
+                }
+                kept += 1;
+            } else {
+                if path.starts_with(&corpus_dir) {
+                    let _ = fs::remove_file(path);
+                }
+                dropped += 1;
+            }
+        }
+        (kept, dropped)
+    }
+
+    /// Last modification of every source of the contract
+    fn sources(contract_path: &Path) -> BTreeMap<PathBuf, SystemTime> {
+        WalkDir::new(contract_path)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != "target")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .path()
+                    .extension()
+                    .map_or(false, |ext| ext == "rs" || ext == "sol")
+                    || entry.file_name() == "Cargo.toml"
+            })
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((entry.into_path(), modified))
+            })
+            .collect()
+    }
+
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect()
+    }
+}
@@ -1,34 +1,64 @@
 use std::{
     fs,
-    fs::File,
     io,
-    io::{
-        BufRead,
-        Write,
-    },
-    path::{
-        Path,
-        PathBuf,
-    },
+    io::BufRead,
+    path::PathBuf,
     process::{
         Command,
         Stdio,
     },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
 
 use serde_derive::{
     Deserialize,
     Serialize,
 };
+use tracing::{
+    debug,
+    info,
+    warn,
+};
 
 use crate::{
-    cli::config::Configuration,
+    cli::config::{
+        Configuration,
+        START_FUZZING_ENV,
+    },
     fuzzer::{
-        fuzz::DICT_FILE,
+        events::{
+            CoverageEvents,
+            Event,
+        },
+        finding::FindingsDb,
+        fuzz::{
+            CORPUS_DIR,
+            DICT_FILE,
+        },
+        harvest::HARVEST_DICT_FILE,
+        notify::flush_notifications,
         parser::MIN_SEED_LEN,
+        scheduler::RARE_POWER_SCHEDULE,
+        stats::StatsTracker,
+        sync::CorpusSync,
     },
 };
 
+/// Set once `fuzz` got interrupted, e.g. by Ctrl-C. AFL++ gets the SIGINT of
+/// the whole process group and stops, then the campaign ends as it does at
+/// `max_executions`.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// How often `fuzz` reads the `fuzzer_stats` of AFL++ and prunes the stats
+const WATCH_EVERY: Duration = Duration::from_secs(10);
+
 pub enum ZiggyCommand {
     Run,
     Cover,
@@ -43,7 +73,8 @@ pub struct ZiggyConfig {
 }
 
 impl ZiggyConfig {
-    pub const ALLOWLIST_PATH: &'static str = "./output/phink/allowlist.txt";
+    /// File of `output_dir` listing the functions instrumented by AFL++
+    pub const ALLOWLIST_FILE: &'static str = "allowlist.txt";
     pub const AFL_DEBUG: &'static str = "1";
 
     pub fn new(config: Configuration, contract_path: PathBuf) -> Self {
@@ -59,11 +90,13 @@ impl ZiggyConfig {
 
     /// This function execute `cargo ziggy + command + args`
     fn start(
+        &self,
         command: ZiggyCommand,
         args: Vec<String>,
         env: Vec<(String, String)>,
     ) -> io::Result<()> {
-        let command_arg = Self::command_to_arg(&command)?;
+        let command_arg = self.command_to_arg(&command)?;
+        let allowlist = self.allowlist_path();
 
         let mut binding = Command::new("cargo");
         let command_builder = binding
@@ -72,11 +105,9 @@ impl ZiggyConfig {
             .env("AFL_FORKSRV_INIT_TMOUT", "10000000")
             .env(
                 "AFL_LLVM_ALLOWLIST",
-                Path::new(Self::ALLOWLIST_PATH)
+                allowlist
                     .canonicalize()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
+                    .unwrap_or_else(|_| allowlist.clone()),
             )
             .env("AFL_DEBUG", Self::AFL_DEBUG)
             .stdout(Stdio::piped());
@@ -99,19 +130,23 @@ impl ZiggyConfig {
         }
 
         let status = ziggy_child.wait()?;
-        if !status.success() {
-            eprintln!("🚫 Can't start `cargo ziggy`, command failed");
+        // An interrupted campaign stops with a non-zero status, yet ends normally
+        if !status.success() && !INTERRUPTED.load(Ordering::SeqCst) {
+            return Err(io::Error::other(format!(
+                "🚫 Can't start `cargo ziggy`, command failed with {}",
+                status
+            )));
         }
         Ok(())
     }
 
-    fn command_to_arg(command: &ZiggyCommand) -> Result<String, io::Error> {
+    fn command_to_arg(&self, command: &ZiggyCommand) -> Result<String, io::Error> {
         let command_arg = match command {
             ZiggyCommand::Run => "run",
             ZiggyCommand::Cover => "cover",
             ZiggyCommand::Fuzz => "fuzz",
             ZiggyCommand::Build => {
-                Self::build_llvm_allowlist()?;
+                self.build_llvm_allowlist()?;
                 "build"
             }
         };
@@ -125,33 +160,114 @@ impl ZiggyConfig {
             vec!["".parse().unwrap()]
         };
 
-        Self::start(ZiggyCommand::Build, build_args, vec![])?;
+        self.start(ZiggyCommand::Build, build_args, vec![])?;
 
-        println!("🏗️ Ziggy Build completed");
+        info!("🏗️ Ziggy Build completed");
 
         let mut fuzzing_args = vec![
             format!("--jobs={}", self.config.cores.unwrap_or_default()),
-            format!("--dict={}", DICT_FILE),
+            format!(
+                "--dict={}",
+                self.config.output_dir().join(DICT_FILE).display()
+            ),
             format!("--minlength={}", MIN_SEED_LEN),
         ];
         if !self.config.use_honggfuzz {
             fuzzing_args.push("--no-honggfuzz".parse().unwrap())
         }
+        if let Some(max_executions) = self.config.max_executions {
+            // `-E` is per instance of AFL++
+            let per_instance =
+                max_executions / u64::from(self.config.cores.unwrap_or(1).max(1));
+            fuzzing_args.push(format!("--afl-flags=-E {}", per_instance.max(1)));
+        }
+        if self.config.schedule_rare_selectors.unwrap_or(false) {
+            fuzzing_args.push(format!("--afl-flags=-p {}", RARE_POWER_SCHEDULE));
+        }
+        let harvested = self.config.output_dir().join(HARVEST_DICT_FILE);
+        if fs::metadata(&harvested).map_or(false, |file| file.len() > 0) {
+            fuzzing_args.push(format!("--afl-flags=-x {}", harvested.display()));
+        }
+        if self.config.sync.is_some() {
+            // AFL++ requires the foreign sync directory to exist when it starts
+            let foreign_dir = CorpusSync::foreign_dir(self);
+            fs::create_dir_all(&foreign_dir)?;
+            fuzzing_args.push(format!("--afl-flags=-F {}", foreign_dir.display()));
+        }
+        fuzzing_args.extend(self.output_args("--corpus"));
 
-        let fuzz_config = vec![(
-            "PHINK_START_FUZZING_WITH_CONFIG".to_string(),
-            serde_json::to_string(self)?,
-        )];
+        if let Some(sync) = &self.config.sync {
+            CorpusSync::new(sync.clone(), self).spawn();
+        }
+
+        let mut fuzz_config =
+            vec![(START_FUZZING_ENV.to_string(), serde_json::to_string(self)?)];
+        if self.config.resume.unwrap_or(false) {
+            fuzz_config.push(("AFL_AUTORESUME".into(), "1".into()));
+        }
 
-        Self::start(ZiggyCommand::Fuzz, fuzzing_args, fuzz_config)
+        if let Err(e) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+            debug!("❗ Can't handle the interruption of the campaign: {}", e);
+        }
+
+        let output_dir = self.config.output_dir();
+        Event::CampaignStart {
+            cores: self.config.cores.unwrap_or_default(),
+        }
+        .emit(&output_dir);
+        // The processes of an earlier campaign all exited
+        self.retire_stats(Duration::ZERO);
+        let stop_watching = Arc::new(AtomicBool::new(false));
+        let watcher = self.watch(stop_watching.clone());
+        let fuzzed = self.start(ZiggyCommand::Fuzz, fuzzing_args, fuzz_config);
+        stop_watching.store(true, Ordering::SeqCst);
+        watcher.thread().unpark();
+        let _ = watcher.join();
+        self.retire_stats(Duration::ZERO);
+        Event::CampaignStop {
+            unique_findings: FindingsDb::load(&output_dir).findings.len(),
+        }
+        .emit(&output_dir);
+        if let Some(notify) = &self.config.notify {
+            flush_notifications(notify, &output_dir);
+        }
+        fuzzed
+    }
+
+    /// Every `WATCH_EVERY` while AFL++ runs, emit the coverage events and
+    /// prune the stats of the exited processes, until `stop` is set and the
+    /// thread unparked
+    fn watch(&self, stop: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+        let mut events = CoverageEvents::new(self.config.plateau_after_secs, self);
+        let stale_after = StatsTracker::stale_after(self.config.stats_interval_secs);
+        let ziggy = self.clone();
+        thread::spawn(move || {
+            loop {
+                thread::park_timeout(WATCH_EVERY);
+                if stop.load(Ordering::SeqCst) {
+                    return;
+                }
+                events.poll();
+                if let Some(stale_after) = stale_after {
+                    ziggy.retire_stats(stale_after);
+                }
+            }
+        })
+    }
+
+    fn retire_stats(&self, stale_after: Duration) {
+        let output_dir = self.config.output_dir();
+        if let Err(e) = StatsTracker::retire_dead(&output_dir, stale_after) {
+            warn!("🙅 Couldn't prune the stats of the exited processes: {}", e);
+        }
     }
 
     pub fn ziggy_cover(&self) -> io::Result<()> {
-        Self::start(
+        self.start(
             ZiggyCommand::Cover,
-            vec![],
+            self.output_args("--input"),
             vec![(
-                "PHINK_START_FUZZING_WITH_CONFIG".into(),
+                START_FUZZING_ENV.into(),
                 serde_json::to_string(self).unwrap(),
             )],
         )?;
@@ -159,35 +275,92 @@ impl ZiggyConfig {
     }
 
     pub fn ziggy_run(&self) -> io::Result<()> {
-        Self::start(
+        let hits = |db: FindingsDb| -> u64 {
+            db.findings.values().map(|known| known.occurrences).sum()
+        };
+        let before = hits(FindingsDb::load(&self.config.output_dir()));
+        let ran = self.start(
             ZiggyCommand::Run,
-            vec![],
+            self.output_args("--inputs"),
             vec![(
-                "PHINK_START_FUZZING_WITH_CONFIG".into(),
+                START_FUZZING_ENV.into(),
                 serde_json::to_string(self).unwrap(),
             )],
-        )?;
+        );
+        // With `halt_on_bug`, the first finding aborts the replay on purpose
+        if ran.is_err() && hits(FindingsDb::load(&self.config.output_dir())) == before {
+            return ran;
+        }
+        if let Some(notify) = &self.config.notify {
+            flush_notifications(notify, &self.config.output_dir());
+        }
         Ok(())
     }
 
-    /// Builds the LLVM allowlist if it doesn't already exist.
-    fn build_llvm_allowlist() -> Result<(), io::Error> {
-        let path = Path::new(Self::ALLOWLIST_PATH);
+    /// Point `cargo ziggy` to the corpus of `output_dir`, passed as
+    /// `corpus_flag`. If `output_dir` is set, the files of AFL++ (e.g. the
+    /// crashes) are moved to `<output_dir>/ziggy` too, so that two
+    /// campaigns don't share them.
+    fn output_args(&self, corpus_flag: &str) -> Vec<String> {
+        let mut args = vec![format!(
+            "{}={}",
+            corpus_flag,
+            self.config.output_dir().join(CORPUS_DIR).display()
+        )];
+        if let Some(ziggy_output) = self.ziggy_output() {
+            args.push(format!("--ziggy-output={}", ziggy_output.display()));
+        }
+        args
+    }
 
-        if path.exists() {
-            println!("❗ AFL_LLVM_ALLOWLIST already exists... skipping");
+    /// `--ziggy-output`, or `None` for the default `./output`
+    fn ziggy_output(&self) -> Option<PathBuf> {
+        self.config
+            .output_dir
+            .as_ref()
+            .map(|output_dir| output_dir.join("ziggy"))
+    }
+
+    /// Where AFL++ keeps the queue and the `fuzzer_stats` of each instance.
+    /// With the default `output_dir`, it is the one of ziggy itself.
+    pub fn afl_dir(&self) -> PathBuf {
+        self.ziggy_output()
+            .map(|ziggy_output| ziggy_output.join("phink").join("afl"))
+            .filter(|afl_dir| afl_dir.exists())
+            .unwrap_or_else(|| self.config.output_dir().join("afl"))
+    }
+
+    /// `ALLOWLIST_FILE` of `output_dir`, so that two campaigns don't share it
+    fn allowlist_path(&self) -> PathBuf {
+        self.config.output_dir().join(Self::ALLOWLIST_FILE)
+    }
+
+    /// Builds the LLVM allowlist, unless it already lists the expected
+    /// functions. An allowlist written by an older version is rewritten, so
+    /// that the new instrumented functions get compiled in.
+    fn build_llvm_allowlist(&self) -> Result<(), io::Error> {
+        let path = self.allowlist_path();
+
+        let functions = [
+            "redirect_coverage*",
+            "redirect_features*",
+            "should_stop_now*",
+            "parse_input*",
+        ];
+        let allowlist: String = functions
+            .iter()
+            .map(|func| format!("fun: {}\n", func))
+            .collect();
+
+        if fs::read_to_string(&path).is_ok_and(|existing| existing == allowlist) {
+            debug!("❗ AFL_LLVM_ALLOWLIST already exists... skipping");
             return Ok(());
         }
 
         fs::create_dir_all(path.parent().unwrap())?;
-        let mut allowlist_file = File::create(path)?;
-
-        let functions = ["redirect_coverage*", "should_stop_now*", "parse_input*"];
-        for func in &functions {
-            writeln!(allowlist_file, "fun: {}", func)?;
-        }
+        fs::write(&path, allowlist)?;
 
-        println!("✅ AFL_LLVM_ALLOWLIST created successfully");
+        debug!("✅ AFL_LLVM_ALLOWLIST created successfully");
         Ok(())
     }
 }
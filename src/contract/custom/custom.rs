@@ -16,6 +16,10 @@ use sp_core::{
 };
 use sp_runtime::BuildStorage;
 use std::fs;
+use tracing::{
+    error,
+    info,
+};
 
 /// This file is made to be customized
 /// Feel free to remove, add, modify code :)
@@ -49,7 +53,7 @@ impl DevelopperPreferences for Preferences {
             match fs::read(format!("{}/target/ink/adder/adder.wasm", ink_fuzzed_path)) {
                 Ok(data) => data.to_owned(),
                 Err(_) => {
-                    println!("❌ Error reading adder wasm file");
+                    error!("❌ Error reading adder wasm file");
                     return;
                 }
             },
@@ -58,8 +62,8 @@ impl DevelopperPreferences for Preferences {
         );
 
         match adder {
-            Ok(code) => println!("ℹ️ Adder hash: {:?}", code.code_hash),
-            Err(_) => println!("❌ Error uploading adder code"),
+            Ok(code) => info!("ℹ️ Adder hash: {:?}", code.code_hash),
+            Err(_) => error!("❌ Error uploading adder code"),
         }
 
         let accumulator = Contracts::bare_upload_code(
@@ -70,7 +74,7 @@ impl DevelopperPreferences for Preferences {
             )) {
                 Ok(data) => data.to_owned(),
                 Err(_) => {
-                    println!("❌ Error reading accumulator wasm file");
+                    error!("❌ Error reading accumulator wasm file");
                     return;
                 }
             },
@@ -79,8 +83,8 @@ impl DevelopperPreferences for Preferences {
         );
 
         match accumulator {
-            Ok(code) => println!("ℹ️ Accumulator hash: {:?}", code.code_hash),
-            Err(_) => println!("❌ Error uploading accumulator code"),
+            Ok(code) => info!("ℹ️ Accumulator hash: {:?}", code.code_hash),
+            Err(_) => error!("❌ Error uploading accumulator code"),
         }
 
         let subber = Contracts::bare_upload_code(
@@ -88,7 +92,7 @@ impl DevelopperPreferences for Preferences {
             match fs::read(format!("{}/target/ink/subber/subber.wasm", ink_fuzzed_path)) {
                 Ok(data) => data.to_owned(),
                 Err(_) => {
-                    println!("❌ Error reading subber wasm file");
+                    error!("❌ Error reading subber wasm file");
                     return;
                 }
             },
@@ -97,8 +101,8 @@ impl DevelopperPreferences for Preferences {
         );
 
         match subber {
-            Ok(code) => println!("ℹ️ Subber hash: {:?}", code.code_hash),
-            Err(_) => println!("❌ Error uploading subber code"),
+            Ok(code) => info!("ℹ️ Subber hash: {:?}", code.code_hash),
+            Err(_) => error!("❌ Error uploading subber code"),
         }
     }
 }
@@ -0,0 +1,97 @@
+use crate::contract::fork::ChainFork;
+use serde_json::Value;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
+};
+use tracing::{
+    info,
+    warn,
+};
+
+/// Under `output_dir`, one directory per downloaded code hash
+pub const FETCHED_DIR: &str = "fetched";
+
+/// Code of a contract downloaded from a live chain by its code hash, laid out
+/// as a built contract, i.e. `target/ink/<name>.{wasm,json}`, so that it can
+/// be fuzzed without the sources of the contract. The code isn't
+/// instrumented, hence fuzzed without any coverage feedback from it.
+pub struct DeployedCode {
+    rpc_url: String,
+    code_hash: String,
+    /// Path or URL of the metadata, which isn't stored on-chain. `{code_hash}`
+    /// is replaced in a URL, e.g. to query a registry of verified contracts.
+    metadata: Option<String>,
+}
+
+impl DeployedCode {
+    pub fn new(rpc_url: String, code_hash: String, metadata: Option<String>) -> Self {
+        Self {
+            rpc_url,
+            code_hash,
+            metadata,
+        }
+    }
+
+    /// Download the code and the metadata, and return the directory to fuzz
+    pub fn download(&self, output_dir: &Path) -> Result<PathBuf, String> {
+        let metadata = self.metadata()?;
+        let specs: Value = serde_json::from_str(&metadata)
+            .map_err(|e| format!("🙅 The metadata isn't valid JSON: {}", e))?;
+        if let Some(hash) = specs["source"]["hash"].as_str() {
+            if !hash.eq_ignore_ascii_case(&self.code_hash) {
+                warn!(
+                    "❓ The metadata was built for the code {}, not {}",
+                    hash, self.code_hash
+                );
+            }
+        }
+
+        info!("📥 Downloading {} from {}", self.code_hash, self.rpc_url);
+        let code = ChainFork::connect(&self.rpc_url)?.fetch_code(&self.code_hash)?;
+
+        let name = self.code_hash.trim_start_matches("0x");
+        let contract_dir = output_dir.join(FETCHED_DIR).join(name);
+        let ink_dir = contract_dir.join("target/ink");
+        fs::create_dir_all(&ink_dir)
+            .map_err(|e| format!("🙅 Can't create {}: {}", ink_dir.display(), e))?;
+        fs::write(ink_dir.join(format!("{}.wasm", name)), &code)
+            .and_then(|_| fs::write(ink_dir.join(format!("{}.json", name)), &metadata))
+            .map_err(|e| format!("🙅 Can't write into {}: {}", ink_dir.display(), e))?;
+
+        warn!("🙅 The downloaded code isn't instrumented, it's fuzzed without coverage");
+        info!(
+            "📥 Downloaded {} bytes of code into {}",
+            code.len(),
+            contract_dir.display()
+        );
+        contract_dir
+            .canonicalize()
+            .map_err(|e| format!("🙅 Can't find {}: {}", contract_dir.display(), e))
+    }
+
+    fn metadata(&self) -> Result<String, String> {
+        let location = self.metadata.as_ref().ok_or(
+            "🙅 The metadata of a contract isn't stored on-chain, give it with `--metadata <path or URL>`",
+        )?;
+
+        if location.starts_with("http://") || location.starts_with("https://") {
+            let url = location.replace("{code_hash}", &self.code_hash);
+            ureq::get(&url)
+                .timeout(Duration::from_secs(30))
+                .call()
+                .map_err(|e| {
+                    format!("🙅 Can't download the metadata from {}: {}", url, e)
+                })?
+                .into_string()
+                .map_err(|e| format!("🙅 Can't read the metadata from {}: {}", url, e))
+        } else {
+            fs::read_to_string(location)
+                .map_err(|e| format!("🙅 Can't read {}: {}", location, e))
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    cli::config::ChainExtensionMock,
+    contract::{
+        remote::AccountIdOf,
+        runtime::Runtime,
+    },
+};
+use pallet_contracts::chain_extension::{
+    ChainExtension,
+    Environment,
+    Ext,
+    InitState,
+    RegisteredChainExtension,
+    RetVal,
+};
+use parity_scale_codec::{
+    Decode,
+    Encode,
+};
+use sp_runtime::DispatchError;
+use std::{
+    cell::RefCell,
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+};
+
+/// Identifier of the coverage chain extension, i.e `PH`. The instrumented
+/// contract calls the function `1` of this extension with each coverage
+/// identifier, which is `ChainExtensionMethod::build(0x5048_0001)` on the ink!
+/// side.
+pub const COVERAGE_EXTENSION_ID: u16 = 0x5048;
+pub const COVERAGE_FUNC_ID: u16 = 0x0001;
+/// Functions of the coverage extension keeping the ghost variables, i.e. the
+/// `phink_ghost_add!`, `phink_ghost_set!` and `phink_ghost_get!` macros of the
+/// instrumented contract
+pub const GHOST_ADD_FUNC_ID: u16 = 0x0002;
+pub const GHOST_SET_FUNC_ID: u16 = 0x0003;
+pub const GHOST_GET_FUNC_ID: u16 = 0x0004;
+/// Start of the keys of the ghost variables in the top storage, followed by
+/// the address of the contract and the name of the variable
+pub const GHOST_PREFIX: &[u8] = b":phink:ghost:";
+
+/// Answer of a mocked chain extension function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockResponse {
+    /// Status code returned to the contract, `0` meaning success
+    pub status: u32,
+    /// SCALE-encoded output, written into the contract's buffer
+    pub output: Vec<u8>,
+}
+
+/// Receives the SCALE-encoded input of the contract
+pub type MockHandler = Box<dyn FnMut(&[u8]) -> MockResponse>;
+
+thread_local! {
+    /// Coverage identifiers hit since the last `take_coverage()`, in order
+    static COVERAGE_HITS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    /// Mocked functions, by `(extension id << 16) | function id`
+    static MOCKS: RefCell<HashMap<u32, MockHandler>> = RefCell::new(HashMap::new());
+    /// Bytes of the current input, consumed by the fuzzed mocks
+    static FUZZED_BYTES: RefCell<(Vec<u8>, usize)> = const { RefCell::new((Vec::new(), 0)) };
+}
+
+/// Register `handler` for the function `func_id` of the chain extension
+/// `ext_id`, i.e `#[ink::chain_extension(extension = ext_id)]` and
+/// `#[ink(function = func_id)]` on the ink! side
+pub fn register_mock(
+    ext_id: u16,
+    func_id: u16,
+    handler: impl FnMut(&[u8]) -> MockResponse + 'static,
+) {
+    let id = (u32::from(ext_id) << 16) | u32::from(func_id);
+    MOCKS.with(|mocks| mocks.borrow_mut().insert(id, Box::new(handler)));
+}
+
+/// Register the mocks scripted in the configuration file
+pub fn register_config_mocks(mocks: &[ChainExtensionMock]) {
+    for mock in mocks {
+        let output = mock
+            .output
+            .as_ref()
+            .map(|output| {
+                hex::decode(output.trim_start_matches("0x"))
+                    .expect("🙅 Can't hex-decode the `output` of a chain extension mock")
+            })
+            .unwrap_or_default();
+        let status = mock.status.unwrap_or(0);
+        let fuzzed_len = mock.fuzzed_len;
+
+        register_mock((mock.id >> 16) as u16, mock.id as u16, move |_| {
+            MockResponse {
+                status,
+                output: fuzzed_len.map_or_else(|| output.clone(), fuzzed_output),
+            }
+        });
+    }
+}
+
+/// Set the bytes returned by the fuzzed mocks for the upcoming input, so that
+/// the fuzzer controls the answers of the chain extension
+pub fn set_fuzzed_bytes(bytes: &[u8]) {
+    FUZZED_BYTES.with(|fuzzed| *fuzzed.borrow_mut() = (bytes.to_vec(), 0));
+}
+
+/// Take `len` bytes out of the current input, wrapping around if needed
+fn fuzzed_output(len: usize) -> Vec<u8> {
+    FUZZED_BYTES.with(|fuzzed| {
+        let (bytes, cursor) = &mut *fuzzed.borrow_mut();
+        if bytes.is_empty() {
+            return vec![0; len];
+        }
+        (0..len)
+            .map(|_| {
+                let byte = bytes[*cursor % bytes.len()];
+                *cursor += 1;
+                byte
+            })
+            .collect()
+    })
+}
+
+/// Return the coverage identifiers hit since the last call, and reset them
+pub fn take_coverage() -> Vec<u64> {
+    COVERAGE_HITS.with(|hits| std::mem::take(&mut *hits.borrow_mut()))
+}
+
+fn ghost_key(contract: &AccountIdOf<Runtime>, name: &[u8]) -> Vec<u8> {
+    [GHOST_PREFIX, contract.as_ref(), name].concat()
+}
+
+/// Value of the ghost variable `name` of `contract`, `0` if never written
+pub fn ghost_value(contract: &AccountIdOf<Runtime>, name: &[u8]) -> i128 {
+    sp_io::storage::get(&ghost_key(contract, name))
+        .and_then(|value| i128::decode(&mut &value[..]).ok())
+        .unwrap_or(0)
+}
+
+/// Every ghost variable written by `contract`, by name
+pub fn ghost_state(contract: &AccountIdOf<Runtime>) -> BTreeMap<String, i128> {
+    let prefix = ghost_key(contract, &[]);
+    let mut state = BTreeMap::new();
+    let mut key = prefix.clone();
+    while let Some(next) = sp_io::storage::next_key(&key) {
+        let Some(name) = next.strip_prefix(prefix.as_slice()) else {
+            break;
+        };
+        state.insert(
+            String::from_utf8_lossy(name).into_owned(),
+            ghost_value(contract, name),
+        );
+        key = next;
+    }
+    state
+}
+
+/// Coverage channel between the instrumented contract and the harness. This
+/// replaces the parsing of `COV=` strings out of the debug buffer, which was
+/// slow and polluted the contract's own debug messages. Any other function is
+/// forwarded to the registered mocks.
+///
+/// The same extension keeps the ghost variables of the properties, e.g. the
+/// sum of all the deposits, which the contract doesn't store. They live in the
+/// top storage rather than in the contract, so that they don't change its
+/// storage deposit, and are rolled back along with a reverted message or an
+/// input, and kept within the end-states of `stateful_snapshots`.
+#[derive(Default)]
+pub struct PhinkCoverageExtension;
+
+impl ChainExtension<Runtime> for PhinkCoverageExtension {
+    fn call<E: Ext<T = Runtime>>(
+        &mut self,
+        env: Environment<E, InitState>,
+    ) -> Result<RetVal, DispatchError> {
+        if env.ext_id() != COVERAGE_EXTENSION_ID {
+            return Self::call_mock(env);
+        }
+        match env.func_id() {
+            COVERAGE_FUNC_ID => {
+                let mut env = env.buf_in_buf_out();
+                let id: u32 = env.read_as()?;
+                COVERAGE_HITS.with(|hits| hits.borrow_mut().push(id.into()));
+                Ok(RetVal::Converging(0))
+            }
+            GHOST_ADD_FUNC_ID | GHOST_SET_FUNC_ID | GHOST_GET_FUNC_ID => {
+                Self::call_ghost(env)
+            }
+            _ => Self::call_mock(env),
+        }
+    }
+}
+
+impl PhinkCoverageExtension {
+    fn call_ghost<E: Ext<T = Runtime>>(
+        env: Environment<E, InitState>,
+    ) -> Result<RetVal, DispatchError> {
+        let func_id = env.func_id();
+        let mut env = env.buf_in_buf_out();
+        let contract = env.ext().address().clone();
+        let in_len = env.in_len();
+
+        if func_id == GHOST_GET_FUNC_ID {
+            let name: Vec<u8> = env.read_as_unbounded(in_len)?;
+            env.write(&ghost_value(&contract, &name).encode(), false, None)?;
+            return Ok(RetVal::Converging(0));
+        }
+
+        let (name, operand): (Vec<u8>, i128) = env.read_as_unbounded(in_len)?;
+        let value = match func_id {
+            GHOST_ADD_FUNC_ID => ghost_value(&contract, &name).saturating_add(operand),
+            _ => operand,
+        };
+        sp_io::storage::set(&ghost_key(&contract, &name), &value.encode());
+        Ok(RetVal::Converging(0))
+    }
+
+    fn call_mock<E: Ext<T = Runtime>>(
+        env: Environment<E, InitState>,
+    ) -> Result<RetVal, DispatchError> {
+        let id = (u32::from(env.ext_id()) << 16) | u32::from(env.func_id());
+        let mut env = env.buf_in_buf_out();
+        let input = env.read(env.in_len())?;
+
+        let response = MOCKS
+            .with(|mocks| {
+                mocks
+                    .borrow_mut()
+                    .get_mut(&id)
+                    .map(|handler| handler(&input))
+            })
+            .ok_or(DispatchError::Other(
+                "🙅 Unknown chain extension, register a mock for it",
+            ))?;
+
+        env.write(&response.output, false, None)?;
+        Ok(RetVal::Converging(response.status))
+    }
+}
+
+impl RegisteredChainExtension<Runtime> for PhinkCoverageExtension {
+    const ID: u16 = COVERAGE_EXTENSION_ID;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzed_output_wraps_around_the_input() {
+        set_fuzzed_bytes(&[1, 2, 3]);
+        assert_eq!(fuzzed_output(2), vec![1, 2]);
+        assert_eq!(fuzzed_output(3), vec![3, 1, 2]);
+
+        set_fuzzed_bytes(&[]);
+        assert_eq!(fuzzed_output(2), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_ghost_state_is_kept_per_contract() {
+        let (contract, other) = (
+            AccountIdOf::<Runtime>::new([1; 32]),
+            AccountIdOf::<Runtime>::new([2; 32]),
+        );
+        sp_io::TestExternalities::default().execute_with(|| {
+            sp_io::storage::set(&ghost_key(&contract, b"deposits"), &42i128.encode());
+            sp_io::storage::set(&ghost_key(&other, b"deposits"), &7i128.encode());
+
+            assert_eq!(ghost_value(&contract, b"deposits"), 42);
+            assert_eq!(ghost_value(&contract, b"withdrawals"), 0);
+            assert_eq!(
+                ghost_state(&contract),
+                BTreeMap::from([("deposits".to_string(), 42)])
+            );
+        });
+    }
+}
@@ -0,0 +1,281 @@
+use parity_scale_codec::Decode;
+use serde_json::{
+    json,
+    Value,
+};
+use sp_core::{
+    crypto::{
+        AccountId32,
+        Ss58Codec,
+    },
+    hashing::{
+        blake2_128,
+        twox_128,
+        twox_64,
+    },
+    storage::{
+        ChildInfo,
+        Storage,
+        StorageChild,
+    },
+};
+use std::{
+    collections::BTreeMap,
+    time::Duration,
+};
+use tracing::info;
+
+/// Storage version of `pallet-contracts` in `release-polkadot-v1.10.0`. The
+/// forked entries are decoded by the embedded runtime, so both must match.
+pub const SUPPORTED_STORAGE_VERSION: u16 = 16;
+const KEYS_PAGE_SIZE: usize = 1000;
+const CHILD_STORAGE_PREFIX: &[u8] = b":child_storage:default:";
+
+/// Fetches the state of a deployed contract through the JSON-RPC API of a
+/// node. Every query is pinned to the finalized head at connection time, so
+/// that the forked state is consistent.
+pub struct ChainFork {
+    rpc_url: String,
+    at: String,
+}
+
+pub struct ForkedContract {
+    pub address: AccountId32,
+    /// Entries to merge into the genesis: the contract info, its code, its
+    /// account, its holds and its whole child trie
+    pub storage: Storage,
+}
+
+impl ChainFork {
+    pub fn connect(rpc_url: &str) -> Result<Self, String> {
+        let mut fork = Self {
+            rpc_url: Self::http_url(rpc_url),
+            at: String::new(),
+        };
+        fork.at = fork
+            .rpc("chain_getFinalizedHead", json!([]))?
+            .as_str()
+            .ok_or("🙅 The node didn't return its finalized head")?
+            .to_string();
+        Ok(fork)
+    }
+
+    pub fn fetch_contract(&self, address: &str) -> Result<ForkedContract, String> {
+        let address = AccountId32::from_ss58check(address)
+            .map_err(|e| format!("🙅 Invalid contract address {}: {:?}", address, e))?;
+
+        let version_key = [
+            twox_128(b"Contracts").as_slice(),
+            &twox_128(b":__STORAGE_VERSION__:"),
+        ]
+        .concat();
+        let version = self
+            .get(&version_key)?
+            .and_then(|version| u16::decode(&mut &version[..]).ok())
+            .unwrap_or_default();
+        if version != SUPPORTED_STORAGE_VERSION {
+            return Err(format!(
+                "🙅 The chain runs `pallet-contracts` storage version {}, only {} is supported",
+                version, SUPPORTED_STORAGE_VERSION
+            ));
+        }
+
+        let mut storage = Storage::default();
+
+        let info_key = Self::storage_key(
+            "Contracts",
+            "ContractInfoOf",
+            &Self::twox_64_concat(address.as_ref()),
+        );
+        let info = self
+            .get(&info_key)?
+            .ok_or_else(|| format!("🙅 No contract is deployed at {}", address))?;
+
+        // `ContractInfo` starts with the trie id, followed by the code hash
+        let mut input = &info[..];
+        let trie_id = Vec::<u8>::decode(&mut input)
+            .map_err(|e| format!("🙅 Can't decode the contract info: {}", e))?;
+        let code_hash = <[u8; 32]>::decode(&mut input)
+            .map_err(|e| format!("🙅 Can't decode the contract info: {}", e))?;
+        storage.top.insert(info_key, info);
+
+        let keys = [
+            Self::storage_key("Contracts", "PristineCode", &code_hash),
+            Self::storage_key("Contracts", "CodeInfoOf", &code_hash),
+            Self::storage_key(
+                "System",
+                "Account",
+                &Self::blake2_128_concat(address.as_ref()),
+            ),
+            Self::storage_key(
+                "Balances",
+                "Holds",
+                &Self::blake2_128_concat(address.as_ref()),
+            ),
+        ];
+        for key in keys {
+            if let Some(value) = self.get(&key)? {
+                storage.top.insert(key, value);
+            }
+        }
+
+        let child_info = ChildInfo::new_default(&trie_id);
+        let data = self.child_entries(&child_info)?;
+        info!(
+            "🍴 Forked {} storage entries of {} at block {}",
+            data.len(),
+            address,
+            self.at
+        );
+        storage.children_default.insert(
+            child_info.storage_key().to_vec(),
+            StorageChild { data, child_info },
+        );
+
+        Ok(ForkedContract { address, storage })
+    }
+
+    /// Code uploaded under `code_hash`, as deployed
+    pub fn fetch_code(&self, code_hash: &str) -> Result<Vec<u8>, String> {
+        let hash = Self::from_hex(code_hash)?;
+        if hash.len() != 32 {
+            return Err(format!("🙅 {} isn't a 32 bytes code hash", code_hash));
+        }
+        let code = self
+            .get(&Self::storage_key("Contracts", "PristineCode", &hash))?
+            .ok_or_else(|| format!("🙅 No code was uploaded under {}", code_hash))?;
+        Vec::<u8>::decode(&mut &code[..])
+            .map_err(|e| format!("🙅 Can't decode the code of {}: {}", code_hash, e))
+    }
+
+    /// The same endpoint over HTTP, since the queries are plain JSON-RPC
+    /// requests. Nodes serve both on the same port.
+    fn http_url(rpc_url: &str) -> String {
+        if let Some(url) = rpc_url.strip_prefix("wss://") {
+            format!("https://{}", url)
+        } else if let Some(url) = rpc_url.strip_prefix("ws://") {
+            format!("http://{}", url)
+        } else {
+            rpc_url.to_string()
+        }
+    }
+
+    fn child_entries(
+        &self,
+        child_info: &ChildInfo,
+    ) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, String> {
+        let child_key =
+            Self::to_hex(&[CHILD_STORAGE_PREFIX, child_info.storage_key()].concat());
+        let mut data = BTreeMap::new();
+        let mut start_key: Option<String> = None;
+
+        loop {
+            let keys: Vec<String> = serde_json::from_value(self.rpc(
+                "childstate_getKeysPaged",
+                json!([child_key, "0x", KEYS_PAGE_SIZE, start_key, self.at]),
+            )?)
+            .map_err(|e| format!("🙅 Unexpected child keys: {}", e))?;
+
+            if keys.is_empty() {
+                break;
+            }
+
+            let values: Vec<Option<String>> = serde_json::from_value(self.rpc(
+                "childstate_getStorageEntries",
+                json!([child_key, keys, self.at]),
+            )?)
+            .map_err(|e| format!("🙅 Unexpected child values: {}", e))?;
+
+            for (key, value) in keys.iter().zip(values) {
+                if let Some(value) = value {
+                    data.insert(Self::from_hex(key)?, Self::from_hex(&value)?);
+                }
+            }
+
+            if keys.len() < KEYS_PAGE_SIZE {
+                break;
+            }
+            start_key = keys.last().cloned();
+        }
+
+        Ok(data)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        match self.rpc("state_getStorage", json!([Self::to_hex(key), self.at]))? {
+            Value::String(value) => Self::from_hex(&value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn rpc(&self, method: &str, params: Value) -> Result<Value, String> {
+        let response: Value = ureq::post(&self.rpc_url)
+            .timeout(Duration::from_secs(30))
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .map_err(|e| format!("🙅 `{}` failed: {}", method, e))?
+            .into_json()
+            .map_err(|e| format!("🙅 `{}` returned invalid JSON: {}", method, e))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(format!("🙅 `{}` failed: {}", method, error));
+        }
+        Ok(response["result"].clone())
+    }
+
+    pub(crate) fn storage_key(pallet: &str, item: &str, hashed_key: &[u8]) -> Vec<u8> {
+        [
+            twox_128(pallet.as_bytes()).as_slice(),
+            &twox_128(item.as_bytes()),
+            hashed_key,
+        ]
+        .concat()
+    }
+
+    pub(crate) fn twox_64_concat(key: &[u8]) -> Vec<u8> {
+        [twox_64(key).as_slice(), key].concat()
+    }
+
+    fn blake2_128_concat(key: &[u8]) -> Vec<u8> {
+        [blake2_128(key).as_slice(), key].concat()
+    }
+
+    fn to_hex(bytes: &[u8]) -> String {
+        format!("0x{}", hex::encode(bytes))
+    }
+
+    pub(crate) fn from_hex(value: &str) -> Result<Vec<u8>, String> {
+        hex::decode(value.trim_start_matches("0x"))
+            .map_err(|e| format!("🙅 Invalid hex `{}`: {}", value, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_account_key() {
+        let address = AccountId32::new([1; 32]);
+        let key = ChainFork::storage_key(
+            "System",
+            "Account",
+            &ChainFork::blake2_128_concat(address.as_ref()),
+        );
+
+        // Well-known prefix of `System::Account`
+        assert!(hex::encode(&key).starts_with(
+            "26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9"
+        ));
+        assert_eq!(key.len(), 16 + 16 + 16 + 32);
+        assert_eq!(&key[48..], address.as_ref() as &[u8]);
+        assert_eq!(
+            ChainFork::http_url("wss://rpc.example.com:443"),
+            "https://rpc.example.com:443"
+        );
+    }
+}
@@ -1,4 +1,10 @@
 pub mod custom;
+pub mod deployed;
+pub mod extension;
+pub mod fork;
 pub mod payload;
 pub mod remote;
 pub mod runtime;
+pub mod snapshot;
+pub mod storage_diff;
+pub mod storage_dump;
@@ -1,5 +1,6 @@
 use serde::Deserialize;
 use serde_json::Value;
+use std::fmt;
 
 pub type Selector = [u8; 4];
 
@@ -8,13 +9,62 @@ pub struct PayloadCrafter {}
 
 /// This prefix defines the way a property start with
 /// # Example
-/// ```
+/// ```ignore
 /// #[ink(message)]
 ///  pub fn phink_assert_abc_dot_com_cant_be_registered(&self) -> bool
 /// ...
 /// ```
 pub const DEFAULT_PHINK_PREFIX: &str = "phink_";
 
+/// Why the signature of an invariant doesn't fit the harness, which calls it
+/// with its selector only, after each input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantFlaw {
+    /// `&mut self`: checking it would change the state the next invariants
+    /// see
+    Mutable,
+    /// Its arguments can't be provided by the harness
+    TakesArguments(usize),
+    /// Neither `()` nor `bool`: its return value is ignored, only a panic
+    /// violates it
+    Returns(String),
+}
+
+impl InvariantFlaw {
+    /// Whether the invariant is left out of the checks, rather than checked
+    /// for its panics only
+    pub fn is_skipped(&self) -> bool {
+        !matches!(self, InvariantFlaw::Returns(_))
+    }
+}
+
+impl fmt::Display for InvariantFlaw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantFlaw::Mutable => {
+                write!(
+                    f,
+                    "takes `&mut self`, and checking it would change the state seen by the next invariants, so it won't be checked"
+                )
+            }
+            InvariantFlaw::TakesArguments(count) => {
+                write!(
+                    f,
+                    "takes {} argument(s), but invariants are called without any, so it won't be checked",
+                    count
+                )
+            }
+            InvariantFlaw::Returns(ty) => {
+                write!(
+                    f,
+                    "returns `{}` rather than `()` or `bool`, so its return value is ignored and only its panics are violations",
+                    ty
+                )
+            }
+        }
+    }
+}
+
 impl PayloadCrafter {
     /// Extract all selectors for a given spec
     /// Parses a JSON and returns a list of all possibles messages
@@ -64,7 +114,7 @@ impl PayloadCrafter {
                 .filter_map(|message| {
                     message["label"]
                         .as_str()
-                        .filter(|label| label.starts_with(DEFAULT_PHINK_PREFIX))
+                        .filter(|label| Self::is_invariant(label))
                         .and_then(|_| message["selector"].as_str())
                         .map(Self::decode_selector)
                 })
@@ -72,6 +122,117 @@ impl PayloadCrafter {
         )
     }
 
+    /// Name of the message without the trait it may come from, e.g.
+    /// `transfer` for `PSP22::transfer`
+    pub fn message_name(label: &str) -> &str {
+        label.rsplit("::").next().unwrap_or(label)
+    }
+
+    /// Whether the message labelled `label` is an invariant, including the
+    /// ones of a `#[ink::trait_definition]`, e.g. `Invariants::phink_assert_x`
+    pub fn is_invariant(label: &str) -> bool {
+        Self::message_name(label).starts_with(DEFAULT_PHINK_PREFIX)
+    }
+
+    /// Every invariant of the metadata whose signature doesn't fit the
+    /// harness, with its flaw
+    pub fn malformed_invariants(json_data: &str) -> Vec<(String, InvariantFlaw)> {
+        let data: Value =
+            serde_json::from_str(json_data).expect("JSON was not well-formatted");
+
+        data["spec"]["messages"]
+            .as_array()
+            .unwrap_or(&Vec::new())
+            .iter()
+            .filter_map(|message| {
+                let label = message["label"]
+                    .as_str()
+                    .filter(|l| Self::is_invariant(l))?;
+                let args = message["args"].as_array().map_or(0, Vec::len);
+                let flaw = if message["mutates"].as_bool() == Some(true) {
+                    InvariantFlaw::Mutable
+                } else if args > 0 {
+                    InvariantFlaw::TakesArguments(args)
+                } else {
+                    let ty = message["returnType"]["type"].as_u64()?;
+                    InvariantFlaw::Returns(Self::returned_type(&data["types"], ty)?)
+                };
+                Some((label.to_string(), flaw))
+            })
+            .collect()
+    }
+
+    /// Name of the type returned within the `MessageResult` of id `ty`, `None`
+    /// if it's `()` or `bool`, or if it can't be resolved
+    fn returned_type(types: &Value, ty: u64) -> Option<String> {
+        let resolve = |id: &Value| {
+            types
+                .as_array()?
+                .iter()
+                .find(|entry| entry["id"] == *id)
+                .map(|entry| &entry["type"])
+        };
+
+        let result = resolve(&ty.into())?;
+        let ok = result["def"]["variant"]["variants"]
+            .as_array()?
+            .iter()
+            .find(|variant| variant["name"] == "Ok")?;
+        let inner = resolve(&ok["fields"][0]["type"])?;
+
+        let def = &inner["def"];
+        if def["primitive"] == "bool"
+            || def["tuple"].as_array().is_some_and(Vec::is_empty)
+        {
+            return None;
+        }
+        let path: Vec<&str> = inner["path"]
+            .as_array()
+            .map(|path| path.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        Some(match (path.last(), def["primitive"].as_str()) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(primitive)) => primitive.to_string(),
+            (None, None) => format!("type #{}", ok["fields"][0]["type"]),
+        })
+    }
+
+    /// Extract the selector of every message whose label matches `filter`
+    pub fn extract_labelled(
+        json_data: &str,
+        filter: impl Fn(&str) -> bool,
+    ) -> Vec<Selector> {
+        let data: Value =
+            serde_json::from_str(json_data).expect("JSON was not well-formatted");
+
+        data["spec"]["messages"]
+            .as_array()
+            .unwrap_or(&Vec::new())
+            .iter()
+            .filter(|message| message["label"].as_str().map_or(false, &filter))
+            .filter_map(|message| message["selector"].as_str())
+            .map(Self::decode_selector)
+            .collect()
+    }
+
+    /// Label and selector of every message, e.g. to complete the message
+    /// names on the command line
+    pub fn extract_messages(json_data: &str) -> Vec<(String, Selector)> {
+        let data: Value =
+            serde_json::from_str(json_data).expect("JSON was not well-formatted");
+
+        data["spec"]["messages"]
+            .as_array()
+            .unwrap_or(&Vec::new())
+            .iter()
+            .filter_map(|message| {
+                let label = message["label"].as_str()?;
+                let selector = Self::get_selector_bytes(message["selector"].as_str()?)?;
+                Some((label.to_string(), selector))
+            })
+            .collect()
+    }
+
     /// Return the smart-contract constructor based on its spec. If there are
     /// multiple constructors, returns the one that preferably doesn't have
     /// args. If no suitable constructor is found or there is an error in
@@ -93,11 +254,20 @@ impl PayloadCrafter {
             return Self::get_selector_bytes(constructors[0]["selector"].as_str()?);
         }
 
-        // Otherwise, look for a constructor without arguments.
-        for constructor in constructors {
-            if constructor["args"].as_array().map_or(false, Vec::is_empty) {
-                return Self::get_selector_bytes(constructor["selector"].as_str()?);
-            }
+        // Otherwise, look for a constructor without arguments, preferably the
+        // one marked `#[ink(constructor, default)]` since ink! 5
+        let without_args: Vec<&Value> = constructors
+            .iter()
+            .filter(|constructor| {
+                constructor["args"].as_array().map_or(false, Vec::is_empty)
+            })
+            .collect();
+        if let Some(constructor) = without_args
+            .iter()
+            .find(|constructor| constructor["default"].as_bool() == Some(true))
+            .or(without_args.first())
+        {
+            return Self::get_selector_bytes(constructor["selector"].as_str()?);
         }
 
         // Return None if no suitable constructor is found.
@@ -126,6 +296,7 @@ mod test {
     use crate::{
         cli::config::Configuration,
         contract::payload::{
+            InvariantFlaw,
             PayloadCrafter,
             Selector,
         },
@@ -176,6 +347,73 @@ mod test {
         assert_eq!(hex::encode(ctor), "9bae9d5e");
     }
 
+    #[test]
+    fn trait_messages_are_handled() {
+        let specs = r#"{"spec": {"constructors": [], "messages": [
+            {"label": "PSP22::transfer", "selector": "0xdb20f9f5"},
+            {"label": "Invariants::phink_assert_supply", "selector": "0x0a0b0c0d"},
+            {"label": "phink_assert_owner", "selector": "0x01020304"}
+        ]}}"#;
+
+        assert_eq!(
+            PayloadCrafter::extract_invariants(specs).unwrap(),
+            vec![[0x0a, 0x0b, 0x0c, 0x0d], [1, 2, 3, 4]]
+        );
+        assert_eq!(PayloadCrafter::message_name("PSP22::transfer"), "transfer");
+        assert_eq!(
+            PayloadCrafter::extract_messages(specs)[0],
+            ("PSP22::transfer".to_string(), [0xdb, 0x20, 0xf9, 0xf5])
+        );
+
+        let config = Configuration {
+            denied_messages: Some(vec!["transfer".into()]),
+            ..Default::default()
+        };
+        assert!(!config.is_message_fuzzed("PSP22::transfer"));
+    }
+
+    #[test]
+    fn malformed_invariants_are_detected() {
+        let specs = r#"{"spec": {"constructors": [], "messages": [
+            {"label": "phink_total_is_kept", "selector": "0x01020304", "mutates": false, "args": [], "returnType": {"type": 1}},
+            {"label": "phink_is_sorted", "selector": "0x01020305", "mutates": false, "args": [], "returnType": {"type": 2}},
+            {"label": "phink_reset", "selector": "0x01020306", "mutates": true, "args": [], "returnType": {"type": 1}},
+            {"label": "phink_owner_is", "selector": "0x01020307", "mutates": false, "args": [{"label": "owner"}], "returnType": {"type": 1}},
+            {"label": "phink_total", "selector": "0x01020308", "mutates": false, "args": [], "returnType": {"type": 4}},
+            {"label": "transfer", "selector": "0x01020309", "mutates": true, "args": [{"label": "to"}], "returnType": {"type": 1}}
+        ]}, "types": [
+            {"id": 0, "type": {"def": {"tuple": []}}},
+            {"id": 1, "type": {"def": {"variant": {"variants": [{"name": "Ok", "fields": [{"type": 0}]}, {"name": "Err"}]}}, "path": ["Result"]}},
+            {"id": 2, "type": {"def": {"variant": {"variants": [{"name": "Ok", "fields": [{"type": 3}]}, {"name": "Err"}]}}, "path": ["Result"]}},
+            {"id": 3, "type": {"def": {"primitive": "bool"}}},
+            {"id": 4, "type": {"def": {"variant": {"variants": [{"name": "Ok", "fields": [{"type": 5}]}, {"name": "Err"}]}}, "path": ["Result"]}},
+            {"id": 5, "type": {"def": {"primitive": "u128"}}}
+        ]}"#;
+
+        assert_eq!(
+            PayloadCrafter::malformed_invariants(specs),
+            vec![
+                ("phink_reset".into(), InvariantFlaw::Mutable),
+                ("phink_owner_is".into(), InvariantFlaw::TakesArguments(1)),
+                ("phink_total".into(), InvariantFlaw::Returns("u128".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn default_constructor_is_preferred() {
+        let spec = r#"{ "spec": { "constructors": [
+            { "label": "new", "selector": "0x9bae9d5e", "args": [], "default": false },
+            { "label": "with_owner", "selector": "0x229b553f", "args": [{ "label": "owner" }], "default": false },
+            { "label": "empty", "selector": "0x61ef7e3e", "args": [], "default": true }
+        ] } }"#;
+
+        assert_eq!(
+            hex::encode(PayloadCrafter::get_constructor(spec).unwrap()),
+            "61ef7e3e"
+        );
+    }
+
     #[test]
     fn encode_works_good() {
         let metadata_path = Path::new("sample/dns/target/ink/dns.json");
@@ -204,23 +442,20 @@ mod test {
         )
         .unwrap();
 
-        let mut transcoder_loader = std::sync::Mutex::new(
-            ContractMessageTranscoder::load(Path::new(metadata_path)).unwrap(),
-        );
+        let transcoder =
+            ContractMessageTranscoder::load(Path::new(metadata_path)).unwrap();
 
         let msg = parse_input(
             encoded_bytes.as_bytes_ref(),
-            &mut transcoder_loader,
-            Configuration::default(),
+            &transcoder,
+            &Configuration::default(),
         )
         .messages;
         println!("{:?}", msg);
 
         for i in 0..msg.len() {
-            let hex = transcoder_loader
-                .lock()
-                .unwrap()
-                .decode_contract_message(&mut &*msg.get(i).unwrap().payload);
+            let hex =
+                transcoder.decode_contract_message(&mut &*msg.get(i).unwrap().payload);
             println!("{:?}", hex);
         }
 
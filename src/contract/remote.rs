@@ -1,18 +1,33 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::{
         Path,
         PathBuf,
     },
+    sync::{
+        Arc,
+        OnceLock,
+    },
 };
 
+use contract_transcode::ContractMessageTranscoder;
+use parity_scale_codec::Decode;
+
 use frame_support::{
     __private::BasicExternalities,
     pallet_prelude::Weight,
-    traits::fungible::Inspect,
+    traits::{
+        fungible::{
+            Inspect,
+            Mutate,
+        },
+        tokens::Preservation,
+    },
 };
 use migration::v13;
 use pallet_contracts::{
+    chain_extension::ReturnFlags,
     migration,
     Code,
     CollectEvents,
@@ -27,24 +42,41 @@ use sp_core::{
     storage::Storage,
     H256,
 };
-use sp_runtime::DispatchError;
+use sp_runtime::{
+    DispatchError,
+    StateVersion,
+};
+use tracing::{
+    debug,
+    error,
+    info,
+};
 use v13::ContractInfoOf;
 
 use payload::PayloadCrafter;
 
 use crate::{
-    cli::config::Configuration,
+    cli::config::{
+        Configuration,
+        ForkConfig,
+    },
     contract::{
         custom::{
             DevelopperPreferences,
             Preferences,
         },
+        extension::register_config_mocks,
+        fork::ChainFork,
         payload,
         runtime::{
+            apply_runtime_config,
             AccountId,
+            Balances,
             Contracts,
             Runtime,
+            RuntimeOrigin,
         },
+        storage_dump::StorageDump,
     },
 };
 
@@ -57,21 +89,63 @@ pub type EventRecord = frame_system::EventRecord<
     <Runtime as frame_system::Config>::Hash,
 >;
 
+/// Salt of the contract instantiated from `differential_wasm_path`
+const DIFFERENTIAL_SALT: &[u8] = b"phink_differential";
+
 pub type FullContractResponse =
     ContractResult<Result<ExecReturnValue, DispatchError>, u128, EventRecord>;
 
+/// Response of a call returning `data` with `flags`, shared by the tests
+#[cfg(test)]
+pub fn test_response(flags: ReturnFlags, data: Vec<u8>) -> FullContractResponse {
+    ContractResult {
+        gas_consumed: Weight::zero(),
+        gas_required: Weight::zero(),
+        storage_deposit: Default::default(),
+        debug_message: vec![],
+        result: Ok(ExecReturnValue { flags, data }),
+        events: None,
+    }
+}
+
 #[derive(Clone)]
 pub struct ContractBridge {
     pub genesis: Storage,
     pub contract_address: AccountIdOf<Runtime>,
     pub json_specs: String,
     pub path_to_specs: PathBuf,
+    /// Loaded once from `path_to_specs`, and shared by every clone
+    transcoder: Arc<OnceLock<ContractMessageTranscoder>>,
+    /// Code uploaded from `upgrade_wasm_path`, which the contract can be
+    /// upgraded to
+    pub upgrade_code_hash: Option<H256>,
+    /// Contract instantiated from `differential_wasm_path`, receiving the
+    /// same messages as the fuzzed one
+    pub differential_address: Option<AccountIdOf<Runtime>>,
 }
 
 impl ContractBridge {
     pub const DEFAULT_GAS_LIMIT: Weight =
         Weight::from_parts(100_000_000_000, 3 * 1024 * 1024);
     pub const DEFAULT_DEPLOYER: AccountId32 = AccountId32::new([1u8; 32]);
+    /// Balance of each account of the genesis able to send a message
+    pub const ORIGIN_ENDOWMENT: BalanceOf<Runtime> = 10000000000000000000 * 2;
+
+    /// Build the genesis storage, either by instantiating the contract or by
+    /// forking it from a live chain if `fork` is configured
+    pub fn initialize(
+        wasm_bytes: Vec<u8>,
+        path_to_specs: &Path,
+        config: Configuration,
+    ) -> ContractBridge {
+        match config.fork.clone() {
+            Some(fork) => {
+                Self::initialize_from_chain(wasm_bytes, path_to_specs, &fork, config)
+                    .unwrap_or_else(|e| panic!("🚨 Can't fork the contract: {}", e))
+            }
+            None => Self::initialize_wasm(wasm_bytes, path_to_specs, config),
+        }
+    }
 
     /// Create a proper genesis storage, deploy and instantiate a given ink!
     /// contract
@@ -80,17 +154,20 @@ impl ContractBridge {
         path_to_specs: &Path,
         config: Configuration,
     ) -> ContractBridge {
-        let mut contract_addr: AccountIdOf<Runtime> = config
-            .deployer_address
-            .clone()
-            .unwrap_or(ContractBridge::DEFAULT_DEPLOYER);
+        Self::configure_runtime(&config);
 
-        println!(
+        let mut contract_addr: AccountIdOf<Runtime> = config.deployer();
+
+        debug!(
             "🛠️Initializing contract address from the origin: {:?}",
             contract_addr
         );
 
         let json_specs = fs::read_to_string(path_to_specs).unwrap();
+        let storage_dump = Self::load_storage_dump(&config)
+            .unwrap_or_else(|e| panic!("🚨 Can't load the storage dump: {}", e));
+        let mut upgrade_code_hash = None;
+        let mut differential_address = None;
         let genesis_storage: Storage = {
             let storage = <Preferences as DevelopperPreferences>::runtime_storage();
 
@@ -98,8 +175,13 @@ impl ContractBridge {
             chain.execute_with(|| {
 
               <Preferences as DevelopperPreferences>::on_contract_initialize();
+                Self::endow_origins(&config);
 
                 let code_hash = Self::upload(&wasm_bytes, contract_addr.clone());
+                upgrade_code_hash = Self::upload_upgrade(&config, contract_addr.clone());
+
+                differential_address =
+                    Self::instantiate_differential(&json_specs, &config, contract_addr.clone());
 
                 contract_addr = Self::instantiate(&json_specs, code_hash, contract_addr.clone(), config).expect(
                     "🙅 Can't fetch the contract address because of incorrect instantiation",
@@ -117,6 +199,11 @@ impl ContractBridge {
                             Encoded data: 9BAE9D5E...3130EE8"
                     );
                 }
+
+                if let Some(dump) = &storage_dump {
+                    dump.apply(&contract_addr)
+                        .unwrap_or_else(|e| panic!("🚨 Can't apply the storage dump: {}", e));
+                }
             });
 
             chain.into_storages()
@@ -127,23 +214,163 @@ impl ContractBridge {
             contract_address: contract_addr,
             json_specs,
             path_to_specs: path_to_specs.to_path_buf(),
+            transcoder: Default::default(),
+            upgrade_code_hash,
+            differential_address,
+        }
+    }
+
+    /// Create the genesis storage from the state of a contract deployed on a
+    /// live chain, so that the invariants are fuzzed against the production
+    /// state. The deployed code is then replaced by `wasm_bytes`, i.e the
+    /// instrumented build, so that the coverage is still reported: its storage
+    /// layout must match the deployed one.
+    pub fn initialize_from_chain(
+        wasm_bytes: Vec<u8>,
+        path_to_specs: &Path,
+        fork: &ForkConfig,
+        config: Configuration,
+    ) -> Result<ContractBridge, String> {
+        Self::configure_runtime(&config);
+
+        info!("🍴 Forking {} from {}", fork.contract_address, fork.rpc_url);
+
+        let json_specs = fs::read_to_string(path_to_specs)
+            .map_err(|e| format!("🙅 Can't read the JSON specs: {}", e))?;
+        if config.differential_wasm_path.is_some() {
+            return Err(
+                "🙅 `differential_wasm_path` can't be combined with `[fork]` yet".into(),
+            );
+        }
+        let forked =
+            ChainFork::connect(&fork.rpc_url)?.fetch_contract(&fork.contract_address)?;
+
+        let mut storage = <Preferences as DevelopperPreferences>::runtime_storage();
+        storage.top.extend(forked.storage.top);
+        storage
+            .children_default
+            .extend(forked.storage.children_default);
+
+        let deployer = config.deployer();
+
+        let mut chain = BasicExternalities::new(storage);
+        let upgrade_code_hash = chain.execute_with(|| {
+            <Preferences as DevelopperPreferences>::on_contract_initialize();
+            Self::endow_origins(&config);
+
+            let code_hash = Self::upload(&wasm_bytes, deployer.clone());
+            Contracts::set_code(RuntimeOrigin::root(), forked.address.clone(), code_hash)
+                .map_err(|e| format!("🙅 Can't replace the deployed code: {:?}", e))?;
+
+            if let Some(dump) = Self::load_storage_dump(&config)? {
+                dump.apply(&forked.address)?;
+            }
+
+            Ok::<_, String>(Self::upload_upgrade(&config, deployer))
+        })?;
+
+        Ok(Self {
+            genesis: chain.into_storages(),
+            contract_address: forked.address,
+            json_specs,
+            path_to_specs: path_to_specs.to_path_buf(),
+            transcoder: Default::default(),
+            upgrade_code_hash,
+            differential_address: None,
+        })
+    }
+
+    /// Apply the `[runtime]` section and register the chain extension mocks,
+    /// both being thread-local
+    fn configure_runtime(config: &Configuration) {
+        if let Some(runtime) = &config.runtime {
+            apply_runtime_config(runtime);
         }
+        if let Some(mocks) = &config.chain_extension_mocks {
+            register_config_mocks(mocks);
+        }
+    }
+
+    /// Transcoder of the contract metadata, parsed on first use only
+    pub fn transcoder(&self) -> &ContractMessageTranscoder {
+        self.transcoder.get_or_init(|| {
+            ContractMessageTranscoder::load(&self.path_to_specs)
+                .expect("🙅 Failed to load `ContractMessageTranscoder`")
+        })
     }
 
     /// Execute a function `payload` from the instantiated contract
     pub fn call(
-        self,
+        &self,
         payload: &[u8],
         who: u8,
         transfer_value: BalanceOf<Runtime>,
-        config: Configuration,
+        config: &Configuration,
+    ) -> FullContractResponse {
+        self.call_with_deposit_limit(
+            payload,
+            who,
+            transfer_value,
+            Configuration::parse_balance(&config.storage_deposit_limit),
+            config,
+        )
+    }
+
+    /// Same as `call`, with a `storage_deposit_limit` other than the
+    /// configured one, `None` meaning no limit
+    pub fn call_with_deposit_limit(
+        &self,
+        payload: &[u8],
+        who: u8,
+        transfer_value: BalanceOf<Runtime>,
+        storage_deposit_limit: Option<BalanceOf<Runtime>>,
+        config: &Configuration,
+    ) -> FullContractResponse {
+        Self::bare_call(
+            self.contract_address.clone(),
+            payload,
+            who,
+            transfer_value,
+            storage_deposit_limit,
+            config,
+        )
+    }
+
+    /// Same as `call_with_deposit_limit`, on the contract instantiated from
+    /// `differential_wasm_path`
+    pub fn call_differential(
+        &self,
+        payload: &[u8],
+        who: u8,
+        transfer_value: BalanceOf<Runtime>,
+        storage_deposit_limit: Option<BalanceOf<Runtime>>,
+        config: &Configuration,
+    ) -> Option<FullContractResponse> {
+        let address = self.differential_address.clone()?;
+        Some(Self::bare_call(
+            address,
+            payload,
+            who,
+            transfer_value,
+            storage_deposit_limit,
+            config,
+        ))
+    }
+
+    fn bare_call(
+        contract_address: AccountIdOf<Runtime>,
+        payload: &[u8],
+        who: u8,
+        transfer_value: BalanceOf<Runtime>,
+        storage_deposit_limit: Option<BalanceOf<Runtime>>,
+        config: &Configuration,
     ) -> FullContractResponse {
         Contracts::bare_call(
-            AccountId32::new([who; 32]),
-            self.contract_address,
+            config.account_of(who),
+            contract_address,
             transfer_value,
             config.default_gas_limit.unwrap_or(Self::DEFAULT_GAS_LIMIT),
-            Configuration::parse_balance(config.storage_deposit_limit),
+            storage_deposit_limit,
             payload.to_owned(),
             DebugInfo::UnsafeDebug,
             CollectEvents::UnsafeCollect,
@@ -151,8 +378,220 @@ impl ContractBridge {
         )
     }
 
+    /// Switch the contract to the code uploaded from `upgrade_wasm_path`, the
+    /// same way the contract would by calling `set_code_hash`. The answer is
+    /// shaped like the one of a message, so that it fits in the sequence.
+    pub fn upgrade(&self) -> FullContractResponse {
+        let result = match self.upgrade_code_hash {
+            Some(code_hash) => {
+                Contracts::set_code(
+                    RuntimeOrigin::root(),
+                    self.contract_address.clone(),
+                    code_hash,
+                )
+                .map(|_| {
+                    ExecReturnValue {
+                        flags: ReturnFlags::empty(),
+                        data: vec![],
+                    }
+                })
+                .map_err(|e| e.error)
+            }
+            None => Err(DispatchError::Other("🙅 No `upgrade_wasm_path` configured")),
+        };
+        Self::pseudo_response(result)
+    }
+
+    /// Transfer `value` from `who` to the contract, without calling it, i.e.
+    /// the `TRANSFER_SELECTOR` pseudo-message
+    pub fn transfer(
+        &self,
+        who: u8,
+        value: BalanceOf<Runtime>,
+        config: &Configuration,
+    ) -> FullContractResponse {
+        Self::transfer_to(&self.contract_address, who, value, config)
+    }
+
+    /// Same as `transfer`, to the contract instantiated from
+    /// `differential_wasm_path`
+    pub fn transfer_differential(
+        &self,
+        who: u8,
+        value: BalanceOf<Runtime>,
+        config: &Configuration,
+    ) -> Option<FullContractResponse> {
+        let address = self.differential_address.as_ref()?;
+        Some(Self::transfer_to(address, who, value, config))
+    }
+
+    fn transfer_to(
+        address: &AccountIdOf<Runtime>,
+        who: u8,
+        value: BalanceOf<Runtime>,
+        config: &Configuration,
+    ) -> FullContractResponse {
+        let result = <Balances as Mutate<AccountId>>::transfer(
+            &config.account_of(who),
+            address,
+            value,
+            Preservation::Expendable,
+        )
+        .map(|_| {
+            ExecReturnValue {
+                flags: ReturnFlags::empty(),
+                data: vec![],
+            }
+        });
+        Self::pseudo_response(result)
+    }
+
+    /// Answer of a pseudo-message, shaped like the one of a message so that
+    /// it fits in the sequence
+    fn pseudo_response(
+        result: Result<ExecReturnValue, DispatchError>,
+    ) -> FullContractResponse {
+        ContractResult {
+            gas_consumed: Weight::zero(),
+            gas_required: Weight::zero(),
+            storage_deposit: Default::default(),
+            debug_message: vec![],
+            result,
+            events: None,
+        }
+    }
+
+    /// Root of the whole storage. The events deposited so far are dropped
+    /// first, since every successful call emits some, even without writing
+    /// anything.
+    pub fn state_root() -> Vec<u8> {
+        frame_system::Pallet::<Runtime>::reset_events();
+        sp_io::storage::root(StateVersion::V1)
+    }
+
+    /// Root of the child trie holding the storage of the contract at
+    /// `address`. `ContractInfo` starts with the identifier of that trie.
+    pub fn trie_root(address: &AccountIdOf<Runtime>) -> Option<Vec<u8>> {
+        let trie_id = Self::trie_id(address)?;
+        Some(sp_io::default_child_storage::root(
+            &trie_id,
+            StateVersion::V1,
+        ))
+    }
+
+    /// Every value stored by the contract at `address`
+    pub fn storage_values(address: &AccountIdOf<Runtime>) -> Vec<Vec<u8>> {
+        Self::storage_entries(address).into_values().collect()
+    }
+
+    /// Every key of the child trie of the contract at `address`, with its
+    /// value
+    pub fn storage_entries(address: &AccountIdOf<Runtime>) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        let Some(trie_id) = Self::trie_id(address) else {
+            return BTreeMap::new();
+        };
+        let mut entries = BTreeMap::new();
+        let mut key = Vec::new();
+        while let Some(next) = sp_io::default_child_storage::next_key(&trie_id, &key) {
+            if let Some(value) = sp_io::default_child_storage::get(&trie_id, &next) {
+                entries.insert(next.clone(), value);
+            }
+            key = next;
+        }
+        entries
+    }
+
+    fn trie_id(address: &AccountIdOf<Runtime>) -> Option<Vec<u8>> {
+        let info = sp_io::storage::get(&ChainFork::storage_key(
+            "Contracts",
+            "ContractInfoOf",
+            &ChainFork::twox_64_concat(address.as_ref()),
+        ))?;
+        Vec::<u8>::decode(&mut &info[..]).ok()
+    }
+
+    /// Whether `responses` may have changed the state since `root_before`. A
+    /// failing or reverted message can't, so the storage root is only
+    /// computed again if one of them succeeded.
+    pub fn has_mutated_state(
+        responses: &[FullContractResponse],
+        root_before: &[u8],
+    ) -> bool {
+        responses
+            .iter()
+            .any(|response| matches!(&response.result, Ok(ret) if !ret.did_revert()))
+            && Self::state_root() != root_before
+    }
+
+    fn load_storage_dump(config: &Configuration) -> Result<Option<StorageDump>, String> {
+        config
+            .storage_dump_path
+            .as_deref()
+            .map(StorageDump::load)
+            .transpose()
+    }
+
+    /// Upload and instantiate `differential_wasm_path`, with the same
+    /// constructor as the fuzzed contract, but another salt so that both
+    /// addresses differ even if the builds are identical
+    fn instantiate_differential(
+        json_specs: &str,
+        config: &Configuration,
+        who: AccountId,
+    ) -> Option<AccountIdOf<Runtime>> {
+        let path = config.differential_wasm_path.as_ref()?;
+        let wasm_bytes = fs::read(path).unwrap_or_else(|e| {
+            panic!("🙅 Can't read the differential WASM at {:?}: {}", path, e)
+        });
+        info!(
+            "🔀 Instantiating the other version of the contract from {:?}",
+            path
+        );
+        let code_hash = Self::upload(&wasm_bytes, who.clone());
+        let address = Self::instantiate_with_salt(
+            json_specs,
+            code_hash,
+            who,
+            config.clone(),
+            DIFFERENTIAL_SALT.to_vec(),
+        );
+        if address.is_none() {
+            panic!("🚨 Can't instantiate `differential_wasm_path` with the constructor of the fuzzed contract");
+        }
+        address
+    }
+
+    /// Give each account of `origins`, and the deployer, the same balance as
+    /// the `[origin; 32]` accounts of the genesis
+    fn endow_origins(config: &Configuration) {
+        let accounts = config
+            .origin_pool()
+            .iter()
+            .map(|account| account.address.clone())
+            .chain([config.deployer()]);
+        for account in accounts {
+            if Balances::free_balance(&account) < Self::ORIGIN_ENDOWMENT {
+                let _ = Balances::force_set_balance(
+                    RuntimeOrigin::root(),
+                    account,
+                    Self::ORIGIN_ENDOWMENT,
+                );
+            }
+        }
+    }
+
+    /// Upload the code the contract can be upgraded to, if any
+    fn upload_upgrade(config: &Configuration, who: AccountId) -> Option<H256> {
+        let path = config.upgrade_wasm_path.as_ref()?;
+        let wasm_bytes = fs::read(path).unwrap_or_else(|e| {
+            panic!("🙅 Can't read the upgrade WASM at {:?}: {}", path, e)
+        });
+        info!("⏫ Uploading the upgrade of the contract from {:?}", path);
+        Some(Self::upload(&wasm_bytes, who))
+    }
+
     pub fn upload(wasm_bytes: &[u8], who: AccountId) -> H256 {
-        println!("📤 Starting upload of WASM bytes by: {:?}", who);
+        debug!("📤 Starting upload of WASM bytes by: {:?}", who);
         let upload_result = Contracts::bare_upload_code(
             who.clone(),
             wasm_bytes.to_owned(),
@@ -161,7 +600,7 @@ impl ContractBridge {
         );
         match upload_result {
             Ok(upload_info) => {
-                println!(
+                debug!(
                     "✅ Upload successful. Code hash: {:?}",
                     upload_info.code_hash
                 );
@@ -178,6 +617,16 @@ impl ContractBridge {
         code_hash: H256,
         who: AccountId,
         config: Configuration,
+    ) -> Option<AccountIdOf<Runtime>> {
+        Self::instantiate_with_salt(json_specs, code_hash, who, config, vec![])
+    }
+
+    fn instantiate_with_salt(
+        json_specs: &str,
+        code_hash: H256,
+        who: AccountId,
+        config: Configuration,
+        salt: Vec<u8>,
     ) -> Option<AccountIdOf<Runtime>> {
         let data: Vec<u8> = if let Some(payload) = config.constructor_payload {
             hex::decode(payload)
@@ -187,7 +636,7 @@ impl ContractBridge {
         };
 
         let instantiate_initial_value: Option<BalanceOf<Runtime>> =
-            Configuration::parse_balance(config.instantiate_initial_value);
+            Configuration::parse_balance(&config.instantiate_initial_value);
 
         let instantiate = Contracts::bare_instantiate(
             who.clone(),
@@ -196,18 +645,18 @@ impl ContractBridge {
             None,
             Code::Existing(code_hash),
             data,
-            vec![],
+            salt,
             DebugInfo::UnsafeDebug,
             CollectEvents::UnsafeCollect,
         );
 
         match instantiate.result {
             Ok(contract_info) => {
-                println!("🔍 Instantiated the contract, using account {:?}", who);
+                info!("🔍 Instantiated the contract, using account {:?}", who);
                 Some(contract_info.account_id)
             }
             Err(e) => {
-                eprintln!("❌ Failed to instantiate the contract, double check your `constructor_payload` please : {:?}", e);
+                error!("❌ Failed to instantiate the contract, double check your `constructor_payload` please : {:?}", e);
                 None
             }
         }
@@ -1,4 +1,13 @@
-use crate::contract::remote::BalanceOf;
+use crate::{
+    cli::config::{
+        Configuration,
+        RuntimeConfig,
+    },
+    contract::{
+        extension::PhinkCoverageExtension,
+        remote::BalanceOf,
+    },
+};
 use frame_support::{
     construct_runtime,
     derive_impl,
@@ -80,7 +89,8 @@ parameter_types! {
     pub static DefaultDepositLimit: BalanceOf<Runtime> = 10_000_000;
     pub const MaxDelegateDependencies: u32 = 32;
     pub const CodeHashLockupDepositPercent: Perbill = Perbill::from_percent(10);
-    pub Schedule: pallet_contracts::Schedule<Runtime> = Default::default();
+    pub static Schedule: pallet_contracts::Schedule<Runtime> = Default::default();
+    pub static MaxCodeLen: u32 = 123 * 1024;
     pub const MinimumPeriod: Moment = SLOT_DURATION / 2;
         pub const TransactionByteFee: Balance = 10 * MILLICENTS;
     pub const OperationalFeeMultiplier: u8 = 5;
@@ -173,8 +183,9 @@ impl pallet_contracts::Config for Runtime {
     type CallFilter = frame_support::traits::Nothing;
     type WeightPrice = pallet_transaction_payment::Pallet<Self>;
     type WeightInfo = pallet_contracts::weights::SubstrateWeight<Self>;
-    // type ChainExtension = LocalChainExtensions<Self, UnifiedAccounts, Xvm>;
-    type ChainExtension = ();
+    /// Phink's coverage channel, don't remove it: the instrumented contract
+    /// relies on it to report its coverage
+    type ChainExtension = PhinkCoverageExtension;
     type Schedule = Schedule;
     type CallStack = [pallet_contracts::Frame<Self>; 5];
     type DepositPerByte = DepositPerByte;
@@ -182,7 +193,7 @@ impl pallet_contracts::Config for Runtime {
     type DepositPerItem = DepositPerItem;
     type CodeHashLockupDepositPercent = CodeHashLockupDepositPercent;
     type AddressGenerator = pallet_contracts::DefaultAddressGenerator;
-    type MaxCodeLen = ConstU32<{ 123 * 1024 }>;
+    type MaxCodeLen = MaxCodeLen;
     type MaxStorageKeyLen = ConstU32<128>;
     type MaxDelegateDependencies = MaxDelegateDependencies;
     type MaxDebugBufferLen = ConstU32<{ 2 * 1024 * 1024 }>;
@@ -207,3 +218,23 @@ construct_runtime!(
         Contracts: pallet_contracts
     }
 );
+
+/// Override the parameters of the runtime with the `[runtime]` section of the
+/// configuration, so that the fuzzing conditions match the targeted chain.
+/// These parameters are thread-local: this must be called on the thread
+/// executing the contract, before instantiating it.
+pub fn apply_runtime_config(config: &RuntimeConfig) {
+    if let Some(existential_deposit) = config.existential_deposit {
+        ExistentialDeposit::set(existential_deposit);
+    }
+    if let Some(max_code_len) = config.max_code_len {
+        MaxCodeLen::set(max_code_len);
+    }
+    if let Some(deposit_per_byte) = Configuration::parse_balance(&config.deposit_per_byte)
+    {
+        DepositPerByte::set(deposit_per_byte);
+    }
+    if let Some(memory_pages) = config.memory_pages {
+        Schedule::mutate(|schedule| schedule.limits.memory_pages = memory_pages);
+    }
+}
@@ -0,0 +1,65 @@
+use sp_core::storage::Storage;
+use sp_io::TestExternalities;
+use std::panic::AssertUnwindSafe;
+
+/// Post-instantiation state of the chain, shared by every input.
+///
+/// Instead of rebuilding the externalities from the genesis `Storage` for each
+/// input, the state is built once. Each input then runs inside a storage
+/// transaction, which is rolled back afterwards. Only the storage written by
+/// an input lands in the overlay, the untouched state is read from the
+/// snapshot.
+pub struct Snapshot {
+    // `TestExternalities` isn't `RefUnwindSafe`, which AFL requires from the
+    // harness closure. A panic aborts the fuzzed process anyway.
+    externalities: AssertUnwindSafe<TestExternalities>,
+}
+
+impl Snapshot {
+    pub fn new(genesis: Storage) -> Self {
+        Self {
+            externalities: AssertUnwindSafe(TestExternalities::new(genesis)),
+        }
+    }
+
+    /// Execute `execute` on top of the snapshot, then discard every change it
+    /// made to the storage
+    pub fn execute_with<R>(&mut self, execute: impl FnOnce() -> R) -> R {
+        self.externalities.execute_with(|| {
+            sp_io::storage::start_transaction();
+            let result = execute();
+            sp_io::storage::rollback_transaction();
+            result
+        })
+    }
+
+    /// Execute `execute` on top of the snapshot, and keep its changes for
+    /// the following executions
+    pub fn commit_with<R>(&mut self, execute: impl FnOnce() -> R) -> R {
+        self.externalities.execute_with(execute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes_are_rolled_back_between_executions() {
+        let mut snapshot = Snapshot::new(Storage {
+            top: [(b"key".to_vec(), b"genesis".to_vec())].into(),
+            ..Default::default()
+        });
+
+        snapshot.execute_with(|| {
+            assert_eq!(sp_io::storage::get(b"key").unwrap().to_vec(), b"genesis");
+            sp_io::storage::set(b"key", b"input");
+            sp_io::storage::set(b"other", b"input");
+        });
+
+        snapshot.execute_with(|| {
+            assert_eq!(sp_io::storage::get(b"key").unwrap().to_vec(), b"genesis");
+            assert!(sp_io::storage::get(b"other").is_none());
+        });
+    }
+}
@@ -0,0 +1,240 @@
+use serde_json::Value;
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// `pallet-contracts` stores each key of the child trie of a contract as
+/// `Blake2_128Concat(key)`, so the ink! key comes after this hash
+const KEY_HASH_LEN: usize = 16;
+
+/// Where each field of the contract lives, read from the `storage` section of
+/// the metadata. The fields of the root struct are stored together under its
+/// root key, each `Mapping` or `Lazy` under its own root key.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StorageLayout {
+    /// Fields stored under each root key, in their encoding order, with their
+    /// type identifier
+    roots: BTreeMap<Vec<u8>, Vec<(String, u32)>>,
+    /// Root keys holding a layout we don't decode, e.g. an enum
+    opaque: BTreeSet<Vec<u8>>,
+}
+
+impl StorageLayout {
+    pub fn from_metadata(specs: &Value) -> Self {
+        let mut layout = Self::default();
+        layout.walk(&specs["storage"], "", &[]);
+        layout
+    }
+
+    fn walk(&mut self, layout: &Value, path: &str, root: &[u8]) {
+        if let Some(inner) = layout.get("root") {
+            let root_key = inner["root_key"]
+                .as_str()
+                .and_then(|key| hex::decode(key.trim_start_matches("0x")).ok())
+                .unwrap_or_default();
+            self.walk(&inner["layout"], path, &root_key);
+        } else if let Some(leaf) = layout.get("leaf") {
+            match leaf["ty"].as_u64() {
+                Some(ty) => {
+                    self.roots
+                        .entry(root.to_vec())
+                        .or_default()
+                        .push((path.to_string(), ty as u32))
+                }
+                None => {
+                    self.opaque.insert(root.to_vec());
+                }
+            }
+        } else if let Some(fields) = layout["struct"]["fields"].as_array() {
+            for field in fields {
+                let name = field["name"].as_str().unwrap_or("?");
+                let path = match path {
+                    "" => name.to_string(),
+                    parent => format!("{}.{}", parent, name),
+                };
+                self.walk(&field["layout"], &path, root);
+            }
+        } else {
+            self.opaque.insert(root.to_vec());
+        }
+    }
+
+    /// Root key and mapping key of the ink! key of `raw_key`. The longest
+    /// root key prefixing it wins, the rest being the key of a `Mapping`.
+    fn resolve<'a>(&self, raw_key: &'a [u8]) -> Option<(&[u8], &'a [u8])> {
+        let key = raw_key.get(KEY_HASH_LEN..).unwrap_or(raw_key);
+        self.roots
+            .keys()
+            .chain(&self.opaque)
+            .filter(|root| key.starts_with(root))
+            .max_by_key(|root| root.len())
+            .map(|root| (root.as_slice(), &key[root.len()..]))
+    }
+}
+
+/// Storage entries of a contract that changed between two states, e.g. the
+/// genesis and the state where an invariant failed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StorageDiff {
+    /// Key, value before and value after. `None` when it wasn't stored.
+    pub changes: Vec<(Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)>,
+}
+
+impl StorageDiff {
+    pub fn between(before: &Entries, after: &Entries) -> Self {
+        let keys: BTreeSet<&Vec<u8>> = before.keys().chain(after.keys()).collect();
+        Self {
+            changes: keys
+                .into_iter()
+                .filter(|key| before.get(*key) != after.get(*key))
+                .map(|key| {
+                    (
+                        key.clone(),
+                        before.get(key).cloned(),
+                        after.get(key).cloned(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// One line per changed field, e.g. `balances[0x2a00…]: 10 -> 0`, its
+    /// values being decoded by `decode` from their type identifier. A field we
+    /// can't decode is printed as its raw key and values.
+    pub fn describe(
+        &self,
+        layout: &StorageLayout,
+        decode: impl Fn(u32, &mut &[u8]) -> Option<String>,
+    ) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (key, before, after) in &self.changes {
+            let decoded = layout.resolve(key).and_then(|(root, mapping_key)| {
+                let fields = layout.roots.get(root)?;
+                let before = Self::decode_fields(fields, before.as_deref(), &decode)?;
+                let after = Self::decode_fields(fields, after.as_deref(), &decode)?;
+                Some(
+                    fields
+                        .iter()
+                        .zip(before.into_iter().zip(after))
+                        .filter(|(_, (before, after))| before != after)
+                        .map(|((path, _), (before, after))| {
+                            let name = match mapping_key {
+                                [] => path.clone(),
+                                mapping_key => {
+                                    format!("{}[0x{}]", path, hex::encode(mapping_key))
+                                }
+                            };
+                            format!("{}: {} -> {}", name, before, after)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            });
+
+            match decoded {
+                Some(decoded) => lines.extend(decoded),
+                None => {
+                    let raw = |value: &Option<Vec<u8>>| {
+                        value.as_ref().map_or("None".into(), |value| {
+                            format!("0x{}", hex::encode(value))
+                        })
+                    };
+                    lines.push(format!(
+                        "0x{}: {} -> {}",
+                        hex::encode(key),
+                        raw(before),
+                        raw(after)
+                    ))
+                }
+            }
+        }
+        lines
+    }
+
+    /// Each field of `value`, decoded in order, or `None` for every field if
+    /// nothing was stored
+    fn decode_fields(
+        fields: &[(String, u32)],
+        value: Option<&[u8]>,
+        decode: &impl Fn(u32, &mut &[u8]) -> Option<String>,
+    ) -> Option<Vec<String>> {
+        let Some(mut value) = value else {
+            return Some(vec!["None".into(); fields.len()]);
+        };
+        let decoded = fields
+            .iter()
+            .map(|(_, ty)| decode(*ty, &mut value))
+            .collect::<Option<Vec<String>>>()?;
+        value.is_empty().then_some(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_scale_codec::Decode;
+    use serde_json::json;
+
+    /// `ty` 0 is a `u32`, `ty` 1 a `bool`
+    fn decode(ty: u32, input: &mut &[u8]) -> Option<String> {
+        match ty {
+            0 => u32::decode(input).ok().map(|n| n.to_string()),
+            1 => bool::decode(input).ok().map(|b| b.to_string()),
+            _ => None,
+        }
+    }
+
+    fn raw_key(key: &[u8]) -> Vec<u8> {
+        [[0xaa; KEY_HASH_LEN].as_slice(), key].concat()
+    }
+
+    #[test]
+    fn test_changed_fields_are_decoded() {
+        let specs = json!({"storage": {"root": {
+            "root_key": "0x00000000",
+            "layout": {"struct": {"name": "Flipper", "fields": [
+                {"name": "value", "layout": {"leaf": {"key": "0x00000000", "ty": 1}}},
+                {"name": "balances", "layout": {"root": {
+                    "root_key": "0x2a2a2a2a",
+                    "layout": {"leaf": {"key": "0x2a2a2a2a", "ty": 0}},
+                }}},
+                {"name": "counter", "layout": {"leaf": {"key": "0x00000000", "ty": 0}}},
+            ]}},
+        }}});
+        let layout = StorageLayout::from_metadata(&specs);
+
+        let root = raw_key(&[0; 4]);
+        let entry = raw_key(&[0x2a, 0x2a, 0x2a, 0x2a, 7]);
+        let unknown = raw_key(&[1, 2, 3, 4]);
+        let before: Entries = [
+            (
+                root.clone(),
+                [vec![0], 1u32.to_le_bytes().to_vec()].concat(),
+            ),
+            (unknown.clone(), vec![1]),
+        ]
+        .into();
+        let after: Entries = [
+            (root, [vec![0], 2u32.to_le_bytes().to_vec()].concat()),
+            (entry, 10u32.to_le_bytes().to_vec()),
+        ]
+        .into();
+
+        let diff = StorageDiff::between(&before, &after);
+        assert_eq!(diff.changes.len(), 3);
+        assert_eq!(
+            diff.describe(&layout, decode),
+            vec![
+                "counter: 1 -> 2".to_string(),
+                format!("0x{}: 0x01 -> None", hex::encode(unknown)),
+                "balances[0x07]: None -> 10".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::contract::fork::ChainFork;
+use parity_scale_codec::Decode;
+use serde_json::Value;
+use sp_core::crypto::AccountId32;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+};
+use tracing::info;
+
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Storage merged into the genesis once the contract is instantiated, so that
+/// the fuzzing starts from a realistic state instead of an empty contract.
+///
+/// Two formats are accepted:
+/// - a raw chain spec, e.g exported by chopsticks or `build-spec --raw`, whose `top` and
+///   `childrenDefault` entries are merged as-is
+/// - a flat `{ "0x<key>": "0x<value>" }` object, written into the storage of the contract
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StorageDump {
+    pub top: Entries,
+    /// By child storage key, without the `:child_storage:default:` prefix
+    pub children: BTreeMap<Vec<u8>, Entries>,
+    /// Entries of the contract's own child trie
+    pub contract: Entries,
+}
+
+impl StorageDump {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("🙅 Can't read the storage dump {:?}: {}", path, e))?;
+        Self::parse(&json)
+    }
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(json)
+            .map_err(|e| format!("🙅 Invalid storage dump: {}", e))?;
+
+        let Some(raw) = json
+            .pointer("/genesis/raw")
+            .or(json.get("top").map(|_| &json))
+        else {
+            return Ok(Self {
+                contract: Self::entries(&json)?,
+                ..Default::default()
+            });
+        };
+
+        let mut children = BTreeMap::new();
+        if let Some(Value::Object(children_default)) = raw.get("childrenDefault") {
+            for (key, entries) in children_default {
+                children.insert(ChainFork::from_hex(key)?, Self::entries(entries)?);
+            }
+        }
+
+        Ok(Self {
+            top: raw
+                .get("top")
+                .map(Self::entries)
+                .transpose()?
+                .unwrap_or_default(),
+            children,
+            contract: Entries::new(),
+        })
+    }
+
+    /// Write the entries into the current externalities
+    pub fn apply(&self, contract: &AccountId32) -> Result<(), String> {
+        for (key, value) in &self.top {
+            sp_io::storage::set(key, value);
+        }
+        for (child, entries) in &self.children {
+            for (key, value) in entries {
+                sp_io::default_child_storage::set(child, key, value);
+            }
+        }
+
+        if !self.contract.is_empty() {
+            // The child trie of the contract is identified by its trie id, i.e
+            // the first field of `ContractInfo`
+            let info_key = ChainFork::storage_key(
+                "Contracts",
+                "ContractInfoOf",
+                &ChainFork::twox_64_concat(contract.as_ref()),
+            );
+            let info = sp_io::storage::get(&info_key).ok_or_else(|| {
+                format!("🙅 No contract is instantiated at {}", contract)
+            })?;
+            let trie_id = Vec::<u8>::decode(&mut &info[..])
+                .map_err(|e| format!("🙅 Can't decode the contract info: {}", e))?;
+
+            for (key, value) in &self.contract {
+                sp_io::default_child_storage::set(&trie_id, key, value);
+            }
+        }
+
+        info!(
+            "🌱 Merged {} entries from the storage dump",
+            self.top.len()
+                + self.children.values().map(BTreeMap::len).sum::<usize>()
+                + self.contract.len()
+        );
+        Ok(())
+    }
+
+    fn entries(json: &Value) -> Result<Entries, String> {
+        json.as_object()
+            .ok_or("🙅 Storage dump entries must be a `{ \"0x<key>\": \"0x<value>\" }` object")?
+            .iter()
+            .map(|(key, value)| {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("🙅 The value of {} isn't a hex string", key))?;
+                Ok((ChainFork::from_hex(key)?, ChainFork::from_hex(value)?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_both_formats() {
+        let flat = StorageDump::parse(r#"{ "0x0102": "0x03" }"#).unwrap();
+        assert_eq!(flat.contract, [(vec![1, 2], vec![3])].into());
+        assert!(flat.top.is_empty());
+
+        let raw = StorageDump::parse(
+            r#"{ "genesis": { "raw": {
+                "top": { "0x01": "0x02" },
+                "childrenDefault": { "0xaa": { "0x03": "0x04" } }
+            } } }"#,
+        )
+        .unwrap();
+        assert_eq!(raw.top, [(vec![1], vec![2])].into());
+        assert_eq!(
+            raw.children,
+            [(vec![0xaa], [(vec![3], vec![4])].into())].into()
+        );
+        assert!(raw.contract.is_empty());
+    }
+}
@@ -1,5 +1,7 @@
+use crate::cli::config::Configuration;
 use std::{
     collections::{
+        BTreeSet,
         HashMap,
         HashSet,
     },
@@ -14,29 +16,30 @@ use std::{
     },
     hint::black_box,
     io::{
+        self,
         Read,
         Write,
     },
+    path::PathBuf,
 };
+#[cfg(not(fuzzing))]
+use tracing::debug;
 
 pub type CoverageTrace = Vec<u8>;
-pub const COVERAGE_PATH: &str = "./output/phink/traces.cov";
+/// Identifiers hit so far, under `output_dir`
+pub const COVERAGE_PATH: &str = "traces.cov";
+pub const DEFAULT_FLUSH_EVERY: usize = 100;
 
 #[derive(Clone)]
 pub struct InputCoverage {
     /// One input might contains multiple messages
     messages_coverage: Vec<MessageCoverage>,
-    /// Simply the Vec of Strings, for example
-    /// COV=128
-    /// COV=129 ...
-    raw_from_debug: Vec<CoverageTrace>,
 }
 
 /// This struct represent the coverage of one message.
 #[derive(Clone, Debug)]
 pub struct MessageCoverage {
-    /// A map where the key is the ID of the parsed value of COV=..., and the value is
-    /// the number of times this coverage point was hit.
+    /// Every coverage identifier hit by the message, in execution order
     pub cov_ids: Vec<u64>,
 }
 
@@ -52,30 +55,32 @@ impl InputCoverage {
     pub fn new() -> Self {
         InputCoverage {
             messages_coverage: Vec::new(),
-            raw_from_debug: Vec::new(),
         }
     }
 
-    pub fn add_cov(&mut self, coverage: &CoverageTrace) {
-        let parsed = Self::parse_coverage(coverage);
-        self.raw_from_debug.push(coverage.clone());
-        self.messages_coverage
-            .push(MessageCoverage { cov_ids: parsed });
+    /// Add the coverage of one message, as reported by `PhinkCoverageExtension`
+    pub fn add_cov(&mut self, cov_ids: Vec<u64>) {
+        self.messages_coverage.push(MessageCoverage { cov_ids });
     }
 
-    fn parse_coverage(coverage: &CoverageTrace) -> Vec<u64> {
-        let coverage_str = String::from_utf8_lossy(coverage);
-        let mut parsed = Vec::new();
-
-        for part in coverage_str.split_whitespace() {
-            if let Some(cov) = part.strip_prefix("COV=") {
-                if let Ok(value) = cov.parse::<u64>() {
-                    parsed.push(value);
-                }
-            }
-        }
+    /// Every coverage identifier hit by this input, sorted and deduplicated
+    pub fn coverage_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .messages_coverage
+            .iter()
+            .flat_map(|entry| entry.cov_ids.iter().copied())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
 
-        parsed
+    /// Coverage identifiers of the `index`-th message, in execution order
+    pub fn message_ids(&self, index: usize) -> &[u64] {
+        self.messages_coverage
+            .get(index)
+            .map(|entry| entry.cov_ids.as_slice())
+            .unwrap_or_default()
     }
 
     pub fn remove_cov_from_trace(trace: CoverageTrace) -> Vec<u8> {
@@ -88,33 +93,6 @@ impl InputCoverage {
         cleaned_str.into_bytes()
     }
 
-    pub fn save(&self) -> std::io::Result<()> {
-        let mut existing_content = String::new();
-        if let Ok(mut file) = File::open(COVERAGE_PATH) {
-            file.read_to_string(&mut existing_content)?;
-        }
-
-        let trace_strings: Vec<String> = self
-            .raw_from_debug
-            .iter()
-            .map(|trace| {
-                trace
-                    .iter()
-                    .map(|byte| format!("{:02x}", byte))
-                    .collect::<String>()
-            })
-            .collect();
-
-        let mut file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(COVERAGE_PATH)?;
-
-        writeln!(file, "{}", trace_strings.join("\n"))?;
-
-        Ok(())
-    }
-
     #[allow(unused_doc_comments)]
     #[allow(clippy::identity_op)]
     pub fn redirect_coverage(&self) {
@@ -126,12 +104,12 @@ impl InputCoverage {
 
         #[cfg(not(fuzzing))]
         {
-            println!(
+            debug!(
                 "[🚧DEBUG TRACE] Detected {} messages traces",
-                self.messages_coverage.clone().len(),
+                self.messages_coverage.len(),
             );
-            println!(
-                "[🚧DEBUG TRACE] Caught coverage identifiers {:?}\n",
+            debug!(
+                "[🚧DEBUG TRACE] Caught coverage identifiers {:?}",
                 &flattened_cov
             );
         }
@@ -146,6 +124,16 @@ impl InputCoverage {
         });
     }
 
+    /// Like `redirect_coverage`, for the feature edges of `RareSelectors`,
+    /// i.e. up to `MAX_SCHEDULED_SELECTORS`
+    pub fn redirect_features(features: &[u64]) {
+        seq_macro::seq!(x in 0..256 {
+            if features.contains(&(x as u64)) {
+                let _ = black_box(x + 1);
+            }
+        });
+    }
+
     pub fn deduplicate(input: &str) -> String {
         let mut unique_lines = HashSet::new();
         input
@@ -155,3 +143,83 @@ impl InputCoverage {
             .join("\n")
     }
 }
+
+/// Coverage of the inputs executed so far, written to `COVERAGE_PATH` every
+/// `flush_every` inputs instead of after each of them
+pub struct CoverageBuffer {
+    enabled: bool,
+    ids: BTreeSet<u64>,
+    pending: usize,
+    flush_every: usize,
+    path: PathBuf,
+}
+
+impl CoverageBuffer {
+    pub fn new(config: &Configuration) -> Self {
+        Self {
+            enabled: config.should_save_coverage(),
+            ids: BTreeSet::new(),
+            pending: 0,
+            flush_every: config
+                .coverage_flush_every
+                .unwrap_or(DEFAULT_FLUSH_EVERY)
+                .max(1),
+            path: config.output_dir().join(COVERAGE_PATH),
+        }
+    }
+
+    pub fn add(&mut self, coverage: &InputCoverage) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.ids.extend(coverage.coverage_ids());
+        self.pending += 1;
+
+        if self.pending >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+
+        #[cfg(not(fuzzing))]
+        debug!(
+            "[🚧UPDATE] Adding the coverage of {} inputs to the coverage file...",
+            self.pending
+        );
+
+        let mut existing_content = String::new();
+        if let Ok(mut file) = File::open(&self.path) {
+            file.read_to_string(&mut existing_content)?;
+        }
+
+        // `CoverageTracker` expects one `COV=` identifier per line, we only
+        // append the ones that aren't already saved
+        let known: HashSet<&str> = existing_content.lines().collect();
+        let new_ids: Vec<String> = self
+            .ids
+            .iter()
+            .map(|id| format!("COV={}", id))
+            .filter(|line| !known.contains(line.as_str()))
+            .collect();
+
+        self.ids.clear();
+        self.pending = 0;
+
+        if new_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+
+        writeln!(file, "{}", new_ids.join("\n"))
+    }
+}
@@ -0,0 +1,147 @@
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashSet,
+    },
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Stored at the root of the instrumented contract
+pub const COVERAGE_MAP: &str = "phink_coverage_map.json";
+
+/// Location, in the original (non-instrumented) sources, of the statement
+/// traced by a coverage identifier
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoveragePoint {
+    /// Path relative to the contract root
+    pub file: String,
+    pub line: usize,
+    /// Function containing the statement
+    #[serde(default)]
+    pub function: String,
+}
+
+/// One outcome of a branch, e.g. the `else` of an `if`, or a `match` arm
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchOutcome {
+    pub label: String,
+    /// Coverage identifier reported when this outcome is taken
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchPoint {
+    pub file: String,
+    pub line: usize,
+    pub function: String,
+    /// Either `if` or `match`
+    pub kind: String,
+    pub outcomes: Vec<BranchOutcome>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub covered: usize,
+    pub total: usize,
+    pub first_line: usize,
+    pub last_line: usize,
+}
+
+/// Built by the instrumenter, so that the coverage report can highlight the
+/// original sources instead of the instrumented ones
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoverageMap {
+    /// Original content of each instrumented file
+    pub files: BTreeMap<String, String>,
+    pub points: BTreeMap<u64, CoveragePoint>,
+    #[serde(default)]
+    pub branches: Vec<BranchPoint>,
+    /// Original contract, before being forked and instrumented
+    #[serde(default)]
+    pub source_dir: Option<PathBuf>,
+}
+
+impl CoverageMap {
+    pub fn load(contract_dir: &Path) -> Option<Self> {
+        let content = fs::read_to_string(contract_dir.join(COVERAGE_MAP)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn save(&self, contract_dir: &Path) -> io::Result<()> {
+        fs::write(
+            contract_dir.join(COVERAGE_MAP),
+            serde_json::to_string(self)?,
+        )
+    }
+
+    /// Covered and total statements, grouped by `file` and function
+    pub fn functions_coverage(
+        &self,
+        hit: &HashSet<usize>,
+    ) -> BTreeMap<(String, String), FunctionCoverage> {
+        let mut functions: BTreeMap<(String, String), FunctionCoverage> = BTreeMap::new();
+
+        for (id, point) in &self.points {
+            let entry = functions
+                .entry((point.file.clone(), point.function.clone()))
+                .or_insert(FunctionCoverage {
+                    first_line: point.line,
+                    last_line: point.line,
+                    ..Default::default()
+                });
+            entry.first_line = entry.first_line.min(point.line);
+            entry.last_line = entry.last_line.max(point.line);
+            entry.total += 1;
+            entry.covered += hit.contains(&(*id as usize)) as usize;
+        }
+
+        functions
+    }
+
+    /// Covered and total branch outcomes, grouped by `file` and function
+    pub fn branches_per_function(
+        &self,
+        hit: &HashSet<usize>,
+    ) -> BTreeMap<(String, String), (usize, usize)> {
+        let mut functions: BTreeMap<(String, String), (usize, usize)> = BTreeMap::new();
+
+        for branch in &self.branches {
+            let entry = functions
+                .entry((branch.file.clone(), branch.function.clone()))
+                .or_default();
+            entry.0 += branch
+                .outcomes
+                .iter()
+                .filter(|outcome| hit.contains(&(outcome.id as usize)))
+                .count();
+            entry.1 += branch.outcomes.len();
+        }
+
+        functions
+    }
+
+    /// For each line of `file`, `None` if nothing is traced there, otherwise
+    /// whether one of its coverage points got hit
+    pub fn lines_status(&self, file: &str, hit: &HashSet<usize>) -> Vec<Option<bool>> {
+        let lines = self.files.get(file).map_or(0, |code| code.lines().count());
+        let mut status = vec![None; lines];
+
+        for (id, point) in self.points.iter().filter(|(_, point)| point.file == file) {
+            if let Some(line) = point.line.checked_sub(1).and_then(|l| status.get_mut(l))
+            {
+                *line = Some(line.unwrap_or(false) || hit.contains(&(*id as usize)));
+            }
+        }
+
+        status
+    }
+}
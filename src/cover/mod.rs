@@ -1,2 +1,4 @@
 pub mod coverage;
+pub mod map;
+pub mod progress;
 pub mod report;
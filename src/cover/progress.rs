@@ -0,0 +1,113 @@
+use crate::{
+    cover::coverage::InputCoverage,
+    fuzzer::fuzz::CORPUS_DIR,
+};
+use serde_derive::Serialize;
+use std::{
+    collections::HashSet,
+    fs::{
+        self,
+        OpenOptions,
+    },
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use tracing::warn;
+
+/// JSON lines file of `output_dir` where the coverage growth is appended
+pub const PROGRESS_PATH: &str = "coverage_progress.jsonl";
+pub const DEFAULT_PROGRESS_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Serialize)]
+struct ProgressEntry {
+    timestamp: u64,
+    /// Each fuzzer instance appends its own entries
+    pid: u32,
+    coverage_points: usize,
+    corpus_size: usize,
+    execs: u64,
+}
+
+/// Periodically records the unique coverage points reached by this fuzzer
+/// process, so that coverage growth curves can be plotted and strategies
+/// compared across campaigns
+pub struct CoverageProgress {
+    interval: Option<Duration>,
+    output_dir: PathBuf,
+    last_dump: Instant,
+    execs: u64,
+    covered: HashSet<u64>,
+}
+
+impl CoverageProgress {
+    /// An `interval_secs` of `0` disables the export
+    pub fn new(interval_secs: Option<u64>, output_dir: &Path) -> Self {
+        let interval_secs = interval_secs.unwrap_or(DEFAULT_PROGRESS_INTERVAL_SECS);
+        Self {
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            output_dir: output_dir.to_path_buf(),
+            last_dump: Instant::now(),
+            execs: 0,
+            covered: HashSet::new(),
+        }
+    }
+
+    pub fn record(&mut self, coverage: Option<&InputCoverage>) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        self.execs += 1;
+        if let Some(coverage) = coverage {
+            self.covered.extend(coverage.coverage_ids());
+        }
+
+        if self.last_dump.elapsed() >= interval {
+            self.last_dump = Instant::now();
+            if let Err(e) = self.dump() {
+                warn!("🙅 Couldn't save the coverage progress: {}", e);
+            }
+        }
+    }
+
+    /// Coverage points reached so far, and the number of executions
+    pub fn covered(&self) -> (Vec<u64>, u64) {
+        (self.covered.iter().copied().collect(), self.execs)
+    }
+
+    pub fn resume(&mut self, covered: &[u64], execs: u64) {
+        self.covered.extend(covered);
+        self.execs = execs;
+    }
+
+    fn dump(&self) -> std::io::Result<()> {
+        let entry = ProgressEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            pid: process::id(),
+            coverage_points: self.covered.len(),
+            corpus_size: fs::read_dir(self.output_dir.join(CORPUS_DIR))
+                .map(|dir| dir.count())
+                .unwrap_or(0),
+            execs: self.execs,
+        };
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(self.output_dir.join(PROGRESS_PATH))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{
     cli::ziggy::ZiggyConfig,
-    cover::coverage::COVERAGE_PATH,
+    cover::{
+        coverage::COVERAGE_PATH,
+        map::CoverageMap,
+    },
+    instrumenter::instrumentation::Instrumenter,
 };
 use std::{
     collections::{
@@ -14,8 +18,20 @@ use std::{
     io::Read,
     path::Path,
 };
+use tracing::{
+    debug,
+    error,
+    warn,
+};
 use walkdir::WalkDir;
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CoverageFormat {
+    #[default]
+    Html,
+    Lcov,
+}
+
 pub struct CoverageTracker {
     coverage: HashMap<String, Vec<bool>>,
     hit_lines: HashSet<usize>,
@@ -63,28 +79,22 @@ impl CoverageTracker {
                 }
             }
 
-            if let Some(cov_num) =
-                trimmed.strip_prefix("ink::env::debug_println!(\"COV={}\", ")
-            {
-                if let Some(cov_num) = cov_num.strip_suffix(");") {
-                    if let Ok(num) = cov_num.parse::<usize>() {
-                        if self.hit_lines.contains(&num) {
-                            // Mark the current line and previous non-empty
-                            // lines as covered
-                            file_coverage[i] = true;
-                            for j in (0..i).rev() {
-                                if !lines[j].trim().is_empty() {
-                                    file_coverage[j] = true;
-                                    break;
-                                }
-                            }
-
-                            // Mark the start of the current block as covered
-                            if let Some(&block_start) = block_stack.last() {
-                                file_coverage[block_start] = true;
-                            }
+            if let Some(num) = Instrumenter::parse_cov_id(trimmed) {
+                if self.hit_lines.contains(&(num as usize)) {
+                    // Mark the current line and previous non-empty
+                    // lines as covered
+                    file_coverage[i] = true;
+                    for j in (0..i).rev() {
+                        if !lines[j].trim().is_empty() {
+                            file_coverage[j] = true;
+                            break;
                         }
                     }
+
+                    // Mark the start of the current block as covered
+                    if let Some(&block_start) = block_stack.last() {
+                        file_coverage[block_start] = true;
+                    }
                 }
             }
         }
@@ -149,6 +159,154 @@ impl CoverageTracker {
         Ok(())
     }
 
+    /// Generate a report on top of the original (non-instrumented) sources,
+    /// thanks to the coverage map built during the instrumentation. Each file
+    /// gets a summary of its covered lines, similar to `cargo llvm-cov`.
+    pub fn generate_original_report(
+        &self,
+        map: &CoverageMap,
+        output_dir: &str,
+    ) -> std::io::Result<()> {
+        fs::create_dir_all(output_dir)?;
+
+        let mut index_html = String::from(
+            "<!DOCTYPE html><html><head><title>Phink Coverage Report</title><style>
+            body { font-family: Arial, sans-serif; margin: 40px; background-color: #f4f4f9; }
+            table { border-collapse: collapse; }
+            td, th { border: 1px solid #ccc; padding: 4px 12px; text-align: left; }
+            a { text-decoration: none; color: #007bff; }
+            </style></head><body><h1>Phink Coverage Report</h1>
+            <table><tr><th>File</th><th>Covered lines</th><th>Coverage</th></tr>",
+        );
+
+        let (mut all_covered, mut all_traced) = (0, 0);
+
+        for (file, source) in &map.files {
+            let status = map.lines_status(file, &self.hit_lines);
+            let traced = status.iter().filter(|s| s.is_some()).count();
+            let covered = status.iter().filter(|s| **s == Some(true)).count();
+            all_traced += traced;
+            all_covered += covered;
+
+            let page = format!("{}.html", file.replace('/', "_"));
+            let mut html = format!(
+                "<!DOCTYPE html><html><head><title>Phink File Coverage</title><style>
+                .covered {{ background-color: #90EE90; }}
+                .uncovered {{ background-color: #FFB6C1; }}
+                </style></head><body><h1>Coverage for {}</h1>
+                <h3>{}/{} lines covered ({})</h3><pre>",
+                html_escape(file),
+                covered,
+                traced,
+                percentage(covered, traced)
+            );
+
+            for (i, line) in source.lines().enumerate() {
+                let class = match status.get(i).copied().flatten() {
+                    Some(true) => "covered",
+                    Some(false) => "uncovered",
+                    None => "",
+                };
+                html.push_str(&format!(
+                    "<span class='{}'>{:4} | {}</span>\n",
+                    class,
+                    i + 1,
+                    html_escape(line)
+                ));
+            }
+            html.push_str("</pre></body></html>");
+            fs::write(format!("{}/{}", output_dir, page), html)?;
+
+            index_html.push_str(&format!(
+                "<tr><td><a href='{}'>{}</a></td><td>{}/{}</td><td>{}</td></tr>",
+                page,
+                html_escape(file),
+                covered,
+                traced,
+                percentage(covered, traced)
+            ));
+        }
+
+        index_html.push_str(&format!(
+            "<tr><th>Total</th><th>{}/{}</th><th>{}</th></tr></table>",
+            all_covered,
+            all_traced,
+            percentage(all_covered, all_traced)
+        ));
+
+        // Statements hits don't tell if both sides of a condition were
+        // explored, so branches outcomes are reported separately
+        let branches = map.branches_per_function(&self.hit_lines);
+        if !branches.is_empty() {
+            index_html.push_str(
+                "<h2>Branch coverage</h2><table><tr><th>File</th><th>Function</th>\
+                <th>Covered outcomes</th><th>Coverage</th></tr>",
+            );
+            for ((file, function), (covered, total)) in &branches {
+                index_html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}/{}</td><td>{}</td></tr>",
+                    html_escape(file),
+                    html_escape(function),
+                    covered,
+                    total,
+                    percentage(*covered, *total)
+                ));
+            }
+            index_html.push_str("</table>");
+        }
+        index_html.push_str("</body></html>");
+        fs::write(format!("{}/index.html", output_dir), index_html)?;
+
+        Ok(())
+    }
+
+    /// Export the coverage in the LCOV tracefile format, consumed by `genhtml`,
+    /// Coveralls or Codecov. We only know if a line got hit, not how many
+    /// times, hence the hit count is either `0` or `1`.
+    pub fn to_lcov(&self, map: &CoverageMap) -> String {
+        let mut lcov = String::from("TN:phink\n");
+
+        for file in map.files.keys() {
+            let status = map.lines_status(file, &self.hit_lines);
+
+            lcov.push_str(&format!("SF:{}\n", file));
+            for (i, line) in status.iter().enumerate() {
+                if let Some(hit) = line {
+                    lcov.push_str(&format!("DA:{},{}\n", i + 1, *hit as u8));
+                }
+            }
+
+            let branches: Vec<_> = map
+                .branches
+                .iter()
+                .filter(|branch| &branch.file == file)
+                .collect();
+            let (mut found, mut hit) = (0, 0);
+            for (block, branch) in branches.iter().enumerate() {
+                for (index, outcome) in branch.outcomes.iter().enumerate() {
+                    let taken = self.hit_lines.contains(&(outcome.id as usize));
+                    found += 1;
+                    hit += taken as usize;
+                    lcov.push_str(&format!(
+                        "BRDA:{},{},{},{}\n",
+                        branch.line, block, index, taken as u8
+                    ));
+                }
+            }
+            if found > 0 {
+                lcov.push_str(&format!("BRF:{}\nBRH:{}\n", found, hit));
+            }
+
+            lcov.push_str(&format!(
+                "LF:{}\nLH:{}\nend_of_record\n",
+                status.iter().filter(|s| s.is_some()).count(),
+                status.iter().filter(|s| **s == Some(true)).count()
+            ));
+        }
+
+        lcov
+    }
+
     fn generate_file_report(
         &self,
         file_path: &str,
@@ -174,7 +332,7 @@ impl CoverageTracker {
 
         for (i, line) in lines.iter().enumerate() {
             let line_class = if coverage[i] { "covered" } else { "uncovered" };
-            if !line.contains("ink::env::debug_println!(\"COV={}\", ") {
+            if Instrumenter::parse_cov_id(line).is_none() {
                 html.push_str(&format!(
                     "<span class='{}'>{:4} | {}</span>\n",
                     line_class,
@@ -190,20 +348,50 @@ impl CoverageTracker {
         Ok(())
     }
 
-    pub fn generate(config: ZiggyConfig) {
-        let mut file = match File::open(COVERAGE_PATH) {
+    pub fn generate(config: ZiggyConfig, format: CoverageFormat) {
+        let mut file = match File::open(config.config.output_dir().join(COVERAGE_PATH)) {
             Ok(file) => file,
             Err(_) => {
-                println!("❌ Coverage file not found. Please execute the \"run\" command to create the coverage file.");
+                error!("❌ Coverage file not found. Please execute the \"run\" command to create the coverage file.");
                 return;
             }
         };
 
         let mut contents = String::new();
         file.read_to_string(&mut contents).unwrap();
-        println!("📄 Successfully read coverage file.");
+        debug!("📄 Successfully read coverage file.");
 
         let mut tracker = CoverageTracker::new(&contents);
+        let report_path = config.config.report_path.clone().unwrap();
+
+        if format == CoverageFormat::Lcov {
+            let Some(map) = CoverageMap::load(&config.contract_path) else {
+                error!("❌ No coverage map found. Please instrument the contract again to export LCOV.");
+                return;
+            };
+            fs::create_dir_all(&report_path)
+                .expect("🙅 Cannot create the report directory");
+            let lcov_path = report_path.join("lcov.info");
+            fs::write(&lcov_path, tracker.to_lcov(&map))
+                .expect("🙅 Cannot write the LCOV file");
+            println!(
+                "📊 LCOV coverage exported at: {}. Paths are relative to the contract root.",
+                lcov_path.display()
+            );
+            return;
+        }
+
+        if let Some(map) = CoverageMap::load(&config.contract_path) {
+            tracker
+                .generate_original_report(&map, report_path.to_str().unwrap())
+                .expect("🙅 Cannot generate coverage report");
+            println!("📊 Coverage report generated at: {}", report_path.display());
+            return;
+        }
+
+        warn!(
+            "⚠️ No coverage map found, the report will display the instrumented sources"
+        );
         for entry in WalkDir::new(config.contract_path)
             .into_iter()
             .filter_map(|e| e.ok())
@@ -224,7 +412,14 @@ impl CoverageTracker {
     }
 }
 
-fn html_escape(s: &str) -> String {
+fn percentage(covered: usize, total: usize) -> String {
+    match total {
+        0 => "-".into(),
+        total => format!("{:.2}%", covered as f64 * 100.0 / total as f64),
+    }
+}
+
+pub fn html_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -245,6 +440,69 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_coverage_map_lines_status() {
+        let mut map = CoverageMap::default();
+        map.files.insert(
+            "lib.rs".into(),
+            "fn a() {\n    let x = 1;\n    let y = 2;\n}".into(),
+        );
+        for (id, line) in [(0, 2), (1, 3)] {
+            map.points.insert(
+                id,
+                crate::cover::map::CoveragePoint {
+                    file: "lib.rs".into(),
+                    line,
+                    function: "a".into(),
+                },
+            );
+        }
+
+        let tracker = CoverageTracker::new("COV=0\n");
+
+        assert_eq!(
+            map.lines_status("lib.rs", &tracker.hit_lines),
+            vec![None, Some(true), Some(false), None]
+        );
+        assert_eq!(
+            tracker.to_lcov(&map),
+            "TN:phink\nSF:lib.rs\nDA:2,1\nDA:3,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_branch_coverage_per_function() {
+        let mut map = CoverageMap::default();
+        map.files
+            .insert("lib.rs".into(), "fn a() {\n    if x {}\n}".into());
+        map.branches.push(crate::cover::map::BranchPoint {
+            file: "lib.rs".into(),
+            line: 2,
+            function: "a".into(),
+            kind: "if".into(),
+            outcomes: vec![
+                crate::cover::map::BranchOutcome {
+                    label: "taken".into(),
+                    id: 4,
+                },
+                crate::cover::map::BranchOutcome {
+                    label: "not taken".into(),
+                    id: 5,
+                },
+            ],
+        });
+
+        let tracker = CoverageTracker::new("COV=5\n");
+
+        assert_eq!(
+            map.branches_per_function(&tracker.hit_lines)[&("lib.rs".into(), "a".into())],
+            (1, 2)
+        );
+        assert!(tracker
+            .to_lcov(&map)
+            .contains("BRDA:2,0,0,0\nBRDA:2,0,1,1\nBRF:2\nBRH:1\n"));
+    }
+
     #[test]
     fn test_coverage_line_parsing() {
         let coverage_string = "COV=123, COV=125, COV=127";
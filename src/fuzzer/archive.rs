@@ -0,0 +1,296 @@
+use crate::{
+    cli::ziggy::ZiggyConfig,
+    cover::{
+        coverage::COVERAGE_PATH,
+        map::COVERAGE_MAP,
+    },
+    fuzzer::{
+        fuzz::{
+            CORPUS_DIR,
+            DICT_FILE,
+        },
+        harvest::HARVEST_DICT_FILE,
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fs::{
+        self,
+        File,
+    },
+    io::Read,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use tracing::{
+    debug,
+    warn,
+};
+
+/// First entry of an archive, telling which contract it was made for
+pub const ARCHIVE_MANIFEST: &str = "phink_archive.json";
+/// The configuration of the exported campaign, written next to the imported
+/// corpus under this name, so that it never overrides the local one
+pub const ARCHIVE_CONFIG: &str = "archived_phink.toml";
+const ZSTD_LEVEL: i32 = 19;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub phink_version: String,
+    /// Blake2-256 of the instrumented WASM blob, `0x`-prefixed
+    pub contract_hash: String,
+}
+
+/// Bundles the corpus, the dictionaries, the coverage and the configuration
+/// of a campaign into a `.tar.zst`, to carry it on from another machine. The
+/// coverage identifiers only mean something for one instrumented build, so an
+/// archive is only imported against the same contract hash.
+pub struct CorpusArchive {
+    ziggy: ZiggyConfig,
+}
+
+impl CorpusArchive {
+    pub fn new(ziggy: ZiggyConfig) -> Self {
+        Self { ziggy }
+    }
+
+    /// Write the campaign into `archive`, returning how many inputs of the
+    /// corpus it holds
+    pub fn export(&self, archive: &Path) -> Result<usize, String> {
+        let output_dir = self.ziggy.config.output_dir();
+        let manifest = ArchiveManifest {
+            phink_version: env!("CARGO_PKG_VERSION").to_string(),
+            contract_hash: self.contract_hash()?,
+        };
+        let config = toml::to_string(&self.ziggy.config)
+            .map_err(|e| format!("🙅 Can't serialize the configuration: {}", e))?;
+
+        let file = File::create(archive)
+            .map_err(|e| format!("🙅 Can't create {}: {}", archive.display(), e))?;
+        let encoder = zstd::Encoder::new(file, ZSTD_LEVEL)
+            .map_err(|e| format!("🙅 Can't compress {}: {}", archive.display(), e))?;
+        let mut builder = tar::Builder::new(encoder);
+        let mut append = |name: &str, content: &[u8]| -> Result<(), String> {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content)
+                .map_err(|e| format!("🙅 Can't archive {}: {}", name, e))
+        };
+
+        append(
+            ARCHIVE_MANIFEST,
+            serde_json::to_string_pretty(&manifest).unwrap().as_bytes(),
+        )?;
+        append(ARCHIVE_CONFIG, config.as_bytes())?;
+
+        for name in [DICT_FILE, HARVEST_DICT_FILE, COVERAGE_PATH] {
+            if let Ok(content) = fs::read(output_dir.join(name)) {
+                append(name, &content)?;
+            }
+        }
+        if let Ok(content) = fs::read(self.ziggy.contract_path.join(COVERAGE_MAP)) {
+            append(COVERAGE_MAP, &content)?;
+        }
+
+        let mut inputs = 0;
+        for path in Self::files(&output_dir.join(CORPUS_DIR)) {
+            let (Some(name), Ok(content)) = (path.file_name(), fs::read(&path)) else {
+                continue;
+            };
+            append(
+                &format!("{}/{}", CORPUS_DIR, name.to_string_lossy()),
+                &content,
+            )?;
+            inputs += 1;
+        }
+
+        builder
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(|e| format!("🙅 Can't write {}: {}", archive.display(), e))?;
+        Ok(inputs)
+    }
+
+    /// Restore `archive` into `output_dir`, returning how many inputs got
+    /// added to the corpus. Unless `force`, the archive must have been
+    /// exported for the same build of the contract.
+    pub fn import(&self, archive: &Path, force: bool) -> Result<usize, String> {
+        let entries = Self::read(archive)?;
+        let manifest: ArchiveManifest = entries
+            .get(ARCHIVE_MANIFEST)
+            .and_then(|content| serde_json::from_slice(content).ok())
+            .ok_or_else(|| {
+                format!(
+                    "🙅 {} isn't an archive of Phink, {} is missing",
+                    archive.display(),
+                    ARCHIVE_MANIFEST
+                )
+            })?;
+        let local = self.contract_hash()?;
+        if let Err(e) = manifest.check(&local) {
+            if !force {
+                return Err(e);
+            }
+            warn!("{}, importing anyway", e);
+        }
+
+        let output_dir = self.ziggy.config.output_dir();
+        let corpus_dir = output_dir.join(CORPUS_DIR);
+        fs::create_dir_all(&corpus_dir)
+            .map_err(|e| format!("🙅 Can't create {}: {}", corpus_dir.display(), e))?;
+        let write = |path: PathBuf, content: &[u8]| {
+            fs::write(&path, content)
+                .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))
+        };
+
+        let mut inputs = 0;
+        for (name, content) in &entries {
+            match name.as_str() {
+                ARCHIVE_MANIFEST => {}
+                ARCHIVE_CONFIG | DICT_FILE | HARVEST_DICT_FILE => {
+                    write(output_dir.join(name), content)?
+                }
+                COVERAGE_PATH => {
+                    write(
+                        output_dir.join(COVERAGE_PATH),
+                        Self::merge_coverage(
+                            &fs::read_to_string(output_dir.join(COVERAGE_PATH))
+                                .unwrap_or_default(),
+                            &String::from_utf8_lossy(content),
+                        )
+                        .as_bytes(),
+                    )?
+                }
+                COVERAGE_MAP => {
+                    // The same build gives the same map, we only fill it in
+                    let path = self.ziggy.contract_path.join(COVERAGE_MAP);
+                    if !path.exists() {
+                        write(path, content)?;
+                    }
+                }
+                _ => {
+                    let Some(input) = name
+                        .strip_prefix(&format!("{}/", CORPUS_DIR))
+                        .filter(|input| !input.is_empty() && !input.contains('/'))
+                    else {
+                        debug!("Skipping the unknown entry {}", name);
+                        continue;
+                    };
+                    let path = corpus_dir.join(input);
+                    if !path.exists() {
+                        write(path, content)?;
+                        inputs += 1;
+                    }
+                }
+            }
+        }
+        Ok(inputs)
+    }
+
+    fn read(archive: &Path) -> Result<BTreeMap<String, Vec<u8>>, String> {
+        let file = File::open(archive)
+            .map_err(|e| format!("🙅 Can't open {}: {}", archive.display(), e))?;
+        let decoder = zstd::Decoder::new(file)
+            .map_err(|e| format!("🙅 Can't decompress {}: {}", archive.display(), e))?;
+        let mut tar = tar::Archive::new(decoder);
+        let mut entries = BTreeMap::new();
+        for entry in tar
+            .entries()
+            .map_err(|e| format!("🙅 Can't read {}: {}", archive.display(), e))?
+        {
+            let mut entry = entry
+                .map_err(|e| format!("🙅 Can't read {}: {}", archive.display(), e))?;
+            let name = entry
+                .path()
+                .map_err(|e| format!("🙅 Invalid entry in {}: {}", archive.display(), e))?
+                .to_string_lossy()
+                .to_string();
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .map_err(|e| format!("🙅 Can't read {}: {}", name, e))?;
+            entries.insert(name, content);
+        }
+        Ok(entries)
+    }
+
+    fn contract_hash(&self) -> Result<String, String> {
+        let finder = Instrumenter::new(self.ziggy.contract_path.clone()).find()?;
+        let wasm = fs::read(&finder.wasm_path)
+            .map_err(|e| format!("🙅 Can't read the WASM blob: {}", e))?;
+        Ok(format!(
+            "0x{}",
+            hex::encode(sp_io::hashing::blake2_256(&wasm))
+        ))
+    }
+
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// `COV=` lines of both coverage files, each once
+    fn merge_coverage(local: &str, imported: &str) -> String {
+        let mut lines: Vec<&str> = local.lines().collect();
+        for line in imported.lines() {
+            if !line.is_empty() && !lines.contains(&line) {
+                lines.push(line);
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+impl ArchiveManifest {
+    /// Whether the archive was made for the build hashed as `contract_hash`
+    pub fn check(&self, contract_hash: &str) -> Result<(), String> {
+        if self.contract_hash != contract_hash {
+            return Err(format!(
+                "🙅 The archive was exported for the contract {}, this one is {}. \
+                 Instrument and build the same sources, or pass --force",
+                self.contract_hash, contract_hash
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_needs_the_same_contract() {
+        let manifest = ArchiveManifest {
+            phink_version: "0.1.2".into(),
+            contract_hash: "0xaa".into(),
+        };
+        assert!(manifest.check("0xaa").is_ok());
+        assert!(manifest.check("0xbb").is_err());
+    }
+
+    #[test]
+    fn test_coverage_is_merged_once() {
+        assert_eq!(
+            CorpusArchive::merge_coverage("COV=1\nCOV=2\n", "COV=2\nCOV=3\n"),
+            "COV=1\nCOV=2\nCOV=3\n"
+        );
+    }
+}
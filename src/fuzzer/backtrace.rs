@@ -0,0 +1,223 @@
+use crate::instrumenter::instrumentation::Instrumenter;
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::{
+    fmt,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use walkdir::WalkDir;
+
+/// Where, in the Rust sources, a contract trapped.
+///
+/// `pallet-contracts` only returns `ContractTrapped`, without the program
+/// counter of the trap, so the WASM name section can't be used here. Instead,
+/// we rely on two things: the location printed by ink!'s panic handler into
+/// the debug buffer, and, as a fallback, the last coverage identifier hit
+/// before trapping, which maps back to the instrumented statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub function: Option<String>,
+    pub snippet: String,
+}
+
+impl fmt::Display for TrapLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(function) = &self.function {
+            write!(f, "`{}` at ", function)?;
+        }
+        write!(
+            f,
+            "{}:{} > {}",
+            self.file.display(),
+            self.line,
+            self.snippet
+        )
+    }
+}
+
+impl TrapLocation {
+    pub fn symbolicate(
+        debug_message: &[u8],
+        cov_ids: &[u64],
+        contract_dir: &Path,
+    ) -> Option<Self> {
+        let trace = String::from_utf8_lossy(debug_message);
+        Self::from_panic(&trace, contract_dir)
+            .or_else(|| Self::from_coverage(cov_ids, contract_dir))
+    }
+
+    /// Extract the message of a panic, e.g. the text of a failing `assert_eq!`,
+    /// stripped from the coverage identifiers
+    pub fn panic_message(debug_message: &[u8]) -> Option<String> {
+        let trace = String::from_utf8_lossy(debug_message);
+        let start = trace.rfind("panicked at")?;
+
+        let message: Vec<&str> = trace[start..]
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("COV="))
+            .collect();
+
+        Some(message.join(" "))
+    }
+
+    /// ink!'s panic handler prints `panicked at src/lib.rs:42:9` into the debug
+    /// buffer when the contract is built in debug mode
+    fn from_panic(trace: &str, contract_dir: &Path) -> Option<Self> {
+        let panic =
+            Regex::new(r"panicked at (?:'.*?', )?([^\s:']+\.rs):(\d+):\d+").unwrap();
+        let captures = panic.captures_iter(trace).last()?;
+        let line: usize = captures[2].parse().ok()?;
+
+        let file = Self::rust_files(contract_dir)
+            .into_iter()
+            .find(|path| path.ends_with(&captures[1]))?;
+
+        Self::at(file, line)
+    }
+
+    /// The statement right after the last coverage identifier hit is the one
+    /// that trapped
+    fn from_coverage(cov_ids: &[u64], contract_dir: &Path) -> Option<Self> {
+        let last_cov = *cov_ids.last()?;
+
+        Self::rust_files(contract_dir).into_iter().find_map(|file| {
+            let code = fs::read_to_string(&file).ok()?;
+            let lines: Vec<&str> = code.lines().collect();
+            let cov_line = lines
+                .iter()
+                .position(|line| Instrumenter::parse_cov_id(line) == Some(last_cov))?;
+            let trapped = (cov_line + 1..lines.len())
+                .find(|i| !lines[*i].trim().is_empty())
+                .unwrap_or(cov_line);
+            Self::at(file, trapped + 1)
+        })
+    }
+
+    fn at(file: PathBuf, line: usize) -> Option<Self> {
+        let code = fs::read_to_string(&file).ok()?;
+        let lines: Vec<&str> = code.lines().collect();
+        let snippet = lines.get(line.checked_sub(1)?)?.trim().to_string();
+
+        let function_regex = Regex::new(r"\bfn\s+(\w+)").unwrap();
+        let function = lines[..line]
+            .iter()
+            .rev()
+            .find_map(|l| function_regex.captures(l).map(|c| c[1].to_string()));
+
+        Some(Self {
+            file,
+            line,
+            function,
+            snippet,
+        })
+    }
+
+    fn rust_files(contract_dir: &Path) -> Vec<PathBuf> {
+        WalkDir::new(contract_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().map_or(false, |ext| ext == "rs"))
+            .filter(|e| !e.path().components().any(|c| c.as_os_str() == "target"))
+            .map(|e| e.into_path())
+            .collect()
+    }
+}
+
+/// Start of the panic message of a violated `phink_assert!` or
+/// `phink_assert_eq_dbg!`, from the `phink-macros` crate
+pub const VIOLATION_PREFIX: &str = "phink-violation: ";
+
+/// Violation raised by the assertions of `phink-macros`, parsed out of the
+/// debug buffer
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Violation {
+    /// `assert` or `assert_eq`
+    pub kind: String,
+    pub condition: String,
+    /// `Debug` of both values of a `phink_assert_eq_dbg!`
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Violation {
+    /// The last violation of `debug_message`, if the contract panicked from one
+    /// of the assertions of `phink-macros`
+    pub fn parse(debug_message: &[u8]) -> Option<Self> {
+        let trace = String::from_utf8_lossy(debug_message);
+        let start = trace.rfind(VIOLATION_PREFIX)? + VIOLATION_PREFIX.len();
+        let json = trace[start..].lines().next()?;
+        serde_json::from_str(json).ok()
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` doesn't hold", self.condition)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if let (Some(left), Some(right)) = (&self.left, &self.right) {
+            write!(f, "\n    left: {}\n   right: {}", left, right)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbolicate_from_last_coverage_id() {
+        let dir = std::env::temp_dir().join("phink_backtrace_test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("lib.rs"),
+            "pub fn register(&mut self) {\n    \
+             phink_cov!(7);\n    \
+             let x = 1;\n    \
+             phink_cov!(8);\n\n    \
+             self.names.get(x).unwrap();\n}\n",
+        )
+        .unwrap();
+
+        let location = TrapLocation::symbolicate(b"", &[7, 8], &dir).unwrap();
+
+        assert_eq!(location.line, 6);
+        assert_eq!(location.function, Some("register".into()));
+        assert_eq!(location.snippet, "self.names.get(x).unwrap();");
+    }
+
+    #[test]
+    fn test_panic_message_of_failing_assertion() {
+        let debug = b"COV=1\npanicked at lib.rs:42:9:\nassertion `left != right` failed\n  left: 69\n right: 69\n";
+
+        assert_eq!(
+            TrapLocation::panic_message(debug),
+            Some("panicked at lib.rs:42:9: assertion `left != right` failed left: 69 right: 69".into())
+        );
+        assert_eq!(TrapLocation::panic_message(b"COV=1\nCOV=2"), None);
+    }
+
+    #[test]
+    fn test_structured_violation_is_parsed() {
+        let debug = b"COV=3\npanicked at lib.rs:42:9:\nphink-violation: {\"kind\":\"assert_eq\",\"condition\":\"self.supply == total\",\"left\":\"100\",\"right\":\"99\"}\n";
+        let violation = Violation::parse(debug).unwrap();
+
+        assert_eq!(violation.kind, "assert_eq");
+        assert_eq!(violation.message, None);
+        assert_eq!(
+            violation.to_string(),
+            "`self.supply == total` doesn't hold\n    left: 100\n   right: 99"
+        );
+        assert_eq!(Violation::parse(b"panicked at lib.rs:42:9:\noops"), None);
+    }
+}
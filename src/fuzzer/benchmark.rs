@@ -0,0 +1,167 @@
+use crate::{
+    cli::{
+        config::Configuration,
+        ziggy::ZiggyConfig,
+    },
+    contract::{
+        payload::{
+            PayloadCrafter,
+            Selector,
+        },
+        remote::ContractBridge,
+        snapshot::Snapshot,
+    },
+    fuzzer::{
+        engine::FuzzerEngine,
+        fuzz::Fuzzer,
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use frame_support::__private::BasicExternalities;
+use prettytable::{
+    Cell,
+    Row,
+    Table,
+};
+use std::{
+    fs,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Selector of no message, so that the call only pays for loading and
+/// instantiating the module, before ink!'s dispatcher rejects it
+const UNKNOWN_SELECTOR: Selector = [0xDE, 0xAD, 0xBE, 0xEF];
+
+/// Measures how many calls per second the harness can execute.
+///
+/// The code of the contract is uploaded in the genesis, so it is validated
+/// only once. However, `pallet-contracts` doesn't cache the compiled module:
+/// every `bare_call` loads `PristineCode` and instantiates it with `wasmi`
+/// again, and there is no hook to keep a prepared module across calls without
+/// forking the pallet. Calling an unknown selector measures this fixed cost,
+/// which is the upper bound of what pre-warming could save.
+pub struct Benchmark {
+    setup: ContractBridge,
+    config: Configuration,
+    iterations: u32,
+}
+
+pub struct Measure {
+    pub label: &'static str,
+    pub calls: u32,
+    pub elapsed: Duration,
+}
+
+impl Measure {
+    pub fn per_call(&self) -> Duration {
+        self.elapsed / self.calls.max(1)
+    }
+
+    pub fn calls_per_sec(&self) -> f64 {
+        self.calls as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl Benchmark {
+    pub fn new(ziggy: ZiggyConfig, iterations: u32) -> Result<Self, String> {
+        let finder = Instrumenter::new(ziggy.contract_path.clone()).find()?;
+        let wasm = fs::read(&finder.wasm_path)
+            .map_err(|e| format!("🙅 Can't read the WASM blob: {}", e))?;
+        let setup =
+            ContractBridge::initialize(wasm, &finder.specs_path, ziggy.config.clone());
+
+        Ok(Self {
+            setup,
+            config: ziggy.config,
+            iterations,
+        })
+    }
+
+    pub fn run(&self) -> Vec<Measure> {
+        let invariants = PayloadCrafter::extract_invariants(&self.setup.json_specs)
+            .unwrap_or_default();
+        let messages: Vec<Selector> = PayloadCrafter::extract_all(&self.setup.json_specs)
+            .into_iter()
+            .filter(|selector| !invariants.contains(selector))
+            .collect();
+
+        vec![
+            self.measure(
+                "Messages, externalities rebuilt from genesis",
+                &messages,
+                false,
+            ),
+            self.measure("Messages, on top of the snapshot", &messages, true),
+            self.measure(
+                "Unknown selector, module preparation only",
+                &[UNKNOWN_SELECTOR],
+                true,
+            ),
+        ]
+    }
+
+    pub fn display(measures: &[Measure]) {
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Scenario"),
+            Cell::new("Calls"),
+            Cell::new("Per call"),
+            Cell::new("Calls/s"),
+        ]));
+
+        for measure in measures {
+            table.add_row(Row::new(vec![
+                Cell::new(measure.label),
+                Cell::new(&measure.calls.to_string()),
+                Cell::new(&format!("{:?}", measure.per_call())),
+                Cell::new(&format!("{:.0}", measure.calls_per_sec())),
+            ]));
+        }
+        table.printstd();
+
+        if let [.., messages, preparation] = measures {
+            let share = preparation.per_call().as_secs_f64()
+                / messages.per_call().as_secs_f64().max(f64::EPSILON);
+            println!(
+                "🧮 Loading and instantiating the module takes {:.1}% of an average call",
+                (share * 100.0).min(100.0)
+            );
+        }
+    }
+
+    fn measure(
+        &self,
+        label: &'static str,
+        payloads: &[Selector],
+        use_snapshot: bool,
+    ) -> Measure {
+        let mut snapshot = Snapshot::new(self.setup.genesis.clone());
+        let start = Instant::now();
+
+        for _ in 0..self.iterations {
+            for payload in payloads {
+                if use_snapshot {
+                    snapshot.execute_with(|| self.call(payload));
+                } else {
+                    BasicExternalities::new(self.setup.genesis.clone())
+                        .execute_with(|| self.call(payload));
+                }
+            }
+        }
+
+        Measure {
+            label,
+            calls: self.iterations * payloads.len() as u32,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn call(&self, payload: &Selector) {
+        <Fuzzer as FuzzerEngine>::timestamp(0);
+        // Called by the deployer, i.e `ContractBridge::DEFAULT_DEPLOYER`
+        let _ = self.setup.call(payload, 1, 0, &self.config);
+    }
+}
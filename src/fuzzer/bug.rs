@@ -2,38 +2,276 @@
 use crate::{
     cli::config::Configuration,
     contract::{
+        extension::ghost_state,
         payload::Selector,
         remote::{
             ContractBridge,
             FullContractResponse,
         },
+        snapshot::Snapshot,
+        storage_diff::{
+            StorageDiff,
+            StorageLayout,
+        },
     },
     cover::coverage::InputCoverage,
     fuzzer::{
+        backtrace::{
+            TrapLocation,
+            Violation,
+        },
+        differential::Divergence,
         engine::FuzzerEngine,
+        events::Event,
+        finding::{
+            Finding,
+            FindingsDb,
+        },
+        format,
         fuzz::Fuzzer,
+        notify::notify_finding,
         parser::{
+            DepositLimit,
             Message,
             OneInput,
             Origin,
         },
+        temporal::{
+            TraceEntry,
+            TraceProperty,
+        },
     },
+    instrumenter::conditions::ENSURES_PANIC,
+};
+use contract_transcode::Value;
+use prettytable::{
+    Cell,
+    Row,
+    Table,
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
 };
-use contract_transcode::ContractMessageTranscoder;
 use sp_runtime::{
     DispatchError,
     ModuleError,
 };
 use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
     panic,
-    sync::Mutex,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+use tracing::{
+    error,
+    info,
 };
 
+/// Panic messages emitted by rustc-generated code when `overflow-checks` are
+/// enabled. As the instrumenter forces them in the forked contract, finding one
+/// of those in the debug buffer of a trapped contract means that we caught an
+/// arithmetic bug.
+pub const ARITHMETIC_PANICS: [&str; 10] = [
+    "attempt to add with overflow",
+    "attempt to subtract with overflow",
+    "attempt to multiply with overflow",
+    "attempt to divide with overflow",
+    "attempt to divide by zero",
+    "attempt to calculate the remainder with overflow",
+    "attempt to calculate the remainder with a divisor of zero",
+    "attempt to negate with overflow",
+    "attempt to shift left with overflow",
+    "attempt to shift right with overflow",
+];
+/// Name of the built-in invariant enabled by `no_arithmetic_panic`
+pub const NO_ARITHMETIC_PANIC: &str = "no_arithmetic_panic";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BugType {
+    /// The contract trapped, e.g. because of a `panic!` or an `unwrap()`
+    ContractTrapped,
+    /// The contract trapped because of an arithmetic overflow
+    ArithmeticOverflow,
+    /// One of the `phink_` invariants failed
+    InvariantViolated,
+    /// A `phink::ensures` postcondition of the message doesn't hold
+    PostconditionViolated,
+    /// The message burnt the whole gas limit, e.g. because of an unbounded loop
+    GasBomb,
+    /// The storage deposit limit got exhausted, although there was none
+    DepositFailure,
+    /// The contract behaved differently from `differential_wasm_path`
+    Divergence,
+    /// A `TraceProperty` doesn't hold over the sequence of messages
+    SequencePropertyViolated,
+}
+
+impl BugType {
+    pub fn description(&self) -> &'static str {
+        match self {
+            BugType::ContractTrapped => "contract trapped",
+            BugType::ArithmeticOverflow => "arithmetic overflow",
+            BugType::InvariantViolated => "invariant violated",
+            BugType::PostconditionViolated => "postcondition violated",
+            BugType::GasBomb => "gas bomb",
+            BugType::DepositFailure => "unexpected storage deposit failure",
+            BugType::Divergence => "divergence from the other version",
+            BugType::SequencePropertyViolated => "sequence property violated",
+        }
+    }
+
+    /// Default severity, before taking the hints of the finding into account
+    pub fn severity(&self) -> Severity {
+        match self {
+            BugType::ContractTrapped => Severity::Medium,
+            BugType::ArithmeticOverflow => Severity::High,
+            BugType::InvariantViolated => Severity::High,
+            BugType::PostconditionViolated => Severity::High,
+            BugType::GasBomb => Severity::Medium,
+            BugType::DepositFailure => Severity::Medium,
+            BugType::Divergence => Severity::Medium,
+            BugType::SequencePropertyViolated => Severity::High,
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum Severity {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub fn raise(self) -> Self {
+        match self {
+            Severity::Low => Severity::Medium,
+            Severity::Medium => Severity::High,
+            _ => Severity::Critical,
+        }
+    }
+
+    pub fn lower(self) -> Self {
+        match self {
+            Severity::Critical => Severity::High,
+            Severity::High => Severity::Medium,
+            _ => Severity::Low,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Execution statistics of one message, used to detect messages that
+/// (almost) always return an `Err(...)`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageStats {
+    pub label: String,
+    pub executions: u64,
+    pub reverts: u64,
+    /// Reverts with `Err(LangError::CouldNotReadInput)`, i.e. the fuzzed
+    /// arguments couldn't be decoded, so the message itself never ran
+    pub lang_errors: u64,
+}
+
+/// SCALE encoding of `Err(LangError::CouldNotReadInput)`, wrapping the output
+/// of every message since ink! 4
+pub const COULD_NOT_READ_INPUT: [u8; 2] = [1, 1];
+
+impl MessageStats {
+    /// Share of the executions whose message ran and returned an `Err(...)`
+    pub fn err_ratio(&self) -> u64 {
+        match self.executions - self.lang_errors {
+            0 => 0,
+            executions => (self.reverts - self.lang_errors) * 100 / executions,
+        }
+    }
+
+    /// Count the executions of `other` too, e.g. of another process
+    pub fn add(&mut self, other: &Self) {
+        if self.label.is_empty() {
+            self.label = other.label.clone();
+        }
+        self.executions += other.executions;
+        self.reverts += other.reverts;
+        self.lang_errors += other.lang_errors;
+    }
+
+    /// Print every message which returned an `Err(...)` in more than
+    /// `err_ratio_threshold` percent of its executions. This is meant to be
+    /// called once the campaign (or the `run`) is over, on `results` by
+    /// hex-encoded selector, e.g. summed over the stats of every process.
+    pub fn display_err_report(threshold: Option<u8>, results: &BTreeMap<String, Self>) {
+        let Some(threshold) = threshold else {
+            return;
+        };
+
+        let mut suspicious: Vec<(&String, &Self)> = results
+            .iter()
+            .filter(|(_, stats)| stats.err_ratio() > threshold as u64)
+            .collect();
+
+        if suspicious.is_empty() {
+            println!(
+                "\n✅ No message returned `Err` in more than {}% of its executions",
+                threshold
+            );
+            return;
+        }
+
+        suspicious.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.err_ratio()));
+
+        println!(
+            "\n🧐 The following messages returned `Err` in more than {}% of their executions. \
+            They might be unreachable for the fuzzer, or permanently broken",
+            threshold
+        );
+
+        let mut table = Table::new();
+        table.add_row(Row::new(vec![
+            Cell::new("Message"),
+            Cell::new("Selector"),
+            Cell::new("Executions"),
+            Cell::new("Undecodable inputs"),
+            Cell::new("Err ratio"),
+        ]));
+        for (selector, stats) in suspicious {
+            table.add_row(Row::new(vec![
+                Cell::new(&stats.label),
+                Cell::new(selector),
+                Cell::new(&stats.executions.to_string()),
+                Cell::new(&stats.lang_errors.to_string()),
+                Cell::new(&format!("{}%", stats.err_ratio())),
+            ]));
+        }
+        table.printstd();
+    }
+}
+
 #[derive(Clone)]
 pub struct BugManager {
     pub contract_bridge: ContractBridge,
     pub invariant_selectors: Vec<Selector>,
     pub configuration: Configuration,
+    /// Swarm of this worker, written into its findings
+    pub swarm: Option<String>,
+    /// Checked over the messages of each input, after the invariants
+    pub trace_properties: Vec<Arc<dyn TraceProperty>>,
 }
 
 impl BugManager {
@@ -45,20 +283,88 @@ impl BugManager {
         Self {
             contract_bridge,
             invariant_selectors,
+            swarm: None,
+            trace_properties: configuration
+                .sequence_properties
+                .iter()
+                .flatten()
+                .map(|property| Arc::new(property.clone()) as Arc<dyn TraceProperty>)
+                .collect(),
             configuration,
         }
     }
 
+    pub fn register_trace_property(&mut self, property: impl TraceProperty + 'static) {
+        self.trace_properties.push(Arc::new(property));
+    }
+
+    /// Name of the first `trace_properties` that `responses` violate, and why
+    pub fn check_trace_properties(
+        &self,
+        decoded_msg: &OneInput,
+        responses: &[FullContractResponse],
+    ) -> Option<(String, String)> {
+        let trace: Vec<TraceEntry> = decoded_msg
+            .messages
+            .iter()
+            .zip(responses)
+            .map(|(message, response)| TraceEntry { message, response })
+            .collect();
+        self.trace_properties.iter().find_map(|property| {
+            property
+                .check(&trace)
+                .map(|reason| (property.name(), reason))
+        })
+    }
+
     pub fn contains_selector(&self, selector: &Selector) -> bool {
         self.invariant_selectors.contains(selector)
     }
 
-    pub fn display_trap(&self, message: Message, response: FullContractResponse) {
+    /// Whether `response` is an arithmetic panic of a non-privileged caller,
+    /// breaking the built-in `no_arithmetic_panic` invariant
+    pub fn breaks_no_arithmetic_panic(
+        configuration: &Configuration,
+        response: &FullContractResponse,
+        message: &Message,
+    ) -> bool {
+        configuration.should_check_arithmetic_panics()
+            && Self::is_arithmetic_panic(response)
+            && !configuration.is_privileged(message.origin.into())
+    }
+
+    pub fn display_trap(
+        &self,
+        message: Message,
+        response: FullContractResponse,
+        input: &[u8],
+        coverage: &InputCoverage,
+        message_cov_ids: &[u64],
+    ) {
+        let bug_type = self.trap_type(&response);
+        let location = self.contract_dir().and_then(|dir| {
+            TrapLocation::symbolicate(&response.debug_message, message_cov_ids, &dir)
+        });
+
         // We print the details only when we don't fuzz, so when we run a seed
         // for instance, otherwise this will pollute the AFL logs
         #[cfg(not(fuzzing))]
-        {
-            println!("\n🤯 A trapped contract got caught! Let's dive into it");
+        if !self.configuration.should_report_tersely() {
+            match bug_type {
+                BugType::ArithmeticOverflow => {
+                    println!("\n🧮 An arithmetic overflow got caught! Let's dive into it")
+                }
+                BugType::GasBomb => {
+                    println!("\n💣 A gas bomb got caught! Let's dive into it")
+                }
+                BugType::DepositFailure => {
+                    println!("\n🏦 An unexpected storage deposit failure got caught! Let's dive into it")
+                }
+                BugType::PostconditionViolated => {
+                    println!("\n📜 A postcondition got violated! Let's dive into it")
+                }
+                _ => println!("\n🤯 A trapped contract got caught! Let's dive into it"),
+            }
 
             println!(
                 "\n🐛 IMPORTANT STACKTRACE : {}\n",
@@ -68,67 +374,564 @@ impl BugManager {
                 .replace("\n", " ")
             );
 
+            if let Some(location) = &location {
+                println!("📍 Trapped in {}\n", location);
+            }
+
             println!("🎉 Find below the trace that caused that trapped contract");
 
             <Fuzzer as FuzzerEngine>::pretty_print(
-                vec![response],
+                vec![response.clone()],
                 OneInput {
                     messages: vec![message.clone()],
                     origin: message.origin,
                     fuzz_option: self.configuration.should_fuzz_origin(),
+                    decode_failures: vec![],
                 },
+                self.contract_bridge.transcoder().metadata(),
+                &self.configuration,
             );
+
+            Self::display_cargo_contract_commands(&[message.clone()]);
+        }
+
+        let mut description =
+            format!("{} in `{}`", bug_type.description(), message.label());
+        let mut invariant = None;
+        if bug_type == BugType::ArithmeticOverflow
+            && Self::breaks_no_arithmetic_panic(&self.configuration, &response, &message)
+        {
+            description.push_str(", breaking `no_arithmetic_panic`");
+            invariant = Some(NO_ARITHMETIC_PANIC.to_string());
         }
+        let mut finding = Finding::new(
+            bug_type,
+            description,
+            input,
+            &[message],
+            &response.debug_message,
+            invariant,
+            coverage,
+        );
+        finding.location = location.map(|location| location.to_string());
 
-        // Artificially trigger a bug for AFL
-        panic!("\n🫡  Job is done! Please, don't mind the backtrace below/above.\n\n");
+        self.record_finding(finding);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn display_invariant(
         &self,
         responses: Vec<FullContractResponse>,
         decoded_msg: OneInput,
         invariant_tested: Selector,
-        transcoder_loader: &mut Mutex<ContractMessageTranscoder>,
+        invariant_response: FullContractResponse,
+        input: &[u8],
+        coverage: &InputCoverage,
+        invariant_cov_ids: &[u64],
     ) {
         let mut invariant_slice: &[u8] = &invariant_tested;
 
-        let hex = transcoder_loader
-            .get_mut()
-            .unwrap()
+        let hex = self
+            .contract_bridge
+            .transcoder()
             .decode_contract_message(&mut invariant_slice)
             .unwrap();
+        let storage_diff = self.storage_diff();
 
         #[cfg(not(fuzzing))]
-        {
+        if !self.configuration.should_report_tersely() {
             println!("\n🤯 An invariant got caught! Let's dive into it");
 
             println!("\n🫵  This was caused by `{}`\n", hex);
 
+            let debug_message = &invariant_response.debug_message;
+            match (
+                Violation::parse(debug_message),
+                TrapLocation::panic_message(debug_message),
+            ) {
+                (Some(violation), _) => println!("💥 {}\n", violation),
+                (None, Some(assertion)) => println!("💥 {}\n", assertion),
+                (None, None)
+                    if self.returned_false(&invariant_tested, &invariant_response) =>
+                {
+                    println!("💥 The property returned `false`\n")
+                }
+                (None, None) => {
+                    println!(
+                        "💥 No assertion message found, ensure that your contract is built in debug mode\n"
+                    )
+                }
+            }
+
+            let ghost_state = ghost_state(&self.contract_bridge.contract_address);
+            if !ghost_state.is_empty() {
+                println!("👻 Ghost variables when it failed:");
+                for (name, value) in ghost_state {
+                    println!("   {} = {}", name, value);
+                }
+                println!();
+            }
+
+            if !storage_diff.is_empty() {
+                println!("💾 Storage changed since the genesis:");
+                for change in &storage_diff {
+                    println!("   {}", change);
+                }
+                println!();
+            }
+
             println!("🎉 Find below the trace that caused that invariant");
-            <Fuzzer as FuzzerEngine>::pretty_print(responses, decoded_msg);
+            <Fuzzer as FuzzerEngine>::pretty_print(
+                responses.clone(),
+                decoded_msg.clone(),
+                self.contract_bridge.transcoder().metadata(),
+                &self.configuration,
+            );
+
+            Self::display_cargo_contract_commands(&decoded_msg.messages);
+        }
+
+        let debug_trace: Vec<u8> = responses
+            .iter()
+            .chain([&invariant_response])
+            .flat_map(|response| response.debug_message.clone())
+            .collect();
+
+        let mut finding = Finding::new(
+            BugType::InvariantViolated,
+            format!("invariant `{}` violated", hex),
+            input,
+            &decoded_msg.messages,
+            &debug_trace,
+            Some(hex::encode(invariant_tested)),
+            coverage,
+        );
+        finding.location = self
+            .contract_dir()
+            .and_then(|dir| {
+                TrapLocation::symbolicate(
+                    &invariant_response.debug_message,
+                    invariant_cov_ids,
+                    &dir,
+                )
+            })
+            .map(|location| location.to_string());
+        finding.storage_diff = storage_diff;
+
+        self.record_finding(finding);
+    }
+
+    /// Fields of the contract storage that changed since the genesis, decoded
+    /// with the storage layout of the metadata. It must be called within the
+    /// externalities of the faulty state.
+    fn storage_diff(&self) -> Vec<String> {
+        let address = &self.contract_bridge.contract_address;
+        let after = ContractBridge::storage_entries(address);
+        let before = Snapshot::new(self.contract_bridge.genesis.clone())
+            .execute_with(|| ContractBridge::storage_entries(address));
+        let Ok(specs) = serde_json::from_str(&self.contract_bridge.json_specs) else {
+            return Vec::new();
+        };
+
+        let transcoder = self.contract_bridge.transcoder();
+        StorageDiff::between(&before, &after).describe(
+            &StorageLayout::from_metadata(&specs),
+            |ty, input| {
+                transcoder
+                    .decode(ty, input)
+                    .ok()
+                    .map(|value| value.to_string())
+            },
+        )
+    }
+
+    pub fn display_trace_property(
+        &self,
+        responses: Vec<FullContractResponse>,
+        decoded_msg: OneInput,
+        violation: (String, String),
+        input: &[u8],
+        coverage: &InputCoverage,
+    ) {
+        let (property, reason) = violation;
+
+        #[cfg(not(fuzzing))]
+        if !self.configuration.should_report_tersely() {
+            println!("\n⏳ A sequence property got violated! Let's dive into it");
+            println!("\n🫵  {}: {}\n", property, reason);
+            println!("🎉 Find below the trace that violated it");
+            <Fuzzer as FuzzerEngine>::pretty_print(
+                responses.clone(),
+                decoded_msg.clone(),
+                self.contract_bridge.transcoder().metadata(),
+                &self.configuration,
+            );
+            Self::display_cargo_contract_commands(&decoded_msg.messages);
+        }
+
+        let debug_trace: Vec<u8> = responses
+            .iter()
+            .flat_map(|response| response.debug_message.clone())
+            .collect();
+
+        let finding = Finding::new(
+            BugType::SequencePropertyViolated,
+            format!("{}: {}", property, reason),
+            input,
+            &decoded_msg.messages,
+            &debug_trace,
+            None,
+            coverage,
+        );
+        self.record_finding(finding);
+    }
+
+    pub fn display_divergence(
+        &self,
+        responses: Vec<FullContractResponse>,
+        decoded_msg: OneInput,
+        divergence: Divergence,
+        input: &[u8],
+        coverage: &InputCoverage,
+    ) {
+        let description = match divergence.message_index {
+            Some(index) => {
+                format!(
+                    "`{}` {}",
+                    decoded_msg.messages[index].label(),
+                    divergence.reason
+                )
+            }
+            None => format!("the sequence {}", divergence.reason),
+        };
+
+        #[cfg(not(fuzzing))]
+        if !self.configuration.should_report_tersely() {
+            println!("\n🔀 Both versions of the contract diverged! Let's dive into it");
+            println!(
+                "\n🫵  {} (this build first, `differential_wasm_path` second)\n",
+                description
+            );
+            println!("🎉 Find below the trace that caused that divergence");
+            <Fuzzer as FuzzerEngine>::pretty_print(
+                responses.clone(),
+                decoded_msg.clone(),
+                self.contract_bridge.transcoder().metadata(),
+                &self.configuration,
+            );
+            Self::display_cargo_contract_commands(&decoded_msg.messages);
+        }
+
+        let debug_trace: Vec<u8> = responses
+            .iter()
+            .flat_map(|response| response.debug_message.clone())
+            .collect();
+
+        let finding = Finding::new(
+            BugType::Divergence,
+            description,
+            input,
+            &decoded_msg.messages,
+            &debug_trace,
+            None,
+            coverage,
+        );
+        self.record_finding(finding);
+    }
+
+    /// Smallest subsequence of `sequence` for which `still_fails` holds,
+    /// removing one message at a time until none of them can be removed.
+    /// `still_fails` is expected to replay the candidate from the genesis.
+    pub fn shrink(
+        sequence: &[u8],
+        configuration: &Configuration,
+        mut still_fails: impl FnMut(&[u8]) -> bool,
+    ) -> Vec<u8> {
+        let mut shrunk = sequence.to_vec();
+        let mut removed = true;
+        while removed {
+            removed = false;
+            let mut index = 0;
+            while index < format::message_count(&shrunk, configuration) {
+                let candidate = format::remove_message(&shrunk, index, configuration);
+                if still_fails(&candidate) {
+                    shrunk = candidate;
+                    removed = true;
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        shrunk
+    }
+
+    /// `sequence` with its origins renamed to `Origin::canonical`, by order
+    /// of appearance, e.g. `CALLER_1` then `CALLER_2`, if `still_fails` holds
+    /// for it. Most findings only depend on the callers being distinct, not
+    /// on who they are.
+    pub fn normalize_origins(
+        sequence: &[u8],
+        configuration: &Configuration,
+        mut still_fails: impl FnMut(&[u8]) -> bool,
+    ) -> Vec<u8> {
+        let Some(origins) = format::origins(sequence, configuration) else {
+            return sequence.to_vec();
+        };
+        let canonical = Origin::canonical(configuration);
+        let mut renamed: HashMap<u8, u8> = HashMap::new();
+        let normalized: Vec<u8> = origins
+            .iter()
+            .map(|byte| {
+                // Two bytes pointing to the same account of `origins` are the
+                // same caller
+                let origin = u8::from(Origin::fuzzed(*byte, configuration));
+                let next = canonical[renamed.len() % canonical.len()];
+                *renamed.entry(origin).or_insert(next)
+            })
+            .collect();
+
+        if normalized == origins {
+            return sequence.to_vec();
+        }
+        let candidate = format::with_origins(sequence, &normalized, configuration);
+        if still_fails(&candidate) {
+            candidate
+        } else {
+            sequence.to_vec()
+        }
+    }
+
+    /// Translate a trace into `cargo contract call` commands, so that the bug
+    /// can be replayed against a local `substrate-contracts-node`. The origin
+    /// can't be kept as-is since Phink's accounts have no known key, so every
+    /// call is signed by `//Alice` instead.
+    pub fn cargo_contract_commands(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|message| {
+                if message.is_upgrade {
+                    return "# Upgrade the contract to `upgrade_wasm_path`, e.g through its `set_code_hash` message".to_string();
+                }
+                if message.is_transfer {
+                    return format!(
+                        "# Transfer {} to $CONTRACT, e.g through `balances.transferAllowDeath`",
+                        message.value_token
+                    );
+                }
+
+                let mut command = format!(
+                    "cargo contract call --contract $CONTRACT --message {}",
+                    message.label()
+                );
+
+                if let Value::Map(map) = &message.message_metadata {
+                    let args: Vec<String> = map
+                        .iter()
+                        .map(|(_, arg)| {
+                            format!("'{}'", arg.to_string().replace('\'', ""))
+                        })
+                        .collect();
+                    if !args.is_empty() {
+                        command.push_str(&format!(" --args {}", args.join(" ")));
+                    }
+                }
+
+                if message.is_payable {
+                    command.push_str(&format!(" --value {}", message.value_token));
+                }
+
+                if let DepositLimit::Tight(limit) = message.deposit_limit {
+                    command.push_str(&format!(" --storage-deposit-limit {}", limit));
+                }
+
+                command.push_str(" --suri //Alice --execute");
+                command
+            })
+            .collect()
+    }
+
+    fn display_cargo_contract_commands(messages: &[Message]) {
+        println!(
+            "\n🔁 To reproduce it on a local node, set $CONTRACT to the address of your \
+            deployed contract and run:\n"
+        );
+        for command in Self::cargo_contract_commands(messages) {
+            println!("{}", command);
+        }
+        println!();
+    }
+
+    /// The specs are stored in `<contract_dir>/target/ink/<name>.json`
+    fn contract_dir(&self) -> Option<PathBuf> {
+        self.contract_bridge
+            .path_to_specs
+            .ancestors()
+            .nth(3)
+            .map(Path::to_path_buf)
+    }
+
+    fn contract_name(&self) -> String {
+        serde_json::from_str::<serde_json::Value>(&self.contract_bridge.json_specs)
+            .ok()
+            .and_then(|specs| specs["contract"]["name"].as_str().map(String::from))
+            .unwrap_or_default()
+    }
+
+    /// Hash of the code, as written in the metadata, else of the metadata
+    /// itself. It prefixes the bug IDs.
+    fn contract_hash(&self) -> String {
+        let specs = &self.contract_bridge.json_specs;
+        serde_json::from_str::<serde_json::Value>(specs)
+            .ok()
+            .and_then(|specs| specs["source"]["hash"].as_str().map(String::from))
+            .unwrap_or_else(|| hex::encode(sp_io::hashing::blake2_256(specs.as_bytes())))
+    }
+
+    /// Save the finding as a self-contained folder, then either halt the
+    /// execution, which is what AFL expects to spot a crash, or keep
+    /// executing the remaining seeds if `halt_on_bug` is disabled
+    fn record_finding(&self, finding: Finding) {
+        let mut finding = finding.classify(&self.configuration);
+        finding.swarm = self.swarm.clone();
+
+        let output_dir = self.configuration.output_dir();
+        let contract_hash = self.contract_hash();
+        let recorded = FindingsDb::update(&output_dir, &contract_hash, |db| {
+            let is_new = db.record(&mut finding, &contract_hash);
+            (is_new, db.findings.get(&finding.dedup_key()).cloned())
+        });
+        // Still reported as a new finding, and still halting
+        let (is_new, known) = recorded.unwrap_or_else(|e| {
+            error!("🙅 Couldn't save the findings database: {}", e);
+            (true, None)
+        });
+        if is_new {
+            match finding.save(&output_dir) {
+                Ok(path) => {
+                    #[cfg(not(fuzzing))]
+                    if !self.configuration.should_report_tersely() {
+                        println!(
+                            "📝 {} severity finding {} saved into {}",
+                            finding.severity,
+                            finding.reference(),
+                            path.display()
+                        );
+                        for hint in &finding.hints {
+                            println!("   {}", hint);
+                        }
+                    }
+                }
+                Err(e) => error!("🙅 Couldn't save the finding: {}", e),
+            }
+            Event::new_finding(&finding, &output_dir).emit(&output_dir);
+
+            if let Some(notify) = &self.configuration.notify {
+                notify_finding(notify, &self.contract_name(), &finding, &output_dir);
+            }
+        } else if let Some(known) = known {
+            #[cfg(not(fuzzing))]
+            if !self.configuration.should_report_tersely() {
+                println!(
+                    "♻️ This bug is already known as {} (seen {} times, first seen at {})",
+                    known.reference(),
+                    known.occurrences,
+                    known.first_seen
+                );
+            }
+        }
+
+        #[cfg(not(fuzzing))]
+        if self.configuration.should_report_tersely() {
+            println!("{}", Self::terse_line(&finding, is_new));
+        }
+
+        if self.configuration.should_halt_on_bug() {
+            // Artificially trigger a bug for AFL
+            panic!(
+                "\n🫡  Job is done! Please, don't mind the backtrace below/above.\n\n"
+            );
         }
-        // Artificially trigger a bug for AFL
-        panic!("\n🫡   Job is done! Please, don't mind the backtrace below/above.\n\n");
+
+        info!("⏭️ `halt_on_bug` is disabled, continuing...");
+    }
+
+    /// The single line printed per finding with `terse_findings`
+    fn terse_line(finding: &Finding, is_new: bool) -> String {
+        format!(
+            "🐛 {} [{}] {}: {} (findings/{})",
+            finding.reference(),
+            if is_new { "new" } else { "known" },
+            finding.severity,
+            finding.description,
+            finding.id
+        )
     }
 
     /// This function aims to call every invariant function via
-    /// `invariant_selectors`.
-    pub fn are_invariants_passing(&self, origin: Origin) -> Result<(), Selector> {
+    /// `invariant_selectors`. An invariant fails when it panics, or when it
+    /// returns `false` for the properties returning a `bool`. The response of
+    /// the first failing invariant is returned, so that its panic message can
+    /// be displayed.
+    pub fn are_invariants_passing(
+        &self,
+        origin: Origin,
+    ) -> Result<(), (Selector, FullContractResponse)> {
         for invariant in &self.invariant_selectors {
-            let invariant_call: FullContractResponse = self.contract_bridge.clone().call(
+            let invariant_call: FullContractResponse = self.contract_bridge.call(
                 invariant.as_ref(),
                 origin.into(),
                 0,
-                self.configuration.clone(),
+                &self.configuration,
             );
-            if invariant_call.result.is_err() {
-                return Err(*invariant)
+            if invariant_call.result.is_err()
+                || self.returned_false(invariant, &invariant_call)
+            {
+                return Err((*invariant, invariant_call))
             }
         }
         Ok(())
     }
 
+    /// Whether the property `selector` returned `false`, i.e. `Ok(false)` as
+    /// ink! wraps the return value of every message into a `MessageResult`
+    pub fn returned_false(
+        &self,
+        selector: &Selector,
+        response: &FullContractResponse,
+    ) -> bool {
+        let Ok(ret) = &response.result else {
+            return false;
+        };
+        if ret.did_revert() {
+            return false;
+        }
+        let transcoder = self.contract_bridge.transcoder();
+        let Some(message) = transcoder
+            .metadata()
+            .spec()
+            .messages()
+            .iter()
+            .find(|message| message.selector().to_bytes() == selector)
+        else {
+            return false;
+        };
+        transcoder
+            .decode_message_return(message.label(), &mut &ret.data[..])
+            .is_ok_and(|value| Self::is_false(&value))
+    }
+
+    /// `false`, or `Ok(false)`
+    fn is_false(value: &Value) -> bool {
+        match value {
+            Value::Bool(false) => true,
+            Value::Tuple(tuple) if tuple.ident().as_deref() == Some("Ok") => {
+                let values: Vec<&Value> = tuple.values().collect();
+                matches!(values.as_slice(), [Value::Bool(false)])
+            }
+            _ => false,
+        }
+    }
+
     pub fn is_contract_trapped(&self, contract_response: &FullContractResponse) -> bool {
         if let Err(DispatchError::Module(ModuleError { message, .. })) =
             contract_response.result
@@ -139,4 +942,208 @@ impl BugManager {
         }
         false
     }
+
+    /// A message running out of gas with Phink's (huge) gas limit most likely
+    /// hides an unbounded loop or storage growth, bricking the contract
+    pub fn is_gas_bomb(&self, contract_response: &FullContractResponse) -> bool {
+        matches!(
+            contract_response.result,
+            Err(DispatchError::Module(ModuleError {
+                message: Some("OutOfGas"),
+                ..
+            }))
+        )
+    }
+
+    /// With `fuzz_storage_deposit_limit`, the storage deposit limit of a
+    /// message got exhausted although it ran without any limit. Tight limits
+    /// are expected to fail, the configured one may be too low for the
+    /// contract, and a caller may not afford the deposit.
+    pub fn is_unexpected_deposit_failure(
+        &self,
+        contract_response: &FullContractResponse,
+        message: &Message,
+    ) -> bool {
+        self.configuration.should_fuzz_storage_deposit_limit()
+            && message.deposit_limit == DepositLimit::Unlimited
+            && matches!(
+                contract_response.result,
+                Err(DispatchError::Module(ModuleError {
+                    message: Some("StorageDepositLimitExhausted"),
+                    ..
+                }))
+            )
+    }
+
+    fn is_deposit_failure(contract_response: &FullContractResponse) -> bool {
+        matches!(
+            contract_response.result,
+            Err(DispatchError::Module(ModuleError {
+                message: Some(
+                    "StorageDepositLimitExhausted" | "StorageDepositNotEnoughFunds"
+                ),
+                ..
+            }))
+        )
+    }
+
+    /// Whether the debug buffer of the call holds one of `ARITHMETIC_PANICS`
+    fn is_arithmetic_panic(contract_response: &FullContractResponse) -> bool {
+        let trace = String::from_utf8_lossy(&contract_response.debug_message);
+        ARITHMETIC_PANICS.iter().any(|panic| trace.contains(panic))
+    }
+
+    /// Classify a trapped contract, by looking for rustc arithmetic panics
+    /// inside the debug buffer of the call, or an exhausted gas limit
+    pub fn trap_type(&self, contract_response: &FullContractResponse) -> BugType {
+        if self.is_gas_bomb(contract_response) {
+            return BugType::GasBomb;
+        }
+        if Self::is_deposit_failure(contract_response) {
+            return BugType::DepositFailure;
+        }
+        let trace = String::from_utf8_lossy(&contract_response.debug_message);
+        if trace.contains(ENSURES_PANIC) {
+            BugType::PostconditionViolated
+        } else if Self::is_arithmetic_panic(contract_response) {
+            BugType::ArithmeticOverflow
+        } else {
+            BugType::ContractTrapped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::remote::test_response;
+    use contract_transcode::Tuple;
+    use pallet_contracts::chain_extension::ReturnFlags;
+
+    #[test]
+    fn test_false_properties_are_violated() {
+        let ok = |value| Value::Tuple(Tuple::new(Some("Ok"), vec![value]));
+        assert!(BugManager::is_false(&ok(Value::Bool(false))));
+        assert!(BugManager::is_false(&Value::Bool(false)));
+        assert!(!BugManager::is_false(&ok(Value::Bool(true))));
+        assert!(!BugManager::is_false(&ok(Value::Unit)));
+    }
+
+    #[test]
+    fn test_overflows_of_the_default_caller_break_no_arithmetic_panic() {
+        let config = Configuration {
+            no_arithmetic_panic: Some(true),
+            ..Default::default()
+        };
+        let message = |origin: Origin| {
+            Message {
+                is_payable: false,
+                payload: vec![0; 4].into(),
+                value_token: 0,
+                message_metadata: Value::Literal("msg".into()),
+                origin,
+                is_upgrade: false,
+                is_transfer: false,
+                deposit_limit: DepositLimit::Configured,
+                lapse: 0,
+            }
+        };
+        let mut overflow = test_response(ReturnFlags::empty(), vec![]);
+        overflow.debug_message =
+            b"panicked at lib.rs:4:9: attempt to add with overflow".to_vec();
+
+        // Every message is sent by the deployer, which isn't told apart
+        let unfuzzed = message(Origin::unfuzzed(&config));
+        assert!(BugManager::breaks_no_arithmetic_panic(
+            &config, &overflow, &unfuzzed
+        ));
+        assert!(!BugManager::breaks_no_arithmetic_panic(
+            &Configuration::default(),
+            &overflow,
+            &unfuzzed
+        ));
+        assert!(!BugManager::breaks_no_arithmetic_panic(
+            &config,
+            &test_response(ReturnFlags::empty(), vec![]),
+            &unfuzzed
+        ));
+
+        let fuzzed = Configuration {
+            fuzz_origin: true,
+            ..config
+        };
+        assert!(!BugManager::breaks_no_arithmetic_panic(
+            &fuzzed,
+            &overflow,
+            &message(Origin::from(1))
+        ));
+        assert!(BugManager::breaks_no_arithmetic_panic(
+            &fuzzed,
+            &overflow,
+            &message(Origin::from(2))
+        ));
+    }
+
+    #[test]
+    fn test_sequences_are_shrunk_to_the_failing_messages() {
+        let config = Configuration::default();
+        let message = |selector: u8| {
+            format::RawMessage {
+                value: 0,
+                origin: 1,
+                deposit_limit: 0,
+                payload: vec![selector; 4],
+            }
+        };
+        let layout = format::SeedLayout::of(&config);
+        let selectors = |sequence: &[u8]| -> Vec<u8> {
+            format::parse(sequence, &config)
+                .1
+                .iter()
+                .map(|message| message.payload[0])
+                .collect()
+        };
+
+        // Fails once 1, 2 and 3 got called in this order, whatever comes in
+        // between
+        let sequence = format::serialize(&[1, 4, 2, 3].map(message), layout);
+        let shrunk = BugManager::shrink(&sequence, &config, |candidate| {
+            let called = selectors(candidate);
+            let mut expected = [1, 2, 3].iter().peekable();
+            for selector in called {
+                expected.next_if_eq(&&selector);
+            }
+            expected.peek().is_none()
+        });
+
+        assert_eq!(selectors(&shrunk), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_origins_are_normalized_if_it_still_fails() {
+        let config = Configuration {
+            fuzz_origin: true,
+            ..Default::default()
+        };
+        let message = |origin: u8| {
+            format::RawMessage {
+                value: 0,
+                origin,
+                deposit_limit: 0,
+                payload: vec![0x63, 0x3a, 0xa5, 0x51],
+            }
+        };
+        let sequence =
+            format::serialize(&[7, 9, 7].map(message), format::SeedLayout::of(&config));
+
+        let normalized = BugManager::normalize_origins(&sequence, &config, |_| true);
+        assert_eq!(format::origins(&normalized, &config), Some(vec![1, 2, 1]));
+        assert_eq!(config.name_of(2), Some("CALLER_2"));
+
+        // Only two distinct callers matter, not that the first one is CALLER_1
+        let kept = BugManager::normalize_origins(&sequence, &config, |candidate| {
+            format::origins(candidate, &config).unwrap()[0] != 1
+        });
+        assert_eq!(kept, sequence);
+    }
 }
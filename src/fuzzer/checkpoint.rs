@@ -0,0 +1,204 @@
+use crate::{
+    cover::progress::CoverageProgress,
+    fuzzer::{
+        stateful::StatePool,
+        stats::{
+            StatsCounters,
+            StatsTracker,
+        },
+        swarm::AFL_WORKER_ENV,
+    },
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+#[cfg(not(fuzzing))]
+use tracing::info;
+use tracing::warn;
+
+/// Directory of `output_dir` where each fuzzer process saves its checkpoint,
+/// named after its AFL++ worker so that it's found again after a restart
+pub const CHECKPOINTS_DIR: &str = "checkpoints";
+pub const DEFAULT_CHECKPOINT_INTERVAL_SECS: u64 = 300;
+/// Checkpoint of a process not started by AFL++, e.g. `phink run`
+const STANDALONE_WORKER: &str = "standalone";
+
+/// What a fuzzer process only keeps in memory. The corpus, the findings and
+/// the dictionaries are already on the disk.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub stats: StatsCounters,
+    pub covered: Vec<u64>,
+    pub progress_execs: u64,
+    /// Coverage identifiers and hex-encoded sequences of `StatePool`
+    pub states_seen: Vec<u64>,
+    pub state_sequences: Vec<String>,
+}
+
+/// Saves a `Checkpoint` every `checkpoint_interval_secs`, so that a crash of
+/// the fuzzer or a reboot of the host loses at most that much of the campaign
+pub struct Checkpointer {
+    interval: Option<Duration>,
+    path: PathBuf,
+    last_save: Instant,
+}
+
+impl Checkpointer {
+    /// An `interval_secs` of `0` disables the checkpoints
+    pub fn new(interval_secs: Option<u64>, output_dir: &Path) -> Self {
+        let interval_secs = interval_secs.unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_SECS);
+        let worker = env::var(AFL_WORKER_ENV)
+            .ok()
+            .and_then(|out_dir| {
+                Path::new(&out_dir)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| STANDALONE_WORKER.into());
+        Self {
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            path: output_dir
+                .join(CHECKPOINTS_DIR)
+                .join(format!("{}.json", worker)),
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Checkpoint saved by the previous run of this worker, if any
+    pub fn load(&self) -> Option<Checkpoint> {
+        let json = fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str(&json) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                warn!("🙅 Ignoring the checkpoint {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Save the state of the campaign if the last checkpoint is older than
+    /// `checkpoint_interval_secs`
+    pub fn tick(
+        &mut self,
+        stats: &StatsTracker,
+        progress: &CoverageProgress,
+        states: &StatePool,
+    ) {
+        if self
+            .interval
+            .is_some_and(|interval| self.last_save.elapsed() >= interval)
+        {
+            self.last_save = Instant::now();
+            let checkpoint = Checkpoint::capture(stats, progress, states);
+            if let Err(e) = self.save(&checkpoint) {
+                warn!("🙅 Couldn't save the checkpoint: {}", e);
+            }
+        }
+    }
+
+    /// Write to a temporary file first, so that a crash while saving never
+    /// leaves a truncated checkpoint behind
+    fn save(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let json = serde_json::to_string(checkpoint).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        fs::write(&temporary, json).map_err(|e| e.to_string())?;
+        fs::rename(&temporary, &self.path).map_err(|e| e.to_string())
+    }
+}
+
+impl Checkpoint {
+    pub fn capture(
+        stats: &StatsTracker,
+        progress: &CoverageProgress,
+        states: &StatePool,
+    ) -> Self {
+        let (covered, progress_execs) = progress.covered();
+        Self {
+            stats: stats.counters(),
+            covered,
+            progress_execs,
+            states_seen: states.seen(),
+            state_sequences: states.sequences().iter().map(hex::encode).collect(),
+        }
+    }
+
+    /// Put the saved state back, `replay` rebuilding each end-state of the
+    /// `StatePool`
+    pub fn restore(
+        self,
+        stats: &mut StatsTracker,
+        progress: &mut CoverageProgress,
+        states: &mut StatePool,
+        replay: impl Fn(&[u8]),
+    ) {
+        stats.resume(&self.stats);
+        progress.resume(&self.covered, self.progress_execs);
+        let sequences = self
+            .state_sequences
+            .iter()
+            .filter_map(|sequence| hex::decode(sequence).ok())
+            .collect();
+        states.resume(&self.states_seen, sequences, replay);
+
+        #[cfg(not(fuzzing))]
+        info!(
+            "⏯️ Resumed from a checkpoint of {} executions",
+            self.stats.execs
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzer::stats::Rejection;
+    use sp_core::storage::Storage;
+
+    #[test]
+    fn test_checkpoint_survives_a_restart() {
+        let dir = std::env::temp_dir().join("phink_checkpoint_test");
+        let _ = fs::remove_dir_all(&dir);
+        let checkpointer = Checkpointer::new(Some(1), &dir);
+        assert!(checkpointer.load().is_none());
+
+        let mut stats = StatsTracker::new(Some(0), &dir);
+        stats.record_rejected(Rejection::NoMessage);
+        let mut states = StatePool::new(Storage::default(), 2);
+        states.keep(b"sequence".to_vec(), |_| {});
+        let checkpoint = Checkpoint {
+            stats: stats.counters(),
+            states_seen: vec![7],
+            state_sequences: states.sequences().iter().map(hex::encode).collect(),
+            ..Default::default()
+        };
+        checkpointer.save(&checkpoint).unwrap();
+
+        let loaded = checkpointer.load().unwrap();
+        assert_eq!(loaded.stats.execs, 1);
+        let mut resumed = StatePool::new(Storage::default(), 2);
+        resumed.resume(
+            &loaded.states_seen,
+            vec![hex::decode(&loaded.state_sequences[0]).unwrap()],
+            |_| {},
+        );
+        assert_eq!(resumed.sequences(), vec![b"sequence".to_vec()]);
+        assert_eq!(resumed.seen(), vec![7]);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::contract::{
+    remote::{
+        AccountIdOf,
+        ContractBridge,
+        FullContractResponse,
+    },
+    runtime::{
+        Runtime,
+        RuntimeEvent,
+    },
+};
+
+/// First difference between the fuzzed contract and the one instantiated
+/// from `differential_wasm_path`, for the same sequence of messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Message after which both contracts diverged, `None` if only their
+    /// storage differs at the end of the sequence
+    pub message_index: Option<usize>,
+    pub reason: String,
+}
+
+impl Divergence {
+    /// Compare the answers of both contracts, message by message. The gas and
+    /// the debug messages are left out, since the instrumentation alone
+    /// changes them.
+    pub fn between(
+        responses: &[FullContractResponse],
+        others: &[FullContractResponse],
+        address: &AccountIdOf<Runtime>,
+        other_address: &AccountIdOf<Runtime>,
+    ) -> Option<Self> {
+        responses
+            .iter()
+            .zip(others)
+            .enumerate()
+            .find_map(|(index, (response, other))| {
+                let reason = match (&response.result, &other.result) {
+                    (Ok(ret), Ok(other_ret))
+                        if ret.flags != other_ret.flags || ret.data != other_ret.data =>
+                    {
+                        format!(
+                            "returned {:?} 0x{}, against {:?} 0x{}",
+                            ret.flags,
+                            hex::encode(&ret.data),
+                            other_ret.flags,
+                            hex::encode(&other_ret.data)
+                        )
+                    }
+                    (Ok(_), Ok(_)) => {
+                        if Self::emitted(response, address)
+                            == Self::emitted(other, other_address)
+                        {
+                            return None;
+                        }
+                        "emitted different events".to_string()
+                    }
+                    (Err(e), Err(other_e)) if e == other_e => return None,
+                    (result, other_result) => {
+                        format!(
+                            "resulted in {:?}, against {:?}",
+                            result.as_ref().map(|_| ()),
+                            other_result.as_ref().map(|_| ())
+                        )
+                    }
+                };
+                Some(Self {
+                    message_index: Some(index),
+                    reason,
+                })
+            })
+    }
+
+    /// Compare the storage of both contracts, once every message executed
+    pub fn in_storage(
+        address: &AccountIdOf<Runtime>,
+        other_address: &AccountIdOf<Runtime>,
+    ) -> Option<Self> {
+        (ContractBridge::trie_root(address) != ContractBridge::trie_root(other_address))
+            .then(|| {
+                Self {
+                    message_index: None,
+                    reason: "ended up with a different storage".into(),
+                }
+            })
+    }
+
+    /// Data of the events emitted by the contract at `address`. The events of
+    /// the whole sequence are collected, including the ones of the other
+    /// contract, hence the filter.
+    fn emitted(
+        response: &FullContractResponse,
+        address: &AccountIdOf<Runtime>,
+    ) -> Vec<Vec<u8>> {
+        response
+            .events
+            .iter()
+            .flatten()
+            .filter_map(|record| {
+                match &record.event {
+                    RuntimeEvent::Contracts(
+                        pallet_contracts::Event::ContractEmitted { contract, data },
+                    ) if contract == address => Some(data.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::remote::test_response;
+    use pallet_contracts::chain_extension::ReturnFlags;
+
+    #[test]
+    fn test_diverging_return_value() {
+        let (address, other_address) = (
+            AccountIdOf::<Runtime>::new([1; 32]),
+            AccountIdOf::<Runtime>::new([2; 32]),
+        );
+        let same = [test_response(ReturnFlags::empty(), vec![0, 1])];
+        assert_eq!(
+            Divergence::between(&same, &same.clone(), &address, &other_address),
+            None
+        );
+
+        let reverted = [test_response(ReturnFlags::REVERT, vec![0, 1])];
+        let divergence =
+            Divergence::between(&same, &reverted, &address, &other_address).unwrap();
+        assert_eq!(divergence.message_index, Some(0));
+        assert!(divergence.reason.contains("0x0001"));
+    }
+}
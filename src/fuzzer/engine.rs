@@ -1,57 +1,80 @@
-use std::{
-    path::PathBuf,
-    sync::Mutex,
-};
+use std::path::PathBuf;
 
 use crate::{
+    cli::config::Configuration,
     contract::{
         remote::FullContractResponse,
         runtime::{
             AllPalletsWithSystem,
             BlockNumber,
+            Runtime,
             RuntimeOrigin,
             Timestamp,
             SLOT_DURATION,
         },
     },
+    cover::coverage::{
+        CoverageBuffer,
+        InputCoverage,
+    },
     fuzzer::{
         bug::BugManager,
         fuzz::Fuzzer,
+        harvest::StorageHarvest,
         parser::OneInput,
+        pretty::ValuePrinter,
+        scheduler::RareSelectors,
+        stateful::StatePool,
+        stats::StatsTracker,
     },
 };
-use contract_transcode::ContractMessageTranscoder;
 use frame_support::traits::{
     OnFinalize,
     OnInitialize,
 };
+use ink_metadata::InkProject;
 use pallet_contracts::ContractResult;
 use prettytable::{
     Cell,
     Row,
     Table,
 };
-use sp_core::crypto::AccountId32;
+
+/// Block every input starts executing in, so that a seed always runs against
+/// the same block number and timestamp
+pub const EXECUTION_BLOCK: BlockNumber = 1;
 
 pub trait FuzzerEngine {
     fn fuzz(self);
+    /// Execute one input, returning its coverage if it got executed
+    #[allow(clippy::too_many_arguments)]
     fn harness(
-        client: Fuzzer,
-        transcoder_loader: &mut Mutex<ContractMessageTranscoder>,
+        client: &Fuzzer,
+        states: &mut StatePool,
         bug_manager: &mut BugManager,
+        coverage_buffer: &mut CoverageBuffer,
+        stats: &mut StatsTracker,
+        scheduler: &RareSelectors,
+        harvest: &mut StorageHarvest,
         input: &[u8],
-    );
+    ) -> Option<InputCoverage>;
     fn exec_seed(self, seed: PathBuf);
 
-    /// Pretty print the result of `OneInput`
+    /// Pretty print the result of `OneInput`, with the types of `metadata`
     #[allow(dead_code)]
-    fn pretty_print(responses: Vec<FullContractResponse>, one_input: OneInput) {
+    fn pretty_print(
+        responses: Vec<FullContractResponse>,
+        one_input: OneInput,
+        metadata: &InkProject,
+        config: &Configuration,
+    ) {
         println!("\n🌱 Executing new seed");
         let mut table = Table::new();
         table.add_row(Row::new(vec![Cell::new("Message"), Cell::new("Details")]));
 
+        let printer = ValuePrinter::new(metadata);
         for (response, message) in responses.iter().zip(&one_input.messages) {
-            let call_description = message.message_metadata.to_string();
+            let call_description = printer.message(&message.message_metadata);
 
             let ContractResult {
                 result: _result, ..
@@ -60,18 +83,27 @@ pub trait FuzzerEngine {
             let debug = format!(
                 "⛽️ Gas required: {}\n\
              🔥 Gas consumed: {}\n\
-             🧑 Origin: {:?} ({})\n\
+             🧑 Origin: {:?} ({}{})\n\
              💾 Storage deposit: {:?}{}",
                 response.gas_required,
                 response.gas_consumed,
                 message.origin,
-                AccountId32::new([message.origin.into(); 32]),
+                config.account_of(message.origin.into()),
+                config
+                    .name_of(message.origin.into())
+                    .map(|name| format!(", {}", name))
+                    .unwrap_or_default(),
                 response.storage_deposit,
                 if message.is_payable {
                     format!(
                         "\n💸 Message was payable and {} units were transferred",
                         message.value_token
                     )
+                } else if message.is_transfer {
+                    format!(
+                        "\n💸 {} units were transferred to the contract",
+                        message.value_token
+                    )
                 } else {
                     String::new()
                 }
@@ -88,7 +120,8 @@ pub trait FuzzerEngine {
 
     /// We need to instantiate a proper timestamp on each call
     fn timestamp(lapse: u32) {
-        let mut block: u32 = 1;
+        let mut block = EXECUTION_BLOCK;
+        frame_system::Pallet::<Runtime>::set_block_number(block);
         Timestamp::set(
             RuntimeOrigin::none(),
             (block as u64).saturating_mul(SLOT_DURATION),
@@ -97,6 +130,7 @@ pub trait FuzzerEngine {
         if lapse > 0 {
             <AllPalletsWithSystem as OnFinalize<BlockNumber>>::on_finalize(block);
             block = block.saturating_add(lapse);
+            frame_system::Pallet::<Runtime>::set_block_number(block);
             <AllPalletsWithSystem as OnInitialize<BlockNumber>>::on_initialize(block);
             Timestamp::set(
                 RuntimeOrigin::none(),
@@ -105,4 +139,20 @@ pub trait FuzzerEngine {
             .unwrap();
         }
     }
+
+    /// Let `lapse` blocks go by after the ones of `timestamp`, e.g. before a
+    /// message of a `FuzzCall`
+    fn advance_blocks(lapse: u32) {
+        let block =
+            (pallet_timestamp::Now::<Runtime>::get() / SLOT_DURATION) as BlockNumber;
+        <AllPalletsWithSystem as OnFinalize<BlockNumber>>::on_finalize(block);
+        let next = block.saturating_add(lapse);
+        frame_system::Pallet::<Runtime>::set_block_number(next);
+        <AllPalletsWithSystem as OnInitialize<BlockNumber>>::on_initialize(next);
+        Timestamp::set(
+            RuntimeOrigin::none(),
+            SLOT_DURATION.saturating_mul(next as u64),
+        )
+        .unwrap();
+    }
 }
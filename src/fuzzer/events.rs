@@ -0,0 +1,253 @@
+use crate::{
+    cli::{
+        dashboard::AflWorker,
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::{
+        bug::{
+            BugType,
+            Severity,
+        },
+        finding::Finding,
+    },
+};
+use serde_derive::Serialize;
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use tracing::warn;
+
+/// JSON lines file of `output_dir` where the events of the campaign are
+/// appended, by the CLI and by every fuzzer process
+pub const EVENTS_PATH: &str = "events.jsonl";
+pub const DEFAULT_PLATEAU_SECS: u64 = 1800;
+/// A coverage milestone is reached once the coverage grew by this many
+/// percents since the previous one
+const MILESTONE_GROWTH_PERCENT: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    CampaignStart {
+        cores: u8,
+    },
+    /// The coverage of the campaign grew significantly. The coverage points
+    /// are the edges found by the AFL++ instance that found the most.
+    CoverageMilestone {
+        coverage_points: usize,
+        execs: u64,
+    },
+    /// A bug that isn't in the findings database yet
+    NewFinding {
+        bug_id: Option<String>,
+        bug_type: BugType,
+        severity: Severity,
+        description: String,
+        path: PathBuf,
+    },
+    /// No new coverage point for `since_secs`
+    Plateau {
+        coverage_points: usize,
+        since_secs: u64,
+    },
+    CampaignStop {
+        unique_findings: usize,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct EventEntry<'a> {
+    timestamp: u64,
+    pid: u32,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl Event {
+    pub fn new_finding(finding: &Finding, output_dir: &Path) -> Self {
+        Event::NewFinding {
+            bug_id: finding.bug_id.clone(),
+            bug_type: finding.bug_type,
+            severity: finding.severity,
+            description: finding.description.clone(),
+            path: finding.path(output_dir),
+        }
+    }
+
+    /// Append the event to `EVENTS_PATH`. Each line is written at once, so
+    /// that the processes of a campaign don't interleave their events.
+    pub fn emit(&self, output_dir: &Path) {
+        if let Err(e) = self.append(output_dir) {
+            warn!("🙅 Couldn't append to {}: {}", EVENTS_PATH, e);
+        }
+    }
+
+    fn append(&self, output_dir: &Path) -> std::io::Result<()> {
+        let entry = EventEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            pid: process::id(),
+            event: self,
+        };
+        let line = format!("{}\n", serde_json::to_string(&entry)?);
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(output_dir.join(EVENTS_PATH))?
+            .write_all(line.as_bytes())
+    }
+}
+
+/// Follows the coverage of the campaign to emit the coverage milestones and
+/// the plateaus. It is polled by `fuzz` itself, reading the `fuzzer_stats` of the
+/// AFL++ instances, since the fuzzer processes are forked by AFL++ and don't
+/// live long enough to see a plateau.
+pub struct CoverageEvents {
+    ziggy: ZiggyConfig,
+    plateau_after: Option<Duration>,
+    points: usize,
+    execs: u64,
+    last_milestone: usize,
+    last_growth: Instant,
+    /// A plateau is only reported once, until the coverage grows again
+    in_plateau: bool,
+}
+
+impl CoverageEvents {
+    /// A `plateau_secs` of `0` disables the plateaus
+    pub fn new(plateau_secs: Option<u64>, ziggy: &ZiggyConfig) -> Self {
+        let plateau_secs = plateau_secs.unwrap_or(DEFAULT_PLATEAU_SECS);
+        Self {
+            ziggy: ziggy.clone(),
+            plateau_after: (plateau_secs > 0).then(|| Duration::from_secs(plateau_secs)),
+            points: 0,
+            execs: 0,
+            last_milestone: 0,
+            last_growth: Instant::now(),
+            in_plateau: false,
+        }
+    }
+
+    /// Emit the events raised since the last poll
+    pub fn poll(&mut self) {
+        // The directory of AFL++ only exists once it started
+        let workers = AflWorker::read_all(&self.ziggy.afl_dir());
+        let before = self.points;
+        self.points = workers
+            .iter()
+            .map(|worker| worker.edges_found as usize)
+            .max()
+            .unwrap_or_default()
+            .max(before);
+        self.execs = workers.iter().map(|worker| worker.execs_done).sum();
+
+        for event in self.observe(before, Instant::now()) {
+            event.emit(&self.ziggy.config.output_dir());
+        }
+    }
+
+    /// Events raised by the coverage going from `before` to the current one
+    fn observe(&mut self, before: usize, now: Instant) -> Vec<Event> {
+        let points = self.points;
+        let mut events = Vec::new();
+
+        if points > before {
+            self.last_growth = now;
+            self.in_plateau = false;
+            if Self::is_milestone(self.last_milestone, points) {
+                self.last_milestone = points;
+                events.push(Event::CoverageMilestone {
+                    coverage_points: points,
+                    execs: self.execs,
+                });
+            }
+        } else if let Some(plateau_after) = self.plateau_after {
+            let since = now.duration_since(self.last_growth);
+            if !self.in_plateau && since >= plateau_after {
+                self.in_plateau = true;
+                events.push(Event::Plateau {
+                    coverage_points: points,
+                    since_secs: since.as_secs(),
+                });
+            }
+        }
+        events
+    }
+
+    fn is_milestone(last: usize, points: usize) -> bool {
+        points * 100 >= last * (100 + MILESTONE_GROWTH_PERCENT) && points > last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::Configuration;
+
+    #[test]
+    fn test_milestones_and_plateaus() {
+        let ziggy =
+            ZiggyConfig::new(Configuration::default(), PathBuf::from("/nonexistent"));
+        let mut events = CoverageEvents::new(Some(60), &ziggy);
+        let start = Instant::now();
+
+        events.points = 100;
+        assert!(matches!(
+            events.observe(0, start).as_slice(),
+            [Event::CoverageMilestone {
+                coverage_points: 100,
+                ..
+            }]
+        ));
+        // Less than 10% more
+        events.points = 105;
+        assert!(events.observe(100, start).is_empty());
+
+        let later = start + Duration::from_secs(61);
+        assert!(matches!(
+            events.observe(105, later).as_slice(),
+            [Event::Plateau {
+                coverage_points: 105,
+                since_secs: 61,
+            }]
+        ));
+        assert!(events.observe(105, later).is_empty());
+
+        events.points = 110;
+        assert!(matches!(
+            events.observe(105, later).as_slice(),
+            [Event::CoverageMilestone {
+                coverage_points: 110,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_events_are_tagged() {
+        let line = serde_json::to_string(&EventEntry {
+            timestamp: 1,
+            pid: 2,
+            event: &Event::CampaignStop { unique_findings: 3 },
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            "{\"timestamp\":1,\"pid\":2,\"event\":\"campaign_stop\",\"unique_findings\":3}"
+        );
+    }
+}
@@ -0,0 +1,506 @@
+use crate::{
+    cli::config::Configuration,
+    cover::coverage::InputCoverage,
+    fuzzer::{
+        bug::{
+            BugType,
+            Severity,
+        },
+        lock::{
+            write_atomically,
+            FileLock,
+        },
+        parser::Message,
+    },
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// Each finding is stored as a self-contained folder under this directory of
+/// `output_dir`
+pub const FINDINGS_DIR: &str = "findings";
+pub const FINDING_METADATA: &str = "finding.json";
+pub const FINDING_SEED: &str = "seed.bin";
+/// Under `FINDINGS_DIR`, the persistent database of every unique finding,
+/// shared across the sessions using the same `output_dir`
+pub const FINDINGS_INDEX: &str = "index.json";
+/// Where the database was stored before, directly under `output_dir`
+const LEGACY_FINDINGS_DB: &str = "findings.db";
+
+/// A message of the trace that led to a finding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FindingMessage {
+    pub label: String,
+    pub decoded: String,
+    pub payload: String,
+    pub origin: u8,
+    pub value: u128,
+    pub is_payable: bool,
+}
+
+impl From<&Message<'_>> for FindingMessage {
+    fn from(message: &Message<'_>) -> Self {
+        Self {
+            label: message.label(),
+            decoded: message.message_metadata.to_string(),
+            payload: hex::encode(&message.payload),
+            origin: message.origin.into(),
+            value: message.value_token,
+            is_payable: message.is_payable,
+        }
+    }
+}
+
+/// Everything needed to understand, share and replay a finding, serialized as
+/// `finding.json` next to the raw `seed.bin`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub id: String,
+    pub bug_type: BugType,
+    pub description: String,
+    pub timestamp: u64,
+    /// Hex representation of the raw seed
+    pub seed: String,
+    pub origin: u8,
+    pub messages: Vec<FindingMessage>,
+    /// The debug buffer of the faulty call, without the coverage identifiers
+    pub debug_trace: String,
+    /// Selector of the invariant that failed, if any
+    pub invariant: Option<String>,
+    pub coverage: Vec<u64>,
+    /// Triage label, derived from the bug type and the hints
+    #[serde(default)]
+    pub severity: Severity,
+    /// Heuristic observations about the trace, helping the triage
+    #[serde(default)]
+    pub hints: Vec<String>,
+    /// Rust source location where the contract trapped, if we could find it
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Swarm of the AFL++ worker that found it, with `swarm`
+    #[serde(default)]
+    pub swarm: Option<String>,
+    /// Stable identifier of the bug, e.g. `PHINK-1a2b3c4d-3`, shared by
+    /// every finding deduplicated into it. Set once recorded.
+    #[serde(default)]
+    pub bug_id: Option<String>,
+    /// Fields of the contract storage that changed between the genesis and
+    /// the violation of an invariant, e.g. `balances[0x2a00…]: 10 -> 0`
+    #[serde(default)]
+    pub storage_diff: Vec<String>,
+}
+
+impl Finding {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        bug_type: BugType,
+        description: String,
+        seed: &[u8],
+        messages: &[Message],
+        debug_trace: &[u8],
+        invariant: Option<String>,
+        coverage: &InputCoverage,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            id: Self::compute_id(seed),
+            bug_type,
+            description,
+            timestamp,
+            seed: hex::encode(seed),
+            origin: messages
+                .first()
+                .map(|m| m.origin.into())
+                .unwrap_or_default(),
+            messages: messages.iter().map(FindingMessage::from).collect(),
+            debug_trace: String::from_utf8_lossy(&InputCoverage::remove_cov_from_trace(
+                debug_trace.to_vec(),
+            ))
+            .into_owned(),
+            invariant,
+            coverage: coverage.coverage_ids(),
+            severity: bug_type.severity(),
+            hints: Vec::new(),
+            location: None,
+            swarm: None,
+            bug_id: None,
+            storage_diff: Vec::new(),
+        }
+    }
+
+    /// Bug ID if recorded, else the identifier of the finding
+    pub fn reference(&self) -> &str {
+        self.bug_id.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Attach heuristic hints to the finding, and adjust its severity
+    /// accordingly. A bug reachable while transferring value is worse, while a
+    /// bug only triggered by the deployer likely requires a privileged caller,
+    /// if other accounts could call the contract too.
+    pub fn classify(mut self, config: &Configuration) -> Self {
+        let mut severity = self.bug_type.severity();
+        self.hints.clear();
+
+        if self.messages.iter().any(|m| m.is_payable && m.value > 0) {
+            self.hints
+                .push("💸 The trace involves a value transfer".into());
+            severity = severity.raise();
+        }
+
+        let deployer = config.deployer();
+        let by_deployer = |origin: u8| config.account_of(origin) == deployer;
+        if config.is_deployer_distinct()
+            && !self.messages.is_empty()
+            && self.messages.iter().all(|m| by_deployer(m.origin))
+        {
+            self.hints.push(
+                "👑 Every message is sent by the deployer, a privileged caller might be required"
+                    .into(),
+            );
+            severity = severity.lower();
+        }
+
+        self.severity = severity;
+        self
+    }
+
+    /// Two findings sharing this key are considered as the same bug: same bug
+    /// type, same failing invariant, and the same minimized trace, i.e. the
+    /// set of distinct selectors called, regardless of their order, repeats
+    /// and arguments
+    pub fn dedup_key(&self) -> String {
+        let mut selectors: Vec<&str> = self
+            .messages
+            .iter()
+            .map(|message| message.payload.get(..8).unwrap_or(&message.payload))
+            .collect();
+        selectors.sort_unstable();
+        selectors.dedup();
+
+        let trace = format!(
+            "{:?}|{}|{}",
+            self.bug_type,
+            self.invariant.clone().unwrap_or_default(),
+            selectors.join(",")
+        );
+        hex::encode(&sp_io::hashing::blake2_256(trace.as_bytes())[..8])
+    }
+
+    /// The identifier is derived from the seed, so saving twice the same
+    /// crashing seed doesn't create two folders
+    fn compute_id(seed: &[u8]) -> String {
+        hex::encode(&sp_io::hashing::blake2_256(seed)[..8])
+    }
+
+    pub fn path(&self, output_dir: &Path) -> PathBuf {
+        output_dir.join(FINDINGS_DIR).join(&self.id)
+    }
+
+    /// Write `seed.bin` and `finding.json` into `FINDINGS_DIR/<id>/`
+    pub fn save(&self, output_dir: &Path) -> io::Result<PathBuf> {
+        let dir = self.path(output_dir);
+        fs::create_dir_all(&dir)?;
+
+        fs::write(
+            dir.join(FINDING_SEED),
+            hex::decode(&self.seed).map_err(|e| io::Error::other(e.to_string()))?,
+        )?;
+        fs::write(
+            dir.join(FINDING_METADATA),
+            serde_json::to_string_pretty(self)?,
+        )?;
+
+        Ok(dir)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnownFinding {
+    /// Identifier of the first finding saved for this bug
+    pub id: String,
+    /// `PHINK-<contract hash>-<n>`, the n-th unique bug of this build of the
+    /// contract. Missing from the databases written before.
+    #[serde(default)]
+    pub bug_id: Option<String>,
+    pub bug_type: BugType,
+    pub description: String,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub occurrences: u64,
+}
+
+impl KnownFinding {
+    /// Bug ID if any, else the identifier of the first finding
+    pub fn reference(&self) -> &str {
+        self.bug_id.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// JSON database stored at `FINDINGS_DIR/FINDINGS_INDEX`, keyed by
+/// `Finding::dedup_key`. It allows `phink run` or a new campaign to not report
+/// dozens of times the same bug, and gives each bug an ID that auditors and
+/// developers can refer to.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FindingsDb {
+    pub findings: BTreeMap<String, KnownFinding>,
+}
+
+impl FindingsDb {
+    pub fn load(output_dir: &Path) -> Self {
+        [
+            output_dir.join(FINDINGS_DIR).join(FINDINGS_INDEX),
+            output_dir.join(LEGACY_FINDINGS_DB),
+        ]
+        .iter()
+        .find_map(|path| {
+            let content = fs::read_to_string(path).ok()?;
+            serde_json::from_str(&content).ok()
+        })
+        .unwrap_or_default()
+    }
+
+    pub fn save(&self, output_dir: &Path) -> io::Result<()> {
+        let dir = output_dir.join(FINDINGS_DIR);
+        fs::create_dir_all(&dir)?;
+        write_atomically(
+            &dir.join(FINDINGS_INDEX),
+            serde_json::to_string_pretty(self)?,
+        )
+    }
+
+    /// Load the database, let `update` modify it, then save it, holding the
+    /// lock of `FINDINGS_INDEX` so that two fuzzing processes don't overwrite
+    /// each other's findings nor give out the same bug ID. The entries
+    /// migrated from `LEGACY_FINDINGS_DB` get the next bug IDs of the
+    /// contract hashed as `contract_hash`.
+    pub fn update<R>(
+        output_dir: &Path,
+        contract_hash: &str,
+        update: impl FnOnce(&mut Self) -> R,
+    ) -> io::Result<R> {
+        let _lock =
+            FileLock::acquire(&output_dir.join(FINDINGS_DIR).join(FINDINGS_INDEX))?;
+        let mut db = Self::load(output_dir);
+        db.assign_bug_ids(contract_hash);
+        let result = update(&mut db);
+        db.save(output_dir)?;
+        Ok(result)
+    }
+
+    /// Give a bug ID to the entries without one, by order of appearance
+    fn assign_bug_ids(&mut self, contract_hash: &str) {
+        let mut missing: Vec<(u64, String, String)> = self
+            .findings
+            .iter()
+            .filter(|(_, known)| known.bug_id.is_none())
+            .map(|(key, known)| (known.first_seen, known.id.clone(), key.clone()))
+            .collect();
+        missing.sort();
+        for (_, _, key) in missing {
+            let bug_id = self.next_bug_id(contract_hash);
+            if let Some(known) = self.findings.get_mut(&key) {
+                known.bug_id = Some(bug_id);
+            }
+        }
+    }
+
+    /// Register `finding`, returning `true` if it was never seen before. It
+    /// gets the bug ID of the bug it duplicates, or the next one of the
+    /// contract hashed as `contract_hash`.
+    pub fn record(&mut self, finding: &mut Finding, contract_hash: &str) -> bool {
+        let next_id = self.next_bug_id(contract_hash);
+        match self.findings.get_mut(&finding.dedup_key()) {
+            Some(known) => {
+                known.last_seen = finding.timestamp;
+                known.occurrences += 1;
+                finding.bug_id = known.bug_id.clone();
+                false
+            }
+            None => {
+                finding.bug_id = Some(next_id);
+                self.findings.insert(
+                    finding.dedup_key(),
+                    KnownFinding {
+                        id: finding.id.clone(),
+                        bug_id: finding.bug_id.clone(),
+                        bug_type: finding.bug_type,
+                        description: finding.description.clone(),
+                        first_seen: finding.timestamp,
+                        last_seen: finding.timestamp,
+                        occurrences: 1,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// `PHINK-<first 8 hex digits of the hash>-<n>`, `n` following the
+    /// highest one already given to a bug of this contract, from 1
+    fn next_bug_id(&self, contract_hash: &str) -> String {
+        let hash = contract_hash.trim_start_matches("0x");
+        let prefix = format!("PHINK-{}-", &hash[..hash.len().min(8)]);
+        let highest = self
+            .findings
+            .values()
+            .filter_map(|known| known.bug_id.as_deref()?.strip_prefix(&prefix))
+            .filter_map(|n| n.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        format!("{}{}", prefix, highest + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(seed: &str, payload: &str) -> Finding {
+        Finding {
+            id: Finding::compute_id(seed.as_bytes()),
+            bug_type: BugType::InvariantViolated,
+            description: "invariant `phink_assert_dangerous_number` violated".into(),
+            timestamp: 42,
+            seed: hex::encode(seed),
+            origin: 1,
+            messages: vec![FindingMessage {
+                label: "set_dangerous_number".into(),
+                decoded: String::new(),
+                payload: payload.into(),
+                origin: 1,
+                value: 0,
+                is_payable: false,
+            }],
+            debug_trace: String::new(),
+            invariant: Some("b587ecdf".into()),
+            coverage: vec![],
+            severity: BugType::InvariantViolated.severity(),
+            hints: vec![],
+            location: None,
+            swarm: None,
+            bug_id: None,
+            storage_diff: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_same_bug_with_other_arguments_is_deduplicated() {
+        let mut db = FindingsDb::default();
+        let hash = "0x1a2b3c4d5e6f";
+
+        let mut second = finding("second", "229b0ad3ffffffff");
+        let mut third = finding("third", "aabbccdd00000000");
+        assert!(db.record(&mut finding("first", "229b0ad300000000"), hash));
+        assert!(!db.record(&mut second, hash));
+        assert!(db.record(&mut third, hash));
+
+        let known = &db.findings[&finding("first", "229b0ad300000000").dedup_key()];
+        assert_eq!(known.occurrences, 2);
+        assert_eq!(known.id, Finding::compute_id(b"first"));
+        assert_eq!(second.reference(), "PHINK-1a2b3c4d-1");
+        assert_eq!(third.reference(), "PHINK-1a2b3c4d-2");
+    }
+
+    #[test]
+    fn test_only_a_distinct_deployer_lowers_the_severity() {
+        // By default, the deployer sends every message
+        let classified =
+            finding("first", "229b0ad300000000").classify(&Configuration::default());
+        assert_eq!(classified.severity, BugType::InvariantViolated.severity());
+        assert!(classified.hints.is_empty());
+
+        let config = Configuration {
+            fuzz_origin: true,
+            ..Default::default()
+        };
+        let classified = finding("first", "229b0ad300000000").classify(&config);
+        assert_eq!(
+            classified.severity,
+            BugType::InvariantViolated.severity().lower()
+        );
+        assert_eq!(classified.hints.len(), 1);
+    }
+
+    #[test]
+    fn test_repeated_selectors_share_the_dedup_key() {
+        let mut repeated = finding("first", "229b0ad300000000");
+        let message = repeated.messages[0].clone();
+        repeated.messages.push(FindingMessage {
+            payload: "aabbccdd00000000".into(),
+            ..message.clone()
+        });
+        let once = repeated.clone();
+        repeated.messages.push(message);
+
+        assert_eq!(repeated.dedup_key(), once.dedup_key());
+        assert_ne!(
+            once.dedup_key(),
+            finding("first", "229b0ad300000000").dedup_key()
+        );
+    }
+
+    #[test]
+    fn test_findings_db_is_saved_under_the_findings() {
+        let dir = std::env::temp_dir().join("phink_findings_db_test");
+        let _ = fs::remove_dir_all(&dir);
+        let mut db = FindingsDb::default();
+        db.record(&mut finding("first", "229b0ad300000000"), "0xff");
+        db.save(&dir).unwrap();
+
+        assert!(dir.join(FINDINGS_DIR).join(FINDINGS_INDEX).is_file());
+        let loaded = FindingsDb::load(&dir);
+        assert_eq!(
+            loaded.findings.values().next().unwrap().reference(),
+            "PHINK-ff-1"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_legacy_findings_get_a_bug_id() {
+        let dir = std::env::temp_dir().join("phink_legacy_findings_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut legacy = FindingsDb::default();
+        legacy.record(&mut finding("first", "229b0ad300000000"), "0xff");
+        for known in legacy.findings.values_mut() {
+            known.bug_id = None;
+        }
+        fs::write(
+            dir.join(LEGACY_FINDINGS_DB),
+            serde_json::to_string(&legacy).unwrap(),
+        )
+        .unwrap();
+
+        let mut second = finding("second", "aabbccdd00000000");
+        let is_new =
+            FindingsDb::update(&dir, "0xff", |db| db.record(&mut second, "0xff"));
+        assert!(is_new.unwrap());
+        assert_eq!(second.reference(), "PHINK-ff-2");
+
+        let loaded = FindingsDb::load(&dir);
+        let first = &loaded.findings[&finding("first", "229b0ad300000000").dedup_key()];
+        assert_eq!(first.reference(), "PHINK-ff-1");
+        assert!(!dir.join(FINDINGS_DIR).join("index.json.lock").exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    cli::config::{
+        Configuration,
+        OriginFuzzingOption::EnableOriginFuzzing,
+    },
+    contract::payload::{
+        PayloadCrafter,
+        Selector,
+    },
+    fuzzer::parser::{
+        Data,
+        DELIMITER,
+        TRANSFER_SELECTOR,
+        UPGRADE_SELECTOR,
+    },
+};
+use ink_metadata::InkProject;
+use parity_scale_codec::{
+    Decode,
+    Encode,
+};
+use std::{
+    fs,
+    path::Path,
+};
+
+/// Start of a versioned seed, i.e `PHNK`
+pub const SEED_MAGIC: [u8; 4] = *b"PHNK";
+pub const SEED_VERSION: u8 = 1;
+/// Version of the seeds made of a SCALE-encoded `Vec<FuzzCall>`, right after
+/// `SEED_MAGIC` and the version
+pub const SCALE_SEED_VERSION: u8 = 2;
+/// `SEED_MAGIC`, the version, then the flags of the `SeedLayout`
+pub const SEED_HEADER_LEN: usize = SEED_MAGIC.len() + 2;
+
+const ORIGIN_FLAG: u8 = 1;
+const DEPOSIT_LIMIT_FLAG: u8 = 1 << 1;
+
+/// Bytes preceding the selector of each message of a seed. A seed of version
+/// 1 is laid out as follows:
+///
+/// ```text
+/// "PHNK" | version (1) | flags (bit 0: origin, bit 1: deposit limit)
+/// message ("********" message)*
+/// message = value (4 bytes) | origin (1 byte)? | deposit limit (1 byte)? | selector | args
+/// ```
+///
+/// A seed without the header, i.e. of version 0, is laid out as the
+/// configuration says, so it gets misread once `fuzz_origin` or
+/// `fuzz_storage_deposit_limit` change. A versioned seed carries its layout,
+/// and is read the same way whatever the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeedLayout {
+    pub origin: bool,
+    pub deposit_limit: bool,
+}
+
+/// One message of a seed, whatever its layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMessage {
+    pub value: u32,
+    /// `1` if the layout has no origin, i.e. the deployer
+    pub origin: u8,
+    /// `0` if the layout has no deposit limit, i.e. `DepositLimit::Configured`
+    pub deposit_limit: u8,
+    /// Selector and SCALE-encoded arguments
+    pub payload: Vec<u8>,
+}
+
+/// One message of a seed of version 2. Since the whole seed is SCALE-encoded,
+/// no delimiter can collide with the arguments, a mutation of one field
+/// doesn't shift the next messages, and decoding then encoding a seed gives
+/// it back as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct FuzzCall {
+    /// Index of the message among `call_selectors`, modulo their count
+    pub selector_index: u16,
+    /// SCALE-encoded arguments of the message
+    pub args: Vec<u8>,
+    pub value: u32,
+    /// Only used with `fuzz_origin`
+    pub origin: u8,
+    /// Only used with `fuzz_storage_deposit_limit`, see `DepositLimit`
+    pub deposit_limit: u8,
+    /// Blocks elapsed before the message
+    pub lapse: u8,
+}
+
+impl SeedLayout {
+    /// Layout of the seeds written under `config`
+    pub fn of(config: &Configuration) -> Self {
+        Self {
+            origin: matches!(config.should_fuzz_origin(), EnableOriginFuzzing),
+            deposit_limit: config.should_fuzz_storage_deposit_limit(),
+        }
+    }
+
+    /// Bytes before the selector of each message
+    pub fn message_header_len(&self) -> usize {
+        4 + usize::from(self.origin) + usize::from(self.deposit_limit)
+    }
+
+    pub fn header(&self) -> [u8; SEED_HEADER_LEN] {
+        let flags = if self.origin { ORIGIN_FLAG } else { 0 }
+            | if self.deposit_limit {
+                DEPOSIT_LIMIT_FLAG
+            } else {
+                0
+            };
+        let [m0, m1, m2, m3] = SEED_MAGIC;
+        [m0, m1, m2, m3, SEED_VERSION, flags]
+    }
+
+    /// Layout of `data` and its messages, `fallback` being the layout of a
+    /// seed without header
+    pub fn split(data: &[u8], fallback: SeedLayout) -> (SeedLayout, &[u8]) {
+        match data {
+            [m0, m1, m2, m3, SEED_VERSION, flags, messages @ ..]
+                if [*m0, *m1, *m2, *m3] == SEED_MAGIC =>
+            {
+                let layout = Self {
+                    origin: flags & ORIGIN_FLAG != 0,
+                    deposit_limit: flags & DEPOSIT_LIMIT_FLAG != 0,
+                };
+                (layout, messages)
+            }
+            _ => (fallback, data),
+        }
+    }
+}
+
+/// Messages of `data`, ignoring `max_messages_per_exec`
+pub fn parse(data: &[u8], config: &Configuration) -> (SeedLayout, Vec<RawMessage>) {
+    let (layout, messages) = SeedLayout::split(data, SeedLayout::of(config));
+    let header_len = layout.message_header_len();
+    let messages = Data {
+        data: messages,
+        pointer: 0,
+        size: 0,
+        max_messages_per_exec: usize::MAX,
+    }
+    .filter(|message| message.len() >= header_len)
+    .map(|message| {
+        let mut optional = message[4..header_len].iter();
+        RawMessage {
+            value: u32::from_ne_bytes(message[..4].try_into().unwrap()),
+            origin: layout
+                .origin
+                .then(|| optional.next().copied())
+                .flatten()
+                .unwrap_or(1),
+            deposit_limit: layout
+                .deposit_limit
+                .then(|| optional.next().copied())
+                .flatten()
+                .unwrap_or(0),
+            payload: message[header_len..].to_vec(),
+        }
+    })
+    .collect();
+    (layout, messages)
+}
+
+/// Versioned seed of `messages`, laid out as `layout`
+pub fn serialize(messages: &[RawMessage], layout: SeedLayout) -> Vec<u8> {
+    let framed: Vec<Vec<u8>> = messages
+        .iter()
+        .map(|message| {
+            let mut framed = message.value.to_ne_bytes().to_vec();
+            if layout.origin {
+                framed.push(message.origin);
+            }
+            if layout.deposit_limit {
+                framed.push(message.deposit_limit);
+            }
+            framed.extend_from_slice(&message.payload);
+            framed
+        })
+        .collect();
+    [
+        layout.header().as_slice(),
+        &framed.join(DELIMITER.as_slice()),
+    ]
+    .concat()
+}
+
+/// Calls of a seed of version 2, `None` for any other seed. Bytes left after
+/// the calls, e.g. appended by a mutation, are ignored.
+pub fn scale_calls(data: &[u8]) -> Option<Vec<FuzzCall>> {
+    match data {
+        [m0, m1, m2, m3, SCALE_SEED_VERSION, calls @ ..]
+            if [*m0, *m1, *m2, *m3] == SEED_MAGIC =>
+        {
+            Vec::<FuzzCall>::decode(&mut &calls[..]).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Seed of version 2 of `calls`
+pub fn serialize_calls(calls: &[FuzzCall]) -> Vec<u8> {
+    [
+        SEED_MAGIC.as_slice(),
+        &[SCALE_SEED_VERSION],
+        &calls.encode(),
+    ]
+    .concat()
+}
+
+/// Messages a `FuzzCall::selector_index` points to: the messages of the
+/// contract except the invariants, in the order of the metadata, then the
+/// upgrade if one is configured, then the transfer with `fuzz_transfers`
+pub fn call_selectors(metadata: &InkProject, config: &Configuration) -> Vec<Selector> {
+    let mut selectors: Vec<Selector> = metadata
+        .spec()
+        .messages()
+        .iter()
+        .filter(|message| !PayloadCrafter::is_invariant(message.label()))
+        .map(|message| *message.selector().to_bytes())
+        .collect();
+    if config.upgrade_wasm_path.is_some() {
+        selectors.push(UPGRADE_SELECTOR);
+    }
+    if config.should_fuzz_transfers() {
+        selectors.push(TRANSFER_SELECTOR);
+    }
+    selectors
+}
+
+/// `messages` as `FuzzCall`s, dropping the ones whose selector isn't one of
+/// `selectors`
+pub fn to_calls(messages: &[RawMessage], selectors: &[Selector]) -> Vec<FuzzCall> {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let selector = message.payload.get(..4)?;
+            let index = selectors.iter().position(|known| known == selector)?;
+            Some(FuzzCall {
+                selector_index: index.try_into().ok()?,
+                args: message.payload[4..].to_vec(),
+                value: message.value,
+                origin: message.origin,
+                deposit_limit: message.deposit_limit,
+                lapse: 0,
+            })
+        })
+        .collect()
+}
+
+/// Number of messages of `data`, whatever its version
+pub fn message_count(data: &[u8], config: &Configuration) -> usize {
+    match scale_calls(data) {
+        Some(calls) => calls.len(),
+        None => parse(data, config).1.len(),
+    }
+}
+
+/// `data` without its message at `index`, keeping its version. A seed
+/// without header gets the one of the layout of `config`.
+pub fn remove_message(data: &[u8], index: usize, config: &Configuration) -> Vec<u8> {
+    if let Some(mut calls) = scale_calls(data) {
+        if index < calls.len() {
+            calls.remove(index);
+        }
+        return serialize_calls(&calls);
+    }
+    let (layout, mut messages) = parse(data, config);
+    if index < messages.len() {
+        messages.remove(index);
+    }
+    serialize(&messages, layout)
+}
+
+/// Origin byte of each message of `data`, `None` if the origins aren't part
+/// of it, i.e. neither its layout nor `fuzz_origin` for a seed of version 2
+pub fn origins(data: &[u8], config: &Configuration) -> Option<Vec<u8>> {
+    if let Some(calls) = scale_calls(data) {
+        return matches!(config.should_fuzz_origin(), EnableOriginFuzzing)
+            .then(|| calls.iter().map(|call| call.origin).collect());
+    }
+    let (layout, messages) = parse(data, config);
+    layout
+        .origin
+        .then(|| messages.iter().map(|message| message.origin).collect())
+}
+
+/// `data` with the origin byte of each message replaced by the one of
+/// `origins`, keeping its version
+pub fn with_origins(data: &[u8], origins: &[u8], config: &Configuration) -> Vec<u8> {
+    if let Some(mut calls) = scale_calls(data) {
+        for (call, origin) in calls.iter_mut().zip(origins) {
+            call.origin = *origin;
+        }
+        return serialize_calls(&calls);
+    }
+    let (layout, mut messages) = parse(data, config);
+    for (message, origin) in messages.iter_mut().zip(origins) {
+        message.origin = *origin;
+    }
+    serialize(&messages, layout)
+}
+
+/// Version of the format of `data`, `0` if it has no header
+pub fn version(data: &[u8]) -> u8 {
+    if scale_calls(data).is_some() {
+        return SCALE_SEED_VERSION;
+    }
+    let fallback = SeedLayout {
+        origin: false,
+        deposit_limit: false,
+    };
+    let (_, messages) = SeedLayout::split(data, fallback);
+    if messages.len() < data.len() {
+        SEED_VERSION
+    } else {
+        0
+    }
+}
+
+/// `data` with the header of the layout of `config`, if it has none yet
+pub fn migrate(data: &[u8], config: &Configuration) -> Option<Vec<u8>> {
+    if scale_calls(data).is_some() {
+        return None;
+    }
+    let fallback = SeedLayout::of(config);
+    let (_, messages) = SeedLayout::split(data, fallback);
+    (messages.len() == data.len()).then(|| [fallback.header().as_slice(), data].concat())
+}
+
+/// Migrate every seed of `corpus` in place, returning how many got migrated
+pub fn migrate_corpus(corpus: &Path, config: &Configuration) -> usize {
+    let Ok(entries) = fs::read_dir(corpus) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            fs::read(path)
+                .ok()
+                .and_then(|seed| migrate(&seed, config))
+                .is_some_and(|migrated| fs::write(path, migrated).is_ok())
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeds_keep_their_layout() {
+        let with_origin = Configuration {
+            fuzz_origin: true,
+            ..Default::default()
+        };
+        let legacy = vec![80, 0, 0, 0, 2, 0x63, 0x3a, 0xa5, 0x51];
+
+        let migrated = migrate(&legacy, &with_origin).unwrap();
+        assert_eq!(&migrated[..SEED_HEADER_LEN], b"PHNK\x01\x01");
+        assert_eq!(migrate(&migrated, &with_origin), None);
+
+        // Read the same way once `fuzz_origin` is disabled
+        let (layout, messages) = parse(&migrated, &Configuration::default());
+        assert_eq!(layout, SeedLayout::of(&with_origin));
+        assert_eq!(
+            messages,
+            vec![RawMessage {
+                value: 80,
+                origin: 2,
+                deposit_limit: 0,
+                payload: vec![0x63, 0x3a, 0xa5, 0x51],
+            }]
+        );
+        assert_eq!(serialize(&messages, layout), migrated);
+
+        let calls = to_calls(&messages, &[[0; 4], [0x63, 0x3a, 0xa5, 0x51]]);
+        assert_eq!(calls[0].selector_index, 1);
+        let seed = serialize_calls(&calls);
+        assert_eq!(scale_calls(&seed), Some(calls));
+        assert_eq!(serialize_calls(&scale_calls(&seed).unwrap()), seed);
+        assert_eq!(migrate(&seed, &with_origin), None);
+        assert_eq!(message_count(&seed, &with_origin), 1);
+        assert_eq!(origins(&migrated, &Configuration::default()), Some(vec![2]));
+        let renamed = with_origins(&migrated, &[1], &with_origin);
+        assert_eq!(origins(&renamed, &with_origin), Some(vec![1]));
+        assert_eq!(origins(&legacy, &Configuration::default()), None);
+        for seed in [&seed, &legacy] {
+            let shrunk = remove_message(seed, 0, &with_origin);
+            assert_eq!(message_count(&shrunk, &with_origin), 0);
+        }
+        assert_eq!(
+            [legacy.as_slice(), &migrated, &seed].map(version),
+            [0, SEED_VERSION, SCALE_SEED_VERSION]
+        );
+    }
+}
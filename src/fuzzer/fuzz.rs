@@ -4,16 +4,20 @@ use std::{
         self,
         Write,
     },
-    path::{
-        Path,
-        PathBuf,
+    path::Path,
+    sync::Arc,
+    time::{
+        Duration,
+        Instant,
     },
-    sync::Mutex,
 };
 
-use contract_transcode::ContractMessageTranscoder;
-use frame_support::__private::BasicExternalities;
 use sp_core::hexdisplay::AsBytesRef;
+use tracing::{
+    error,
+    info,
+    warn,
+};
 
 use crate::{
     cli::{
@@ -21,6 +25,10 @@ use crate::{
         ziggy::ZiggyConfig,
     },
     contract::{
+        extension::{
+            set_fuzzed_bytes,
+            take_coverage,
+        },
         payload::{
             PayloadCrafter,
             Selector,
@@ -29,29 +37,70 @@ use crate::{
             ContractBridge,
             FullContractResponse,
         },
+        runtime::SLOT_DURATION,
+        snapshot::Snapshot,
+    },
+    cover::{
+        coverage::{
+            CoverageBuffer,
+            InputCoverage,
+        },
+        progress::CoverageProgress,
     },
-    cover::coverage::InputCoverage,
     fuzzer::{
-        bug::BugManager,
-        engine::FuzzerEngine,
+        bug::{
+            BugManager,
+            MessageStats,
+        },
+        checkpoint::Checkpointer,
+        differential::Divergence,
+        engine::{
+            FuzzerEngine,
+            EXECUTION_BLOCK,
+        },
+        format::{
+            self,
+            FuzzCall,
+            SeedLayout,
+        },
         fuzz::FuzzingMode::{
             ExecuteOneInput,
             Fuzz,
+            Trace,
         },
+        harvest::StorageHarvest,
         parser::{
             parse_input,
             OneInput,
+            TRANSFER_SELECTOR,
+            UPGRADE_SELECTOR,
+        },
+        scheduler::RareSelectors,
+        stateful::StatePool,
+        stats::{
+            Rejection,
+            StatsTracker,
         },
+        swarm::Swarm,
+        temporal::TraceProperty,
+        trace::SeedTrace,
+    },
+    instrumenter::{
+        constants::ContractConstants,
+        instrumentation::Instrumenter,
     },
-    instrumenter::instrumentation::Instrumenter,
 };
 
-pub const CORPUS_DIR: &str = "./output/phink/corpus";
-pub const DICT_FILE: &str = "./output/phink/selectors.dict";
+/// Both under `output_dir`
+pub const CORPUS_DIR: &str = "corpus";
+pub const DICT_FILE: &str = "selectors.dict";
 pub const MAX_MESSAGES_PER_EXEC: usize = 4; // One execution contains maximum 4 messages.
 
 pub enum FuzzingMode {
-    ExecuteOneInput(PathBuf),
+    /// Execute the raw bytes of one input
+    ExecuteOneInput(Vec<u8>),
+    /// Execute the raw bytes of one input, and print what each message did
+    Trace(Vec<u8>),
     Fuzz,
 }
 
@@ -59,6 +108,14 @@ pub enum FuzzingMode {
 pub struct Fuzzer {
     pub setup: ContractBridge,
     pub fuzzing_config: Configuration,
+    /// Messages left out by `allowed_messages` or `denied_messages`
+    pub denied_selectors: Vec<Selector>,
+    /// Part of the inputs this worker explores, with `swarm`
+    pub swarm: Option<Swarm>,
+    /// Literals of the contract, added to the dictionary
+    pub constants: ContractConstants,
+    /// Registered on top of the `sequence_properties` of the configuration
+    pub trace_properties: Vec<Arc<dyn TraceProperty>>,
 }
 
 impl Fuzzer {
@@ -66,164 +123,490 @@ impl Fuzzer {
         Self {
             setup,
             fuzzing_config: Default::default(),
+            denied_selectors: Vec::new(),
+            swarm: None,
+            constants: ContractConstants::default(),
+            trace_properties: Vec::new(),
         }
     }
 
     pub fn execute_harness(mode: FuzzingMode, config: ZiggyConfig) -> io::Result<()> {
-        let finder = Instrumenter::new(config.contract_path).find().unwrap();
+        Self::execute_harness_with(mode, config, Vec::new())
+    }
+
+    /// `execute_harness`, also checking `trace_properties` over each input
+    pub fn execute_harness_with(
+        mode: FuzzingMode,
+        config: ZiggyConfig,
+        trace_properties: Vec<Arc<dyn TraceProperty>>,
+    ) -> io::Result<()> {
+        let finder = Instrumenter::new(config.contract_path.clone())
+            .find()
+            .unwrap();
         let wasm = fs::read(&finder.wasm_path)?;
-        let setup = ContractBridge::initialize_wasm(
-            wasm,
-            &finder.specs_path,
-            config.config.clone(),
-        );
+        let setup =
+            ContractBridge::initialize(wasm, &finder.specs_path, config.config.clone());
         let mut fuzzer = Fuzzer::new(setup);
+        fuzzer.set_config(config.config);
+        fuzzer.constants = ContractConstants::load(&config.contract_path);
+        fuzzer.trace_properties = trace_properties;
 
         match mode {
             Fuzz => {
-                fuzzer.set_config(config.config);
                 fuzzer.fuzz();
             }
-            ExecuteOneInput(seed_path) => {
-                fuzzer.exec_seed(seed_path);
+            ExecuteOneInput(data) => {
+                fuzzer.exec_seed(data);
+            }
+            Trace(data) => {
+                let trace = SeedTrace::record(&fuzzer, &data)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                print!("{}", trace);
             }
         }
 
         Ok(())
     }
 
-    fn build_corpus_and_dict(selectors: &[Selector]) -> io::Result<()> {
-        fs::create_dir_all(CORPUS_DIR)?;
-        let mut dict_file = fs::File::create(DICT_FILE)?;
+    /// With `scale_inputs`, the seeds are `FuzzCall`s into `call_selectors`.
+    /// The `constants` of the contract are added to the dictionary.
+    fn build_corpus_and_dict(
+        selectors: &[Selector],
+        call_selectors: &[Selector],
+        constants: &ContractConstants,
+        config: &Configuration,
+    ) -> io::Result<()> {
+        let output_dir = config.output_dir();
+        let corpus_dir = output_dir.join(CORPUS_DIR);
+        fs::create_dir_all(&corpus_dir)?;
+        let migrated = format::migrate_corpus(&corpus_dir, config);
+        if migrated > 0 {
+            info!("🏷️ Added the versioned header to {} seeds", migrated);
+        }
+        let mut dict_file = fs::File::create(output_dir.join(DICT_FILE))?;
 
         write_dict_header(&mut dict_file)?;
 
+        let header = SeedLayout::of(config).header();
         for (i, selector) in selectors.iter().enumerate() {
-            write_corpus_file(i, selector)?;
             write_dict_entry(&mut dict_file, selector);
+            if !config.scale_inputs.unwrap_or(false) {
+                write_corpus_file(
+                    &corpus_dir,
+                    i,
+                    &[header.as_slice(), selector].concat(),
+                )?;
+                continue;
+            }
+            // Constructors have no index
+            if let Some(index) = call_selectors.iter().position(|s| s == selector) {
+                let call = FuzzCall {
+                    selector_index: index as u16,
+                    origin: 1,
+                    ..Default::default()
+                };
+                write_corpus_file(&corpus_dir, i, &format::serialize_calls(&[call]))?;
+            }
+        }
+        for constant in &constants.entries {
+            write_dict_entry(&mut dict_file, constant);
         }
 
         Ok(())
     }
 
-    fn should_stop_now(bug_manager: &BugManager, decoded_msgs: &OneInput) -> bool {
-        decoded_msgs.messages.is_empty()
-            || decoded_msgs.messages.iter().any(|payload| {
-                payload
-                    .payload
-                    .get(..4)
-                    .and_then(|slice| slice.try_into().ok())
-                    .map_or(false, |slice: &[u8; 4]| {
-                        bug_manager.contains_selector(slice)
-                    })
-            })
+    /// Why `decoded_msgs` shouldn't be executed, if it shouldn't
+    fn should_stop_now(
+        bug_manager: &BugManager,
+        denied_selectors: &[Selector],
+        swarm: Option<&Swarm>,
+        decoded_msgs: &OneInput,
+    ) -> Option<Rejection> {
+        if decoded_msgs.messages.is_empty() {
+            return Some(Rejection::NoMessage);
+        }
+        if swarm.map_or(false, |swarm| swarm.rejects(decoded_msgs)) {
+            return Some(Rejection::OutsideSwarm);
+        }
+        decoded_msgs.messages.iter().find_map(|payload| {
+            let slice: &[u8; 4] = payload.payload.get(..4)?.try_into().ok()?;
+            // The invariants left out by `invariants` aren't fuzzed either
+            if bug_manager.contains_selector(slice)
+                || PayloadCrafter::is_invariant(&payload.label())
+            {
+                Some(Rejection::CallsInvariant)
+            } else if denied_selectors.contains(slice) {
+                Some(Rejection::CallsDeniedMessage)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Everything `input` depends on besides the contract, printed by
+    /// `execute` so that triagers sharing a seed can tell whether they run it
+    /// the same way: the block and timestamp every input starts from, the
+    /// deployer and the account behind each origin of the input
+    pub fn environment(&self, input: &[u8]) -> String {
+        let config = &self.fuzzing_config;
+        let decoded_msgs = parse_input(input, self.setup.transcoder(), config);
+        let mut origins: Vec<u8> = decoded_msgs
+            .messages
+            .iter()
+            .map(|message| message.origin.into())
+            .collect();
+        origins.sort_unstable();
+        origins.dedup();
+
+        let mut environment = format!(
+            "🧪 Execution environment\n   🌱 Seed format: version {}\n   🧱 Block number: {}\n   ⏱️  Timestamp: {}\n   🏗️  Deployer: {}\n",
+            format::version(input),
+            EXECUTION_BLOCK,
+            u64::from(EXECUTION_BLOCK) * SLOT_DURATION,
+            config.deployer()
+        );
+        for origin in origins {
+            environment.push_str(&format!(
+                "   🧑 Origin {}: {}{}\n",
+                origin,
+                config.account_of(origin),
+                config
+                    .name_of(origin)
+                    .map(|name| format!(" ({})", name))
+                    .unwrap_or_default()
+            ));
+        }
+        environment
+    }
+
+    /// Messages the fuzzer calls, i.e. neither the invariants nor the denied
+    /// ones, shared by the swarm and the rare-selector scheduling
+    pub fn fuzzed_selectors(&self) -> Vec<Selector> {
+        PayloadCrafter::extract_labelled(&self.setup.json_specs, |label| {
+            !PayloadCrafter::is_invariant(label)
+                && self.fuzzing_config.is_message_fuzzed(label)
+        })
     }
 
     fn set_config(&mut self, config: Configuration) {
+        self.denied_selectors =
+            PayloadCrafter::extract_labelled(&self.setup.json_specs, |label| {
+                !PayloadCrafter::is_invariant(label) && !config.is_message_fuzzed(label)
+            });
         self.fuzzing_config = config;
+        if self.fuzzing_config.swarm.unwrap_or(false) {
+            self.swarm = Swarm::of_current_worker(
+                &self.fuzzed_selectors(),
+                self.fuzzing_config.fuzz_origin,
+            );
+        }
     }
 }
 
 impl FuzzerEngine for Fuzzer {
     fn fuzz(self) {
-        let (mut transcoder_loader, invariant_manager) = init_fuzzer(self.clone());
+        let mut invariant_manager = init_fuzzer(self.clone());
+        let mut progress = CoverageProgress::new(
+            self.fuzzing_config.coverage_progress_interval_secs,
+            &self.fuzzing_config.output_dir(),
+        );
+        let mut states = StatePool::new(
+            self.setup.genesis.clone(),
+            self.fuzzing_config.stateful_snapshots.unwrap_or(0),
+        );
+        let mut coverage_buffer = CoverageBuffer::new(&self.fuzzing_config);
+        let mut stats = StatsTracker::new(
+            self.fuzzing_config.stats_interval_secs,
+            &self.fuzzing_config.output_dir(),
+        );
+        let scheduler = RareSelectors::new(
+            self.fuzzed_selectors(),
+            self.fuzzing_config.schedule_rare_selectors.unwrap_or(false),
+        );
+        let mut harvest = StorageHarvest::new(
+            &self.fuzzing_config.output_dir(),
+            self.fuzzing_config.harvest_storage_values.unwrap_or(false),
+        );
+        let mut checkpointer = Checkpointer::new(
+            self.fuzzing_config.checkpoint_interval_secs,
+            &self.fuzzing_config.output_dir(),
+        );
+        if self.fuzzing_config.resume.unwrap_or(false) {
+            if let Some(checkpoint) = checkpointer.load() {
+                checkpoint.restore(&mut stats, &mut progress, &mut states, |sequence| {
+                    replay(&self, sequence)
+                });
+            }
+        }
 
         ziggy::fuzz!(|data: &[u8]| {
-            Self::harness(
-                self.clone(),
-                &mut transcoder_loader,
-                &mut invariant_manager.clone(),
+            let coverage = Self::harness(
+                &self,
+                &mut states,
+                &mut invariant_manager,
+                &mut coverage_buffer,
+                &mut stats,
+                &scheduler,
+                &mut harvest,
                 data,
             );
+            progress.record(coverage.as_ref());
+            checkpointer.tick(&stats, &progress, &states);
         });
+
+        coverage_buffer
+            .flush()
+            .expect("🙅 Cannot save the coverage");
+        stats.dump();
+
+        // Only reached when running the seeds, not while fuzzing
+        #[cfg(not(fuzzing))]
+        MessageStats::display_err_report(
+            self.fuzzing_config.err_ratio_threshold,
+            &stats.stats().message_results,
+        );
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn harness(
-        client: Fuzzer,
-        transcoder_loader: &mut Mutex<ContractMessageTranscoder>,
+        client: &Fuzzer,
+        states: &mut StatePool,
         bug_manager: &mut BugManager,
+        coverage_buffer: &mut CoverageBuffer,
+        stats: &mut StatsTracker,
+        scheduler: &RareSelectors,
+        harvest: &mut StorageHarvest,
         input: &[u8],
-    ) {
+    ) -> Option<InputCoverage> {
+        let started = Instant::now();
         let decoded_msgs: OneInput =
-            parse_input(input, transcoder_loader, client.fuzzing_config.clone());
-
-        if Self::should_stop_now(bug_manager, &decoded_msgs) {
-            return;
+            parse_input(input, client.setup.transcoder(), &client.fuzzing_config);
+
+        stats.record_decode_failures(&decoded_msgs.decode_failures);
+        if let Some(rejection) = Self::should_stop_now(
+            bug_manager,
+            &client.denied_selectors,
+            client.swarm.as_ref(),
+            &decoded_msgs,
+        ) {
+            stats.record_rejected(rejection);
+            return None;
         }
 
-        let mut chain = BasicExternalities::new(client.setup.genesis.clone());
-        chain.execute_with(|| <Fuzzer as FuzzerEngine>::timestamp(0));
-
         let mut coverage = InputCoverage::new();
+        set_fuzzed_bytes(input);
+
+        // Findings are reported with the messages leading to the starting
+        // state, if it isn't the genesis
+        let (snapshot, prefix) = states.pick(input);
+        let sequence = StatePool::sequence(prefix, input, &client.fuzzing_config, || {
+            format::call_selectors(
+                client.setup.transcoder().metadata(),
+                &client.fuzzing_config,
+            )
+        });
+
+        // Everything done by this input is rolled back once it's executed, so
+        // the next one starts over from the post-instantiation state, or from
+        // a kept end-state
+        let (all_msg_responses, invariants_time) = snapshot.execute_with(|| {
+            <Fuzzer as FuzzerEngine>::timestamp(0);
+
+            let root_before = client
+                .fuzzing_config
+                .should_skip_unchanged_invariants()
+                .then(ContractBridge::state_root);
 
-        let all_msg_responses =
-            execute_messages(&client.clone(), &decoded_msgs, &mut chain, &mut coverage);
+            let mut other_responses = Vec::new();
+            let all_msg_responses = execute_messages(
+                client,
+                &decoded_msgs,
+                &mut coverage,
+                &mut other_responses,
+            );
+
+            if client.fuzzing_config.err_ratio_threshold.is_some() {
+                stats.record_results(&decoded_msgs.messages, &all_msg_responses);
+            }
+            harvest.harvest(&all_msg_responses, &client.setup.contract_address);
 
-        chain.execute_with(|| {
-            check_invariants(
+            check_divergence(
+                client,
                 bug_manager,
+                coverage_buffer,
                 &all_msg_responses,
+                &other_responses,
                 &decoded_msgs,
-                transcoder_loader,
-            )
-        });
+                &sequence,
+                &coverage,
+            );
 
-        // If we are not in fuzzing mode, we save the coverage
-        // If you ever wish to have real-time coverage while fuzzing (and a lose
-        // of performance) Simply comment out the following line :)
-        #[cfg(not(fuzzing))]
-        {
-            println!("[🚧UPDATE] Adding to the coverage file...");
-            coverage.save().expect("🙅 Cannot save the coverage");
+            let state_mutated = root_before.map_or(true, |root| {
+                ContractBridge::has_mutated_state(&all_msg_responses, &root)
+            });
+
+            // If we are not in fuzzing mode, we save the coverage. While
+            // fuzzing, this is only done if `save_coverage_during_fuzzing` is
+            // enabled, since it comes with a loss of performance
+            coverage_buffer
+                .add(&coverage)
+                .expect("🙅 Cannot save the coverage");
 
-            <Fuzzer as FuzzerEngine>::pretty_print(all_msg_responses, decoded_msgs);
+            let invariants_time = check_invariants(
+                client,
+                bug_manager,
+                coverage_buffer,
+                &all_msg_responses,
+                &decoded_msgs,
+                &sequence,
+                &coverage,
+                state_mutated,
+            );
+
+            if let Some(violation) =
+                bug_manager.check_trace_properties(&decoded_msgs, &all_msg_responses)
+            {
+                flush_before_reporting(coverage_buffer);
+                bug_manager.display_trace_property(
+                    all_msg_responses.clone(),
+                    decoded_msgs.clone(),
+                    violation,
+                    &sequence,
+                    &coverage,
+                );
+            }
+
+            (all_msg_responses, invariants_time)
+        });
+
+        if states.is_new(&coverage) {
+            states.keep(sequence, |sequence| replay(client, sequence));
         }
 
+        let features = scheduler.features(&decoded_msgs.messages, &all_msg_responses);
+
+        stats.record_hits(&decoded_msgs.messages);
+        stats.record_accepted(
+            decoded_msgs.messages.len(),
+            started.elapsed(),
+            invariants_time,
+        );
+
+        #[cfg(not(fuzzing))]
+        <Fuzzer as FuzzerEngine>::pretty_print(
+            all_msg_responses,
+            decoded_msgs,
+            client.setup.transcoder().metadata(),
+            &client.fuzzing_config,
+        );
+
         // We now fake the coverage
         coverage.redirect_coverage();
+        InputCoverage::redirect_features(&features);
+        Some(coverage)
     }
 
-    fn exec_seed(self, seed: PathBuf) {
-        let (mut transcoder_loader, mut invariant_manager) = init_fuzzer(self.clone());
-        let data = fs::read(seed).unwrap();
+    fn exec_seed(self, data: Vec<u8>) {
+        println!("{}", self.environment(&data));
+        let mut invariant_manager = init_fuzzer(self.clone());
+        let mut states = StatePool::new(self.setup.genesis.clone(), 0);
+        let mut coverage_buffer = CoverageBuffer::new(&self.fuzzing_config);
+        let mut stats = StatsTracker::new(Some(0), &self.fuzzing_config.output_dir());
         Self::harness(
-            self,
-            &mut transcoder_loader,
+            &self,
+            &mut states,
             &mut invariant_manager,
+            &mut coverage_buffer,
+            &mut stats,
+            &RareSelectors::new(Vec::new(), false),
+            &mut StorageHarvest::new(&self.fuzzing_config.output_dir(), false),
             data.as_bytes_ref(),
         );
+        coverage_buffer
+            .flush()
+            .expect("🙅 Cannot save the coverage");
+        MessageStats::display_err_report(
+            self.fuzzing_config.err_ratio_threshold,
+            &stats.stats().message_results,
+        );
     }
 }
 
-fn init_fuzzer(fuzzer: Fuzzer) -> (Mutex<ContractMessageTranscoder>, BugManager) {
-    let transcoder_loader = Mutex::new(
-        ContractMessageTranscoder::load(Path::new(&fuzzer.setup.path_to_specs))
-            .expect("🙅 Failed to load `ContractMessageTranscoder`"),
-    );
+fn init_fuzzer(fuzzer: Fuzzer) -> BugManager {
+    // Parse the metadata now rather than while executing the first input
+    let _ = fuzzer.setup.transcoder();
 
     let specs = &fuzzer.setup.json_specs;
     let selectors = PayloadCrafter::extract_all(specs);
     let invariants = PayloadCrafter::extract_invariants(specs)
         .expect("🙅 No invariants found, check your contract");
 
-    let selectors_without_invariants: Vec<Selector> = selectors
+    let mut selectors_without_invariants: Vec<Selector> = selectors
         .into_iter()
-        .filter(|s| !invariants.contains(s))
+        .filter(|s| !invariants.contains(s) && !fuzzer.denied_selectors.contains(s))
         .collect();
 
-    let invariant_manager =
-        BugManager::from(invariants, fuzzer.setup.clone(), fuzzer.fuzzing_config);
+    // Let the fuzzer discover the upgrade and the transfers like any other
+    // message
+    if fuzzer.setup.upgrade_code_hash.is_some() {
+        selectors_without_invariants.push(UPGRADE_SELECTOR);
+    }
+    if fuzzer.fuzzing_config.should_fuzz_transfers() {
+        selectors_without_invariants.push(TRANSFER_SELECTOR);
+    }
+
+    let malformed = PayloadCrafter::malformed_invariants(specs);
+    for (label, flaw) in &malformed {
+        warn!("⚠️ The invariant `{}` {}", label, flaw);
+    }
 
-    Fuzzer::build_corpus_and_dict(&selectors_without_invariants)
-        .expect("🙅 Failed to create initial corpus");
+    // Every invariant is kept out of the fuzzed messages, but only the
+    // selected ones get checked
+    let checked_invariants = PayloadCrafter::extract_labelled(specs, |label| {
+        PayloadCrafter::is_invariant(label)
+            && fuzzer.fuzzing_config.is_invariant_checked(label)
+            && !malformed
+                .iter()
+                .any(|(malformed, flaw)| malformed == label && flaw.is_skipped())
+    });
+    if checked_invariants.is_empty() && !invariants.is_empty() {
+        panic!("🙅 No invariant matches `invariants` or can be checked, check your configuration and the warnings above");
+    }
+    if invariants.is_empty() && fuzzer.fuzzing_config.should_check_arithmetic_panics() {
+        info!("🧮 No invariant found, only checking the built-in `no_arithmetic_panic`");
+    }
 
-    println!(
-        "\n🚀  Now fuzzing `{}` ({})!\n",
+    let mut invariant_manager = BugManager::from(
+        checked_invariants,
+        fuzzer.setup.clone(),
+        fuzzer.fuzzing_config.clone(),
+    );
+    invariant_manager
+        .trace_properties
+        .extend(fuzzer.trace_properties.iter().cloned());
+    if let Some(swarm) = &fuzzer.swarm {
+        info!("🐝 Swarm of this worker: {}", swarm);
+        invariant_manager.swarm = Some(swarm.to_string());
+    }
+
+    Fuzzer::build_corpus_and_dict(
+        &selectors_without_invariants,
+        &format::call_selectors(
+            fuzzer.setup.transcoder().metadata(),
+            &fuzzer.fuzzing_config,
+        ),
+        &fuzzer.constants,
+        &fuzzer.fuzzing_config,
+    )
+    .expect("🙅 Failed to create initial corpus");
+
+    info!(
+        "🚀  Now fuzzing `{}` ({})!",
         fuzzer.setup.path_to_specs.as_os_str().to_str().unwrap(),
         fuzzer.setup.contract_address
     );
 
-    (transcoder_loader, invariant_manager)
+    invariant_manager
 }
 
 fn write_dict_header(dict_file: &mut fs::File) -> io::Result<()> {
@@ -236,14 +619,14 @@ fn write_dict_header(dict_file: &mut fs::File) -> io::Result<()> {
     writeln!(dict_file, "delimiter=\"\x2A\x2A\x2A\x2A\x2A\x2A\x2A\x2A\"")
 }
 
-fn write_corpus_file(index: usize, selector: &Selector) -> io::Result<()> {
-    let file_path = PathBuf::from(CORPUS_DIR).join(format!("selector_{}.bin", index));
-    fs::write(file_path, selector)
+fn write_corpus_file(corpus_dir: &Path, index: usize, seed: &[u8]) -> io::Result<()> {
+    let file_path = corpus_dir.join(format!("selector_{}.bin", index));
+    fs::write(file_path, seed)
 }
 
-fn write_dict_entry(dict_file: &mut fs::File, selector: &Selector) {
+pub fn write_dict_entry(dict_file: &mut fs::File, bytes: &[u8]) {
     use std::fmt::Write;
-    let selector_string = selector.iter().fold(String::new(), |mut acc, b| {
+    let selector_string = bytes.iter().fold(String::new(), |mut acc, b| {
         write!(&mut acc, "\\x{:02X}", b).unwrap();
         acc
     });
@@ -251,74 +634,273 @@ fn write_dict_entry(dict_file: &mut fs::File, selector: &Selector) {
         .expect("😅 Failed to write to dict_file");
 }
 
+/// Execute every message of `decoded_msgs`, and push the answers of the
+/// contract of `differential_wasm_path`, if any, to `other_responses`
 fn execute_messages(
     client: &Fuzzer,
     decoded_msgs: &OneInput,
-    chain: &mut BasicExternalities,
     coverage: &mut InputCoverage,
+    other_responses: &mut Vec<FullContractResponse>,
 ) -> Vec<FullContractResponse> {
     let mut all_msg_responses = Vec::new();
 
-    chain.execute_with(|| {
-        for message in &decoded_msgs.messages {
-            let transfer_value = if message.is_payable {
-                message.value_token
-            } else {
-                0
-            };
+    // Drop anything reported outside of these messages, e.g. by the
+    // constructor or the invariants of the previous input
+    let _ = take_coverage();
 
-            let result: FullContractResponse = client.setup.clone().call(
+    for message in &decoded_msgs.messages {
+        if message.lapse > 0 {
+            <Fuzzer as FuzzerEngine>::advance_blocks(message.lapse);
+        }
+        let transfer_value = if message.is_payable {
+            message.value_token
+        } else {
+            0
+        };
+
+        let result: FullContractResponse = if message.is_upgrade {
+            client.setup.upgrade()
+        } else if message.is_transfer {
+            client.setup.transfer(
+                message.origin.into(),
+                message.value_token,
+                &client.fuzzing_config,
+            )
+        } else {
+            client.setup.call_with_deposit_limit(
                 &message.payload,
-                decoded_msgs.origin.into(),
+                message.origin.into(),
                 transfer_value,
-                client.fuzzing_config.clone(),
-            );
-
-            coverage.add_cov(&result.debug_message);
-            all_msg_responses.push(result);
+                message.deposit_limit.resolve(&client.fuzzing_config),
+                &client.fuzzing_config,
+            )
+        };
+
+        coverage.add_cov(take_coverage());
+        all_msg_responses.push(result);
+
+        if message.is_transfer {
+            if let Some(other) = client.setup.transfer_differential(
+                message.origin.into(),
+                message.value_token,
+                &client.fuzzing_config,
+            ) {
+                other_responses.push(other);
+            }
+        } else if !message.is_upgrade {
+            if let Some(other) = client.setup.call_differential(
+                &message.payload,
+                message.origin.into(),
+                transfer_value,
+                message.deposit_limit.resolve(&client.fuzzing_config),
+                &client.fuzzing_config,
+            ) {
+                // The other version may be instrumented too
+                let _ = take_coverage();
+                other_responses.push(other);
+            }
         }
-    });
+    }
 
     all_msg_responses
 }
 
-fn check_invariants(
+/// Execute `sequence` from the genesis regardless of `max_messages_per_exec`,
+/// to rebuild the end-state it led to
+fn replay(client: &Fuzzer, sequence: &[u8]) {
+    execute_sequence(client, sequence);
+}
+
+/// `replay`, returning the decoded messages and their responses
+fn execute_sequence<'a>(
+    client: &Fuzzer,
+    sequence: &'a [u8],
+) -> (OneInput<'a>, Vec<FullContractResponse>) {
+    let mut config = client.fuzzing_config.clone();
+    config.max_messages_per_exec = Some(usize::MAX);
+    let decoded_msgs = parse_input(sequence, client.setup.transcoder(), &config);
+    <Fuzzer as FuzzerEngine>::timestamp(0);
+    let responses = execute_messages(
+        client,
+        &decoded_msgs,
+        &mut InputCoverage::new(),
+        &mut Vec::new(),
+    );
+    (decoded_msgs, responses)
+}
+
+/// Smallest subsequence of `sequence` on which `invariant` is still the
+/// first invariant to fail, with its origins normalized, each candidate being
+/// replayed from the genesis
+fn minimize_invariant_violation(
+    client: &Fuzzer,
+    bug_manager: &BugManager,
+    sequence: &[u8],
+    invariant: Selector,
+) -> Vec<u8> {
+    let still_fails = |candidate: &[u8]| {
+        Snapshot::new(client.setup.genesis.clone()).execute_with(|| {
+            let (decoded_msgs, _) = execute_sequence(client, candidate);
+            matches!(
+                bug_manager.are_invariants_passing(decoded_msgs.origin),
+                Err((failing, _)) if failing == invariant
+            )
+        })
+    };
+    let shrunk = BugManager::shrink(sequence, &client.fuzzing_config, &still_fails);
+    BugManager::normalize_origins(&shrunk, &client.fuzzing_config, &still_fails)
+}
+
+/// Report the first difference between both versions of the contract. An
+/// upgrade can't be replayed on the other version, such a sequence isn't
+/// compared.
+#[allow(clippy::too_many_arguments)]
+fn check_divergence(
+    client: &Fuzzer,
     bug_manager: &mut BugManager,
+    coverage_buffer: &mut CoverageBuffer,
     all_msg_responses: &[FullContractResponse],
+    other_responses: &[FullContractResponse],
     decoded_msgs: &OneInput,
-    transcoder_loader: &mut Mutex<ContractMessageTranscoder>,
+    input: &[u8],
+    coverage: &InputCoverage,
 ) {
-    all_msg_responses
-        .iter()
-        .filter(|response| bug_manager.is_contract_trapped(response))
-        .for_each(|response| {
-            bug_manager.display_trap(decoded_msgs.messages[0].clone(), response.clone());
-        });
+    let Some(other_address) = &client.setup.differential_address else {
+        return;
+    };
+    if other_responses.len() != all_msg_responses.len() {
+        return;
+    }
 
-    if let Err(invariant_tested) = bug_manager.are_invariants_passing(decoded_msgs.origin)
-    {
-        bug_manager.display_invariant(
+    let address = &client.setup.contract_address;
+    let divergence =
+        Divergence::between(all_msg_responses, other_responses, address, other_address)
+            .or_else(|| Divergence::in_storage(address, other_address));
+    if let Some(divergence) = divergence {
+        flush_before_reporting(coverage_buffer);
+        bug_manager.display_divergence(
             all_msg_responses.to_vec(),
             decoded_msgs.clone(),
-            invariant_tested,
-            transcoder_loader,
+            divergence,
+            input,
+            coverage,
         );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+fn check_invariants(
+    client: &Fuzzer,
+    bug_manager: &mut BugManager,
+    coverage_buffer: &mut CoverageBuffer,
+    all_msg_responses: &[FullContractResponse],
+    decoded_msgs: &OneInput,
+    input: &[u8],
+    coverage: &InputCoverage,
+    state_mutated: bool,
+) -> Duration {
+    all_msg_responses
+        .iter()
+        .zip(&decoded_msgs.messages)
+        .enumerate()
+        .filter(|(_, (response, message))| {
+            bug_manager.is_contract_trapped(response)
+                || bug_manager.is_gas_bomb(response)
+                || bug_manager.is_unexpected_deposit_failure(response, message)
+        })
+        .for_each(|(index, (response, message))| {
+            flush_before_reporting(coverage_buffer);
+            bug_manager.display_trap(
+                message.clone(),
+                response.clone(),
+                input,
+                coverage,
+                coverage.message_ids(index),
+            );
+        });
+
+    // The invariants already passed on this very same state
+    if !state_mutated {
+        return Duration::ZERO;
+    }
+
+    let started = Instant::now();
+    let invariants = bug_manager.are_invariants_passing(decoded_msgs.origin);
+    let invariants_time = started.elapsed();
+
+    if let Err((invariant_tested, invariant_response)) = invariants {
+        // Only the invariants got executed since the last message, the last
+        // identifier being the one of the failing invariant. It is taken
+        // before the minimization, whose replays report coverage too.
+        let invariant_cov_ids = take_coverage();
+        flush_before_reporting(coverage_buffer);
+        let shrunk = client
+            .fuzzing_config
+            .should_minimize_findings()
+            .then(|| {
+                minimize_invariant_violation(client, bug_manager, input, invariant_tested)
+            })
+            .filter(|shrunk| shrunk.as_slice() != input);
+
+        match shrunk {
+            // Reported from the execution of the minimized sequence, so that
+            // the trace and the ghost variables are the ones of that sequence
+            Some(shrunk) => {
+                Snapshot::new(client.setup.genesis.clone()).execute_with(|| {
+                    let (decoded_msgs, responses) = execute_sequence(client, &shrunk);
+                    if let Err((invariant_tested, invariant_response)) =
+                        bug_manager.are_invariants_passing(decoded_msgs.origin)
+                    {
+                        bug_manager.display_invariant(
+                            responses,
+                            decoded_msgs,
+                            invariant_tested,
+                            invariant_response,
+                            &shrunk,
+                            coverage,
+                            &invariant_cov_ids,
+                        );
+                    }
+                });
+            }
+            None => {
+                bug_manager.display_invariant(
+                    all_msg_responses.to_vec(),
+                    decoded_msgs.clone(),
+                    invariant_tested,
+                    invariant_response,
+                    input,
+                    coverage,
+                    &invariant_cov_ids,
+                );
+            }
+        }
+    }
+
+    invariants_time
+}
+
+/// Reporting a bug might halt the whole process, which would lose the
+/// coverage buffered so far
+fn flush_before_reporting(coverage_buffer: &mut CoverageBuffer) {
+    if let Err(e) = coverage_buffer.flush() {
+        error!("🙅 Cannot save the coverage: {}", e);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
 
+    use contract_transcode::ContractMessageTranscoder;
+
     use super::*;
 
     #[test]
     fn test_parse_input() {
         let metadata_path = Path::new("sample/dns/target/ink/dns.json");
-        let transcoder = Mutex::new(
-            ContractMessageTranscoder::load(metadata_path)
-                .expect("Failed to load ContractMessageTranscoder"),
-        );
+        let transcoder = ContractMessageTranscoder::load(metadata_path)
+            .expect("Failed to load ContractMessageTranscoder");
 
         let encoded_bytes = hex::decode(
             "229b553f9400000000000000000027272727272727272700002727272727272727272727",
@@ -326,15 +908,12 @@ mod tests {
         .expect("Failed to decode hex string");
 
         let hex = transcoder
-            .lock()
-            .unwrap()
             .decode_contract_message(&mut &encoded_bytes[..])
             .expect("Failed to decode contract message");
 
         println!("{:#?}", hex);
 
-        let binding = transcoder.lock().unwrap();
-        let messages = binding.metadata().spec().messages();
+        let messages = transcoder.metadata().spec().messages();
         println!("{:#?}", messages);
     }
 }
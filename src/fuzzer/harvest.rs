@@ -0,0 +1,174 @@
+use crate::{
+    contract::{
+        remote::{
+            AccountIdOf,
+            ContractBridge,
+            FullContractResponse,
+        },
+        runtime::{
+            Runtime,
+            RuntimeEvent,
+        },
+    },
+    fuzzer::fuzz::write_dict_entry,
+};
+use std::{
+    collections::HashSet,
+    fs::{
+        self,
+        OpenOptions,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use tracing::warn;
+
+/// Under `output_dir`, the values harvested while fuzzing, as an AFL++
+/// dictionary
+pub const HARVEST_DICT_FILE: &str = "harvested.dict";
+/// Size of an `AccountId` or of a `Hash`
+const VALUE_LEN: usize = 32;
+/// The candidates found not stored are remembered up to this many, then
+/// forgotten at once, so that one stored later gets another chance
+const MAX_REJECTED: usize = 1 << 16;
+
+/// With `harvest_storage_values`, the 32-byte values returned or emitted by
+/// the contract which it also stores, e.g. the hash of a registered domain or
+/// the account of an owner, are appended to `HARVEST_DICT_FILE`. AFL++ only
+/// loads its dictionaries once started, so they are used from the next
+/// `fuzz` on, e.g. after each restart of `watch`.
+pub struct StorageHarvest {
+    path: Option<PathBuf>,
+    known: HashSet<[u8; VALUE_LEN]>,
+    /// Candidates that weren't stored, so that returning the same values
+    /// again doesn't read the whole storage of the contract each time
+    rejected: HashSet<[u8; VALUE_LEN]>,
+}
+
+impl StorageHarvest {
+    pub fn new(output_dir: &Path, enabled: bool) -> Self {
+        let path = enabled.then(|| output_dir.join(HARVEST_DICT_FILE));
+        let known = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .map(|dict| Self::parse(&dict))
+            .unwrap_or_default();
+        Self {
+            path,
+            known,
+            rejected: HashSet::new(),
+        }
+    }
+
+    /// Harvest the values of `responses`, within the externalities they were
+    /// executed in
+    pub fn harvest(
+        &mut self,
+        responses: &[FullContractResponse],
+        address: &AccountIdOf<Runtime>,
+    ) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let candidates: HashSet<[u8; VALUE_LEN]> = responses
+            .iter()
+            .flat_map(|response| {
+                let returned = response.result.as_ref().ok().map(|ret| ret.data.clone());
+                let emitted = response.events.iter().flatten().filter_map(|record| {
+                    match &record.event {
+                        RuntimeEvent::Contracts(
+                            pallet_contracts::Event::ContractEmitted { contract, data },
+                        ) if contract == address => Some(data.clone()),
+                        _ => None,
+                    }
+                });
+                returned.into_iter().chain(emitted).collect::<Vec<_>>()
+            })
+            .flat_map(|data| Self::windows(&data))
+            .filter(|value| !self.known.contains(value) && !self.rejected.contains(value))
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let stored = ContractBridge::storage_values(address);
+        let (new, rejected): (Vec<[u8; VALUE_LEN]>, Vec<[u8; VALUE_LEN]>) =
+            candidates.into_iter().partition(|value| {
+                stored
+                    .iter()
+                    .any(|stored| stored.windows(VALUE_LEN).any(|window| window == value))
+            });
+        self.reject(rejected);
+        if new.is_empty() {
+            return;
+        }
+
+        match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(mut dict_file) => {
+                for value in new {
+                    write_dict_entry(&mut dict_file, &value);
+                    self.known.insert(value);
+                }
+            }
+            Err(e) => warn!("🙅 Can't append to {}: {}", path.display(), e),
+        }
+    }
+
+    fn reject(&mut self, rejected: Vec<[u8; VALUE_LEN]>) {
+        if self.rejected.len() + rejected.len() > MAX_REJECTED {
+            self.rejected.clear();
+        }
+        self.rejected.extend(rejected);
+    }
+
+    /// Every 32-byte window of `data`, except the ones of a single byte
+    /// repeated, e.g. padding
+    fn windows(data: &[u8]) -> Vec<[u8; VALUE_LEN]> {
+        data.windows(VALUE_LEN)
+            .filter(|window| window.iter().any(|byte| *byte != window[0]))
+            .filter_map(|window| window.try_into().ok())
+            .collect()
+    }
+
+    /// Values of a dictionary written by `write_dict_entry`
+    fn parse(dict: &[u8]) -> HashSet<[u8; VALUE_LEN]> {
+        String::from_utf8_lossy(dict)
+            .lines()
+            .filter_map(|line| {
+                let hex: String = line.trim_matches('"').split("\\x").collect();
+                hex::decode(hex).ok()?.try_into().ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harvested_values_are_read_back() {
+        assert!(StorageHarvest::windows(&[7; 32]).is_empty());
+        let mut data = vec![7; 32];
+        data[31] = 8;
+        assert_eq!(StorageHarvest::windows(&data).len(), 1);
+
+        let dir = std::env::temp_dir().join("phink_harvest_test");
+        let path = dir.join(HARVEST_DICT_FILE);
+        let _ = fs::create_dir_all(&dir);
+        let mut dict_file = fs::File::create(&path).unwrap();
+        write_dict_entry(&mut dict_file, &StorageHarvest::windows(&data)[0]);
+
+        let mut harvest = StorageHarvest::new(&dir, true);
+        assert_eq!(harvest.known.len(), 1);
+
+        harvest.reject(vec![[1; 32]]);
+        assert!(harvest.rejected.contains(&[1; 32]));
+        harvest.reject(vec![[2; 32]; MAX_REJECTED]);
+        assert!(!harvest.rejected.contains(&[1; 32]));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    thread,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+/// A lock older than this was left behind by a process killed while holding
+/// it, e.g. by AFL++'s timeout
+const STALE_LOCK_SECS: u64 = 10;
+const RETRY_DELAY: Duration = Duration::from_millis(5);
+
+/// Lock shared by every fuzzing process of an `output_dir` around a
+/// read-modify-write of one file, held until dropped. It is the file
+/// `<path>.lock`, created with `create_new` so that only one process gets it.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Wait for the lock of `path`
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let mut lock = path.as_os_str().to_owned();
+        lock.push(".lock");
+        let lock = PathBuf::from(lock);
+        if let Some(dir) = lock.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock)
+            {
+                Ok(_) => return Ok(Self { path: lock }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if Self::is_stale(&lock) {
+                        let _ = fs::remove_file(&lock);
+                    } else {
+                        thread::sleep(RETRY_DELAY);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn is_stale(lock: &Path) -> bool {
+        fs::metadata(lock)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age >= Duration::from_secs(STALE_LOCK_SECS))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Write `contents` to a temporary file first, then rename it to `path`, so
+/// that a reader never sees a truncated file
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = std::env::temp_dir().join("phink_lock_test");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("index.json");
+
+        let lock = FileLock::acquire(&path).unwrap();
+        assert!(dir.join("index.json.lock").is_file());
+        drop(lock);
+        assert!(!dir.join("index.json.lock").exists());
+
+        write_atomically(&path, "{}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
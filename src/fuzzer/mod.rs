@@ -1,4 +1,27 @@
+pub mod archive;
+pub mod backtrace;
+pub mod benchmark;
 pub mod bug;
+pub mod checkpoint;
+pub mod differential;
 pub mod engine;
+pub mod events;
+pub mod finding;
+pub mod format;
 pub mod fuzz;
+pub mod harvest;
+pub mod lock;
+pub mod notify;
 pub mod parser;
+pub mod pretty;
+pub mod recycle;
+pub mod report;
+pub mod reproducer;
+pub mod scheduler;
+pub mod seed;
+pub mod stateful;
+pub mod stats;
+pub mod swarm;
+pub mod sync;
+pub mod temporal;
+pub mod trace;
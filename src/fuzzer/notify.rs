@@ -0,0 +1,185 @@
+use crate::{
+    cli::config::NotifyConfig,
+    fuzzer::{
+        finding::{
+            Finding,
+            FINDING_SEED,
+        },
+        lock::{
+            write_atomically,
+            FileLock,
+        },
+    },
+};
+use serde_json::{
+    json,
+    Value,
+};
+use std::{
+    fs,
+    io,
+    io::Write,
+    path::Path,
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+use tracing::warn;
+
+/// Timestamp of the last notification sent. This is stored on disk, since
+/// each AFL crash kills the process
+pub const LAST_NOTIFICATION: &str = "last_notification";
+/// Findings held back by `min_interval_secs`, one JSON payload per line, sent
+/// along with the next notification
+pub const PENDING_NOTIFICATIONS: &str = "pending_notifications.jsonl";
+pub const DEFAULT_MIN_INTERVAL_SECS: u64 = 60;
+
+/// Send a webhook notification for a new unique finding. If another one was
+/// sent less than `min_interval_secs` ago, the finding is queued instead, and
+/// sent with the next notification, under `queued`.
+pub fn notify_finding(
+    config: &NotifyConfig,
+    contract_name: &str,
+    finding: &Finding,
+    output_dir: &Path,
+) {
+    let Some(url) = &config.webhook_url else {
+        return;
+    };
+
+    let payload = json!({
+        "contract": contract_name,
+        "id": finding.id,
+        "bug_id": finding.bug_id,
+        "bug_type": finding.bug_type,
+        "description": finding.description,
+        "invariant": finding.invariant,
+        "trace": finding
+            .messages
+            .iter()
+            .map(|message| message.decoded.clone())
+            .collect::<Vec<String>>(),
+        "seed_path": finding.path(output_dir).join(FINDING_SEED),
+    });
+    let min_interval = config
+        .min_interval_secs
+        .unwrap_or(DEFAULT_MIN_INTERVAL_SECS);
+
+    match take_batch(output_dir, Some(payload), finding.timestamp, min_interval) {
+        Ok(batch) => send(url, batch, output_dir),
+        Err(e) => warn!("🙅 Couldn't queue the webhook notification: {}", e),
+    }
+}
+
+/// Send the findings still queued, e.g. once the campaign is over
+pub fn flush_notifications(config: &NotifyConfig, output_dir: &Path) {
+    let Some(url) = &config.webhook_url else {
+        return;
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    match take_batch(output_dir, None, now, 0) {
+        Ok(batch) => send(url, batch, output_dir),
+        Err(e) => warn!("🙅 Couldn't read the queued notifications: {}", e),
+    }
+}
+
+/// Under the lock of `LAST_NOTIFICATION`, either queue `payload` if the last
+/// notification is too recent, or take the queue along with `payload`,
+/// recording `now` as the time of the last notification
+fn take_batch(
+    output_dir: &Path,
+    payload: Option<Value>,
+    now: u64,
+    min_interval: u64,
+) -> io::Result<Vec<Value>> {
+    let last_notification = output_dir.join(LAST_NOTIFICATION);
+    let _lock = FileLock::acquire(&last_notification)?;
+
+    let last_sent: u64 = fs::read_to_string(&last_notification)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or_default();
+    if now < last_sent.saturating_add(min_interval) {
+        if let Some(payload) = &payload {
+            queue(output_dir, std::slice::from_ref(payload))?;
+        }
+        return Ok(Vec::new());
+    }
+
+    let pending = output_dir.join(PENDING_NOTIFICATIONS);
+    let mut batch: Vec<Value> = fs::read_to_string(&pending)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    batch.extend(payload);
+    if batch.is_empty() {
+        return Ok(batch);
+    }
+    let _ = fs::remove_file(&pending);
+    write_atomically(&last_notification, now.to_string())?;
+    Ok(batch)
+}
+
+/// Append `payloads` to `PENDING_NOTIFICATIONS`, with the lock of
+/// `LAST_NOTIFICATION` held
+fn queue(output_dir: &Path, payloads: &[Value]) -> io::Result<()> {
+    let mut pending = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output_dir.join(PENDING_NOTIFICATIONS))?;
+    for payload in payloads {
+        writeln!(pending, "{}", payload)?;
+    }
+    Ok(())
+}
+
+/// One `POST` for the whole batch: its last finding, with the earlier ones
+/// under `queued`. They are queued again if it can't be sent.
+fn send(url: &str, batch: Vec<Value>, output_dir: &Path) {
+    let Some(mut payload) = batch.last().cloned() else {
+        return;
+    };
+    if batch.len() > 1 {
+        payload["queued"] = json!(batch[..batch.len() - 1]);
+    }
+
+    if let Err(e) = ureq::post(url)
+        .timeout(Duration::from_secs(10))
+        .send_json(payload)
+    {
+        warn!("🙅 Couldn't send the webhook notification: {}", e);
+        let requeued = FileLock::acquire(&output_dir.join(LAST_NOTIFICATION))
+            .and_then(|_lock| queue(output_dir, &batch));
+        if let Err(e) = requeued {
+            warn!("🙅 Couldn't queue the webhook notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limited_findings_are_queued() {
+        let dir = std::env::temp_dir().join("phink_notify_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = take_batch(&dir, Some(json!({"id": "a"})), 100, 60).unwrap();
+        assert_eq!(first, vec![json!({"id": "a"})]);
+        let held = take_batch(&dir, Some(json!({"id": "b"})), 120, 60).unwrap();
+        assert!(held.is_empty());
+        assert!(dir.join(PENDING_NOTIFICATIONS).is_file());
+
+        let next = take_batch(&dir, Some(json!({"id": "c"})), 200, 60).unwrap();
+        assert_eq!(next, vec![json!({"id": "b"}), json!({"id": "c"})]);
+        assert!(take_batch(&dir, None, 300, 0).unwrap().is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -7,7 +7,14 @@ use crate::{
         remote::BalanceOf,
         runtime::Runtime,
     },
-    fuzzer::fuzz::MAX_MESSAGES_PER_EXEC,
+    fuzzer::{
+        format::{
+            self,
+            FuzzCall,
+            SeedLayout,
+        },
+        fuzz::MAX_MESSAGES_PER_EXEC,
+    },
 };
 use contract_transcode::{
     ContractMessageTranscoder,
@@ -17,7 +24,7 @@ use ink_metadata::{
     InkProject,
     Selector,
 };
-use std::sync::Mutex;
+use std::borrow::Cow;
 use OriginFuzzingOption::{
     DisableOriginFuzzing,
     EnableOriginFuzzing,
@@ -25,9 +32,16 @@ use OriginFuzzingOption::{
 
 pub const DELIMITER: [u8; 8] = [42; 8]; // call delimiter for each message
 pub const MIN_SEED_LEN: usize = 4;
-/// 0..4 covers indices 0, 1, 2, and 3. (value to be transfered)
-/// 4 covers index 4. (origin) (optionnal)
-/// 5.. starts from index 5 and goes to the end of the array.
+/// Pseudo-message upgrading the contract to `upgrade_wasm_path`, i.e `UPGR`.
+/// It is only recognized if an upgrade is configured.
+pub const UPGRADE_SELECTOR: [u8; 4] = *b"UPGR";
+/// Pseudo-message transferring its value to the contract without calling it,
+/// i.e `XFER`. It is only recognized if `fuzz_transfers` is enabled.
+pub const TRANSFER_SELECTOR: [u8; 4] = *b"XFER";
+/// Label of the `TRANSFER_SELECTOR` pseudo-message
+pub const TRANSFER_LABEL: &str = "transfer_to_contract";
+/// Messages of an input, as separated by `DELIMITER`. See `SeedLayout` for
+/// the layout of each message.
 #[derive(Clone, Copy)]
 pub struct Data<'a> {
     pub data: &'a [u8],
@@ -36,20 +50,99 @@ pub struct Data<'a> {
     pub max_messages_per_exec: usize,
 }
 
+/// A message decoded from an input, borrowing its payload from the raw input
 #[derive(Debug, Clone)]
-pub struct Message {
+pub struct Message<'a> {
     pub is_payable: bool,
-    pub payload: Vec<u8>,
+    /// Selector and SCALE-encoded arguments
+    pub payload: Cow<'a, [u8]>,
     pub value_token: BalanceOf<Runtime>,
     pub message_metadata: Value,
     pub origin: Origin,
+    /// `UPGRADE_SELECTOR`, i.e not a message of the contract
+    pub is_upgrade: bool,
+    /// `TRANSFER_SELECTOR`, i.e a plain transfer of `value_token` to the
+    /// contract
+    pub is_transfer: bool,
+    pub deposit_limit: DepositLimit,
+    /// Blocks elapsed before the message, only set by `FuzzCall`s
+    pub lapse: u32,
+}
+
+/// Storage deposit limit of a message, taken from the input if
+/// `fuzz_storage_deposit_limit` is enabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DepositLimit {
+    /// `storage_deposit_limit` of the configuration
+    #[default]
+    Configured,
+    Unlimited,
+    /// A limit that any message writing to the storage is expected to exhaust
+    Tight(BalanceOf<Runtime>),
+}
+
+impl DepositLimit {
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => DepositLimit::Configured,
+            1 => DepositLimit::Unlimited,
+            n => DepositLimit::Tight((n - 2).into()),
+        }
+    }
+
+    pub fn resolve(&self, config: &Configuration) -> Option<BalanceOf<Runtime>> {
+        match self {
+            DepositLimit::Configured => {
+                Configuration::parse_balance(&config.storage_deposit_limit)
+            }
+            DepositLimit::Unlimited => None,
+            DepositLimit::Tight(limit) => Some(*limit),
+        }
+    }
+}
+
+impl Message<'_> {
+    /// Return the name of the message, i.e `register` for
+    /// `register { name: 0x94... }`
+    pub fn label(&self) -> String {
+        match &self.message_metadata {
+            Value::Map(map) => map.ident(),
+            _ => None,
+        }
+        .unwrap_or_else(|| self.message_metadata.to_string())
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct OneInput {
-    pub messages: Vec<Message>,
+pub struct OneInput<'a> {
+    pub messages: Vec<Message<'a>>,
+    /// Origin of the last message, calling the invariants. Each message is
+    /// executed with its own `Message::origin`.
     pub origin: Origin,
     pub fuzz_option: OriginFuzzingOption,
+    /// Why each message of the input that couldn't be decoded got dropped
+    pub decode_failures: Vec<DecodeFailure>,
+}
+
+/// Reason a message of an input is dropped while decoding it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DecodeFailure {
+    /// Too short to hold the origin or the deposit limit of its layout
+    Truncated,
+    /// The selector isn't one of the contract
+    UnknownSelector,
+    /// The selector is known, but not its SCALE-encoded arguments
+    InvalidArguments,
+}
+
+impl DecodeFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecodeFailure::Truncated => "truncated",
+            DecodeFailure::UnknownSelector => "unknown_selector",
+            DecodeFailure::InvalidArguments => "invalid_arguments",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -70,6 +163,45 @@ impl From<Origin> for u8 {
     }
 }
 
+impl Origin {
+    /// Origin of the fuzzed `byte`, i.e. the index of an account of `origins`
+    /// if configured, see `Configuration::account_of`
+    pub fn fuzzed(byte: u8, config: &Configuration) -> Self {
+        match config.origin_pool().len() {
+            0 => Origin(byte),
+            len => Origin((usize::from(byte) % len) as u8),
+        }
+    }
+
+    /// Origins the reproducers are normalized to, in this order: the unfuzzed
+    /// one, e.g. `CALLER_1`, then every other account
+    pub fn canonical(config: &Configuration) -> Vec<u8> {
+        let unfuzzed = Self::unfuzzed(config).0;
+        let others: Vec<u8> = match config.origin_pool().len() {
+            0 => (1..=u8::MAX).chain([0]).collect(),
+            len => (0..len.min(256)).map(|index| index as u8).collect(),
+        };
+        [unfuzzed]
+            .into_iter()
+            .chain(others.into_iter().filter(|origin| *origin != unfuzzed))
+            .collect()
+    }
+
+    /// Origin of the messages when it isn't fuzzed: the deployer of `origins`
+    /// if any, or their first account
+    pub fn unfuzzed(config: &Configuration) -> Self {
+        let pool = config.origin_pool();
+        if pool.is_empty() {
+            return Origin::default();
+        }
+        let deployer = pool
+            .iter()
+            .position(|account| account.deployer.unwrap_or(false))
+            .unwrap_or(0);
+        Origin(deployer as u8)
+    }
+}
+
 impl<'a> Data<'a> {
     fn size_limit_reached(&self) -> bool {
         self.size >= self.max_messages_per_exec
@@ -100,21 +232,51 @@ impl<'a> Iterator for Data<'a> {
         }
     }
 }
-fn is_message_payable(selector: &Selector, metadata: &InkProject) -> bool {
+/// Whether the message of `selector` is payable, `None` if the contract has
+/// no such message
+fn message_payability(selector: &Selector, metadata: &InkProject) -> Option<bool> {
     metadata
         .spec()
         .messages()
         .iter()
         .find(|msg| msg.selector().eq(selector))
         .map(|msg| msg.payable())
-        .unwrap_or(false)
 }
 
-pub fn parse_input(
-    data: &[u8],
-    transcoder: &mut Mutex<ContractMessageTranscoder>,
-    config: Configuration,
-) -> OneInput {
+/// Selectors of the messages of `data`, without decoding their arguments, so
+/// that a corpus can be described without the contract's metadata. The
+/// `FuzzCall`s of a seed of version 2 only point to their selector, none is
+/// returned for them.
+pub fn parse_selectors(data: &[u8], config: &Configuration) -> Vec<[u8; 4]> {
+    if format::scale_calls(data).is_some() {
+        return Vec::new();
+    }
+    let (layout, data) = SeedLayout::split(data, SeedLayout::of(config));
+    let header = layout.message_header_len();
+
+    Data {
+        data,
+        pointer: 0,
+        size: 0,
+        max_messages_per_exec: config
+            .max_messages_per_exec
+            .unwrap_or(MAX_MESSAGES_PER_EXEC),
+    }
+    .filter_map(|payload| payload.get(header..header + 4)?.try_into().ok())
+    .collect()
+}
+
+pub fn parse_input<'a>(
+    data: &'a [u8],
+    transcoder: &ContractMessageTranscoder,
+    config: &Configuration,
+) -> OneInput<'a> {
+    if let Some(calls) = format::scale_calls(data) {
+        return parse_calls(calls, transcoder, config);
+    }
+
+    // A versioned seed is read with its own layout, see `SeedLayout`
+    let (layout, data) = SeedLayout::split(data, SeedLayout::of(config));
     let max_messages_per_exec = config
         .max_messages_per_exec
         .unwrap_or(MAX_MESSAGES_PER_EXEC);
@@ -128,8 +290,13 @@ pub fn parse_input(
 
     let mut input = OneInput {
         messages: vec![],
-        origin: Default::default(),
-        fuzz_option: config.should_fuzz_origin(),
+        origin: Origin::unfuzzed(config),
+        fuzz_option: if layout.origin {
+            EnableOriginFuzzing
+        } else {
+            DisableOriginFuzzing
+        },
+        decode_failures: vec![],
     };
 
     for decoded_payloads in iterable {
@@ -139,45 +306,174 @@ pub fn parse_input(
                 .expect("missing transfer value bytes"),
         );
 
-        let encoded_message: &[u8];
+        let mut encoded_message: &[u8];
 
         match input.fuzz_option {
             EnableOriginFuzzing => {
-                input.origin = Origin(decoded_payloads[4]);
-                encoded_message = &decoded_payloads[5..];
+                let Some((&origin, message)) = decoded_payloads[4..].split_first() else {
+                    input.decode_failures.push(DecodeFailure::Truncated);
+                    continue;
+                };
+                input.origin = Origin::fuzzed(origin, config);
+                encoded_message = message;
             }
             DisableOriginFuzzing => encoded_message = &decoded_payloads[4..],
         }
 
-        let binding = transcoder.get_mut().unwrap();
-        let decoded_msg = binding.decode_contract_message(&mut &*encoded_message);
-
-        match &decoded_msg {
-            Ok(_) => {
-                if iterable.max_messages_per_exec != 0
-                    && input.messages.len() <= iterable.max_messages_per_exec
-                {
-                    let is_payable: bool = is_message_payable(
-                        &Selector::from(
-                            <&[u8] as TryInto<[u8; 4]>>::try_into(&encoded_message[0..4])
-                                .unwrap(),
-                        ),
-                        transcoder.get_mut().unwrap().metadata(),
-                    );
-
-                    input.messages.push(Message {
-                        is_payable,
-                        payload: encoded_message.into(),
-                        value_token: value_token.into(),
-                        message_metadata: decoded_msg.unwrap(),
-                        origin: input.origin,
-                    });
-                }
-            }
-            Err(_) => {
+        let mut deposit_limit = DepositLimit::Configured;
+        if layout.deposit_limit {
+            let Some((&byte, message)) = encoded_message.split_first() else {
+                input.decode_failures.push(DecodeFailure::Truncated);
                 continue;
-            }
+            };
+            deposit_limit = DepositLimit::from_byte(byte);
+            encoded_message = message;
+        }
+
+        if iterable.max_messages_per_exec == 0
+            || input.messages.len() > iterable.max_messages_per_exec
+        {
+            continue;
+        }
+
+        match decode_message(
+            Cow::Borrowed(encoded_message),
+            value_token,
+            input.origin,
+            deposit_limit,
+            0,
+            transcoder,
+            config,
+        ) {
+            Ok(message) => input.messages.push(message),
+            Err(failure) => input.decode_failures.push(failure),
         }
     }
     input
 }
+
+/// Messages of a seed of `FuzzCall`s, whose selectors are indices into the
+/// messages of `call_selectors`
+fn parse_calls<'a>(
+    calls: Vec<FuzzCall>,
+    transcoder: &ContractMessageTranscoder,
+    config: &Configuration,
+) -> OneInput<'a> {
+    let mut input = OneInput {
+        messages: vec![],
+        origin: Origin::unfuzzed(config),
+        fuzz_option: config.should_fuzz_origin(),
+        decode_failures: vec![],
+    };
+    let selectors = format::call_selectors(transcoder.metadata(), config);
+    if selectors.is_empty() {
+        return input;
+    }
+
+    let max_messages_per_exec = config
+        .max_messages_per_exec
+        .unwrap_or(MAX_MESSAGES_PER_EXEC);
+    for call in calls.into_iter().take(max_messages_per_exec) {
+        // Each call keeps its own origin, `input.origin` ending up as the one
+        // of the last call
+        let origin = match input.fuzz_option {
+            EnableOriginFuzzing => Origin::fuzzed(call.origin, config),
+            DisableOriginFuzzing => Origin::unfuzzed(config),
+        };
+        input.origin = origin;
+        let deposit_limit = if config.should_fuzz_storage_deposit_limit() {
+            DepositLimit::from_byte(call.deposit_limit)
+        } else {
+            DepositLimit::Configured
+        };
+        let selector = selectors[usize::from(call.selector_index) % selectors.len()];
+        match decode_message(
+            Cow::Owned([selector.as_slice(), &call.args].concat()),
+            call.value,
+            origin,
+            deposit_limit,
+            call.lapse.into(),
+            transcoder,
+            config,
+        ) {
+            Ok(message) => input.messages.push(message),
+            Err(failure) => input.decode_failures.push(failure),
+        }
+    }
+    input
+}
+
+/// The message of `payload`, i.e. a selector and its SCALE-encoded
+/// arguments, or why it can't be decoded
+fn decode_message<'a>(
+    payload: Cow<'a, [u8]>,
+    value_token: u32,
+    origin: Origin,
+    deposit_limit: DepositLimit,
+    lapse: u32,
+    transcoder: &ContractMessageTranscoder,
+    config: &Configuration,
+) -> Result<Message<'a>, DecodeFailure> {
+    if config.upgrade_wasm_path.is_some() && payload.starts_with(&UPGRADE_SELECTOR) {
+        let upgrade = match payload {
+            Cow::Borrowed(payload) => Cow::Borrowed(&payload[..UPGRADE_SELECTOR.len()]),
+            Cow::Owned(_) => Cow::Owned(UPGRADE_SELECTOR.to_vec()),
+        };
+        return Ok(Message {
+            is_payable: false,
+            payload: upgrade,
+            value_token: 0,
+            message_metadata: Value::Literal("upgrade".into()),
+            origin,
+            is_upgrade: true,
+            is_transfer: false,
+            deposit_limit,
+            lapse,
+        });
+    }
+
+    if config.should_fuzz_transfers() && payload.starts_with(&TRANSFER_SELECTOR) {
+        let transfer = match payload {
+            Cow::Borrowed(payload) => Cow::Borrowed(&payload[..TRANSFER_SELECTOR.len()]),
+            Cow::Owned(_) => Cow::Owned(TRANSFER_SELECTOR.to_vec()),
+        };
+        return Ok(Message {
+            is_payable: false,
+            payload: transfer,
+            value_token: value_token.into(),
+            message_metadata: Value::Literal(TRANSFER_LABEL.into()),
+            origin,
+            is_upgrade: false,
+            is_transfer: true,
+            deposit_limit,
+            lapse,
+        });
+    }
+
+    let selector = payload
+        .get(..4)
+        .and_then(|selector| <[u8; 4]>::try_from(selector).ok())
+        .map(Selector::from)
+        .ok_or(DecodeFailure::Truncated)?;
+    let Some(is_payable) = message_payability(&selector, transcoder.metadata()) else {
+        return Err(DecodeFailure::UnknownSelector);
+    };
+
+    // The payload is borrowed from the input when possible, only the decoded
+    // message itself is allocated
+    let message_metadata = transcoder
+        .decode_contract_message(&mut &*payload)
+        .map_err(|_| DecodeFailure::InvalidArguments)?;
+
+    Ok(Message {
+        is_payable,
+        payload,
+        value_token: value_token.into(),
+        message_metadata,
+        origin,
+        is_upgrade: false,
+        is_transfer: false,
+        deposit_limit,
+        lapse,
+    })
+}
@@ -0,0 +1,296 @@
+use contract_transcode::Value;
+use ink_metadata::InkProject;
+use scale_info::{
+    form::PortableForm,
+    Field,
+    PortableRegistry,
+    TypeDef,
+};
+use sp_core::crypto::{
+    AccountId32,
+    Ss58Codec,
+};
+
+/// Values longer than this are split over several lines
+const MAX_INLINE_LEN: usize = 60;
+const INDENT: &str = "  ";
+
+/// Renders the decoded messages with the types of the metadata: nested
+/// structs and enums are indented, and account IDs are shown as SS58
+/// addresses wherever they are, e.g. within an `Option<AccountId>`
+pub struct ValuePrinter<'a> {
+    metadata: &'a InkProject,
+}
+
+/// Field of a struct or of an enum variant, by name or by position
+#[derive(Clone, Copy)]
+enum FieldKey<'k> {
+    Name(&'k str),
+    Index(usize),
+}
+
+impl<'a> ValuePrinter<'a> {
+    pub fn new(metadata: &'a InkProject) -> Self {
+        Self { metadata }
+    }
+
+    fn registry(&self) -> &PortableRegistry {
+        self.metadata.registry()
+    }
+
+    /// A decoded message, e.g. `register {\n  name: 0x94...,\n}`
+    pub fn message(&self, message: &Value) -> String {
+        let Value::Map(map) = message else {
+            return message.to_string();
+        };
+        let label = map.ident().unwrap_or_default();
+        let args = self.args(message);
+        if args.is_empty() {
+            return label;
+        }
+        let fields: Vec<String> = args
+            .into_iter()
+            .map(|(name, value)| format!("{}{}: {},", INDENT, name, Self::shift(&value)))
+            .collect();
+        format!("{} {{\n{}\n}}", label, fields.join("\n"))
+    }
+
+    /// Each argument of a decoded message, by name
+    pub fn args(&self, message: &Value) -> Vec<(String, String)> {
+        let Value::Map(map) = message else {
+            return Vec::new();
+        };
+        let label = map.ident().unwrap_or_default();
+        let spec = self
+            .metadata
+            .spec()
+            .messages()
+            .iter()
+            .find(|spec| *spec.label() == label);
+        map.iter()
+            .map(|(name, value)| {
+                let name = name.to_string();
+                let ty = spec.and_then(|spec| {
+                    spec.args()
+                        .iter()
+                        .find(|arg| *arg.label() == name)
+                        .map(|arg| arg.ty().ty().id)
+                });
+                let rendered = self.render(ty, value);
+                (name, rendered)
+            })
+            .collect()
+    }
+
+    /// `value`, of the type `ty` of the registry if known
+    fn render(&self, ty: Option<u32>, value: &Value) -> String {
+        let ty = ty.map(|ty| self.unwrap_compact(ty));
+        if let Some(account) = self.account(ty, value) {
+            return account;
+        }
+
+        match value {
+            Value::Map(map) => {
+                let ident = map.ident();
+                let fields: Vec<String> = map
+                    .iter()
+                    .map(|(name, field)| {
+                        let name = name.to_string();
+                        let field_ty =
+                            self.field_type(ty, ident.as_deref(), FieldKey::Name(&name));
+                        format!("{}: {}", name, self.render(field_ty, field))
+                    })
+                    .collect();
+                Self::group(ident.as_deref(), "{", "}", &fields)
+            }
+            Value::Tuple(tuple) => {
+                let ident = tuple.ident();
+                let values: Vec<String> = tuple
+                    .values()
+                    .enumerate()
+                    .map(|(i, field)| {
+                        let field_ty =
+                            self.field_type(ty, ident.as_deref(), FieldKey::Index(i));
+                        self.render(field_ty, field)
+                    })
+                    .collect();
+                match ident {
+                    Some(ident) if values.is_empty() => ident,
+                    ident => Self::group(ident.as_deref(), "(", ")", &values),
+                }
+            }
+            Value::Seq(seq) => {
+                let elem_ty = self.field_type(ty, None, FieldKey::Index(0));
+                let elems: Vec<String> = seq
+                    .elems()
+                    .iter()
+                    .map(|elem| self.render(elem_ty, elem))
+                    .collect();
+                Self::group(None, "[", "]", &elems)
+            }
+            other => other.to_string(),
+        }
+    }
+
+    /// SS58 address of `value` if `ty` is an `AccountId`
+    fn account(&self, ty: Option<u32>, value: &Value) -> Option<String> {
+        let resolved = self.registry().resolve(ty?)?;
+        if resolved.path.segments.last().map(String::as_str) != Some("AccountId") {
+            return None;
+        }
+        if let Value::Literal(literal) = value {
+            return Some(literal.clone());
+        }
+        let bytes: [u8; 32] = Self::bytes(value)?.try_into().ok()?;
+        Some(AccountId32::new(bytes).to_ss58check())
+    }
+
+    /// Raw bytes of `value`, e.g. of a `[u8; 32]` or of a struct wrapping one
+    fn bytes(value: &Value) -> Option<Vec<u8>> {
+        match value {
+            Value::Hex(hex) => Some(hex.bytes().to_vec()),
+            Value::Seq(seq) => {
+                seq.elems()
+                    .iter()
+                    .map(|elem| {
+                        match elem {
+                            Value::UInt(byte) => u8::try_from(*byte).ok(),
+                            _ => None,
+                        }
+                    })
+                    .collect()
+            }
+            Value::Tuple(tuple) if tuple.values().count() == 1 => {
+                Self::bytes(tuple.values().next()?)
+            }
+            Value::Map(map) if map.iter().count() == 1 => {
+                Self::bytes(map.iter().next()?.1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Type of the field `key` of `ty`, the variant `ident` for an enum
+    fn field_type(
+        &self,
+        ty: Option<u32>,
+        ident: Option<&str>,
+        key: FieldKey,
+    ) -> Option<u32> {
+        let find = |fields: &[Field<PortableForm>]| {
+            match key {
+                FieldKey::Name(name) => {
+                    fields
+                        .iter()
+                        .find(|field| field.name.as_deref() == Some(name))
+                        .or_else(|| {
+                            fields
+                                .iter()
+                                .enumerate()
+                                .find(|(i, _)| name == i.to_string())
+                                .map(|(_, field)| field)
+                        })
+                }
+                FieldKey::Index(i) => fields.get(i),
+            }
+            .map(|field| field.ty.id)
+        };
+
+        match &self.registry().resolve(ty?)?.type_def {
+            TypeDef::Composite(composite) => find(&composite.fields),
+            TypeDef::Variant(variants) => {
+                let variant = variants
+                    .variants
+                    .iter()
+                    .find(|variant| Some(variant.name.as_str()) == ident)?;
+                find(&variant.fields)
+            }
+            TypeDef::Tuple(tuple) => {
+                match key {
+                    FieldKey::Index(i) => tuple.fields.get(i).map(|field| field.id),
+                    FieldKey::Name(_) => None,
+                }
+            }
+            TypeDef::Sequence(sequence) => Some(sequence.type_param.id),
+            TypeDef::Array(array) => Some(array.type_param.id),
+            _ => None,
+        }
+    }
+
+    /// The inner type of a `Compact<T>`, `ty` otherwise
+    fn unwrap_compact(&self, ty: u32) -> u32 {
+        match self
+            .registry()
+            .resolve(ty)
+            .map(|resolved| &resolved.type_def)
+        {
+            Some(TypeDef::Compact(compact)) => compact.type_param.id,
+            _ => ty,
+        }
+    }
+
+    /// `ident(a, b)` on one line if short enough, one item per line otherwise
+    fn group(ident: Option<&str>, open: &str, close: &str, items: &[String]) -> String {
+        let ident = ident.map(|ident| format!("{} ", ident)).unwrap_or_default();
+        let ident: &str = if open == "(" {
+            ident.trim_end()
+        } else {
+            &ident
+        };
+        if items.is_empty() {
+            return format!("{}{}{}", ident, open, close);
+        }
+        let inline = format!("{}{}{}{}", ident, open, items.join(", "), close);
+        if inline.len() <= MAX_INLINE_LEN && !inline.contains('\n') {
+            return inline;
+        }
+        let lines: Vec<String> = items
+            .iter()
+            .map(|item| format!("{}{},", INDENT, Self::shift(item)))
+            .collect();
+        format!("{}{}\n{}\n{}", ident, open, lines.join("\n"), close)
+    }
+
+    /// Indent every line of `value` but the first
+    fn shift(value: &str) -> String {
+        value.replace('\n', &format!("\n{}", INDENT))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_values_are_indented() {
+        let short = ValuePrinter::group(Some("Some"), "(", ")", &["42".into()]);
+        assert_eq!(short, "Some(42)");
+
+        let inner = ValuePrinter::group(
+            Some("Domain"),
+            "{",
+            "}",
+            &[
+                format!("name: 0x{}", "94".repeat(32)),
+                "owner: 5C62Ck4UrFPiBtoCmeSrgF7x9yv9mn38446dhCpsi2mLHiFT".into(),
+            ],
+        );
+        let outer = ValuePrinter::group(Some("Some"), "(", ")", &[inner]);
+        assert_eq!(
+            outer,
+            format!(
+                "Some(\n  Domain {{\n    name: 0x{},\n    owner: 5C62Ck4UrFPiBtoCmeSrgF7x9yv9mn38446dhCpsi2mLHiFT,\n  }},\n)",
+                "94".repeat(32)
+            )
+        );
+
+        let bytes = Value::Tuple(contract_transcode::Tuple::new(
+            Some("AccountId"),
+            vec![Value::Seq(contract_transcode::Seq::new(vec![
+                Value::UInt(1),
+                Value::UInt(1),
+            ]))],
+        ));
+        assert_eq!(ValuePrinter::bytes(&bytes), Some(vec![1, 1]));
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{
+    cli::{
+        config::Configuration,
+        dashboard::Dashboard,
+    },
+    contract::payload::{
+        PayloadCrafter,
+        Selector,
+    },
+    fuzzer::{
+        format::{
+            self,
+            SeedLayout,
+        },
+        fuzz::CORPUS_DIR,
+        parser::{
+            DELIMITER,
+            TRANSFER_SELECTOR,
+            UPGRADE_SELECTOR,
+        },
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use contract_transcode::ContractMessageTranscoder;
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+};
+
+/// Imports the corpus of a contract into the campaign of another one exposing
+/// the same interface, e.g. two PSP22 tokens. The selectors are remapped by
+/// the label of their message, or by its name for the messages of a trait,
+/// and the messages the other contract doesn't have are dropped. The inputs
+/// without a versioned header must be framed the way the configuration
+/// says, i.e. share `fuzz_origin` and `fuzz_storage_deposit_limit`. The
+/// seeds of version 2 get their `FuzzCall::selector_index` remapped the same
+/// way, between the `call_selectors` of both contracts.
+pub struct CorpusRecycler {
+    mapping: HashMap<Selector, Selector>,
+    /// Index of each of the `call_selectors` of the first contract among the
+    /// ones of the other, for the seeds of version 2
+    call_mapping: Vec<Option<u16>>,
+    /// Layout of the inputs without a versioned header
+    layout: SeedLayout,
+}
+
+impl CorpusRecycler {
+    /// Recycle the corpus of the contract at `from` into the one at `to`
+    pub fn new(from: &Path, to: &Path, config: &Configuration) -> Result<Self, String> {
+        let (from_labels, to_labels) = (Dashboard::labels(from), Dashboard::labels(to));
+        if from_labels.is_empty() || to_labels.is_empty() {
+            return Err(
+                "🙅 Both contracts must be built, their metadata gives the messages"
+                    .into(),
+            );
+        }
+        let mapping = Self::mapping(&from_labels, &to_labels);
+        Ok(Self {
+            call_mapping: Self::call_mapping(
+                &mapping,
+                &Self::call_selectors(from, config)?,
+                &Self::call_selectors(to, config)?,
+            ),
+            mapping,
+            layout: SeedLayout::of(config),
+        })
+    }
+
+    /// `format::call_selectors` of the contract at `contract_path`
+    fn call_selectors(
+        contract_path: &Path,
+        config: &Configuration,
+    ) -> Result<Vec<Selector>, String> {
+        let finder = Instrumenter::new(contract_path.to_path_buf()).find()?;
+        let transcoder =
+            ContractMessageTranscoder::load(&finder.specs_path).map_err(|e| {
+                format!("🙅 Failed to load `ContractMessageTranscoder`: {}", e)
+            })?;
+        Ok(format::call_selectors(transcoder.metadata(), config))
+    }
+
+    /// Position of each of `from` among `to`, through `mapping`. The upgrade
+    /// and the transfer keep their selector.
+    fn call_mapping(
+        mapping: &HashMap<Selector, Selector>,
+        from: &[Selector],
+        to: &[Selector],
+    ) -> Vec<Option<u16>> {
+        from.iter()
+            .map(|selector| {
+                let target = match *selector {
+                    UPGRADE_SELECTOR | TRANSFER_SELECTOR => selector,
+                    _ => mapping.get(selector)?,
+                };
+                to.iter().position(|known| known == target)?.try_into().ok()
+            })
+            .collect()
+    }
+
+    /// Selectors of `from` to the ones of the same message in `to`, both by
+    /// hex-encoded selector to label
+    fn mapping(
+        from: &HashMap<String, String>,
+        to: &HashMap<String, String>,
+    ) -> HashMap<Selector, Selector> {
+        let decode = |selector: &str| -> Option<Selector> {
+            hex::decode(selector).ok()?.try_into().ok()
+        };
+        from.iter()
+            .filter(|(_, label)| !PayloadCrafter::is_invariant(label))
+            .filter_map(|(selector, label)| {
+                let target =
+                    to.iter().find(|(_, other)| *other == label).or_else(|| {
+                        to.iter().find(|(_, other)| {
+                            PayloadCrafter::message_name(other)
+                                == PayloadCrafter::message_name(label)
+                        })
+                    })?;
+                Some((decode(selector)?, decode(target.0)?))
+            })
+            .collect()
+    }
+
+    /// `input` with its selectors remapped, as a versioned seed, `None` if
+    /// none of its messages exist in the other contract
+    pub fn remap(&self, input: &[u8]) -> Option<Vec<u8>> {
+        if let Some(calls) = format::scale_calls(input) {
+            return self.remap_calls(calls);
+        }
+        let (layout, input) = SeedLayout::split(input, self.layout);
+        let header = layout.message_header_len();
+        let messages: Vec<Vec<u8>> = Self::split(input)
+            .filter_map(|message| {
+                let selector: Selector =
+                    message.get(header..header + 4)?.try_into().ok()?;
+                let target = self.mapping.get(&selector)?;
+                let mut remapped = message.to_vec();
+                remapped[header..header + 4].copy_from_slice(target);
+                Some(remapped)
+            })
+            .collect();
+        (!messages.is_empty()).then(|| {
+            [
+                layout.header().as_slice(),
+                &messages.join(DELIMITER.as_slice()),
+            ]
+            .concat()
+        })
+    }
+
+    /// `calls` pointing to the `call_selectors` of the other contract, as a
+    /// seed of version 2
+    fn remap_calls(&self, calls: Vec<format::FuzzCall>) -> Option<Vec<u8>> {
+        if self.call_mapping.is_empty() {
+            return None;
+        }
+        let calls: Vec<format::FuzzCall> = calls
+            .into_iter()
+            .filter_map(|mut call| {
+                let index = usize::from(call.selector_index) % self.call_mapping.len();
+                call.selector_index = self.call_mapping[index]?;
+                Some(call)
+            })
+            .collect();
+        (!calls.is_empty()).then(|| format::serialize_calls(&calls))
+    }
+
+    /// Remap every input of `corpus` into `<output_dir>/corpus`, returning how
+    /// many got imported and dropped
+    pub fn import(
+        &self,
+        corpus: &Path,
+        output_dir: &Path,
+    ) -> Result<(usize, usize), String> {
+        let into = output_dir.join(CORPUS_DIR);
+        fs::create_dir_all(&into)
+            .map_err(|e| format!("🙅 Can't create {}: {}", into.display(), e))?;
+        let entries = fs::read_dir(corpus)
+            .map_err(|e| format!("🙅 Can't read {}: {}", corpus.display(), e))?;
+
+        let (mut imported, mut dropped) = (0, 0);
+        for path in entries.flatten().map(|entry| entry.path()) {
+            match fs::read(&path).ok().and_then(|input| self.remap(&input)) {
+                Some(remapped) => {
+                    let name = hex::encode(&sp_io::hashing::blake2_256(&remapped)[..8]);
+                    fs::write(into.join(format!("recycled_{}.bin", name)), remapped)
+                        .map_err(|e| {
+                            format!("🙅 Can't write into {}: {}", into.display(), e)
+                        })?;
+                    imported += 1;
+                }
+                None => dropped += 1,
+            }
+        }
+        Ok((imported, dropped))
+    }
+
+    /// Messages of `input`, as separated by `DELIMITER`
+    fn split(input: &[u8]) -> impl Iterator<Item = &[u8]> {
+        let mut rest = Some(input);
+        std::iter::from_fn(move || {
+            let current = rest?;
+            match current
+                .windows(DELIMITER.len())
+                .position(|window| window == DELIMITER)
+            {
+                Some(position) => {
+                    rest = Some(&current[position + DELIMITER.len()..]);
+                    Some(&current[..position])
+                }
+                None => {
+                    rest = None;
+                    Some(current)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selectors_are_remapped_by_message() {
+        let from = HashMap::from([
+            ("db20f9f5".to_string(), "PSP22::transfer".to_string()),
+            ("0b396f18".to_string(), "approve".to_string()),
+            ("11111111".to_string(), "burn".to_string()),
+        ]);
+        let to = HashMap::from([
+            ("aaaaaaaa".to_string(), "transfer".to_string()),
+            ("bbbbbbbb".to_string(), "PSP22::approve".to_string()),
+        ]);
+        let layout = SeedLayout {
+            origin: false,
+            deposit_limit: false,
+        };
+        let recycler = CorpusRecycler {
+            mapping: CorpusRecycler::mapping(&from, &to),
+            call_mapping: Vec::new(),
+            layout,
+        };
+
+        let input = [
+            vec![0, 0, 0, 0, 0xdb, 0x20, 0xf9, 0xf5, 7],
+            DELIMITER.to_vec(),
+            vec![0, 0, 0, 0, 0x11, 0x11, 0x11, 0x11],
+            DELIMITER.to_vec(),
+            vec![0, 0, 0, 0, 0x0b, 0x39, 0x6f, 0x18],
+        ]
+        .concat();
+        let expected = [
+            layout.header().to_vec(),
+            vec![0, 0, 0, 0, 0xaa, 0xaa, 0xaa, 0xaa, 7],
+            DELIMITER.to_vec(),
+            vec![0, 0, 0, 0, 0xbb, 0xbb, 0xbb, 0xbb],
+        ]
+        .concat();
+
+        assert_eq!(recycler.remap(&input), Some(expected));
+        assert_eq!(recycler.remap(&[0, 0, 0, 0, 0x11, 0x11, 0x11, 0x11]), None);
+    }
+    #[test]
+    fn test_scale_seeds_are_remapped_by_index() {
+        let mapping = HashMap::from([([1; 4], [4; 4]), ([2; 4], [5; 4])]);
+        let recycler = CorpusRecycler {
+            call_mapping: CorpusRecycler::call_mapping(
+                &mapping,
+                &[[1; 4], [2; 4], [3; 4], TRANSFER_SELECTOR],
+                &[[5; 4], [4; 4], TRANSFER_SELECTOR],
+            ),
+            mapping,
+            layout: SeedLayout {
+                origin: false,
+                deposit_limit: false,
+            },
+        };
+        let call = |selector_index| {
+            format::FuzzCall {
+                selector_index,
+                ..Default::default()
+            }
+        };
+
+        let input = format::serialize_calls(&[call(0), call(2), call(5), call(3)]);
+        assert_eq!(
+            recycler.remap(&input),
+            Some(format::serialize_calls(&[call(1), call(0), call(2)]))
+        );
+        assert_eq!(recycler.remap(&format::serialize_calls(&[call(2)])), None);
+    }
+}
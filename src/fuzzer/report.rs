@@ -0,0 +1,439 @@
+use crate::{
+    cli::ziggy::ZiggyConfig,
+    contract::payload::{
+        PayloadCrafter,
+        DEFAULT_PHINK_PREFIX,
+    },
+    cover::{
+        coverage::COVERAGE_PATH,
+        map::CoverageMap,
+        report::html_escape,
+    },
+    fuzzer::{
+        finding::{
+            Finding,
+            FindingsDb,
+            FINDINGS_DIR,
+            FINDING_METADATA,
+            FINDING_SEED,
+        },
+        fuzz::DICT_FILE,
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    fs,
+    path::PathBuf,
+};
+use walkdir::WalkDir;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+/// Aggregates the coverage statistics and every recorded finding into a
+/// single document, meant to be pasted into an audit report
+pub struct FindingsReport {
+    format: ReportFormat,
+    body: String,
+}
+
+impl FindingsReport {
+    pub fn generate(
+        config: ZiggyConfig,
+        format: ReportFormat,
+        output: Option<PathBuf>,
+    ) -> Result<PathBuf, String> {
+        let output = output.unwrap_or_else(|| {
+            config
+                .config
+                .output_dir()
+                .join(format!("report.{}", format.extension()))
+        });
+
+        let mut report = Self {
+            format,
+            body: String::new(),
+        };
+        report.heading(1, "Phink fuzzing report");
+        report.paragraph(&format!("Contract: `{}`", config.contract_path.display()));
+
+        report.coverage_section(&config);
+        report.uncovered_section(&config);
+        report.findings_section(&config);
+
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("🙅 Can't create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&output, report.finish())
+            .map_err(|e| format!("🙅 Can't write {}: {}", output.display(), e))?;
+
+        Ok(output)
+    }
+
+    fn coverage_section(&mut self, config: &ZiggyConfig) {
+        self.heading(2, "Coverage");
+
+        let hit = Self::covered_points(config);
+        let instrumented = Self::instrumented_points(config);
+
+        if instrumented == 0 {
+            self.paragraph(
+                "No instrumented code found, the contract must be instrumented first.",
+            );
+            return;
+        }
+
+        self.table(
+            &["Instrumented points", "Covered points", "Coverage"],
+            &[vec![
+                instrumented.to_string(),
+                hit.len().to_string(),
+                format!("{:.2}%", hit.len() as f64 * 100.0 / instrumented as f64),
+            ]],
+        );
+    }
+
+    /// List the messages and functions that were never (fully) executed, with
+    /// a guess about why, to spot the blind spots of the harness
+    fn uncovered_section(&mut self, config: &ZiggyConfig) {
+        let Some(map) = CoverageMap::load(&config.contract_path) else {
+            return;
+        };
+        self.heading(2, "Uncovered code");
+
+        let hit: HashSet<usize> =
+            fs::read_to_string(config.config.output_dir().join(COVERAGE_PATH))
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|s| s.strip_prefix("COV="))
+                .filter_map(|s| s.parse().ok())
+                .collect();
+
+        let specs: serde_json::Value = Instrumenter::new(config.contract_path.clone())
+            .find()
+            .ok()
+            .and_then(|finder| fs::read_to_string(finder.specs_path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let messages: Vec<(&str, &str)> = specs["spec"]["messages"]
+            .as_array()
+            .map(|messages| {
+                messages
+                    .iter()
+                    .filter_map(|m| Some((m["label"].as_str()?, m["selector"].as_str()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let dict = fs::read_to_string(config.config.output_dir().join(DICT_FILE)).ok();
+
+        let mut rows = Vec::new();
+        for ((file, function), coverage) in map.functions_coverage(&hit) {
+            if coverage.covered == coverage.total
+                || function.starts_with(DEFAULT_PHINK_PREFIX)
+                || function.is_empty()
+            {
+                continue;
+            }
+
+            let selector = messages
+                .iter()
+                .find(|(label, _)| {
+                    PayloadCrafter::message_name(label) == function.as_str()
+                })
+                .map(|(_, selector)| selector);
+            let is_message = selector.is_some();
+            // The dictionary contains the selectors the fuzzer starts from
+            let filtered_out = match (selector, &dict) {
+                (Some(selector), Some(dict)) => {
+                    let escaped: String = hex::decode(selector.trim_start_matches("0x"))
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|b| format!("\\x{:02X}", b))
+                        .collect();
+                    !dict.contains(&escaped)
+                }
+                _ => false,
+            };
+            let source: String = map
+                .files
+                .get(&file)
+                .map(|code| {
+                    code.lines()
+                        .skip(coverage.first_line.saturating_sub(1))
+                        .take(coverage.last_line + 1 - coverage.first_line)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default();
+
+            let reason = Self::uncovered_reason(
+                is_message,
+                filtered_out,
+                coverage.covered,
+                &source,
+                config.config.fuzz_origin,
+            );
+            rows.push(vec![
+                format!("{}::{}", file, function),
+                if is_message { "message" } else { "function" }.into(),
+                format!("{}/{}", coverage.covered, coverage.total),
+                reason.into(),
+            ]);
+        }
+
+        if rows.is_empty() {
+            self.paragraph("Every message and function got fully covered 🎉");
+            return;
+        }
+        self.table(
+            &["Function", "Kind", "Covered points", "Likely reason"],
+            &rows,
+        );
+    }
+
+    fn uncovered_reason(
+        is_message: bool,
+        filtered_out: bool,
+        covered: usize,
+        source: &str,
+        fuzz_origin: bool,
+    ) -> &'static str {
+        let access_control = source.contains("caller()")
+            && (source.contains("==")
+                || source.contains("!=")
+                || source.contains("owner"));
+
+        match (is_message, covered) {
+            (true, 0) if filtered_out => {
+                "selector filtered out: it's not part of the fuzzing dictionary"
+            }
+            (true, 0) => {
+                "never decoded: no seed contains a valid encoding of this message, \
+                 consider adding seeds or a dictionary"
+            }
+            (false, 0) => "never reached from any message",
+            _ if access_control && !fuzz_origin => {
+                "guarded by access control, every message uses the same origin: \
+                 try `fuzz_origin = true`"
+            }
+            _ if access_control => "guarded by access control",
+            _ => "partially covered, some conditions are never met",
+        }
+    }
+
+    /// Coverage identifiers hit so far, according to `COVERAGE_PATH`
+    pub fn covered_points(config: &ZiggyConfig) -> HashSet<u64> {
+        fs::read_to_string(config.config.output_dir().join(COVERAGE_PATH))
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|s| s.strip_prefix("COV="))
+            .filter_map(|s| s.trim_matches(',').parse().ok())
+            .collect()
+    }
+
+    /// Count every `phink_cov!` tracing statement inserted by the instrumenter
+    pub fn instrumented_points(config: &ZiggyConfig) -> usize {
+        let cov = Regex::new(r"\bphink_cov!\(\d+\)").unwrap();
+
+        WalkDir::new(&config.contract_path)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().map_or(false, |ext| ext == "rs"))
+            .filter(|e| !e.path().components().any(|c| c.as_os_str() == "target"))
+            .filter_map(|e| fs::read_to_string(e.path()).ok())
+            .map(|code| cov.find_iter(&code).count())
+            .sum()
+    }
+
+    fn findings_section(&mut self, config: &ZiggyConfig) {
+        let mut findings: Vec<Finding> =
+            fs::read_dir(config.config.output_dir().join(FINDINGS_DIR))
+                .map(|dir| {
+                    dir.filter_map(|entry| entry.ok())
+                        .filter_map(|entry| {
+                            fs::read_to_string(entry.path().join(FINDING_METADATA)).ok()
+                        })
+                        .filter_map(|json| serde_json::from_str(&json).ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+        findings.sort_by_key(|finding| {
+            (std::cmp::Reverse(finding.severity), finding.timestamp)
+        });
+
+        self.heading(2, &format!("Findings ({})", findings.len()));
+        if findings.is_empty() {
+            self.paragraph("No bug found 🎉");
+            return;
+        }
+
+        let db = FindingsDb::load(&config.config.output_dir());
+
+        self.table(
+            &["ID", "Severity", "Description"],
+            &findings
+                .iter()
+                .map(|finding| {
+                    vec![
+                        finding.reference().to_string(),
+                        finding.severity.to_string(),
+                        finding.description.clone(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        for finding in &findings {
+            self.heading(
+                3,
+                &format!("{} — {}", finding.reference(), finding.description),
+            );
+
+            let mut details = vec![
+                vec!["Severity".into(), finding.severity.to_string()],
+                vec![
+                    "Bug type".into(),
+                    finding.bug_type.description().to_string(),
+                ],
+            ];
+            if let Some(known) = db.findings.get(&finding.dedup_key()) {
+                details.push(vec!["Occurrences".into(), known.occurrences.to_string()]);
+                details.push(vec!["First seen".into(), known.first_seen.to_string()]);
+                details.push(vec!["Last seen".into(), known.last_seen.to_string()]);
+            }
+            if let Some(location) = &finding.location {
+                details.push(vec!["Location".into(), location.clone()]);
+            }
+            if let Some(invariant) = &finding.invariant {
+                details.push(vec!["Invariant selector".into(), invariant.clone()]);
+            }
+            self.table(&["", ""], &details);
+
+            if !finding.hints.is_empty() {
+                self.paragraph(&format!("Hints: {}", finding.hints.join(", ")));
+            }
+
+            self.paragraph("Decoded trace:");
+            self.code(
+                &finding
+                    .messages
+                    .iter()
+                    .map(|message| {
+                        format!(
+                            "(origin: {}, value: {}) {}",
+                            message.origin, message.value, message.decoded
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+
+            if !finding.storage_diff.is_empty() {
+                self.paragraph("Storage changed since the genesis:");
+                self.code(&finding.storage_diff.join("\n"));
+            }
+
+            if !finding.debug_trace.trim().is_empty() {
+                self.paragraph("Debug trace:");
+                self.code(&finding.debug_trace);
+            }
+
+            let seed = finding.path(&config.config.output_dir()).join(FINDING_SEED);
+            self.paragraph("Reproduction steps:");
+            self.code(&format!(
+                "phink execute {seed} {contract}\nphink reproduce {seed} {contract}",
+                seed = seed.display(),
+                contract = config.contract_path.display()
+            ));
+        }
+    }
+
+    fn heading(&mut self, level: usize, text: &str) {
+        let _ = match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.body, "{} {}\n", "#".repeat(level), text)
+            }
+            ReportFormat::Html => {
+                writeln!(self.body, "<h{level}>{}</h{level}>", html_escape(text))
+            }
+        };
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        let _ = match self.format {
+            ReportFormat::Markdown => writeln!(self.body, "{}\n", text),
+            ReportFormat::Html => writeln!(self.body, "<p>{}</p>", html_escape(text)),
+        };
+    }
+
+    fn code(&mut self, text: &str) {
+        let _ = match self.format {
+            ReportFormat::Markdown => writeln!(self.body, "```\n{}\n```\n", text),
+            ReportFormat::Html => writeln!(self.body, "<pre>{}</pre>", html_escape(text)),
+        };
+    }
+
+    fn table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        match self.format {
+            ReportFormat::Markdown => {
+                let _ = writeln!(self.body, "| {} |", headers.join(" | "));
+                let _ = writeln!(self.body, "|{}", " --- |".repeat(headers.len()));
+                for row in rows {
+                    let cells: Vec<String> =
+                        row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                    let _ = writeln!(self.body, "| {} |", cells.join(" | "));
+                }
+                let _ = writeln!(self.body);
+            }
+            ReportFormat::Html => {
+                let _ = write!(self.body, "<table><tr>");
+                for header in headers {
+                    let _ = write!(self.body, "<th>{}</th>", html_escape(header));
+                }
+                let _ = writeln!(self.body, "</tr>");
+                for row in rows {
+                    let _ = write!(self.body, "<tr>");
+                    for cell in row {
+                        let _ = write!(self.body, "<td>{}</td>", html_escape(cell));
+                    }
+                    let _ = writeln!(self.body, "</tr>");
+                }
+                let _ = writeln!(self.body, "</table>");
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        match self.format {
+            ReportFormat::Markdown => self.body,
+            ReportFormat::Html => {
+                format!(
+                    "<!DOCTYPE html><html><head><title>Phink Fuzzing Report</title><style>
+                    body {{ font-family: Arial, sans-serif; margin: 40px; }}
+                    table {{ border-collapse: collapse; margin-bottom: 20px; }}
+                    td, th {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+                    pre {{ background-color: #f4f4f9; padding: 10px; }}
+                    </style></head><body>\n{}</body></html>",
+                    self.body
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    cli::ziggy::ZiggyConfig,
+    contract::payload::PayloadCrafter,
+    fuzzer::{
+        format,
+        fuzz::MAX_MESSAGES_PER_EXEC,
+        parser::{
+            parse_input,
+            DepositLimit,
+            Message,
+        },
+        pretty::ValuePrinter,
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use contract_transcode::{
+    ContractMessageTranscoder,
+    Value,
+};
+use sp_core::crypto::{
+    AccountId32,
+    Ss58Codec,
+};
+use std::{
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+/// Generates an `#[ink::test]` reproducing the exact call sequence of a seed,
+/// so that developers can drop a bug found by Phink into their test suite
+/// without needing to understand Phink's input format.
+pub struct Reproducer {
+    transcoder: ContractMessageTranscoder,
+    json_specs: String,
+    ziggy: ZiggyConfig,
+}
+
+impl Reproducer {
+    pub fn new(ziggy: ZiggyConfig) -> Result<Self, String> {
+        let finder = Instrumenter::new(ziggy.contract_path.clone()).find()?;
+        let json_specs = fs::read_to_string(&finder.specs_path)
+            .map_err(|e| format!("🙅 Can't read the JSON specs: {}", e))?;
+        let transcoder =
+            ContractMessageTranscoder::load(&finder.specs_path).map_err(|e| {
+                format!("🙅 Failed to load `ContractMessageTranscoder`: {}", e)
+            })?;
+
+        Ok(Self {
+            transcoder,
+            json_specs,
+            ziggy,
+        })
+    }
+
+    /// Every message of `seed` as decoded by the harness, with its typed
+    /// arguments, origin and value, without executing anything
+    pub fn describe(&self, seed: &[u8]) -> Result<String, String> {
+        let mut config = self.ziggy.config.clone();
+        let max_messages = config
+            .max_messages_per_exec
+            .unwrap_or(MAX_MESSAGES_PER_EXEC);
+        config.max_messages_per_exec = Some(usize::MAX);
+        let decoded = parse_input(seed, &self.transcoder, &config);
+        if decoded.messages.is_empty() {
+            return Err("🙅 This seed doesn't contain any valid message".into());
+        }
+
+        let printer = ValuePrinter::new(self.transcoder.metadata());
+        let mut description = String::new();
+        let _ = writeln!(
+            description,
+            "🌱 Seed of version {}, {} messages",
+            format::version(seed),
+            decoded.messages.len()
+        );
+        for (i, message) in decoded.messages.iter().enumerate() {
+            if i == max_messages {
+                let _ = writeln!(
+                    description,
+                    "⚠️ The next messages are beyond `max_messages_per_exec` ({}), they don't get executed",
+                    max_messages
+                );
+            }
+            let _ = writeln!(description, "{}. {}", i + 1, message.label());
+            for (name, value) in printer.args(&message.message_metadata) {
+                let _ = writeln!(
+                    description,
+                    "   {}: {}",
+                    name,
+                    value.replace('\n', "\n   ")
+                );
+            }
+            let origin = u8::from(message.origin);
+            let config = &self.ziggy.config;
+            let _ = writeln!(
+                description,
+                "   🧑 Origin: {} ({}){}",
+                origin,
+                config.account_of(origin).to_ss58check(),
+                config
+                    .name_of(origin)
+                    .map(|name| format!(", {}", name))
+                    .unwrap_or_default()
+            );
+            if message.is_payable || message.is_transfer {
+                let _ = writeln!(description, "   💸 Value: {}", message.value_token);
+            }
+            if message.deposit_limit != DepositLimit::Configured {
+                let _ = writeln!(
+                    description,
+                    "   💾 Deposit limit: {:?}",
+                    message.deposit_limit
+                );
+            }
+            if message.lapse > 0 {
+                let _ = writeln!(description, "   ⏱️ After {} blocks", message.lapse);
+            }
+        }
+        Ok(description.trim_end().to_string())
+    }
+
+    pub fn generate(&self, seed_path: &Path) -> Result<String, String> {
+        let seed = fs::read(seed_path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", seed_path.display(), e))?;
+
+        let decoded = parse_input(&seed, &self.transcoder, &self.ziggy.config);
+        if decoded.messages.is_empty() {
+            return Err("🙅 This seed doesn't contain any valid message".into());
+        }
+
+        let storage = self.storage_name();
+        let deployer = self.ziggy.config.deployer();
+
+        let mut test = String::new();
+        let _ = writeln!(test, "#[cfg(feature = \"phink\")]");
+        let _ = writeln!(test, "#[ink::test]");
+        let _ = writeln!(test, "fn phink_reproducer() {{");
+        let _ = writeln!(
+            test,
+            "    // Generated by `phink reproduce` from {}",
+            seed_path.display()
+        );
+        let _ = writeln!(test, "    use ink::env::{{test::*, DefaultEnvironment}};\n");
+        let _ = writeln!(
+            test,
+            "    set_caller::<DefaultEnvironment>({});",
+            Self::account_to_rust(&deployer)
+        );
+        let _ = writeln!(
+            test,
+            "    let mut contract = {}::{};\n",
+            storage,
+            self.constructor_call()
+        );
+
+        for message in &decoded.messages {
+            self.write_message(&mut test, message);
+        }
+
+        let _ = writeln!(test, "    // Finally, we check every invariant");
+        for invariant in self.invariants_labels() {
+            let _ = writeln!(test, "    {};", Self::call(&storage, &invariant, &[]));
+        }
+        let _ = writeln!(test, "}}");
+
+        Ok(test)
+    }
+
+    fn write_message(&self, test: &mut String, message: &Message) {
+        if message.is_upgrade {
+            let _ = writeln!(
+                test,
+                "    // The contract got upgraded to `upgrade_wasm_path` here, which can't be replayed off-chain\n"
+            );
+            return;
+        }
+        if message.is_transfer {
+            let _ = writeln!(
+                test,
+                "    // Plain transfer of {} to the contract, without calling it",
+                message.value_token
+            );
+            let _ = writeln!(
+                test,
+                "    let contract_id = callee::<DefaultEnvironment>();"
+            );
+            let _ = writeln!(
+                test,
+                "    set_account_balance::<DefaultEnvironment>(contract_id, get_account_balance::<DefaultEnvironment>(contract_id).unwrap_or_default() + {});\n",
+                message.value_token
+            );
+            return;
+        }
+
+        let args = match &message.message_metadata {
+            Value::Map(map) => map.iter().map(|(_, v)| Self::value_to_rust(v)).collect(),
+            _ => vec![],
+        };
+
+        let _ = writeln!(test, "    // {}", message.message_metadata);
+        let _ = writeln!(
+            test,
+            "    set_caller::<DefaultEnvironment>({});",
+            Self::account_to_rust(&self.ziggy.config.account_of(message.origin.into()))
+        );
+        if message.is_payable {
+            let _ = writeln!(
+                test,
+                "    set_value_transferred::<DefaultEnvironment>({});",
+                message.value_token
+            );
+        }
+        let _ = writeln!(
+            test,
+            "    let _ = {};\n",
+            Self::call(&self.storage_name(), &message.label(), &args)
+        );
+    }
+
+    /// Call of a message on `contract`. The ones of a trait, e.g.
+    /// `PSP22::transfer`, are called through the trait, which must be in
+    /// scope of the test module.
+    fn call(storage: &str, label: &str, args: &[String]) -> String {
+        match label.rsplit_once("::") {
+            Some((trait_path, name)) => {
+                let mut all_args = vec!["&mut contract".to_string()];
+                all_args.extend(args.iter().cloned());
+                format!(
+                    "<{} as {}>::{}({})",
+                    storage,
+                    trait_path,
+                    name,
+                    all_args.join(", ")
+                )
+            }
+            None => format!("contract.{}({})", label, args.join(", ")),
+        }
+    }
+
+    /// Return the constructor call, decoded from `constructor_payload` if any,
+    /// or the default constructor found in the specs otherwise
+    fn constructor_call(&self) -> String {
+        if let Some(payload) = &self.ziggy.config.constructor_payload {
+            let decoded = hex::decode(payload).ok().and_then(|data| {
+                self.transcoder
+                    .decode_contract_constructor(&mut &data[..])
+                    .ok()
+            });
+
+            if let Some(Value::Map(map)) = decoded {
+                let args: Vec<String> =
+                    map.iter().map(|(_, v)| Self::value_to_rust(v)).collect();
+                return format!(
+                    "{}({})",
+                    map.ident().unwrap_or_else(|| "new".into()),
+                    args.join(", ")
+                );
+            }
+        }
+
+        let selector =
+            PayloadCrafter::get_constructor(&self.json_specs).map(|s| s.to_vec());
+        let specs: serde_json::Value =
+            serde_json::from_str(&self.json_specs).unwrap_or_default();
+
+        specs["spec"]["constructors"]
+            .as_array()
+            .and_then(|constructors| {
+                constructors.iter().find(|ctor| {
+                    ctor["selector"]
+                        .as_str()
+                        .and_then(|s| hex::decode(s.trim_start_matches("0x")).ok())
+                        .map(|bytes| Some(bytes) == selector)
+                        .unwrap_or(false)
+                })
+            })
+            .and_then(|ctor| ctor["label"].as_str())
+            .map(|label| format!("{}()", label))
+            .unwrap_or_else(|| "new(/* 🙅 couldn't guess the arguments */)".into())
+    }
+
+    /// The name of the `#[ink(storage)]` struct, i.e the contract type
+    fn storage_name(&self) -> String {
+        let specs: serde_json::Value =
+            serde_json::from_str(&self.json_specs).unwrap_or_default();
+        specs["storage"]["root"]["layout"]["struct"]["name"]
+            .as_str()
+            .unwrap_or("Contract")
+            .to_string()
+    }
+
+    fn invariants_labels(&self) -> Vec<String> {
+        let invariants =
+            PayloadCrafter::extract_invariants(&self.json_specs).unwrap_or_default();
+
+        self.transcoder
+            .metadata()
+            .spec()
+            .messages()
+            .iter()
+            .filter(|msg| invariants.contains(msg.selector().to_bytes()))
+            .map(|msg| msg.label().to_string())
+            .collect()
+    }
+
+    fn account_to_rust(account: &AccountId32) -> String {
+        let bytes: &[u8] = account.as_ref();
+        format!(
+            "ink::primitives::AccountId::from({})",
+            Self::bytes_to_rust(bytes)
+        )
+    }
+
+    fn bytes_to_rust(bytes: &[u8]) -> String {
+        if !bytes.is_empty() && bytes.iter().all(|b| *b == bytes[0]) {
+            return format!("[{}u8; {}]", bytes[0], bytes.len());
+        }
+        let bytes: Vec<String> = bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+        format!("[{}]", bytes.join(", "))
+    }
+
+    /// Best-effort conversion of a decoded SCALE value into Rust code. Enums,
+    /// for example, won't be prefixed with their type, so some arguments might
+    /// need a quick manual review.
+    pub fn value_to_rust(value: &Value) -> String {
+        match value {
+            Value::Bool(b) => b.to_string(),
+            Value::Char(c) => format!("{:?}", c),
+            Value::UInt(n) => n.to_string(),
+            Value::Int(n) => n.to_string(),
+            Value::String(s) => format!("{:?}.into()", s),
+            Value::Hex(hex) => format!("{}.into()", Self::bytes_to_rust(hex.bytes())),
+            Value::Literal(literal) => {
+                match AccountId32::from_ss58check(literal) {
+                    Ok(account) => Self::account_to_rust(&account),
+                    Err(_) => literal.clone(),
+                }
+            }
+            Value::Seq(seq) => {
+                let elems: Vec<String> =
+                    seq.elems().iter().map(Self::value_to_rust).collect();
+                format!("vec![{}]", elems.join(", "))
+            }
+            Value::Tuple(tuple) => {
+                let values: Vec<String> =
+                    tuple.values().map(Self::value_to_rust).collect();
+                match tuple.ident() {
+                    Some(ident) if values.is_empty() => ident,
+                    Some(ident) => format!("{}({})", ident, values.join(", ")),
+                    None => format!("({})", values.join(", ")),
+                }
+            }
+            Value::Map(map) => {
+                let fields: Vec<String> = map
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, Self::value_to_rust(v)))
+                    .collect();
+                format!(
+                    "{} {{ {} }}",
+                    map.ident().unwrap_or_default(),
+                    fields.join(", ")
+                )
+            }
+            Value::Unit => "()".into(),
+        }
+    }
+}
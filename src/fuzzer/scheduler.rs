@@ -0,0 +1,115 @@
+use crate::{
+    contract::{
+        payload::Selector,
+        remote::FullContractResponse,
+    },
+    fuzzer::parser::Message,
+};
+
+/// Each scheduled selector has one feature edge, see `RareSelectors::features`
+pub const MAX_SCHEDULED_SELECTORS: usize = 256;
+/// Power schedule of AFL++ giving more energy to the inputs reaching the
+/// edges seldom hit
+pub const RARE_POWER_SCHEDULE: &str = "rare";
+
+/// Rare-selector scheduling, with `schedule_rare_selectors`. AFL++ favors,
+/// for each edge, the smallest and fastest input reaching it, so a cheap
+/// message like `register` ends up in most of the favored inputs. Each
+/// message that executed without reverting is reported as an edge of its
+/// own, so that every message keeps a favored input, and AFL++ runs the
+/// `rare` power schedule, which gives more energy to the inputs reaching the
+/// edges seldom hit, i.e. calling the messages that seldom run successfully.
+/// The edges only depend on the input, otherwise AFL++ would see an unstable
+/// path.
+#[derive(Debug, Clone)]
+pub struct RareSelectors {
+    enabled: bool,
+    /// The index of a selector names its edge
+    selectors: Vec<Selector>,
+}
+
+impl RareSelectors {
+    pub fn new(mut selectors: Vec<Selector>, enabled: bool) -> Self {
+        selectors.truncate(MAX_SCHEDULED_SELECTORS);
+        Self { enabled, selectors }
+    }
+
+    /// Feature edges of an input: `i` if the `i`-th selector ran without
+    /// reverting
+    pub fn features(
+        &self,
+        messages: &[Message],
+        responses: &[FullContractResponse],
+    ) -> Vec<u64> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let mut features: Vec<u64> = messages
+            .iter()
+            .zip(responses)
+            .filter(|(_, response)| {
+                response
+                    .result
+                    .as_ref()
+                    .is_ok_and(|value| !value.did_revert())
+            })
+            .filter_map(|(message, _)| {
+                self.selectors.iter().position(|selector| {
+                    message.payload.get(..4) == Some(selector.as_slice())
+                })
+            })
+            .map(|position| position as u64)
+            .collect();
+        features.sort_unstable();
+        features.dedup();
+        features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::config::Configuration,
+        contract::remote::test_response,
+        fuzzer::parser::{
+            DepositLimit,
+            Origin,
+        },
+    };
+    use contract_transcode::Value;
+    use pallet_contracts::chain_extension::ReturnFlags;
+
+    fn message(selector: Selector) -> Message<'static> {
+        Message {
+            is_payable: false,
+            payload: selector.to_vec().into(),
+            value_token: 0,
+            message_metadata: Value::Literal("msg".into()),
+            origin: Origin::unfuzzed(&Configuration::default()),
+            is_upgrade: false,
+            is_transfer: false,
+            deposit_limit: DepositLimit::Configured,
+            lapse: 0,
+        }
+    }
+
+    #[test]
+    fn test_features_only_depend_on_the_input() {
+        let scheduler = RareSelectors::new(vec![[0; 4], [1; 4], [2; 4]], true);
+        let messages = [message([2; 4]), message([1; 4]), message([2; 4])];
+        let responses = [
+            test_response(ReturnFlags::empty(), vec![0]),
+            test_response(ReturnFlags::REVERT, vec![0]),
+            test_response(ReturnFlags::empty(), vec![0]),
+        ];
+
+        assert_eq!(scheduler.features(&messages, &responses), vec![2]);
+        // Executing the same input again reports the same edges
+        assert_eq!(scheduler.features(&messages, &responses), vec![2]);
+        assert!(RareSelectors::new(vec![[2; 4]], false)
+            .features(&messages, &responses)
+            .is_empty());
+    }
+}
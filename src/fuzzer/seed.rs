@@ -0,0 +1,316 @@
+use crate::{
+    cli::{
+        config::{
+            Configuration,
+            OriginFuzzingOption::EnableOriginFuzzing,
+        },
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::{
+        format::SeedLayout,
+        fuzz::{
+            CORPUS_DIR,
+            MAX_MESSAGES_PER_EXEC,
+        },
+        parser::{
+            parse_selectors,
+            DELIMITER,
+            TRANSFER_LABEL,
+            TRANSFER_SELECTOR,
+        },
+    },
+    instrumenter::instrumentation::Instrumenter,
+};
+use contract_transcode::ContractMessageTranscoder;
+use serde_derive::Deserialize;
+use std::{
+    fs,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// One message of a hand-written seed
+pub struct SeedMessage {
+    /// Label of the message, e.g. `transfer`
+    pub message: String,
+    /// Arguments as `cargo contract call --args` takes them, e.g. SS58
+    /// addresses or `0x` hex
+    pub args: Vec<String>,
+    pub value: u32,
+    pub origin: u8,
+}
+
+/// Calls of a YAML seed description, e.g.
+///
+/// ```yaml
+/// - message: transfer
+///   args: [5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY, 80]
+/// - message: flip
+///   origin: 2
+/// ```
+#[derive(Debug, Deserialize)]
+struct YamlCall {
+    message: String,
+    /// Strings, e.g. SS58 addresses or `0x` hex, integers or booleans
+    #[serde(default)]
+    args: Vec<serde_yaml::Value>,
+    #[serde(default)]
+    value: u32,
+    #[serde(default = "YamlCall::deployer")]
+    origin: u8,
+}
+
+impl YamlCall {
+    fn deployer() -> u8 {
+        1
+    }
+
+    fn into_message(self) -> Result<SeedMessage, String> {
+        let args = self
+            .args
+            .into_iter()
+            .map(|arg| {
+                match arg {
+                    serde_yaml::Value::String(arg) => Ok(arg),
+                    serde_yaml::Value::Number(arg) => Ok(arg.to_string()),
+                    serde_yaml::Value::Bool(arg) => Ok(arg.to_string()),
+                    other => {
+                        Err(format!(
+                            "🙅 Unsupported argument of `{}`: {:?}, write it as a string",
+                            self.message, other
+                        ))
+                    }
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SeedMessage {
+            message: self.message,
+            args,
+            value: self.value,
+            origin: self.origin,
+        })
+    }
+}
+
+/// Encodes messages into the input format of the harness, so that the corpus
+/// can be bootstrapped with known interesting sequences instead of
+/// hand-crafted hex
+pub struct SeedGenerator {
+    transcoder: ContractMessageTranscoder,
+    config: Configuration,
+}
+
+impl SeedGenerator {
+    pub fn new(ziggy: ZiggyConfig) -> Result<Self, String> {
+        let finder = Instrumenter::new(ziggy.contract_path.clone()).find()?;
+        let transcoder =
+            ContractMessageTranscoder::load(&finder.specs_path).map_err(|e| {
+                format!("🙅 Failed to load `ContractMessageTranscoder`: {}", e)
+            })?;
+
+        Ok(Self {
+            transcoder,
+            config: ziggy.config,
+        })
+    }
+
+    pub fn encode(&self, message: &SeedMessage) -> Result<Vec<u8>, String> {
+        if message.message == TRANSFER_LABEL && self.config.should_fuzz_transfers() {
+            return Self::frame(&self.config, message, &TRANSFER_SELECTOR);
+        }
+        let payload = self
+            .transcoder
+            .encode(&message.message, &message.args)
+            .map_err(|e| format!("🙅 Can't encode `{}`: {}", message.message, e))?;
+        Self::frame(&self.config, message, &payload)
+    }
+
+    /// Encode every call listed by the YAML `description` into one sequence
+    pub fn encode_yaml(&self, description: &str) -> Result<Vec<u8>, String> {
+        let calls: Vec<YamlCall> = serde_yaml::from_str(description)
+            .map_err(|e| format!("🙅 Invalid YAML description: {}", e))?;
+        if calls.is_empty() {
+            return Err("🙅 The YAML description doesn't list any call".into());
+        }
+        let messages = calls
+            .into_iter()
+            .map(|call| self.encode(&call.into_message()?))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(messages.join(DELIMITER.as_slice()))
+    }
+
+    /// Prepend the header the parser expects to `payload`: the transferred
+    /// value, then the origin and the deposit limit if they are fuzzed
+    fn frame(
+        config: &Configuration,
+        message: &SeedMessage,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let mut seed = message.value.to_ne_bytes().to_vec();
+        match config.should_fuzz_origin() {
+            EnableOriginFuzzing => seed.push(message.origin),
+            _ if message.origin != 1 => return Err(
+                "🙅 `fuzz_origin` is disabled, every message is called by the deployer"
+                    .into(),
+            ),
+            _ => {}
+        }
+        if config.should_fuzz_storage_deposit_limit() {
+            // `DepositLimit::Configured`
+            seed.push(0);
+        }
+        seed.extend_from_slice(payload);
+        Ok(seed)
+    }
+
+    /// Write `messages` as a new seed at `path`, or to the corpus if `path` is
+    /// `None`. Returns where the seed got written.
+    pub fn write_new(
+        &self,
+        messages: &[u8],
+        path: Option<&Path>,
+    ) -> Result<PathBuf, String> {
+        let Some(path) = path else {
+            return self.write(messages, None);
+        };
+        let seed = [SeedLayout::of(&self.config).header().as_slice(), messages].concat();
+        fs::write(path, seed)
+            .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))?;
+        Ok(path.to_path_buf())
+    }
+
+    /// Append `message` to the seed at `path`, or write it as a new seed of
+    /// the corpus if `path` is `None`. Returns where the seed got written.
+    pub fn write(&self, message: &[u8], path: Option<&Path>) -> Result<PathBuf, String> {
+        let (path, seed) = match path {
+            Some(path) => {
+                let mut seed = fs::read(path)
+                    .map_err(|e| format!("🙅 Can't read {}: {}", path.display(), e))?;
+                seed.extend_from_slice(&DELIMITER);
+                seed.extend_from_slice(message);
+                (path.to_path_buf(), seed)
+            }
+            None => {
+                let corpus = self.config.output_dir().join(CORPUS_DIR);
+                fs::create_dir_all(&corpus).map_err(|e| {
+                    format!("🙅 Can't create {}: {}", corpus.display(), e)
+                })?;
+                let name = hex::encode(&sp_io::hashing::blake2_256(message)[..8]);
+                (
+                    corpus.join(format!("generated_{}.bin", name)),
+                    [SeedLayout::of(&self.config).header().as_slice(), message].concat(),
+                )
+            }
+        };
+
+        let max_messages = self
+            .config
+            .max_messages_per_exec
+            .unwrap_or(MAX_MESSAGES_PER_EXEC);
+        if parse_selectors(&seed, &self.config).len() >= max_messages {
+            println!(
+                "⚠️ This seed reaches `max_messages_per_exec` ({}), the next messages won't be executed",
+                max_messages
+            );
+        }
+
+        fs::write(&path, seed)
+            .map_err(|e| format!("🙅 Can't write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// Raw bytes of an input given on the command line: the path of a seed, `-`
+/// for stdin, or the input itself if `hex`
+pub fn read_input(seed: &str, hex: bool) -> Result<Vec<u8>, String> {
+    let raw = match seed {
+        "-" => {
+            let mut raw = Vec::new();
+            io::stdin()
+                .read_to_end(&mut raw)
+                .map_err(|e| format!("🙅 Can't read stdin: {}", e))?;
+            raw
+        }
+        _ if hex => seed.as_bytes().to_vec(),
+        path => {
+            return fs::read(path).map_err(|e| format!("🙅 Can't read {}: {}", path, e))
+        }
+    };
+    if !hex {
+        return Ok(raw);
+    }
+
+    // Pasted blobs often come wrapped over several lines
+    let text: String = String::from_utf8_lossy(&raw)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    hex::decode(text.trim_start_matches("0x"))
+        .map_err(|e| format!("🙅 Invalid hex input: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framed_message_is_parsed_back() {
+        let config = Configuration {
+            fuzz_origin: true,
+            ..Default::default()
+        };
+        let message = SeedMessage {
+            message: "flip".into(),
+            args: vec![],
+            value: 80,
+            origin: 2,
+        };
+        let seed =
+            SeedGenerator::frame(&config, &message, &[0x63, 0x3a, 0xa5, 0x51]).unwrap();
+
+        assert_eq!(seed, vec![80, 0, 0, 0, 2, 0x63, 0x3a, 0xa5, 0x51]);
+        assert_eq!(
+            parse_selectors(&seed, &config),
+            vec![[0x63, 0x3a, 0xa5, 0x51]]
+        );
+        assert!(SeedGenerator::frame(&Configuration::default(), &message, &[]).is_err());
+    }
+
+    #[test]
+    fn test_yaml_calls_take_readable_arguments() {
+        let calls: Vec<YamlCall> = serde_yaml::from_str(
+            "- message: transfer\n  args: [5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY, 80, true]\n  value: 3\n- message: flip\n",
+        )
+        .unwrap();
+        let messages: Vec<SeedMessage> = calls
+            .into_iter()
+            .map(|call| call.into_message().unwrap())
+            .collect();
+
+        assert_eq!(
+            messages[0].args,
+            vec![
+                "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY",
+                "80",
+                "true"
+            ]
+        );
+        assert_eq!((messages[0].value, messages[0].origin), (3, 1));
+        assert_eq!(messages[1].message, "flip");
+    }
+
+    #[test]
+    fn test_read_hex_input() {
+        assert_eq!(
+            read_input("0x50000000\n633aa551", true).unwrap(),
+            vec![80, 0, 0, 0, 0x63, 0x3a, 0xa5, 0x51]
+        );
+        assert!(read_input("0x5", true).is_err());
+    }
+}
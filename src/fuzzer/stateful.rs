@@ -0,0 +1,242 @@
+use crate::{
+    cli::config::Configuration,
+    contract::{
+        payload::Selector,
+        snapshot::Snapshot,
+    },
+    cover::coverage::InputCoverage,
+    fuzzer::{
+        format::{
+            self,
+            SeedLayout,
+        },
+        parser::DELIMITER,
+    },
+};
+use sp_core::storage::Storage;
+use std::collections::{
+    HashSet,
+    VecDeque,
+};
+
+/// State reached by a sequence of inputs, kept to start the next inputs from
+struct EndState {
+    snapshot: Snapshot,
+    /// Every message leading to this state from the genesis, as one input
+    sequence: Vec<u8>,
+}
+
+/// Starting points of the inputs, with `stateful_snapshots`: the genesis, and
+/// the end-states of the inputs that reached new coverage. An input executed
+/// on top of an end-state is reported along with the sequence leading to it,
+/// so that the finding replays from the genesis.
+///
+/// Since the pool grows while fuzzing, the same input may start from another
+/// state later on, which AFL++ sees as a lower stability.
+pub struct StatePool {
+    genesis: Storage,
+    root: Snapshot,
+    states: VecDeque<EndState>,
+    capacity: usize,
+    /// Coverage identifiers reached by any input so far
+    seen: HashSet<u64>,
+}
+
+impl StatePool {
+    /// Pool of up to `capacity` end-states, `0` always starting from the
+    /// genesis
+    pub fn new(genesis: Storage, capacity: usize) -> Self {
+        Self {
+            root: Snapshot::new(genesis.clone()),
+            genesis,
+            states: VecDeque::new(),
+            capacity,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Starting point of `input`, picked from its hash so that the same
+    /// input starts from the same state, and the sequence leading to it
+    pub fn pick(&mut self, input: &[u8]) -> (&mut Snapshot, Option<Vec<u8>>) {
+        if self.states.is_empty() {
+            return (&mut self.root, None);
+        }
+        let hash = u64::from_le_bytes(sp_io::hashing::twox_64(input));
+        match (hash % (self.states.len() as u64 + 1)) as usize {
+            0 => (&mut self.root, None),
+            index => {
+                let state = &mut self.states[index - 1];
+                (&mut state.snapshot, Some(state.sequence.clone()))
+            }
+        }
+    }
+
+    /// Record the coverage of an input, returning `true` if it reached an
+    /// identifier no input reached before, i.e. its end-state is worth
+    /// keeping
+    pub fn is_new(&mut self, coverage: &InputCoverage) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        let before = self.seen.len();
+        self.seen.extend(coverage.coverage_ids());
+        self.seen.len() > before
+    }
+
+    /// Keep the state reached by `replay`, which executes `sequence` from the
+    /// genesis. Once the pool is full, the oldest state is dropped.
+    pub fn keep(&mut self, sequence: Vec<u8>, replay: impl FnOnce(&[u8])) {
+        let mut snapshot = Snapshot::new(self.genesis.clone());
+        snapshot.commit_with(|| replay(&sequence));
+        if self.states.len() >= self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(EndState { snapshot, sequence });
+    }
+
+    /// Sequences leading to the kept states, from the oldest one
+    pub fn sequences(&self) -> Vec<Vec<u8>> {
+        self.states
+            .iter()
+            .map(|state| state.sequence.clone())
+            .collect()
+    }
+
+    /// Coverage identifiers reached by any input so far
+    pub fn seen(&self) -> Vec<u64> {
+        self.seen.iter().copied().collect()
+    }
+
+    /// Rebuild the pool from a checkpoint, replaying each sequence with
+    /// `replay`
+    pub fn resume(
+        &mut self,
+        seen: &[u64],
+        sequences: Vec<Vec<u8>>,
+        replay: impl Fn(&[u8]),
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.seen.extend(seen);
+        for sequence in sequences {
+            self.keep(sequence, &replay);
+        }
+    }
+
+    /// `input` executed after `prefix`, as one input. The versioned header
+    /// of `input`, if any, is dropped, only the one of `prefix` leads. If
+    /// either of them is a seed of version 2, both are merged as `FuzzCall`s
+    /// into the messages of `selectors`, i.e. `format::call_selectors`.
+    pub fn sequence(
+        prefix: Option<Vec<u8>>,
+        input: &[u8],
+        config: &Configuration,
+        selectors: impl FnOnce() -> Vec<Selector>,
+    ) -> Vec<u8> {
+        let Some(prefix) = prefix else {
+            return input.to_vec();
+        };
+        match (format::scale_calls(&prefix), format::scale_calls(input)) {
+            (None, None) => {
+                let fallback = SeedLayout {
+                    origin: false,
+                    deposit_limit: false,
+                };
+                let (_, messages) = SeedLayout::split(input, fallback);
+                [prefix.as_slice(), &DELIMITER, messages].concat()
+            }
+            (prefix_calls, input_calls) => {
+                let selectors = selectors();
+                let as_calls = |data: &[u8], calls: Option<Vec<format::FuzzCall>>| {
+                    calls.unwrap_or_else(|| {
+                        format::to_calls(&format::parse(data, config).1, &selectors)
+                    })
+                };
+                format::serialize_calls(
+                    &[
+                        as_calls(&prefix, prefix_calls),
+                        as_calls(input, input_calls),
+                    ]
+                    .concat(),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inputs_start_from_kept_states() {
+        let mut pool = StatePool::new(Storage::default(), 1);
+        let mut coverage = InputCoverage::new();
+        coverage.add_cov(vec![1, 2]);
+        assert!(pool.is_new(&coverage));
+        assert!(!pool.is_new(&coverage));
+
+        pool.keep(b"first".to_vec(), |sequence| {
+            sp_io::storage::set(b"key", sequence);
+        });
+
+        let (from_state, from_genesis): (Vec<_>, Vec<_>) = (0..32u8)
+            .map(|byte| {
+                let (snapshot, prefix) = pool.pick(&[byte]);
+                let value = snapshot.execute_with(|| sp_io::storage::get(b"key"));
+                (value.map(|v| v.to_vec()), prefix)
+            })
+            .partition(|(value, _)| value.is_some());
+
+        assert!(!from_state.is_empty() && !from_genesis.is_empty());
+        assert!(from_state.iter().all(|(value, prefix)| {
+            value.as_deref() == Some(b"first".as_slice())
+                && prefix.as_deref() == Some(b"first".as_slice())
+        }));
+        assert!(from_genesis.iter().all(|(_, prefix)| prefix.is_none()));
+        assert_eq!(
+            StatePool::sequence(None, b"next", &Configuration::default(), Vec::new),
+            b"next"
+        );
+    }
+
+    #[test]
+    fn test_scale_seeds_are_merged_as_calls() {
+        let call = |selector_index, args: &[u8]| {
+            format::FuzzCall {
+                selector_index,
+                args: args.to_vec(),
+                ..Default::default()
+            }
+        };
+        let config = Configuration::default();
+        let selectors = || vec![[0xaa; 4], [0xbb; 4]];
+        let prefix = format::serialize_calls(&[call(1, &[7])]);
+        let legacy = format::serialize(
+            &[format::RawMessage {
+                value: 0,
+                origin: 1,
+                deposit_limit: 0,
+                payload: vec![0xaa, 0xaa, 0xaa, 0xaa, 9],
+            }],
+            SeedLayout::of(&config),
+        );
+
+        let merged =
+            StatePool::sequence(Some(prefix.clone()), &legacy, &config, selectors);
+        assert_eq!(
+            format::scale_calls(&merged),
+            Some(vec![
+                call(1, &[7]),
+                format::FuzzCall {
+                    origin: 1,
+                    ..call(0, &[9])
+                }
+            ])
+        );
+
+        let both = StatePool::sequence(Some(prefix.clone()), &prefix, &config, selectors);
+        assert_eq!(format::scale_calls(&both), Some(vec![call(1, &[7]); 2]));
+    }
+}
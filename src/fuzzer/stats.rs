@@ -0,0 +1,548 @@
+use crate::{
+    contract::{
+        payload::Selector,
+        remote::FullContractResponse,
+    },
+    fuzzer::{
+        bug::{
+            MessageStats,
+            COULD_NOT_READ_INPUT,
+        },
+        lock::write_atomically,
+        parser::{
+            DecodeFailure,
+            Message,
+        },
+    },
+};
+use serde_derive::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+#[cfg(not(fuzzing))]
+use tracing::info;
+use tracing::warn;
+
+/// Directory of `output_dir` where each fuzzer process overwrites its
+/// `<pid>.json` every `stats_interval_secs`
+pub const STATS_DIR: &str = "stats";
+/// File of `output_dir` where the counters of the exited fuzzer processes are
+/// summed, once their `<pid>.json` is pruned
+pub const RETIRED_STATS: &str = "retired_stats.json";
+pub const DEFAULT_STATS_INTERVAL_SECS: u64 = 10;
+/// The file of a process not updated for this many `stats_interval_secs` is
+/// the one of a process that exited
+const STALE_INTERVALS: u64 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FuzzingStats {
+    pub pid: u32,
+    pub elapsed_secs: u64,
+    pub execs: u64,
+    pub execs_per_sec: f64,
+    /// Percentage of the inputs dropped by `should_stop_now`, e.g. because no
+    /// message could be decoded
+    pub rejected_percent: f64,
+    /// Inputs dropped by `should_stop_now`, by `Rejection`
+    #[serde(default)]
+    pub rejections: BTreeMap<String, u64>,
+    /// Messages dropped while decoding the inputs, by `DecodeFailure`
+    #[serde(default)]
+    pub decode_failures: BTreeMap<String, u64>,
+    pub avg_messages_per_input: f64,
+    /// Percentage of the execution time spent calling the invariants
+    pub invariants_time_percent: f64,
+    /// Calls of each message, by hex-encoded selector
+    #[serde(default)]
+    pub selector_hits: BTreeMap<String, u64>,
+    /// Executions and reverts of each message, by hex-encoded selector, with
+    /// `err_ratio_threshold`
+    #[serde(default)]
+    pub message_results: BTreeMap<String, MessageStats>,
+}
+
+/// Raw counters of a `StatsTracker`, saved by the checkpoints
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsCounters {
+    pub elapsed_secs: u64,
+    pub execs: u64,
+    pub rejected: u64,
+    pub messages: u64,
+    pub exec_time_secs: f64,
+    pub invariants_time_secs: f64,
+    pub selector_hits: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub rejections: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub decode_failures: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub message_results: BTreeMap<String, MessageStats>,
+}
+
+/// Counters of the fuzzer processes whose stats got pruned, so that pruning
+/// them doesn't lose the executions and the results of the messages
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RetiredStats {
+    pub processes: u64,
+    pub execs: u64,
+    pub message_results: BTreeMap<String, MessageStats>,
+}
+
+impl RetiredStats {
+    pub fn load(output_dir: &Path) -> Self {
+        fs::read_to_string(output_dir.join(RETIRED_STATS))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn add(&mut self, stats: &FuzzingStats) {
+        self.processes += 1;
+        self.execs += stats.execs;
+        for (selector, message) in &stats.message_results {
+            self.message_results
+                .entry(selector.clone())
+                .or_default()
+                .add(message);
+        }
+    }
+}
+
+/// Reason an input is dropped before being executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// None of its messages could be decoded
+    NoMessage,
+    /// It calls an invariant
+    CallsInvariant,
+    /// It calls a message left out by `messages` or `skip_messages`
+    CallsDeniedMessage,
+    /// It is outside of the subset of this `swarm` worker
+    OutsideSwarm,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::NoMessage => "no_message",
+            Rejection::CallsInvariant => "calls_invariant",
+            Rejection::CallsDeniedMessage => "calls_denied_message",
+            Rejection::OutsideSwarm => "outside_swarm",
+        }
+    }
+}
+
+/// Throughput of this fuzzer process, so that the configuration can be tuned
+/// based on data, e.g. `max_messages_per_exec` or the dictionary
+pub struct StatsTracker {
+    interval: Option<Duration>,
+    path: PathBuf,
+    started: Instant,
+    /// Time fuzzed before the campaign got resumed
+    resumed: Duration,
+    last_dump: Instant,
+    execs: u64,
+    rejected: u64,
+    messages: u64,
+    exec_time: Duration,
+    invariants_time: Duration,
+    selector_hits: BTreeMap<Selector, u64>,
+    rejections: BTreeMap<String, u64>,
+    decode_failures: BTreeMap<String, u64>,
+    message_results: BTreeMap<String, MessageStats>,
+}
+
+impl StatsTracker {
+    /// An `interval_secs` of `0` disables the stats
+    pub fn new(interval_secs: Option<u64>, output_dir: &Path) -> Self {
+        let interval_secs = interval_secs.unwrap_or(DEFAULT_STATS_INTERVAL_SECS);
+        Self {
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            path: output_dir
+                .join(STATS_DIR)
+                .join(format!("{}.json", process::id())),
+            started: Instant::now(),
+            resumed: Duration::ZERO,
+            last_dump: Instant::now(),
+            execs: 0,
+            rejected: 0,
+            messages: 0,
+            exec_time: Duration::ZERO,
+            invariants_time: Duration::ZERO,
+            selector_hits: BTreeMap::new(),
+            rejections: BTreeMap::new(),
+            decode_failures: BTreeMap::new(),
+            message_results: BTreeMap::new(),
+        }
+    }
+
+    pub fn record_rejected(&mut self, rejection: Rejection) {
+        self.execs += 1;
+        self.rejected += 1;
+        *self
+            .rejections
+            .entry(rejection.as_str().to_string())
+            .or_default() += 1;
+        self.tick();
+    }
+
+    /// Count the messages of an input dropped while decoding it, whether the
+    /// input is executed or rejected
+    pub fn record_decode_failures(&mut self, failures: &[DecodeFailure]) {
+        for failure in failures {
+            *self
+                .decode_failures
+                .entry(failure.as_str().to_string())
+                .or_default() += 1;
+        }
+    }
+
+    pub fn record_accepted(
+        &mut self,
+        messages: usize,
+        exec_time: Duration,
+        invariants_time: Duration,
+    ) {
+        self.execs += 1;
+        self.messages += messages as u64;
+        self.exec_time += exec_time;
+        self.invariants_time += invariants_time;
+        self.tick();
+    }
+
+    /// Count the calls of each message, shown by `phink dashboard`
+    pub fn record_hits(&mut self, messages: &[Message]) {
+        if self.interval.is_none() {
+            return;
+        }
+        for message in messages {
+            if let Some(selector) = message
+                .payload
+                .get(..4)
+                .and_then(|slice| Selector::try_from(slice).ok())
+            {
+                *self.selector_hits.entry(selector).or_default() += 1;
+            }
+        }
+    }
+
+    /// Keep track of how many times each message reverted, reported once the
+    /// campaign is over with `err_ratio_threshold`
+    pub fn record_results(
+        &mut self,
+        messages: &[Message],
+        responses: &[FullContractResponse],
+    ) {
+        for (message, response) in messages.iter().zip(responses) {
+            let Some(selector) = message.payload.get(..4) else {
+                continue;
+            };
+            let stats = self
+                .message_results
+                .entry(hex::encode(selector))
+                .or_insert_with(|| {
+                    MessageStats {
+                        label: message.label(),
+                        ..Default::default()
+                    }
+                });
+
+            stats.executions += 1;
+            if let Ok(value) = &response.result {
+                if value.did_revert() {
+                    stats.reverts += 1;
+                    if value.data == COULD_NOT_READ_INPUT {
+                        stats.lang_errors += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> FuzzingStats {
+        let accepted = self.execs - self.rejected;
+        let elapsed = self.resumed + self.started.elapsed();
+        FuzzingStats {
+            pid: process::id(),
+            elapsed_secs: elapsed.as_secs(),
+            execs: self.execs,
+            execs_per_sec: self.execs as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            rejected_percent: Self::ratio(self.rejected as f64, self.execs as f64)
+                * 100.0,
+            rejections: self.rejections.clone(),
+            decode_failures: self.decode_failures.clone(),
+            avg_messages_per_input: Self::ratio(self.messages as f64, accepted as f64),
+            invariants_time_percent: Self::ratio(
+                self.invariants_time.as_secs_f64(),
+                self.exec_time.as_secs_f64(),
+            ) * 100.0,
+            selector_hits: self
+                .selector_hits
+                .iter()
+                .map(|(selector, hits)| (hex::encode(selector), *hits))
+                .collect(),
+            message_results: self.message_results.clone(),
+        }
+    }
+
+    pub fn counters(&self) -> StatsCounters {
+        StatsCounters {
+            elapsed_secs: (self.resumed + self.started.elapsed()).as_secs(),
+            execs: self.execs,
+            rejected: self.rejected,
+            messages: self.messages,
+            exec_time_secs: self.exec_time.as_secs_f64(),
+            invariants_time_secs: self.invariants_time.as_secs_f64(),
+            selector_hits: self
+                .selector_hits
+                .iter()
+                .map(|(selector, hits)| (hex::encode(selector), *hits))
+                .collect(),
+            rejections: self.rejections.clone(),
+            decode_failures: self.decode_failures.clone(),
+            message_results: self.message_results.clone(),
+        }
+    }
+
+    /// Carry on counting from `counters`, e.g. those of a checkpoint
+    pub fn resume(&mut self, counters: &StatsCounters) {
+        self.resumed = Duration::from_secs(counters.elapsed_secs);
+        self.execs = counters.execs;
+        self.rejected = counters.rejected;
+        self.messages = counters.messages;
+        self.exec_time = Duration::from_secs_f64(counters.exec_time_secs);
+        self.invariants_time = Duration::from_secs_f64(counters.invariants_time_secs);
+        self.selector_hits = counters
+            .selector_hits
+            .iter()
+            .filter_map(|(selector, hits)| {
+                Some((hex::decode(selector).ok()?.try_into().ok()?, *hits))
+            })
+            .collect();
+        self.rejections = counters.rejections.clone();
+        self.decode_failures = counters.decode_failures.clone();
+        self.message_results = counters.message_results.clone();
+    }
+
+    /// How old the stats of a process get once it exited, `None` if the stats
+    /// are disabled
+    pub fn stale_after(interval_secs: Option<u64>) -> Option<Duration> {
+        let interval_secs = interval_secs.unwrap_or(DEFAULT_STATS_INTERVAL_SECS);
+        (interval_secs > 0).then(|| Duration::from_secs(interval_secs * STALE_INTERVALS))
+    }
+
+    /// Stats of the processes of `output_dir`, along with the age of their file
+    pub fn all(output_dir: &Path) -> Vec<(PathBuf, Duration, FuzzingStats)> {
+        fs::read_dir(output_dir.join(STATS_DIR))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let age = fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .unwrap_or_default();
+                let stats =
+                    serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+                Some((path, age, stats))
+            })
+            .collect()
+    }
+
+    /// Sum the stats not updated for `stale_after` into `RETIRED_STATS`, then
+    /// remove them, so that `STATS_DIR` doesn't grow with every process AFL++
+    /// restarts. Only `fuzz` calls it, `stale_after` being `ZERO` once AFL++
+    /// exited.
+    pub fn retire_dead(output_dir: &Path, stale_after: Duration) -> io::Result<()> {
+        let dead: Vec<(PathBuf, FuzzingStats)> = Self::all(output_dir)
+            .into_iter()
+            .filter(|(_, age, _)| *age >= stale_after)
+            .map(|(path, _, stats)| (path, stats))
+            .collect();
+        if dead.is_empty() {
+            return Ok(());
+        }
+
+        let mut retired = RetiredStats::load(output_dir);
+        for (_, stats) in &dead {
+            retired.add(stats);
+        }
+        write_atomically(
+            &output_dir.join(RETIRED_STATS),
+            serde_json::to_string_pretty(&retired)?,
+        )?;
+        for (path, _) in dead {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// `message_results` summed over the stats of every process of the
+    /// campaign of `output_dir`, including the retired ones
+    pub fn campaign_results(output_dir: &Path) -> BTreeMap<String, MessageStats> {
+        let mut results = RetiredStats::load(output_dir).message_results;
+        for (_, _, stats) in Self::all(output_dir) {
+            for (selector, message) in &stats.message_results {
+                results.entry(selector.clone()).or_default().add(message);
+            }
+        }
+        results
+    }
+
+    /// Write the stats to `STATS_DIR/<pid>.json`, and print them when not
+    /// fuzzing
+    pub fn dump(&mut self) {
+        if self.interval.is_none() {
+            return;
+        }
+        self.last_dump = Instant::now();
+        let stats = self.stats();
+
+        #[cfg(not(fuzzing))]
+        info!(
+            "📊 {:.0} execs/s, {:.1}% of inputs rejected{}, {:.2} messages per input, {:.1}% of the time spent in invariants",
+            stats.execs_per_sec,
+            stats.rejected_percent,
+            Self::breakdown(&stats.rejections, &stats.decode_failures),
+            stats.avg_messages_per_input,
+            stats.invariants_time_percent
+        );
+
+        let written = serde_json::to_string_pretty(&stats)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                fs::write(&self.path, json).map_err(|e| e.to_string())
+            });
+        if let Err(e) = written {
+            warn!("🙅 Couldn't save the stats: {}", e);
+        }
+    }
+
+    fn tick(&mut self) {
+        if self
+            .interval
+            .is_some_and(|interval| self.last_dump.elapsed() >= interval)
+        {
+            self.dump();
+        }
+    }
+
+    /// e.g. ` (no_message: 12, calls_invariant: 3; messages dropped:
+    /// unknown_selector: 40)`, empty if nothing got rejected nor dropped
+    #[cfg(not(fuzzing))]
+    fn breakdown(
+        rejections: &BTreeMap<String, u64>,
+        decode_failures: &BTreeMap<String, u64>,
+    ) -> String {
+        let join = |counts: &BTreeMap<String, u64>| {
+            counts
+                .iter()
+                .map(|(reason, count)| format!("{}: {}", reason, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (rejections.is_empty(), decode_failures.is_empty()) {
+            (true, true) => String::new(),
+            (false, true) => format!(" ({})", join(rejections)),
+            (true, false) => format!(" (messages dropped: {})", join(decode_failures)),
+            (false, false) => {
+                format!(
+                    " ({}; messages dropped: {})",
+                    join(rejections),
+                    join(decode_failures)
+                )
+            }
+        }
+    }
+
+    fn ratio(numerator: f64, denominator: f64) -> f64 {
+        if denominator == 0.0 {
+            0.0
+        } else {
+            numerator / denominator
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_ratios() {
+        let mut tracker = StatsTracker::new(Some(0), Path::new("."));
+        tracker.record_rejected(Rejection::NoMessage);
+        tracker.record_decode_failures(&[
+            DecodeFailure::UnknownSelector,
+            DecodeFailure::UnknownSelector,
+        ]);
+        tracker.record_accepted(3, Duration::from_millis(8), Duration::from_millis(2));
+        tracker.record_accepted(1, Duration::from_millis(2), Duration::ZERO);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.execs, 3);
+        assert!((stats.rejected_percent - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.avg_messages_per_input, 2.0);
+        assert!((stats.invariants_time_percent - 20.0).abs() < 1e-9);
+        assert_eq!(stats.rejections.get("no_message"), Some(&1));
+        assert_eq!(stats.decode_failures.get("unknown_selector"), Some(&2));
+    }
+    #[test]
+    fn test_message_results_are_summed_over_processes() {
+        let dir = std::env::temp_dir().join("phink_message_results_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(STATS_DIR)).unwrap();
+
+        let mut tracker = StatsTracker::new(Some(0), &dir);
+        tracker.message_results.insert(
+            "633aa551".into(),
+            MessageStats {
+                label: "flip".into(),
+                executions: 10,
+                reverts: 9,
+                lang_errors: 0,
+            },
+        );
+        for pid in [1, 2] {
+            let stats = FuzzingStats {
+                pid,
+                ..tracker.stats()
+            };
+            fs::write(
+                dir.join(STATS_DIR).join(format!("{}.json", pid)),
+                serde_json::to_string(&stats).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let results = StatsTracker::campaign_results(&dir);
+        assert_eq!(results["633aa551"].executions, 20);
+        assert_eq!(results["633aa551"].reverts, 18);
+        assert_eq!(results["633aa551"].label, "flip");
+
+        // Pruning the files of the exited processes keeps their counters
+        StatsTracker::retire_dead(&dir, Duration::ZERO).unwrap();
+        assert_eq!(fs::read_dir(dir.join(STATS_DIR)).unwrap().count(), 0);
+        assert_eq!(RetiredStats::load(&dir).processes, 2);
+        assert_eq!(StatsTracker::campaign_results(&dir), results);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
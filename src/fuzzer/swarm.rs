@@ -0,0 +1,138 @@
+use crate::{
+    contract::{
+        payload::Selector,
+        remote::BalanceOf,
+        runtime::Runtime,
+    },
+    fuzzer::parser::OneInput,
+};
+use rand::{
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng,
+    SeedableRng,
+};
+use std::{
+    env,
+    fmt,
+    path::Path,
+};
+
+/// Set by AFL++ to the output directory of the worker, named after it
+pub const AFL_WORKER_ENV: &str = "AFL_CUSTOM_INFO_OUT";
+/// The main AFL++ instance keeps the whole configuration
+pub const MAIN_WORKER: &str = "mainaflfuzzer";
+
+const ORIGIN_POOLS: [u8; 3] = [2, 4, 16];
+const VALUE_RANGES: [BalanceOf<Runtime>; 3] = [0, 1_000, 1_000_000];
+
+/// Swarm testing: each AFL++ worker only explores a random part of the
+/// configuration, e.g. half of the messages and a few origins, which finds
+/// other bugs than every worker fuzzing everything. The part is derived from
+/// the name of the worker, so it's the same after a restart.
+///
+/// The inputs outside of the part are rejected rather than rewritten, so a
+/// finding replays the same way without the swarm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swarm {
+    pub worker: String,
+    /// Messages this worker never calls
+    pub disabled: Vec<Selector>,
+    /// With `fuzz_origin`, only the origins below this one are used
+    pub origins: Option<u8>,
+    /// Highest value transferred to a payable message
+    pub max_value: Option<BalanceOf<Runtime>>,
+}
+
+impl Swarm {
+    /// Swarm of the AFL++ worker running this process, if any. The main
+    /// worker has none.
+    pub fn of_current_worker(selectors: &[Selector], fuzz_origin: bool) -> Option<Self> {
+        let out_dir = env::var(AFL_WORKER_ENV).ok()?;
+        let worker = Path::new(&out_dir)
+            .file_name()?
+            .to_string_lossy()
+            .to_string();
+        (worker != MAIN_WORKER).then(|| Self::of_worker(worker, selectors, fuzz_origin))
+    }
+
+    pub fn of_worker(worker: String, selectors: &[Selector], fuzz_origin: bool) -> Self {
+        let seed = u64::from_le_bytes(sp_io::hashing::twox_64(worker.as_bytes()));
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut disabled: Vec<Selector> = selectors
+            .iter()
+            .filter(|_| rng.gen_bool(0.5))
+            .copied()
+            .collect();
+        // At least one message is left to call
+        if disabled.len() == selectors.len() {
+            disabled.pop();
+        }
+
+        Self {
+            worker,
+            disabled,
+            origins: fuzz_origin
+                .then(|| ORIGIN_POOLS.choose(&mut rng).copied())
+                .flatten()
+                .filter(|_| rng.gen_bool(0.5)),
+            max_value: VALUE_RANGES
+                .choose(&mut rng)
+                .copied()
+                .filter(|_| rng.gen_bool(0.5)),
+        }
+    }
+
+    /// Whether `decoded_msgs` falls outside of the part of this worker
+    pub fn rejects(&self, decoded_msgs: &OneInput) -> bool {
+        self.origins
+            .map_or(false, |origins| u8::from(decoded_msgs.origin) >= origins)
+            || decoded_msgs.messages.iter().any(|message| {
+                self.max_value
+                    .map_or(false, |max| message.is_payable && message.value_token > max)
+                    || message.payload.get(..4).map_or(false, |selector| {
+                        self.disabled
+                            .iter()
+                            .any(|disabled| disabled[..] == *selector)
+                    })
+            })
+    }
+}
+
+impl fmt::Display for Swarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} messages off", self.worker, self.disabled.len())?;
+        if let Some(origins) = self.origins {
+            write!(f, ", {} origins", origins)?;
+        }
+        if let Some(max_value) = self.max_value {
+            write!(f, ", value <= {}", max_value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swarm_depends_on_the_worker_only() {
+        let selectors = [[0; 4], [1; 4], [2; 4], [3; 4]];
+        let swarm = Swarm::of_worker("secondaryfuzzer1".into(), &selectors, true);
+        assert_eq!(
+            swarm,
+            Swarm::of_worker("secondaryfuzzer1".into(), &selectors, true)
+        );
+        assert!(swarm.disabled.len() < selectors.len());
+
+        let swarms: Vec<Swarm> = (1..8)
+            .map(|i| Swarm::of_worker(format!("secondaryfuzzer{}", i), &selectors, true))
+            .collect();
+        assert!(swarms.iter().any(|other| other.disabled != swarm.disabled));
+        assert!(swarms
+            .iter()
+            .all(|swarm| swarm.to_string().starts_with("secondary")));
+    }
+}
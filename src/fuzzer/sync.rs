@@ -0,0 +1,217 @@
+use crate::{
+    cli::{
+        config::SyncConfig,
+        ziggy::ZiggyConfig,
+    },
+    fuzzer::fuzz::CORPUS_DIR,
+};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fs,
+    io::Read,
+    path::{
+        Path,
+        PathBuf,
+    },
+    thread,
+    time::Duration,
+};
+use tracing::{
+    debug,
+    info,
+    warn,
+};
+
+pub const DEFAULT_SYNC_INTERVAL_SECS: u64 = 300;
+/// Directory of `output_dir` where the entries of the other machines are
+/// pulled. AFL++ reads it as a foreign sync directory (`-F`), since it only
+/// loads the corpus when it starts.
+pub const FOREIGN_SYNC_DIR: &str = "foreign_sync";
+/// Remote entries are named after the hash of their content, so that each
+/// machine pushes a given input once and anything else in the listing (e.g.
+/// the HTML of an autoindex) is ignored
+const ENTRY_PREFIX: &str = "phink_";
+
+/// Shares the corpus with the other machines fuzzing the same contract,
+/// through any endpoint accepting a `PUT` of each entry and listing them on a
+/// `GET`, e.g. WebDAV, an S3 bucket or nginx with `dav_methods`. The new
+/// inputs of the local corpus and of the AFL++ queues are pushed, and the ones
+/// of the others are pulled into `<output_dir>/foreign_sync`, which AFL++
+/// imports from while fuzzing.
+pub struct CorpusSync {
+    config: SyncConfig,
+    corpus_dir: PathBuf,
+    foreign_dir: PathBuf,
+    afl_dir: PathBuf,
+    /// Entries known to be on both sides
+    synced: HashSet<String>,
+}
+
+impl CorpusSync {
+    pub fn new(config: SyncConfig, ziggy: &ZiggyConfig) -> Self {
+        Self {
+            config,
+            corpus_dir: ziggy.config.output_dir().join(CORPUS_DIR),
+            foreign_dir: Self::foreign_dir(ziggy),
+            afl_dir: ziggy.afl_dir(),
+            synced: HashSet::new(),
+        }
+    }
+
+    /// Where the entries are pulled, passed to AFL++ with `-F`
+    pub fn foreign_dir(ziggy: &ZiggyConfig) -> PathBuf {
+        ziggy.config.output_dir().join(FOREIGN_SYNC_DIR)
+    }
+
+    /// Sync every `interval_secs` in the background, until the process exits
+    pub fn spawn(mut self) {
+        let interval = Duration::from_secs(
+            self.config
+                .interval_secs
+                .unwrap_or(DEFAULT_SYNC_INTERVAL_SECS),
+        );
+        info!("🔄 Syncing the corpus with {}", self.config.url);
+        thread::spawn(move || {
+            loop {
+                match self.sync() {
+                    Ok((pushed, pulled)) => {
+                        debug!("🔄 Corpus synced, {} pushed, {} pulled", pushed, pulled)
+                    }
+                    Err(e) => warn!("🙅 Couldn't sync the corpus: {}", e),
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+
+    /// Push the local entries missing remotely, then pull the remote ones
+    /// missing locally. Returns how many got pushed and pulled.
+    pub fn sync(&mut self) -> Result<(usize, usize), String> {
+        let remote = Self::remote_entries(&self.get("")?);
+
+        let mut pushed = 0;
+        for path in self.local_inputs() {
+            let Ok(input) = fs::read(&path) else {
+                continue;
+            };
+            let name = Self::entry_name(&input);
+            if self.synced.contains(&name) || remote.contains(&name) {
+                self.synced.insert(name);
+                continue;
+            }
+            self.request("PUT", &name)
+                .send_bytes(&input)
+                .map_err(|e| format!("can't push {}: {}", name, e))?;
+            self.synced.insert(name);
+            pushed += 1;
+        }
+
+        fs::create_dir_all(&self.foreign_dir)
+            .map_err(|e| format!("can't create {}: {}", self.foreign_dir.display(), e))?;
+        let mut pulled = 0;
+        for name in remote {
+            if self.synced.contains(&name) {
+                continue;
+            }
+            let input = self.get(&name)?;
+            fs::write(self.foreign_dir.join(&name), input)
+                .map_err(|e| format!("can't write {}: {}", name, e))?;
+            self.synced.insert(name);
+            pulled += 1;
+        }
+
+        Ok((pushed, pulled))
+    }
+
+    /// Inputs of the corpus and of the queue of each AFL++ instance
+    fn local_inputs(&self) -> Vec<PathBuf> {
+        let queues = fs::read_dir(&self.afl_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join("queue"));
+
+        [self.corpus_dir.clone()]
+            .into_iter()
+            .chain(queues)
+            .flat_map(|dir| Self::files(&dir))
+            .collect()
+    }
+
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        let response = self
+            .request("GET", name)
+            .call()
+            .map_err(|e| format!("can't fetch {}{}: {}", self.config.url, name, e))?;
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| format!("can't read {}{}: {}", self.config.url, name, e))?;
+        Ok(body)
+    }
+
+    fn request(&self, method: &str, name: &str) -> ureq::Request {
+        let url = format!("{}/{}", self.config.url.trim_end_matches('/'), name);
+        let request = ureq::request(method, &url).timeout(Duration::from_secs(30));
+        match &self.config.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    fn entry_name(input: &[u8]) -> String {
+        format!(
+            "{}{}",
+            ENTRY_PREFIX,
+            hex::encode(&sp_io::hashing::blake2_256(input)[..16])
+        )
+    }
+
+    /// Names of the entries found in a listing, whatever its format: a plain
+    /// list, an HTML index, a WebDAV `PROPFIND` or an S3 `ListObjects`
+    fn remote_entries(listing: &[u8]) -> HashSet<String> {
+        Regex::new(&format!("{}[0-9a-f]{{32}}", ENTRY_PREFIX))
+            .unwrap()
+            .find_iter(&String::from_utf8_lossy(listing))
+            .map(|name| name.as_str().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_entries_are_found_in_any_listing() {
+        let name = CorpusSync::entry_name(b"\x00\x00\x00\x00\x63\x3a\xa5\x51");
+        let html = format!(
+            "<html><body><a href=\"../\">../</a><a href=\"{0}\">{0}</a></body></html>",
+            name
+        );
+        let s3 = format!(
+            "<ListBucketResult><Contents><Key>{}</Key></Contents><Contents><Key>README</Key></Contents></ListBucketResult>",
+            name
+        );
+
+        assert_eq!(name.len(), ENTRY_PREFIX.len() + 32);
+        for listing in [html, s3] {
+            assert_eq!(
+                CorpusSync::remote_entries(listing.as_bytes()),
+                HashSet::from([name.clone()])
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    cli::config::SequenceProperty,
+    contract::{
+        payload::PayloadCrafter,
+        remote::FullContractResponse,
+    },
+    fuzzer::parser::Message,
+};
+
+/// One executed message of an input, as seen by a `TraceProperty`
+pub struct TraceEntry<'a> {
+    pub message: &'a Message<'a>,
+    pub response: &'a FullContractResponse,
+}
+
+impl TraceEntry<'_> {
+    /// Whether the message neither failed nor reverted
+    pub fn succeeded(&self) -> bool {
+        matches!(&self.response.result, Ok(ret) if !ret.did_revert())
+    }
+
+    /// Whether this is the message `label`, either by its full label, e.g.
+    /// `PSP22::transfer`, or by its name
+    pub fn is(&self, label: &str) -> bool {
+        let own = self.message.label();
+        own == label || PayloadCrafter::message_name(&own) == label
+    }
+}
+
+/// Property over the ordered messages of an input and their responses,
+/// checked by the harness along with the invariants, e.g. "once `pause`
+/// succeeded, `transfer` must fail". Register it with
+/// `BugManager::register_trace_property`, or `Campaign::with_trace_property`
+/// from the library.
+///
+/// Only the messages of the input are given, not the ones leading to its
+/// starting state with `stateful_snapshots`.
+pub trait TraceProperty: Send + Sync {
+    /// Shown in the findings, e.g. `transfer fails after pause`
+    fn name(&self) -> String;
+
+    /// Why `trace` violates the property, `None` if it holds
+    fn check(&self, trace: &[TraceEntry]) -> Option<String>;
+}
+
+impl TraceProperty for SequenceProperty {
+    fn name(&self) -> String {
+        format!("`{}` fails after `{}`", self.then_fails, self.after)
+    }
+
+    fn check(&self, trace: &[TraceEntry]) -> Option<String> {
+        let first = trace
+            .iter()
+            .position(|entry| entry.is(&self.after) && entry.succeeded())?;
+        let (offset, _) = trace[first + 1..]
+            .iter()
+            .enumerate()
+            .find(|(_, entry)| entry.is(&self.then_fails) && entry.succeeded())?;
+        Some(format!(
+            "`{}` (message {}) succeeded after `{}` (message {})",
+            self.then_fails,
+            first + offset + 2,
+            self.after,
+            first + 1
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::config::Configuration,
+        contract::remote::test_response,
+        fuzzer::parser::{
+            DepositLimit,
+            Origin,
+        },
+    };
+    use contract_transcode::Value;
+    use pallet_contracts::chain_extension::ReturnFlags;
+
+    fn message(label: &str) -> Message<'static> {
+        Message {
+            is_payable: false,
+            payload: Default::default(),
+            value_token: 0,
+            message_metadata: Value::Literal(label.into()),
+            origin: Origin::unfuzzed(&Configuration::default()),
+            is_upgrade: false,
+            is_transfer: false,
+            deposit_limit: DepositLimit::Configured,
+            lapse: 0,
+        }
+    }
+
+    #[test]
+    fn test_message_succeeding_after_another() {
+        let property = SequenceProperty {
+            after: "pause".into(),
+            then_fails: "transfer".into(),
+        };
+        let messages = [
+            message("PSP22::transfer"),
+            message("pause"),
+            message("PSP22::transfer"),
+        ];
+        let ok = test_response(ReturnFlags::empty(), vec![0]);
+        let reverted = test_response(ReturnFlags::REVERT, vec![0]);
+
+        let trace = |responses: [&FullContractResponse; 3]| -> Vec<TraceEntry> {
+            messages
+                .iter()
+                .zip(responses)
+                .map(|(message, response)| TraceEntry { message, response })
+                .collect()
+        };
+        assert_eq!(property.check(&trace([&ok, &ok, &reverted])), None);
+        assert_eq!(
+            property.check(&trace([&ok, &ok, &ok])),
+            Some("`transfer` (message 3) succeeded after `pause` (message 2)".into())
+        );
+    }
+}
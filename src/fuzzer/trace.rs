@@ -0,0 +1,221 @@
+use crate::{
+    contract::{
+        extension::take_coverage,
+        remote::{
+            BalanceOf,
+            ContractBridge,
+            FullContractResponse,
+        },
+        runtime::{
+            Runtime,
+            RuntimeEvent,
+        },
+        snapshot::Snapshot,
+    },
+    fuzzer::{
+        engine::FuzzerEngine,
+        fuzz::Fuzzer,
+        parser::parse_input,
+        pretty::ValuePrinter,
+    },
+};
+use sp_core::crypto::AccountId32;
+use std::{
+    collections::{
+        BTreeMap,
+        HashSet,
+    },
+    fmt,
+};
+
+/// What one message of a seed did
+pub struct TraceStep {
+    pub call: String,
+    pub caller: AccountId32,
+    pub value: BalanceOf<Runtime>,
+    pub outcome: String,
+    pub gas_consumed: String,
+    pub gas_required: String,
+    pub events: Vec<String>,
+    /// Hex-encoded keys of the contract storage the message wrote or removed
+    pub written_keys: Vec<String>,
+    /// Coverage identifiers no previous message of the seed reached
+    pub new_coverage: Vec<u64>,
+}
+
+/// Step-by-step timeline of a seed, printed by `phink trace`
+pub struct SeedTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl SeedTrace {
+    /// Execute `input` from the genesis, recording each of its messages
+    pub fn record(fuzzer: &Fuzzer, input: &[u8]) -> Result<Self, String> {
+        let decoded_msgs =
+            parse_input(input, fuzzer.setup.transcoder(), &fuzzer.fuzzing_config);
+        if decoded_msgs.messages.is_empty() {
+            return Err("🙅 This seed doesn't contain any valid message".into());
+        }
+
+        let address = &fuzzer.setup.contract_address;
+        let printer = ValuePrinter::new(fuzzer.setup.transcoder().metadata());
+        let mut snapshot = Snapshot::new(fuzzer.setup.genesis.clone());
+        let steps = snapshot.execute_with(|| {
+            <Fuzzer as FuzzerEngine>::timestamp(0);
+            let _ = take_coverage();
+
+            let mut seen_coverage = HashSet::new();
+            let mut seen_events = 0;
+            let mut steps = Vec::new();
+            for message in &decoded_msgs.messages {
+                if message.lapse > 0 {
+                    <Fuzzer as FuzzerEngine>::advance_blocks(message.lapse);
+                }
+                let value = if message.is_payable || message.is_transfer {
+                    message.value_token
+                } else {
+                    0
+                };
+                let before = ContractBridge::storage_entries(address);
+                let response: FullContractResponse = if message.is_upgrade {
+                    fuzzer.setup.upgrade()
+                } else if message.is_transfer {
+                    fuzzer.setup.transfer(
+                        message.origin.into(),
+                        message.value_token,
+                        &fuzzer.fuzzing_config,
+                    )
+                } else {
+                    fuzzer.setup.call_with_deposit_limit(
+                        &message.payload,
+                        message.origin.into(),
+                        value,
+                        message.deposit_limit.resolve(&fuzzer.fuzzing_config),
+                        &fuzzer.fuzzing_config,
+                    )
+                };
+                let after = ContractBridge::storage_entries(address);
+
+                // Events pile up within the block, only the last ones are this
+                // message's
+                let events = response.events.as_deref().unwrap_or_default();
+                let new_events = events
+                    .get(seen_events..)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|record| Self::describe_event(&record.event))
+                    .collect();
+                seen_events = events.len();
+
+                steps.push(TraceStep {
+                    call: printer.message(&message.message_metadata),
+                    caller: fuzzer.fuzzing_config.account_of(message.origin.into()),
+                    value,
+                    outcome: match &response.result {
+                        Ok(ret) if ret.did_revert() => {
+                            format!("reverted 0x{}", hex::encode(&ret.data))
+                        }
+                        Ok(ret) => format!("returned 0x{}", hex::encode(&ret.data)),
+                        Err(e) => format!("failed with {:?}", e),
+                    },
+                    gas_consumed: response.gas_consumed.to_string(),
+                    gas_required: response.gas_required.to_string(),
+                    events: new_events,
+                    written_keys: Self::written_keys(&before, &after),
+                    new_coverage: take_coverage()
+                        .into_iter()
+                        .filter(|id| seen_coverage.insert(*id))
+                        .collect(),
+                });
+            }
+            steps
+        });
+
+        Ok(Self { steps })
+    }
+
+    /// Keys whose value differs between both states, including the removed
+    /// ones
+    fn written_keys(
+        before: &BTreeMap<Vec<u8>, Vec<u8>>,
+        after: &BTreeMap<Vec<u8>, Vec<u8>>,
+    ) -> Vec<String> {
+        let mut keys: Vec<&Vec<u8>> = after
+            .iter()
+            .filter(|(key, value)| before.get(*key) != Some(*value))
+            .map(|(key, _)| key)
+            .chain(before.keys().filter(|key| !after.contains_key(*key)))
+            .collect();
+        keys.sort();
+        keys.into_iter()
+            .map(|key| format!("0x{}", hex::encode(key)))
+            .collect()
+    }
+
+    fn describe_event(event: &RuntimeEvent) -> String {
+        match event {
+            RuntimeEvent::Contracts(pallet_contracts::Event::ContractEmitted {
+                contract,
+                data,
+            }) => format!("ContractEmitted by {}: 0x{}", contract, hex::encode(data)),
+            other => format!("{:?}", other),
+        }
+    }
+}
+
+impl fmt::Display for SeedTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "⏱️  Step {}: {}",
+                i + 1,
+                step.call.replace('\n', "\n   ")
+            )?;
+            writeln!(f, "   🧑 Caller: {}", step.caller)?;
+            writeln!(f, "   💸 Value: {}", step.value)?;
+            writeln!(f, "   📤 Outcome: {}", step.outcome)?;
+            writeln!(
+                f,
+                "   🔥 Gas consumed: {} (required: {})",
+                step.gas_consumed, step.gas_required
+            )?;
+            writeln!(f, "   📣 Events emitted: {}", step.events.len())?;
+            for event in &step.events {
+                writeln!(f, "      - {}", event)?;
+            }
+            writeln!(f, "   💾 Storage keys written: {}", step.written_keys.len())?;
+            for key in &step.written_keys {
+                writeln!(f, "      - {}", key)?;
+            }
+            writeln!(
+                f,
+                "   🎯 Coverage points newly hit: {}{}",
+                step.new_coverage.len(),
+                if step.new_coverage.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {:?}", step.new_coverage)
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_written_keys_include_removed_ones() {
+        let before =
+            BTreeMap::from([(vec![1], vec![0]), (vec![2], vec![0]), (vec![3], vec![0])]);
+        let after =
+            BTreeMap::from([(vec![1], vec![0]), (vec![2], vec![1]), (vec![4], vec![0])]);
+        assert_eq!(
+            SeedTrace::written_keys(&before, &after),
+            vec!["0x02", "0x03", "0x04"]
+        );
+    }
+}
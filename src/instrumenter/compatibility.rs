@@ -0,0 +1,180 @@
+use serde_json::Value;
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+};
+use tracing::warn;
+
+/// Major version of ink! whose metadata the embedded transcoder decodes
+pub const SUPPORTED_INK_MAJOR: u64 = 5;
+/// Majors of `cargo-contract` building ink! 5 contracts. `cargo-contract` 6
+/// targets `pallet-revive` instead.
+pub const SUPPORTED_CARGO_CONTRACT_MAJORS: [u64; 2] = [4, 5];
+
+/// Detects an ink! or `cargo-contract` version Phink can't work with, before
+/// it shows up as an opaque decoding panic deep inside the harness
+pub struct Compatibility;
+
+impl Compatibility {
+    /// Check the ink! dependency of the contract and the installed
+    /// `cargo-contract`, before instrumenting and building
+    pub fn check_sources(contract_dir: &Path) -> Result<(), String> {
+        let manifest_path = contract_dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", manifest_path.display(), e))?;
+        match Self::ink_major(&manifest) {
+            Some(major) => Self::check_ink_major(major, "Cargo.toml")?,
+            None => {
+                warn!(
+                    "❓ Couldn't find the version of ink! in {}, it must be ink! {}",
+                    manifest_path.display(),
+                    SUPPORTED_INK_MAJOR
+                )
+            }
+        }
+
+        let output = Command::new("cargo")
+            .args(["contract", "--version"])
+            .output()
+            .map_err(|e| format!("🙅 Can't run `cargo contract --version`: {}", e))?;
+        let version = String::from_utf8_lossy(&output.stdout);
+        match Self::cargo_contract_major(&version) {
+            Some(major) if !SUPPORTED_CARGO_CONTRACT_MAJORS.contains(&major) => {
+                Err(format!(
+                    "🙅 `{}` can't build ink! {} contracts for `pallet-contracts`, install a \
+                    supported one with `cargo install --force --locked cargo-contract --version ^{}`",
+                    version.trim(),
+                    SUPPORTED_INK_MAJOR,
+                    SUPPORTED_CARGO_CONTRACT_MAJORS[SUPPORTED_CARGO_CONTRACT_MAJORS.len() - 1]
+                ))
+            }
+            Some(_) => Ok(()),
+            None => {
+                Err(
+                    "🙅 `cargo-contract` isn't installed, run `cargo install --force --locked cargo-contract`"
+                        .into(),
+                )
+            }
+        }
+    }
+
+    /// Check the JSON metadata of a built contract, before handing it over to
+    /// the transcoder
+    pub fn check_metadata(specs_path: &Path) -> Result<(), String> {
+        let specs: Value = fs::read_to_string(specs_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .ok_or_else(|| {
+                format!("🙅 Can't parse the JSON specs {}", specs_path.display())
+            })?;
+
+        // Solang emits the metadata of the ink! version it targets, which the
+        // transcoder decodes as-is
+        if Self::is_solang(&specs) {
+            return Ok(());
+        }
+        match Self::metadata_version(&specs) {
+            Some(version) => Self::check_ink_major(version, "metadata"),
+            None => {
+                Err(format!(
+                    "🙅 {} doesn't have any metadata version, is it the `.json` generated by `cargo contract build`?",
+                    specs_path.display()
+                ))
+            }
+        }
+    }
+
+    fn check_ink_major(major: u64, found_in: &str) -> Result<(), String> {
+        match major {
+            SUPPORTED_INK_MAJOR => Ok(()),
+            major if major < SUPPORTED_INK_MAJOR => {
+                Err(format!(
+                    "🙅 The contract uses ink! {} ({}), while Phink only decodes ink! {} contracts. \
+                    Migrate the contract to ink! {} (https://use.ink/faq/migrating-from-ink-4-to-5) and build it again",
+                    major, found_in, SUPPORTED_INK_MAJOR, SUPPORTED_INK_MAJOR
+                ))
+            }
+            major => {
+                Err(format!(
+                    "🙅 The contract uses ink! {} ({}), which targets `pallet-revive`. Only ink! {} \
+                    contracts for `pallet-contracts` are supported yet",
+                    major, found_in, SUPPORTED_INK_MAJOR
+                ))
+            }
+        }
+    }
+
+    /// Major of the `ink` dependency, e.g. `5` for `ink = { version = "5.0.0" }`
+    fn ink_major(manifest: &str) -> Option<u64> {
+        let manifest: toml::Table = manifest.parse().ok()?;
+        let ink = manifest.get("dependencies")?.get("ink")?;
+        let requirement = ink.as_str().or_else(|| ink.get("version")?.as_str())?;
+        Self::leading_number(
+            requirement.trim_start_matches(|c: char| !c.is_ascii_digit()),
+        )
+    }
+
+    /// Major of `cargo contract --version`, e.g. `4` for
+    /// `cargo-contract-contract 4.1.1-unknown-x86_64-unknown-linux-gnu`
+    fn cargo_contract_major(version: &str) -> Option<u64> {
+        Self::leading_number(version.split_whitespace().nth(1)?)
+    }
+
+    /// `version` of the metadata, a number since ink! 5 and a string before
+    fn metadata_version(specs: &Value) -> Option<u64> {
+        match &specs["version"] {
+            Value::Number(version) => version.as_u64(),
+            Value::String(version) => Self::leading_number(version),
+            _ => None,
+        }
+    }
+
+    /// Whether the metadata was generated by Solang, i.e. its `source.language`
+    /// is `Solidity <version>`
+    pub fn is_solang(specs: &Value) -> bool {
+        specs["source"]["language"]
+            .as_str()
+            .map_or(false, |language| language.starts_with("Solidity"))
+    }
+
+    fn leading_number(version: &str) -> Option<u64> {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_versions_are_detected() {
+        let manifest =
+            "[dependencies]\nink = { version = \"=5.0.0\", default-features = false }\n";
+        assert_eq!(Compatibility::ink_major(manifest), Some(5));
+        assert_eq!(
+            Compatibility::cargo_contract_major(
+                "cargo-contract-contract 4.1.1-unknown-x86_64-unknown-linux-gnu"
+            ),
+            Some(4)
+        );
+        assert_eq!(
+            Compatibility::metadata_version(&json!({ "version": 5 })),
+            Some(5)
+        );
+        assert_eq!(
+            Compatibility::metadata_version(&json!({ "version": "4" })),
+            Some(4)
+        );
+        assert!(Compatibility::is_solang(
+            &json!({ "source": { "language": "Solidity 0.3.3" }, "version": "4" })
+        ));
+        assert!(Compatibility::check_ink_major(4, "metadata").is_err());
+        assert!(Compatibility::check_ink_major(5, "metadata").is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+use quote::ToTokens;
+use syn::{
+    parse::Parser,
+    parse_quote_spanned,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit_mut::{
+        self,
+        VisitMut,
+    },
+    Attribute,
+    Expr,
+    ImplItemFn,
+    Meta,
+    ReturnType,
+    Token,
+};
+
+/// Start of the panic message of a violated `phink::ensures`, which tells a
+/// postcondition apart from any other trap
+pub const ENSURES_PANIC: &str = "phink: ensures";
+
+/// Expands the `phink::requires(...)` and `phink::ensures(...)` attributes of
+/// the messages of the forked contract, also when wrapped into a
+/// `#[cfg_attr(feature = "phink", ...)]`:
+///
+/// ```ignore
+/// #[ink(message)]
+/// #[cfg_attr(feature = "phink", phink::requires(amount > 0))]
+/// #[cfg_attr(feature = "phink", phink::ensures(result.is_ok() || self.balance < amount))]
+/// pub fn withdraw(&mut self, amount: Balance) -> Result<(), Error> { ... }
+/// ```
+///
+/// A false precondition reverts the message, since the fuzzer called it
+/// with inputs it doesn't accept. The postconditions are checked once the
+/// body returned, its return value being `result`, and a false one panics
+/// with `ENSURES_PANIC`.
+#[derive(Default)]
+pub struct ConditionsExpander;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Requires,
+    Ensures,
+}
+
+impl ConditionsExpander {
+    /// Remove the conditions out of `attrs`
+    fn take_conditions(attrs: &mut Vec<Attribute>) -> Vec<(Condition, Expr)> {
+        let mut conditions = Vec::new();
+        attrs.retain(|attr| {
+            match Self::condition(attr) {
+                Some(condition) => {
+                    conditions.push(condition);
+                    false
+                }
+                None => true,
+            }
+        });
+        conditions
+    }
+
+    /// The condition of `attr`, either `#[phink::requires(...)]` or the same
+    /// within a `#[cfg_attr(...)]`
+    fn condition(attr: &Attribute) -> Option<(Condition, Expr)> {
+        let Meta::List(list) = phink_attribute(attr)? else {
+            return None;
+        };
+        let condition = match list.path.segments.last()?.ident.to_string().as_str() {
+            "requires" => Condition::Requires,
+            "ensures" => Condition::Ensures,
+            _ => return None,
+        };
+        Some((condition, syn::parse2(list.tokens).ok()?))
+    }
+}
+
+/// `#[phink::<name>...]` out of `attr`, also when within a
+/// `#[cfg_attr(predicate, phink::<name>...)]`
+pub(crate) fn phink_attribute(attr: &Attribute) -> Option<Meta> {
+    let meta = if attr.path().is_ident("cfg_attr") {
+        let Meta::List(list) = &attr.meta else {
+            return None;
+        };
+        let metas = Punctuated::<Meta, Token![,]>::parse_terminated
+            .parse2(list.tokens.clone())
+            .ok()?;
+        // Only `cfg_attr(predicate, attribute)`, the contract keeping any
+        // other attribute
+        match metas.len() {
+            2 => metas.into_iter().nth(1)?,
+            _ => return None,
+        }
+    } else {
+        attr.meta.clone()
+    };
+
+    let segments = &meta.path().segments;
+    (segments.len() == 2 && segments[0].ident == "phink").then_some(meta)
+}
+
+impl VisitMut for ConditionsExpander {
+    fn visit_impl_item_fn_mut(&mut self, function: &mut ImplItemFn) {
+        visit_mut::visit_impl_item_fn_mut(self, function);
+
+        let conditions = Self::take_conditions(&mut function.attrs);
+        if conditions.is_empty() {
+            return;
+        }
+        let of = |kind: Condition| {
+            conditions
+                .iter()
+                .filter(move |(condition, _)| *condition == kind)
+                .map(|(_, expr)| expr)
+        };
+        let requires = of(Condition::Requires);
+        let ensures = of(Condition::Ensures);
+        let violations = of(Condition::Ensures).map(|expr| {
+            format!("{} `{}` violated", ENSURES_PANIC, expr.to_token_stream())
+        });
+
+        let output = match &function.sig.output {
+            ReturnType::Default => parse_quote_spanned! { function.block.span()=> () },
+            ReturnType::Type(_, ty) => (**ty).clone(),
+        };
+        let body = &function.block;
+        // The generated statements point to the body, for the coverage map
+        function.block = parse_quote_spanned! { body.span()=>
+            {
+                #(
+                    if !(#requires) {
+                        ink::env::return_value(ink::env::ReturnFlags::REVERT, &());
+                    }
+                )*
+                #[allow(unused_variables, clippy::redundant_closure_call)]
+                let result: #output = (|| -> #output #body)();
+                #(
+                    if !(#ensures) {
+                        panic!("{}", #violations);
+                    }
+                )*
+                result
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_file;
+
+    #[test]
+    fn test_conditions_are_expanded_around_the_body() {
+        let mut ast = parse_file(
+            r#"
+            impl Vault {
+                #[ink(message)]
+                #[cfg_attr(feature = "phink", phink::requires(amount > 0))]
+                #[phink::ensures(result <= amount)]
+                #[cfg_attr(feature = "std", derive(Debug))]
+                pub fn withdraw(&mut self, amount: u128) -> u128 {
+                    self.balance -= amount;
+                    amount
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        ConditionsExpander.visit_file_mut(&mut ast);
+
+        let expanded = ast.to_token_stream().to_string();
+        assert!(!expanded.contains("phink :: requires"));
+        assert!(!expanded.contains("phink :: ensures"));
+        assert!(expanded.contains("cfg_attr (feature = \"std\""));
+        assert!(expanded.contains("if ! (amount > 0)"));
+        assert!(expanded.contains("let result : u128 ="));
+        assert!(expanded.contains("phink: ensures `result <= amount` violated"));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::instrumenter::mutation::is_cfg_test;
+use parity_scale_codec::Encode;
+use std::{
+    collections::BTreeSet,
+    fs,
+    io,
+    path::Path,
+};
+use syn::{
+    parse_file,
+    visit::{
+        self,
+        Visit,
+    },
+    Attribute,
+    Expr,
+    ExprArray,
+    ExprLit,
+    ImplItemFn,
+    ItemFn,
+    ItemMod,
+    Lit,
+    LitInt,
+};
+
+/// Written next to the coverage map of the instrumented contract
+pub const CONSTANTS_FILE: &str = "phink_constants.json";
+/// AFL++ ignores the longer dictionary entries
+pub const MAX_DICT_ENTRY_LEN: usize = 128;
+
+/// Literal constants of the contract, e.g. `1377`, `b"forbidden"` or a
+/// `[u8; 32]` of literals, as the bytes a message argument would carry. Once
+/// in the dictionary, AFL++ can inject the exact values the contract compares
+/// against. The tests are left out.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContractConstants {
+    pub entries: BTreeSet<Vec<u8>>,
+}
+
+impl ContractConstants {
+    /// Collect the constants of the Rust `code`, ignored if it doesn't parse
+    pub fn extend(&mut self, code: &str) {
+        if let Ok(ast) = parse_file(code) {
+            ConstantsVisitor {
+                entries: &mut self.entries,
+            }
+            .visit_file(&ast);
+        }
+    }
+
+    pub fn save(&self, contract_dir: &Path) -> io::Result<()> {
+        let entries: Vec<String> = self.entries.iter().map(hex::encode).collect();
+        fs::write(
+            contract_dir.join(CONSTANTS_FILE),
+            serde_json::to_string(&entries)?,
+        )
+    }
+
+    /// Constants saved by the instrumentation of the contract at
+    /// `contract_dir`, if any
+    pub fn load(contract_dir: &Path) -> Self {
+        let entries = fs::read_to_string(contract_dir.join(CONSTANTS_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str::<Vec<String>>(&json).ok())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| hex::decode(entry).ok())
+            .collect();
+        Self { entries }
+    }
+
+    fn insert(entries: &mut BTreeSet<Vec<u8>>, bytes: Vec<u8>) {
+        if !bytes.is_empty() && bytes.len() <= MAX_DICT_ENTRY_LEN {
+            entries.insert(bytes);
+        }
+    }
+
+    /// Little-endian encodings of `int`, in the width of its suffix if any,
+    /// or as an `u32`, `u64` and `u128` otherwise. `0` and `1` are left to
+    /// the mutations of AFL++.
+    fn integers(int: &LitInt) -> Vec<Vec<u8>> {
+        let Ok(value) = int.base10_parse::<u128>() else {
+            return Vec::new();
+        };
+        if value <= 1 {
+            return Vec::new();
+        }
+        let widths: &[usize] = match int.suffix() {
+            "u8" | "i8" => &[1],
+            "u16" | "i16" => &[2],
+            "u32" | "i32" => &[4],
+            "u64" | "i64" | "usize" | "isize" => &[8],
+            "u128" | "i128" => &[16],
+            _ => &[4, 8, 16],
+        };
+        widths
+            .iter()
+            .filter(|width| **width == 16 || value < 1 << (8 * **width))
+            .map(|width| value.to_le_bytes()[..*width].to_vec())
+            .collect()
+    }
+
+    /// Bytes of `[1, 2, 3]`, if every element is a literal fitting in an `u8`
+    fn byte_array(array: &ExprArray) -> Option<Vec<u8>> {
+        array
+            .elems
+            .iter()
+            .map(|elem| {
+                match elem {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Int(int), ..
+                    }) => int.base10_parse::<u8>().ok(),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+struct ConstantsVisitor<'a> {
+    entries: &'a mut BTreeSet<Vec<u8>>,
+}
+
+impl<'ast> Visit<'ast> for ConstantsVisitor<'_> {
+    // Doc comments and `#[ink(...)]` arguments aren't compared against
+    fn visit_attribute(&mut self, _: &'ast Attribute) {}
+
+    fn visit_item_mod(&mut self, module: &'ast ItemMod) {
+        if !is_cfg_test(&module.attrs) {
+            visit::visit_item_mod(self, module);
+        }
+    }
+
+    fn visit_item_fn(&mut self, function: &'ast ItemFn) {
+        if !is_cfg_test(&function.attrs) {
+            visit::visit_item_fn(self, function);
+        }
+    }
+
+    fn visit_impl_item_fn(&mut self, function: &'ast ImplItemFn) {
+        if !is_cfg_test(&function.attrs) {
+            visit::visit_impl_item_fn(self, function);
+        }
+    }
+
+    fn visit_expr_array(&mut self, array: &'ast ExprArray) {
+        match ContractConstants::byte_array(array) {
+            Some(bytes) if bytes.len() > 1 => {
+                ContractConstants::insert(self.entries, bytes);
+            }
+            _ => visit::visit_expr_array(self, array),
+        }
+    }
+
+    fn visit_lit(&mut self, lit: &'ast Lit) {
+        // Strings are SCALE-encoded with their length, but may be compared
+        // against as raw bytes too
+        let bytes = match lit {
+            Lit::Int(int) => {
+                for bytes in ContractConstants::integers(int) {
+                    ContractConstants::insert(self.entries, bytes);
+                }
+                return;
+            }
+            Lit::Str(string) => string.value().into_bytes(),
+            Lit::ByteStr(bytes) => bytes.value(),
+            _ => return,
+        };
+        ContractConstants::insert(self.entries, bytes.encode());
+        ContractConstants::insert(self.entries, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literals_are_collected_outside_of_tests() {
+        let mut constants = ContractConstants::default();
+        constants.extend(
+            r#"
+            const FORBIDDEN_DOMAIN: [u8; 3] = [1, 2, 3];
+            fn register(&mut self, value: u128) {
+                if value == 1377 && self.name == "phink" {
+                    self.count += 1;
+                }
+            }
+            #[cfg(test)]
+            mod tests {
+                fn test() { assert_eq!(42u8, 42); }
+            }
+            "#,
+        );
+
+        let entries = &constants.entries;
+        assert!(entries.contains(&vec![1, 2, 3]));
+        assert!(entries.contains(&1377u32.to_le_bytes().to_vec()));
+        assert!(entries.contains(&1377u128.to_le_bytes().to_vec()));
+        assert!(entries.contains(&b"phink".to_vec()));
+        assert!(entries.contains(&[&[20u8][..], b"phink"].concat()));
+        assert!(!entries.contains(&vec![42]));
+        assert!(!entries.contains(&1u32.to_le_bytes().to_vec()));
+    }
+}
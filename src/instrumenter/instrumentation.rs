@@ -13,9 +13,26 @@ use std::{
         PathBuf,
     },
     process::Command,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
-use crate::instrumenter::instrumentation::instrument::ContractCovUpdater;
+use crate::instrumenter::{
+    compatibility::Compatibility,
+    conditions::ConditionsExpander,
+    constants::ContractConstants,
+    instrumentation::instrument::ContractCovUpdater,
+    invariants::{
+        ExternalInvariants,
+        InvariantsExpander,
+    },
+};
+use indicatif::{
+    ProgressBar,
+    ProgressStyle,
+};
 use quote::quote;
 use rand::{
     distributions::Alphanumeric,
@@ -25,6 +42,14 @@ use syn::{
     parse_file,
     visit_mut::VisitMut,
 };
+use tracing::{
+    debug,
+    enabled,
+    info,
+    info_span,
+    warn,
+    Level,
+};
 use walkdir::WalkDir;
 
 /// The objective of this `struct` is to assist Phink in instrumenting ink!
@@ -38,6 +63,10 @@ use walkdir::WalkDir;
 /// Phink opted for a Rust AST approach. For each code instruction on the
 /// smart-contract, Phink will automatically add a tracing code, which will then
 /// be fetched at the end of the input execution in order to get coverage.
+/// Macro inserted before each statement of the contract, sending its coverage
+/// identifier to the harness through `PhinkCoverageExtension`
+pub const COV_MACRO: &str = "phink_cov!";
+
 #[derive(Default, Clone)]
 pub struct Instrumenter {
     pub contract_dir: PathBuf,
@@ -68,8 +97,37 @@ impl Instrumenter {
         Self { contract_dir }
     }
 
+    /// Return the coverage identifier traced by a line of instrumented code,
+    /// i.e `12` for `phink_cov!(12);`
+    pub fn parse_cov_id(line: &str) -> Option<u64> {
+        line.trim()
+            .strip_prefix(COV_MACRO)?
+            .strip_prefix('(')?
+            .split(')')
+            .next()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Progress bar of one phase, e.g. `Forking`, hidden with `--quiet`
+    fn progress_bar(len: u64, phase: &'static str) -> ProgressBar {
+        if !enabled!(Level::INFO) {
+            return ProgressBar::hidden();
+        }
+        let bar = ProgressBar::new(len).with_prefix(phase);
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{prefix:>13} [{bar:40}] {pos}/{len} {wide_msg}",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        );
+        bar
+    }
+
     pub fn find(&self) -> Result<InkFilesPath, String> {
-        let wasm_path = fs::read_dir(self.contract_dir.join("target/ink/"))
+        let artifacts: Vec<PathBuf> = fs::read_dir(self.contract_dir.join("target/ink/"))
             .map_err(|e| {
                 format!(
                     "🙅 It seems that your contract is not compiled into `target/ink`. \
@@ -79,27 +137,129 @@ impl Instrumenter {
                     e
                 )
             })?
-            .filter_map(|entry| {
-                let path = entry.ok()?.path();
-                if path.is_file()
-                    && path.extension().and_then(OsStr::to_str) == Some("wasm")
-                {
-                    Some(path)
-                } else {
-                    None
-                }
-            })
-            .next()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file())
+            .collect();
+
+        let has_extension = |path: &Path, extension: &str| {
+            path.extension().and_then(OsStr::to_str) == Some(extension)
+        };
+
+        // ink! v6 contracts target `pallet-revive`, which doesn't exist in the
+        // `polkadot-sdk` release embedded by Phink
+        if !artifacts.iter().any(|path| has_extension(path, "wasm"))
+            && artifacts.iter().any(|path| has_extension(path, "polkavm"))
+        {
+            return Err("🙅 Found a PolkaVM (`.polkavm`) artifact: contracts targeting \
+                `pallet-revive` aren't supported, only WASM contracts for `pallet-contracts` are (see \"PolkaVM contracts\" in the README)"
+                .into());
+        }
+
+        let wasm_path = artifacts
+            .iter()
+            .find(|path| has_extension(path, "wasm"))
+            .cloned()
             .ok_or("🙅 No .wasm file found in target directory")?;
 
-        let specs_path =
-            PathBuf::from(wasm_path.to_str().unwrap().replace(".wasm", ".json"));
+        // Solang bundles the metadata and the WASM blob into a `.contract`
+        // instead of a `.json`
+        let mut specs_path = wasm_path.with_extension("json");
+        if !specs_path.is_file() && wasm_path.with_extension("contract").is_file() {
+            specs_path = wasm_path.with_extension("contract");
+        }
+        Compatibility::check_metadata(&specs_path)?;
 
         Ok(InkFilesPath {
             wasm_path,
             specs_path,
         })
     }
+
+    /// Whether the contract is written in Solidity, to be compiled by Solang
+    /// instead of `cargo contract`
+    pub fn is_solidity(&self) -> bool {
+        !self.contract_dir.join("Cargo.toml").exists()
+            && !self.solidity_sources().is_empty()
+    }
+
+    fn solidity_sources(&self) -> Vec<PathBuf> {
+        fs::read_dir(&self.contract_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().and_then(OsStr::to_str) == Some("sol"))
+            .collect()
+    }
+
+    /// Compile the Solidity sources with `solang compile --target polkadot`,
+    /// into `target/ink` as `cargo contract` would
+    fn build_solidity(&self) -> Result<InkFilesPath, String> {
+        let started = Instant::now();
+        let output = Command::new("solang")
+            .current_dir(&self.contract_dir)
+            .args(["compile", "--target", "polkadot", "--output", "target/ink"])
+            .args(self.solidity_sources())
+            .output()
+            .map_err(|e| {
+                format!(
+                    "🙅 Can't run `solang`, is it installed? (https://solang.readthedocs.io) {}",
+                    e
+                )
+            })?;
+
+        if output.status.success() {
+            info!("🏗️ Contract built with Solang in {:.1?}", started.elapsed());
+            self.find()
+        } else {
+            Err(format!(
+                "🙅 Solang couldn't compile the contract in {} ({})\n{}",
+                &self.contract_dir.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+
+    /// Force `overflow-checks = true` inside the release profile of the forked
+    /// contract. This way, any arithmetic overflow panics instead of silently
+    /// wrapping, and `BugManager` can report it as a dedicated bug class. The
+    /// debug profile already enables those checks by default.
+    pub fn enforce_overflow_checks(&self) -> Result<(), String> {
+        let manifest_path = self.contract_dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path).map_err(|e| {
+            format!("🙅 Failed to read {}: {:?}", manifest_path.display(), e)
+        })?;
+
+        let mut cargo: toml::Table = manifest.parse().map_err(|e| {
+            format!("🙅 Failed to parse {}: {}", manifest_path.display(), e)
+        })?;
+
+        let profile = cargo
+            .entry("profile")
+            .or_insert(toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or("🙅 `profile` is not a table in the contract's Cargo.toml")?;
+
+        let release = profile
+            .entry("release")
+            .or_insert(toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or("🙅 `profile.release` is not a table in the contract's Cargo.toml")?;
+
+        release.insert("overflow-checks".into(), toml::Value::Boolean(true));
+
+        let serialized = toml::to_string(&cargo)
+            .map_err(|e| format!("🙅 Failed to serialize Cargo.toml: {}", e))?;
+        fs::write(&manifest_path, serialized).map_err(|e| {
+            format!("🙅 Failed to write {}: {:?}", manifest_path.display(), e)
+        })?;
+
+        info!(
+            "🧮 Enforced `overflow-checks` in {}",
+            manifest_path.display()
+        );
+        Ok(())
+    }
 }
 pub trait ContractBuilder {
     fn build(&self) -> Result<InkFilesPath, String>;
@@ -107,10 +267,22 @@ pub trait ContractBuilder {
 
 impl ContractBuilder for Instrumenter {
     fn build(&self) -> Result<InkFilesPath, String> {
-        let status = Command::new("cargo")
+        if self.is_solidity() {
+            return self.build_solidity();
+        }
+        let started = Instant::now();
+        let spinner = Self::progress_bar(0, "Building");
+        spinner.set_style(
+            ProgressStyle::with_template("{prefix:>13} {spinner} {elapsed} {wide_msg}")
+                .unwrap(),
+        );
+        spinner.set_message("cargo contract build --features=phink");
+        spinner.enable_steady_tick(Duration::from_millis(100));
+
+        let output = Command::new("cargo")
             .current_dir(&self.contract_dir)
             .args(["contract", "build", "--features=phink"])
-            .status()
+            .output()
             .map_err(|e| {
                 format!(
                     "🙅 Failed to execute cargo command: {}.\
@@ -118,16 +290,19 @@ impl ContractBuilder for Instrumenter {
                     e
                 )
             })?;
+        spinner.finish_and_clear();
 
-        if status.success() {
+        if output.status.success() {
+            info!("🏗️ Contract built in {:.1?}", started.elapsed());
             self.find()
         } else {
             Err(format!(
                 "🙅 It seems that your instrumented smart contract did not compile properly. \
                 Please go to {}, edit the `lib.rs` file, and run cargo contract build again.\
-                (more infos: {})",
+                (more infos: {})\n{}",
                 &self.contract_dir.display(),
-                status
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
             ))
         }
     }
@@ -144,13 +319,17 @@ impl ContractForker for Instrumenter {
             .collect();
 
         let new_dir = Path::new("/tmp").join(format!("ink_fuzzed_{}", random_string));
-        println!("🏗️ Creating new directory: {:?}", new_dir);
+        debug!("🏗️ Creating new directory: {:?}", new_dir);
         fs::create_dir_all(&new_dir)
             .map_err(|e| format!("🙅 Failed to create directory: {}", e))?;
 
-        println!("📁 Starting to copy files from {:?}", self.contract_dir);
+        info!("📁 Starting to copy files from {:?}", self.contract_dir);
 
-        for entry in WalkDir::new(&self.contract_dir) {
+        let started = Instant::now();
+        let entries: Vec<_> = WalkDir::new(&self.contract_dir).into_iter().collect();
+        let bar = Self::progress_bar(entries.len() as u64, "Forking");
+        for entry in entries {
+            bar.inc(1);
             let entry = entry.map_err(|e| format!("🙅 Failed to read entry: {}", e))?;
             let target_path = new_dir.join(
                 entry
@@ -160,18 +339,21 @@ impl ContractForker for Instrumenter {
             );
 
             if entry.path().is_dir() {
-                println!("📂 Creating subdirectory: {:?}", target_path);
+                debug!("📂 Creating subdirectory: {:?}", target_path);
                 fs::create_dir_all(&target_path)
                     .map_err(|e| format!("🙅 Failed to create subdirectory: {}", e))?;
             } else {
-                println!("📄 Copying file: {:?} -> {:?}", entry.path(), target_path);
+                debug!("📄 Copying file: {:?} -> {:?}", entry.path(), target_path);
                 copy(entry.path(), &target_path)
                     .map_err(|e| format!("🙅 Failed to copy file: {}", e))?;
             }
         }
 
-        println!(
-            "✅ Fork completed successfully! New directory: {:?}",
+        bar.finish_and_clear();
+
+        info!(
+            "✅ Fork completed successfully in {:.1?}! New directory: {:?}",
+            started.elapsed(),
             new_dir
         );
         Ok(new_dir)
@@ -180,19 +362,76 @@ impl ContractForker for Instrumenter {
 
 impl ContractInstrumenter for Instrumenter {
     fn instrument(&mut self) -> Result<&mut Instrumenter, String> {
+        let _span =
+            info_span!("instrument", contract = %self.contract_dir.display()).entered();
+        if self.is_solidity() {
+            // There's no Rust AST to instrument: the contract is only forked,
+            // and fuzzed without any coverage feedback from it
+            self.contract_dir = self.fork()?;
+            warn!(
+                "🙅 Solidity contracts can't be instrumented yet, they're fuzzed without coverage"
+            );
+            return Ok(self);
+        }
+        Compatibility::check_sources(&self.contract_dir)?;
+        let source_dir = self.contract_dir.canonicalize().ok();
         let new_working_dir = self.fork()?;
         self.contract_dir = new_working_dir.clone();
-        let mut contract_cov_manager = ContractCovUpdater { line_id: 0 };
-        for entry in WalkDir::new(&new_working_dir)
+        self.enforce_overflow_checks()?;
+        ExternalInvariants::inject(&new_working_dir)?;
+        let mut contract_cov_manager = ContractCovUpdater::default();
+        contract_cov_manager.coverage_map.source_dir = source_dir;
+        let started = Instant::now();
+        let sources: Vec<_> = WalkDir::new(&new_working_dir)
             .into_iter()
             .filter_map(|e| e.ok())
             .filter(|e| e.path().extension().map_or(false, |ext| ext == "rs"))
             .filter(|e| !e.path().components().any(|c| c.as_os_str() == "target"))
-        // Don't instrument anything inside target
-        {
+            // Don't instrument anything inside target
+            .collect();
+        let bar = Self::progress_bar(sources.len() as u64, "Instrumenting");
+        let mut constants = ContractConstants::default();
+        for entry in &sources {
             let path = entry.path();
+            if let Ok(code) = fs::read_to_string(path) {
+                constants.extend(&code);
+            }
+            contract_cov_manager.current_file = path
+                .strip_prefix(&new_working_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string();
+            bar.set_message(contract_cov_manager.current_file.clone());
             self.instrument_file(path, &mut contract_cov_manager)?;
+            bar.inc(1);
         }
+        bar.finish_and_clear();
+
+        info!(
+            "🧪 Instrumented {} files in {:.1?}: {} coverage points injected, {} of them tracing {} branches",
+            sources.len(),
+            started.elapsed(),
+            contract_cov_manager.line_id,
+            contract_cov_manager
+                .coverage_map
+                .branches
+                .iter()
+                .map(|branch| branch.outcomes.len())
+                .sum::<usize>(),
+            contract_cov_manager.coverage_map.branches.len()
+        );
+
+        contract_cov_manager
+            .coverage_map
+            .save(&new_working_dir)
+            .map_err(|e| format!("🙅 Failed to save the coverage map: {:?}", e))?;
+        constants
+            .save(&new_working_dir)
+            .map_err(|e| format!("🙅 Failed to save the constants: {:?}", e))?;
+        debug!(
+            "📖 {} constants of the contract added to the dictionary",
+            constants.entries.len()
+        );
         Ok(self)
     }
 
@@ -208,12 +447,17 @@ impl ContractInstrumenter for Instrumenter {
             return Ok(());
         }
 
-        println!(
+        debug!(
             "📝 Instrumenting file: {} with {:?}",
             path.display(),
-            contract_cov_manager
+            contract_cov_manager.line_id
         );
 
+        contract_cov_manager
+            .coverage_map
+            .files
+            .insert(contract_cov_manager.current_file.clone(), code.clone());
+
         let modified_code =
             Self::parse_and_visit(&code, contract_cov_manager).map_err(|_| {
                 format!("🙅 Failed to parse and visit code in {}", path.display())
@@ -235,6 +479,8 @@ impl ContractInstrumenter for Instrumenter {
             "⚠️ This is most likely that your ink! contract \
         contains invalid syntax. Try to compile it first. Also, ensure that `cargo-contract` is installed.",
         );
+        InvariantsExpander.visit_file_mut(&mut ast);
+        ConditionsExpander.visit_file_mut(&mut ast);
         visitor.visit_file_mut(&mut ast);
 
         Ok(quote!(#ast).to_string())
@@ -243,61 +489,228 @@ impl ContractInstrumenter for Instrumenter {
     fn save_and_format(source_code: String, rust_file: PathBuf) -> Result<(), io::Error> {
         let mut file = File::create(rust_file.clone())?;
         file.write_all(source_code.as_bytes())?;
-        println!("✍️ Writing instrumented source code");
+        debug!("✍️ Writing instrumented source code");
         file.flush()?;
-        println!("🛠️ Formatting {} with rustfmt...", rust_file.display());
-        Command::new("rustfmt")
-            .arg(rust_file)
+        debug!("🛠️ Formatting {} with rustfmt...", rust_file.display());
+        let status = Command::new("rustfmt")
+            .arg(&rust_file)
             .arg("--edition=2021")
             .status()?;
+        if !status.success() {
+            warn!(
+                "⚠️ rustfmt failed on {}, the instrumented code is left unformatted. Run `phink doctor` to check your setup",
+                rust_file.display()
+            );
+        }
         Ok(())
     }
 
     /// Checks if the given code string is already instrumented.
     /// This function looks for the presence of the pattern
-    /// `ink::env::debug_println!("COV=abc")` where `abc` can be any number. If
+    /// `phink_cov!(abc)` where `abc` can be any number. If
     /// this pattern is found, it means the code is instrumented.
     fn already_instrumented(code: &str) -> bool {
-        Regex::new(r#"\bink::env::debug_println!\("COV=\d+"\)"#)
-            .unwrap()
-            .is_match(code)
+        Regex::new(r"\bphink_cov!\(\d+\)").unwrap().is_match(code)
     }
 }
 
 mod instrument {
+    use crate::{
+        contract::extension::{
+            COVERAGE_EXTENSION_ID,
+            COVERAGE_FUNC_ID,
+            GHOST_ADD_FUNC_ID,
+            GHOST_GET_FUNC_ID,
+            GHOST_SET_FUNC_ID,
+        },
+        cover::map::{
+            BranchOutcome,
+            BranchPoint,
+            CoverageMap,
+            CoveragePoint,
+        },
+    };
     use proc_macro2::Span;
+    use quote::ToTokens;
     use syn::{
         parse_quote,
-        visit_mut::VisitMut,
+        spanned::Spanned,
+        visit_mut::{
+            self,
+            VisitMut,
+        },
+        Block,
         Expr,
+        ExprIf,
+        ExprMatch,
+        File,
+        ImplItemFn,
+        Item,
+        ItemFn,
         LitInt,
         Stmt,
         Token,
     };
 
-    #[derive(Debug)]
+    #[derive(Debug, Default)]
     pub struct ContractCovUpdater {
         pub line_id: u64,
+        /// File being instrumented, relative to the contract root
+        pub current_file: String,
+        /// Function being instrumented, used to group the branches
+        pub current_fn: String,
+        /// Where each coverage identifier lands in the original sources
+        pub coverage_map: CoverageMap,
+    }
+
+    impl ContractCovUpdater {
+        /// Allocate a new identifier, and return its tracing statement
+        fn cov_stmt(&mut self) -> (u64, Stmt) {
+            let id = self.line_id;
+            self.line_id = self.line_id + 1;
+
+            let line_lit = LitInt::new(id.to_string().as_str(), Span::call_site());
+            let insert_expr: Expr = parse_quote! {
+                phink_cov!(#line_lit)
+            };
+            // Convert this expression into a statement
+            (
+                id,
+                Stmt::Expr(insert_expr, Some(Token![;](Span::call_site()))),
+            )
+        }
+
+        /// Prepend a dedicated tracing statement to a branch, so that we know
+        /// if this outcome was taken, regardless of the statements inside
+        fn trace_branch(&mut self, block: &mut Block, label: String) -> BranchOutcome {
+            let (id, stmt) = self.cov_stmt();
+            block.stmts.insert(0, stmt);
+            BranchOutcome { label, id }
+        }
+
+        /// `phink_ghost_add!(b"deposits", value as i128)`,
+        /// `phink_ghost_set!(b"deposits", 0)` and `phink_ghost_get!(b"deposits")`,
+        /// to record and read the ghost variables of the properties
+        fn ghost_macros() -> Vec<Item> {
+            let method = |func_id: u16| {
+                LitInt::new(
+                    &format!(
+                        "{:#010x}",
+                        ((COVERAGE_EXTENSION_ID as u32) << 16) | func_id as u32
+                    ),
+                    Span::call_site(),
+                )
+            };
+            let (add, set, get) = (
+                method(GHOST_ADD_FUNC_ID),
+                method(GHOST_SET_FUNC_ID),
+                method(GHOST_GET_FUNC_ID),
+            );
+            vec![
+                parse_quote! {
+                    #[allow(unused_macros)]
+                    macro_rules! phink_ghost_add {
+                        ($name:expr, $delta:expr) => {{
+                            let name: &[u8] = $name;
+                            let delta: i128 = $delta;
+                            ink::env::chain_extension::ChainExtensionMethod::build(#add)
+                                .input::<(&[u8], i128)>()
+                                .output::<(), false>()
+                                .ignore_error_code()
+                                .call(&(name, delta))
+                        }};
+                    }
+                },
+                parse_quote! {
+                    #[allow(unused_macros)]
+                    macro_rules! phink_ghost_set {
+                        ($name:expr, $value:expr) => {{
+                            let name: &[u8] = $name;
+                            let value: i128 = $value;
+                            ink::env::chain_extension::ChainExtensionMethod::build(#set)
+                                .input::<(&[u8], i128)>()
+                                .output::<(), false>()
+                                .ignore_error_code()
+                                .call(&(name, value))
+                        }};
+                    }
+                },
+                parse_quote! {
+                    #[allow(unused_macros)]
+                    macro_rules! phink_ghost_get {
+                        ($name:expr) => {{
+                            let name: &[u8] = $name;
+                            ink::env::chain_extension::ChainExtensionMethod::build(#get)
+                                .input::<&[u8]>()
+                                .output::<i128, false>()
+                                .ignore_error_code()
+                                .call(&name)
+                        }};
+                    }
+                },
+            ]
+        }
+
+        fn add_branch(&mut self, kind: &str, line: usize, outcomes: Vec<BranchOutcome>) {
+            self.coverage_map.branches.push(BranchPoint {
+                file: self.current_file.clone(),
+                line,
+                function: self.current_fn.clone(),
+                kind: kind.into(),
+                outcomes,
+            });
+        }
     }
 
     impl VisitMut for &mut ContractCovUpdater {
+        /// `phink_cov!` must be defined in every instrumented file. Thanks to
+        /// the textual scoping of `macro_rules!`, defining it on top of the file
+        /// makes it available in all the modules below, including the
+        /// `#[ink::contract]` one.
+        fn visit_file_mut(&mut self, file: &mut File) {
+            visit_mut::visit_file_mut(self, file);
+
+            let (ext_id, func_id) = (COVERAGE_EXTENSION_ID, COVERAGE_FUNC_ID);
+            let method_id = LitInt::new(
+                &format!("{:#010x}", ((ext_id as u32) << 16) | func_id as u32),
+                Span::call_site(),
+            );
+            let cov_macro: Item = parse_quote! {
+                #[allow(unused_macros)]
+                macro_rules! phink_cov {
+                    ($id:literal) => {
+                        ink::env::chain_extension::ChainExtensionMethod::build(#method_id)
+                            .input::<u32>()
+                            .output::<(), false>()
+                            .ignore_error_code()
+                            .call(&$id)
+                    };
+                }
+            };
+            file.items.insert(0, cov_macro);
+            for ghost_macro in Self::ghost_macros().into_iter().rev() {
+                file.items.insert(1, ghost_macro);
+            }
+        }
+
         fn visit_block_mut(&mut self, block: &mut syn::Block) {
             let mut new_stmts = Vec::new();
             // Temporarily replace block.stmts with an empty Vec to avoid
             // borrowing issues
             let mut stmts = std::mem::take(&mut block.stmts);
             for mut stmt in stmts.drain(..) {
-                let line_lit =
-                    LitInt::new(self.line_id.to_string().as_str(), Span::call_site());
+                // Requires the `span-locations` feature of `proc-macro2`
+                let line = stmt.span().start().line;
+                let (id, pre_stmt) = self.cov_stmt();
+                self.coverage_map.points.insert(
+                    id,
+                    CoveragePoint {
+                        file: self.current_file.clone(),
+                        line,
+                        function: self.current_fn.clone(),
+                    },
+                );
 
-                self.line_id = self.line_id + 1;
-
-                let insert_expr: Expr = parse_quote! {
-                    ink::env::debug_println!("COV={}", #line_lit)
-                };
-                // Convert this expression into a statement
-                let pre_stmt: Stmt =
-                    Stmt::Expr(insert_expr, Some(Token![;](Span::call_site())));
                 new_stmts.push(pre_stmt);
                 // Use recursive visitation to handle nested blocks and other
                 // statement types
@@ -306,5 +719,73 @@ mod instrument {
             }
             block.stmts = new_stmts;
         }
+
+        fn visit_item_fn_mut(&mut self, item: &mut ItemFn) {
+            let previous =
+                std::mem::replace(&mut self.current_fn, item.sig.ident.to_string());
+            visit_mut::visit_item_fn_mut(self, item);
+            self.current_fn = previous;
+        }
+
+        fn visit_impl_item_fn_mut(&mut self, item: &mut ImplItemFn) {
+            let previous =
+                std::mem::replace(&mut self.current_fn, item.sig.ident.to_string());
+            visit_mut::visit_impl_item_fn_mut(self, item);
+            self.current_fn = previous;
+        }
+
+        /// Both outcomes of an `if` are traced. A missing `else` is added (the
+        /// `if` is then of type `()` anyway), and an `else if` becomes
+        /// `else { if ... }` so that the `else` outcome can be traced too.
+        fn visit_expr_if_mut(&mut self, expr: &mut ExprIf) {
+            let line = expr.if_token.span.start().line;
+            visit_mut::visit_expr_if_mut(self, expr);
+
+            let taken = self.trace_branch(&mut expr.then_branch, "taken".into());
+
+            let mut else_block: Block = match expr.else_branch.take() {
+                Some((_, else_expr)) => {
+                    match *else_expr {
+                        Expr::Block(block) => block.block,
+                        other => parse_quote!({ #other }),
+                    }
+                }
+                None => parse_quote!({}),
+            };
+            let not_taken = self.trace_branch(&mut else_block, "not taken".into());
+            expr.else_branch = Some((
+                Token![else](Span::call_site()),
+                Box::new(Expr::Block(syn::ExprBlock {
+                    attrs: vec![],
+                    label: None,
+                    block: else_block,
+                })),
+            ));
+
+            self.add_branch("if", line, vec![taken, not_taken]);
+        }
+
+        /// Every arm of a `match` is an outcome
+        fn visit_expr_match_mut(&mut self, expr: &mut ExprMatch) {
+            let line = expr.match_token.span.start().line;
+            visit_mut::visit_expr_match_mut(self, expr);
+
+            let mut outcomes = Vec::new();
+            for arm in expr.arms.iter_mut() {
+                let body = &arm.body;
+                let mut block: Block = parse_quote!({ #body });
+                outcomes.push(
+                    self.trace_branch(&mut block, arm.pat.to_token_stream().to_string()),
+                );
+                *arm.body = Expr::Block(syn::ExprBlock {
+                    attrs: vec![],
+                    label: None,
+                    block,
+                });
+                arm.comma = Some(Token![,](Span::call_site()));
+            }
+
+            self.add_branch("match", line, outcomes);
+        }
     }
 }
@@ -0,0 +1,203 @@
+use crate::{
+    contract::payload::DEFAULT_PHINK_PREFIX,
+    instrumenter::conditions::phink_attribute,
+};
+use proc_macro2::Ident;
+use quote::ToTokens;
+use std::{
+    fs,
+    path::Path,
+};
+use syn::{
+    parse_file,
+    parse_quote,
+    visit_mut::{
+        self,
+        VisitMut,
+    },
+    Attribute,
+    ImplItem,
+    Item,
+    ItemImpl,
+    Meta,
+};
+use tracing::info;
+
+/// File of invariants kept next to the contract instead of inside it. It is
+/// injected into the `#[ink::contract]` module of the forked copy only, so
+/// that the original contract doesn't need any `phink` feature block.
+pub const INVARIANTS_FILE: &str = "phink_invariants.rs";
+
+pub struct ExternalInvariants;
+
+impl ExternalInvariants {
+    /// Inject `phink_invariants.rs` into the `lib.rs` of the forked contract,
+    /// and remove it from the fork so that it doesn't get instrumented on its
+    /// own. Returns whether there was anything to inject.
+    pub fn inject(fork_dir: &Path) -> Result<bool, String> {
+        let invariants_path = fork_dir.join(INVARIANTS_FILE);
+        if !invariants_path.is_file() {
+            return Ok(false);
+        }
+        let invariants = fs::read_to_string(&invariants_path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", invariants_path.display(), e))?;
+
+        let lib_path = ["lib.rs", "src/lib.rs"]
+            .iter()
+            .map(|lib| fork_dir.join(lib))
+            .find(|lib| lib.is_file())
+            .ok_or(format!(
+                "🙅 Can't find the `lib.rs` to inject {} into",
+                INVARIANTS_FILE
+            ))?;
+        let lib = fs::read_to_string(&lib_path)
+            .map_err(|e| format!("🙅 Can't read {}: {}", lib_path.display(), e))?;
+
+        fs::write(&lib_path, Self::with_invariants(&lib, &invariants)?)
+            .map_err(|e| format!("🙅 Can't write {}: {}", lib_path.display(), e))?;
+        fs::remove_file(&invariants_path).map_err(|e| {
+            format!("🙅 Can't remove {}: {}", invariants_path.display(), e)
+        })?;
+
+        info!(
+            "🧩 Injected {} into {}",
+            INVARIANTS_FILE,
+            lib_path.display()
+        );
+        Ok(true)
+    }
+
+    /// `lib` with `invariants` appended at the end of its `#[ink::contract]`
+    /// module. The text is spliced as-is, so that the comments and the lines
+    /// of the contract are left untouched for the coverage report.
+    fn with_invariants(lib: &str, invariants: &str) -> Result<String, String> {
+        parse_file(invariants)
+            .map_err(|e| format!("🙅 {} isn't valid Rust: {}", INVARIANTS_FILE, e))?;
+        let ast =
+            parse_file(lib).map_err(|e| format!("🙅 lib.rs isn't valid Rust: {}", e))?;
+
+        let is_contract = match ast.items.last() {
+            Some(Item::Mod(module)) => {
+                module.content.is_some()
+                    && module.attrs.iter().any(|attr| {
+                        attr.path()
+                            .segments
+                            .last()
+                            .map_or(false, |segment| segment.ident == "contract")
+                    })
+            }
+            _ => false,
+        };
+        if !is_contract {
+            return Err(format!(
+                "🙅 The `#[ink::contract]` module must be the last item of lib.rs to inject {}",
+                INVARIANTS_FILE
+            ));
+        }
+
+        let end = lib.rfind('}').unwrap();
+        Ok(format!(
+            "{}\n{}\n{}",
+            &lib[..end],
+            invariants.trim_end(),
+            &lib[end..]
+        ))
+    }
+}
+
+/// Expands the `impl` blocks marked `#[phink::invariant]`, from the
+/// `phink-macros` crate, into the boilerplate of the invariants: the block
+/// becomes an `#[ink(impl)]` behind the `phink` feature, and each of its
+/// methods a public `#[ink(message)]` prefixed with `phink_`
+#[derive(Default)]
+pub struct InvariantsExpander;
+
+impl InvariantsExpander {
+    fn is_invariant(attr: &Attribute) -> bool {
+        phink_attribute(attr).is_some_and(|meta| {
+            matches!(&meta, Meta::Path(path) if path.segments[1].ident == "invariant")
+        })
+    }
+
+    /// Whether one of `attrs` is `#[ink(<arg>)]`, possibly among other
+    /// arguments
+    fn has_ink(attrs: &[Attribute], arg: &str) -> bool {
+        attrs.iter().any(|attr| {
+            attr.path().is_ident("ink")
+                && attr.to_token_stream().to_string().contains(arg)
+        })
+    }
+}
+
+impl VisitMut for InvariantsExpander {
+    fn visit_item_impl_mut(&mut self, block: &mut ItemImpl) {
+        visit_mut::visit_item_impl_mut(self, block);
+
+        let attrs = block.attrs.len();
+        block.attrs.retain(|attr| !Self::is_invariant(attr));
+        if block.attrs.len() == attrs {
+            return;
+        }
+        block.attrs.push(parse_quote!(#[cfg(feature = "phink")]));
+        if !Self::has_ink(&block.attrs, "impl") {
+            block.attrs.push(parse_quote!(#[ink(impl)]));
+        }
+
+        for item in &mut block.items {
+            let ImplItem::Fn(function) = item else {
+                continue;
+            };
+            if !Self::has_ink(&function.attrs, "message") {
+                function.attrs.push(parse_quote!(#[ink(message)]));
+            }
+            function.attrs.push(parse_quote!(#[cfg(feature = "phink")]));
+            function.vis = parse_quote!(pub);
+            let name = function.sig.ident.to_string();
+            if !name.starts_with(DEFAULT_PHINK_PREFIX) {
+                function.sig.ident = Ident::new(
+                    &format!("{}{}", DEFAULT_PHINK_PREFIX, name),
+                    function.sig.ident.span(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invariants_are_injected_into_the_contract() {
+        let lib = "#[ink::contract]\nmod dns {\n    #[ink(storage)]\n    pub struct Dns {}\n}\n";
+        let invariants =
+            "impl Dns {\n    #[ink(message)]\n    pub fn phink_assert_ok(&self) {}\n}\n";
+
+        let injected = ExternalInvariants::with_invariants(lib, invariants).unwrap();
+        let ast = parse_file(&injected).unwrap();
+        let Some(Item::Mod(module)) = ast.items.last() else {
+            panic!("the contract module is gone")
+        };
+        assert_eq!(module.content.as_ref().unwrap().1.len(), 2);
+        assert!(injected.starts_with("#[ink::contract]\nmod dns {\n    #[ink(storage)]"));
+
+        assert!(ExternalInvariants::with_invariants("fn main() {}", invariants).is_err());
+    }
+
+    #[test]
+    fn test_invariant_blocks_get_their_boilerplate() {
+        let mut ast = parse_file(
+            "#[cfg_attr(feature = \"phink\", phink::invariant)]\nimpl Dns {\n    fn supply_is_capped(&self) {}\n    #[ink(message)]\n    pub fn phink_owner_is_set(&self) {}\n}\n",
+        )
+        .unwrap();
+        InvariantsExpander.visit_file_mut(&mut ast);
+
+        let expanded = ast.to_token_stream().to_string();
+        assert!(!expanded.contains("phink :: invariant"));
+        assert!(
+            expanded.contains("# [cfg (feature = \"phink\")] # [ink (impl)] impl Dns")
+        );
+        assert!(expanded.contains("# [ink (message)] # [cfg (feature = \"phink\")] pub fn phink_supply_is_capped"));
+        assert_eq!(expanded.matches("# [ink (message)]").count(), 2);
+    }
+}
@@ -1,2 +1,7 @@
 pub mod cleaner;
+pub mod compatibility;
+pub mod conditions;
+pub mod constants;
 pub mod instrumentation;
+pub mod invariants;
+pub mod mutation;
@@ -0,0 +1,209 @@
+use crate::contract::payload::DEFAULT_PHINK_PREFIX;
+use quote::{
+    quote,
+    ToTokens,
+};
+use syn::{
+    parse_file,
+    spanned::Spanned,
+    visit_mut::{
+        self,
+        VisitMut,
+    },
+    BinOp,
+    ExprBinary,
+    ExprLit,
+    ImplItemFn,
+    ItemFn,
+    ItemMod,
+    Lit,
+    LitBool,
+    LitInt,
+    Type,
+};
+
+/// One small semantic change of the contract, e.g. `a < b` into `a <= b`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mutation {
+    /// Line of the original code
+    pub line: usize,
+    pub description: String,
+}
+
+/// Injects mutations into the sources of a contract, one at a time, to check
+/// whether the invariants catch them. The invariants themselves and the tests
+/// are left untouched, as are the macros, e.g. `assert!`, which syn doesn't
+/// parse.
+pub struct Mutator;
+
+impl Mutator {
+    /// Number of mutations `code` allows
+    pub fn count(code: &str) -> usize {
+        let Ok(mut ast) = parse_file(code) else {
+            return 0;
+        };
+        let mut visitor = MutationVisitor::new(None);
+        visitor.visit_file_mut(&mut ast);
+        visitor.seen
+    }
+
+    /// `code` with its `index`-th mutation applied
+    pub fn apply(code: &str, index: usize) -> Option<(String, Mutation)> {
+        let mut ast = parse_file(code).ok()?;
+        let mut visitor = MutationVisitor::new(Some(index));
+        visitor.visit_file_mut(&mut ast);
+        let mutation = visitor.applied?;
+        Some((quote!(#ast).to_string(), mutation))
+    }
+}
+
+/// Whether the item is only compiled for the tests
+pub(crate) fn is_cfg_test(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg") && attr.to_token_stream().to_string().contains("test")
+    })
+}
+
+struct MutationVisitor {
+    target: Option<usize>,
+    seen: usize,
+    applied: Option<Mutation>,
+}
+
+impl MutationVisitor {
+    fn new(target: Option<usize>) -> Self {
+        Self {
+            target,
+            seen: 0,
+            applied: None,
+        }
+    }
+
+    /// Count one more mutation site, and tell whether it's the one to apply
+    fn is_target(&mut self) -> bool {
+        let hit = self.target == Some(self.seen);
+        self.seen += 1;
+        hit
+    }
+
+    fn record(&mut self, line: usize, before: String, after: String) {
+        self.applied = Some(Mutation {
+            line,
+            description: format!("`{}` → `{}`", before, after),
+        });
+    }
+
+    fn flipped(op: &BinOp) -> Option<BinOp> {
+        let span = op.span();
+        Some(match op {
+            BinOp::Add(_) => BinOp::Sub(syn::Token![-](span)),
+            BinOp::Sub(_) => BinOp::Add(syn::Token![+](span)),
+            BinOp::Mul(_) => BinOp::Div(syn::Token![/](span)),
+            BinOp::Lt(_) => BinOp::Le(syn::Token![<=](span)),
+            BinOp::Le(_) => BinOp::Lt(syn::Token![<](span)),
+            BinOp::Gt(_) => BinOp::Ge(syn::Token![>=](span)),
+            BinOp::Ge(_) => BinOp::Gt(syn::Token![>](span)),
+            BinOp::Eq(_) => BinOp::Ne(syn::Token![!=](span)),
+            BinOp::Ne(_) => BinOp::Eq(syn::Token![==](span)),
+            BinOp::And(_) => BinOp::Or(syn::Token![||](span)),
+            BinOp::Or(_) => BinOp::And(syn::Token![&&](span)),
+            BinOp::AddAssign(_) => BinOp::SubAssign(syn::Token![-=](span)),
+            BinOp::SubAssign(_) => BinOp::AddAssign(syn::Token![+=](span)),
+            _ => return None,
+        })
+    }
+}
+
+impl VisitMut for MutationVisitor {
+    fn visit_item_mod_mut(&mut self, module: &mut ItemMod) {
+        if !is_cfg_test(&module.attrs) {
+            visit_mut::visit_item_mod_mut(self, module);
+        }
+    }
+
+    fn visit_item_fn_mut(&mut self, function: &mut ItemFn) {
+        if !function
+            .sig
+            .ident
+            .to_string()
+            .starts_with(DEFAULT_PHINK_PREFIX)
+            && !is_cfg_test(&function.attrs)
+        {
+            visit_mut::visit_item_fn_mut(self, function);
+        }
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, function: &mut ImplItemFn) {
+        if !function
+            .sig
+            .ident
+            .to_string()
+            .starts_with(DEFAULT_PHINK_PREFIX)
+            && !is_cfg_test(&function.attrs)
+        {
+            visit_mut::visit_impl_item_fn_mut(self, function);
+        }
+    }
+
+    fn visit_expr_binary_mut(&mut self, expr: &mut ExprBinary) {
+        if let Some(flipped) = Self::flipped(&expr.op) {
+            if self.is_target() {
+                let line = expr.op.span().start().line;
+                let before = expr.to_token_stream().to_string();
+                expr.op = flipped;
+                self.record(line, before, expr.to_token_stream().to_string());
+            }
+        }
+        visit_mut::visit_expr_binary_mut(self, expr);
+    }
+
+    fn visit_expr_lit_mut(&mut self, expr: &mut ExprLit) {
+        let mutated = match &expr.lit {
+            Lit::Int(int) => {
+                let Ok(value) = int.base10_parse::<u128>() else {
+                    return;
+                };
+                Lit::Int(LitInt::new(
+                    &format!("{}{}", value.wrapping_add(1), int.suffix()),
+                    int.span(),
+                ))
+            }
+            Lit::Bool(boolean) => {
+                Lit::Bool(LitBool {
+                    value: !boolean.value,
+                    span: boolean.span,
+                })
+            }
+            _ => return,
+        };
+        if self.is_target() {
+            let line = expr.span().start().line;
+            let before = expr.to_token_stream().to_string();
+            expr.lit = mutated;
+            self.record(line, before, expr.to_token_stream().to_string());
+        }
+    }
+
+    /// Lengths of arrays, e.g. `[u8; 32]`, would rather break the build
+    fn visit_type_mut(&mut self, _: &mut Type) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mutations_skip_invariants_and_tests() {
+        let code = "fn transfer(a: u32, b: u32) -> bool {\n    a + 1 < b\n}\n\
+            fn phink_assert_x() { let _ = 1 == 2; }\n\
+            #[cfg(test)]\nmod tests { fn t() { let _ = 3 > 4; } }\n";
+
+        // `+`, `1` and `<` of `transfer`
+        assert_eq!(Mutator::count(code), 3);
+        let (mutated, mutation) = Mutator::apply(code, 2).unwrap();
+        assert_eq!(mutation.line, 2);
+        assert_eq!(mutation.description, "`a + 1 < b` → `a + 1 <= b`");
+        assert!(mutated.contains("a + 1 <= b"));
+        assert!(Mutator::apply(code, 3).is_none());
+    }
+}
@@ -0,0 +1,138 @@
+#![recursion_limit = "1024"]
+
+//! 🐙 Phink, a property-based and coverage-guided fuzzer for ink! smart
+//! contracts, as a library.
+//!
+//! The `phink` binary is a thin CLI on top of these modules. Other tools can
+//! embed Phink through `Campaign`, e.g. to instrument a contract and replay a
+//! few seeds from a test, or to drive a campaign from a custom CI wrapper:
+//!
+//! ```no_run
+//! use phink::{
+//!     Campaign,
+//!     Configuration,
+//! };
+//!
+//! let config = Configuration {
+//!     halt_on_bug: Some(false),
+//!     ..Default::default()
+//! };
+//! let campaign = Campaign::instrument(config, "sample/dns".into()).unwrap();
+//! campaign
+//!     .execute(vec![0, 0, 0, 0, 0x63, 0x3a, 0xa5, 0x51])
+//!     .unwrap();
+//! println!("{:?}", campaign.summary("execute").findings);
+//! ```
+//!
+//! `Campaign::fuzz` and `Campaign::run` go through `cargo ziggy`, which builds
+//! the harness from the sources of Phink: they must be called from its
+//! checkout, like the binary.
+
+pub mod cli;
+pub mod contract;
+pub mod cover;
+pub mod fuzzer;
+pub mod instrumenter;
+
+pub use crate::{
+    cli::{
+        config::Configuration,
+        output::Summary,
+        ziggy::ZiggyConfig,
+    },
+    contract::remote::ContractBridge,
+    fuzzer::{
+        fuzz::{
+            Fuzzer,
+            FuzzingMode,
+        },
+        temporal::{
+            TraceEntry,
+            TraceProperty,
+        },
+    },
+    instrumenter::instrumentation::{
+        ContractBuilder,
+        ContractInstrumenter,
+        InkFilesPath,
+        Instrumenter,
+    },
+};
+use std::{
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+
+/// A campaign against one instrumented contract
+pub struct Campaign {
+    ziggy: ZiggyConfig,
+    trace_properties: Vec<Arc<dyn TraceProperty>>,
+}
+
+impl Campaign {
+    /// Campaign against a contract already instrumented and built, e.g. the
+    /// `/tmp/ink_fuzzed_*` directory of a previous `instrument`
+    pub fn new(config: Configuration, contract_path: PathBuf) -> Self {
+        Self {
+            ziggy: ZiggyConfig::new(config, contract_path),
+            trace_properties: Vec::new(),
+        }
+    }
+
+    /// Also check `property` over the messages of each input executed by
+    /// `execute`. AFL++ runs the harness built from the sources of Phink,
+    /// which only knows the `sequence_properties` of the configuration.
+    pub fn with_trace_property(mut self, property: impl TraceProperty + 'static) -> Self {
+        self.trace_properties.push(Arc::new(property));
+        self
+    }
+
+    /// Instrument and build the contract of `contract_dir` in a fork, and
+    /// start a campaign against it. The original contract isn't modified.
+    pub fn instrument(
+        config: Configuration,
+        contract_dir: PathBuf,
+    ) -> Result<Self, String> {
+        let mut instrumenter = Instrumenter::new(contract_dir);
+        instrumenter.instrument()?.build()?;
+        Ok(Self::new(config, instrumenter.contract_dir))
+    }
+
+    pub fn contract_path(&self) -> &Path {
+        &self.ziggy.contract_path
+    }
+
+    pub fn config(&self) -> &Configuration {
+        &self.ziggy.config
+    }
+
+    /// Fuzz with AFL++, until it's interrupted or `max_executions` is reached
+    pub fn fuzz(&self) -> io::Result<()> {
+        self.ziggy.ziggy_fuzz()
+    }
+
+    /// Execute every seed of the corpus
+    pub fn run(&self) -> io::Result<()> {
+        self.ziggy.ziggy_run()
+    }
+
+    /// Execute one input in this process. Unless `halt_on_bug` is `false`, a
+    /// bug panics, as it does within AFL++.
+    pub fn execute(&self, input: Vec<u8>) -> io::Result<()> {
+        Fuzzer::execute_harness_with(
+            FuzzingMode::ExecuteOneInput(input),
+            self.ziggy.clone(),
+            self.trace_properties.clone(),
+        )
+    }
+
+    /// Coverage and findings of the campaign so far, as printed by
+    /// `--output json`
+    pub fn summary(&self, command: &'static str) -> Summary {
+        Summary::campaign(command, &self.ziggy)
+    }
+}
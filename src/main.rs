@@ -1,26 +1,92 @@
-#![recursion_limit = "1024"]
-
 extern crate core;
 
 use std::{
     env::var,
-    path::PathBuf,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
 };
 
-use clap::Parser;
+use clap::{
+    CommandFactory,
+    Parser,
+};
+use clap_complete::{
+    engine::ArgValueCompleter,
+    CompleteEnv,
+    Shell,
+};
 
-use crate::{
+use phink::{
     cli::{
-        config::Configuration,
+        bisect::Bisector,
+        campaign::CampaignStats,
+        completion::{
+            complete_message,
+            write_registration,
+        },
+        config::{
+            Configuration,
+            START_FUZZING_ENV,
+        },
+        dashboard::Dashboard,
+        docker::{
+            DockerAction,
+            DockerRunner,
+        },
+        doctor::Doctor,
+        echidna::EchidnaConfig,
+        github::GithubActions,
+        init::ProjectInit,
+        logging::{
+            self,
+            Verbosity,
+        },
+        mutate::MutationTester,
+        output::{
+            replay_summary,
+            OutputMode,
+            Summary,
+            EXIT_SETUP_ERROR,
+        },
+        triage::Triage,
+        watch::Watcher,
         ziggy::ZiggyConfig,
     },
-    cover::report::CoverageTracker,
-    fuzzer::fuzz::{
-        Fuzzer,
-        FuzzingMode::{
-            ExecuteOneInput,
-            Fuzz,
+    contract::deployed::DeployedCode,
+    cover::report::{
+        CoverageFormat,
+        CoverageTracker,
+    },
+    fuzzer::{
+        archive::CorpusArchive,
+        benchmark::Benchmark,
+        bug::MessageStats,
+        finding::FindingsDb,
+        fuzz::{
+            Fuzzer,
+            FuzzingMode::{
+                ExecuteOneInput,
+                Fuzz,
+                Trace,
+            },
+            CORPUS_DIR,
+        },
+        recycle::CorpusRecycler,
+        report::{
+            FindingsReport,
+            ReportFormat,
         },
+        reproducer::Reproducer,
+        seed::{
+            read_input,
+            SeedGenerator,
+            SeedMessage,
+        },
+        stats::StatsTracker,
     },
     instrumenter::{
         cleaner::Cleaner,
@@ -32,12 +98,6 @@ use crate::{
     },
 };
 
-mod cli;
-mod contract;
-mod cover;
-mod fuzzer;
-mod instrumenter;
-
 /// This struct defines the command line arguments expected by Phink.
 #[derive(Parser, Debug)]
 #[clap(
@@ -54,31 +114,348 @@ struct Cli {
     /// Path to the Phink configuration file.
     #[clap(long, short, value_parser, default_value = "phink.toml")]
     config: PathBuf,
+
+    /// `[profile.<name>]` of the configuration file to apply, e.g. `ci`
+    #[clap(long, global = true)]
+    profile: Option<String>,
+
+    /// Print every step, e.g. each copied file. `RUST_LOG` takes precedence
+    #[clap(long, short, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print the warnings, the errors and the results
+    #[clap(long, short, global = true)]
+    quiet: bool,
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
+    /// Set up a contract for Phink: a commented `phink.toml`, the `phink`
+    /// feature, and a template of invariants
+    Init {
+        /// Root directory of the contract, the current one by default
+        #[clap(default_value = ".")]
+        contract_path: PathBuf,
+    },
+    /// Print the completion script of Phink for `shell`
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+    /// Check the prerequisites, e.g. cargo-contract, AFL++ and the system
+    /// settings it expects, and print how to fix them
+    Doctor,
     /// Starts the fuzzing process. Instrumentation required before!
-    Fuzz(Contract),
+    Fuzz {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        #[clap(required_unless_present = "code_hash")]
+        contract_path: Option<PathBuf>,
+        /// Fuzz the code uploaded under this hash on `--rpc` instead, without
+        /// any coverage since it isn't instrumented
+        #[clap(long, requires_all = ["rpc", "metadata"], conflicts_with = "contract_path")]
+        code_hash: Option<String>,
+        /// RPC endpoint of the chain to download `--code-hash` from
+        #[clap(long, requires = "code_hash")]
+        rpc: Option<String>,
+        /// Path or URL of the metadata of `--code-hash`, where `{code_hash}`
+        /// is replaced, e.g. `https://registry.example.com/{code_hash}.json`
+        #[clap(long, requires = "code_hash")]
+        metadata: Option<String>,
+        /// Save the coverage while fuzzing (slower), overriding
+        /// `save_coverage_during_fuzzing`
+        #[clap(long)]
+        save_coverage: bool,
+        /// Write the coverage to the disk every N inputs, overriding
+        /// `coverage_flush_every`
+        #[clap(long)]
+        flush_every: Option<usize>,
+        /// Echidna `config.yaml` whose test limit, sequence length, senders,
+        /// workers and function filters override the configuration
+        #[clap(long)]
+        from_echidna: Option<PathBuf>,
+        /// Carry on with the previous campaign: AFL++ resumes its queue, and
+        /// each worker restores its last checkpoint
+        #[clap(long)]
+        resume: bool,
+        /// `json` prints the findings and the coverage once done
+        #[clap(long, value_enum, default_value_t = OutputMode::Text)]
+        output: OutputMode,
+        /// Exit with `1` when this campaign hit some finding. `false` exits
+        /// with `0` anyway, only a setup error exits with `2`
+        #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+        error_on_finding: bool,
+    },
+    /// Instrument, build and fuzz the contract again every time one of its
+    /// sources changes, keeping the corpus that is still valid
+    Watch {
+        /// Path of the original contract, not the instrumented one
+        contract_path: PathBuf,
+        /// Seconds each fuzzing campaign lasts, unless the contract changes
+        #[clap(long, default_value_t = 300)]
+        duration_secs: u64,
+    },
+    /// Inject small mutations into the contract, e.g. `<` into `<=`, and
+    /// report the ones that neither the invariants nor the corpus catch
+    Mutate {
+        /// Path of the original contract, not the instrumented one
+        contract_path: PathBuf,
+        /// Corpus to replay against each mutant, the one of `output_dir` by
+        /// default
+        #[clap(long)]
+        corpus: Option<PathBuf>,
+        /// Stop after this many mutants, each one being built
+        #[clap(long)]
+        max_mutants: Option<usize>,
+    },
+    /// Run `fuzz`, `run` or `coverage` inside a container shipping AFL++ and
+    /// `cargo-contract`, e.g. on macOS
+    Docker {
+        #[clap(value_enum)]
+        action: DockerAction,
+        /// Path where the instrumented contract is located
+        contract_path: PathBuf,
+        /// Image to use, pulled if missing. By default, one is built locally
+        #[clap(long)]
+        image: Option<String>,
+    },
+    /// Live view of a running campaign: workers, execs/s, corpus, coverage,
+    /// findings and calls of each message. Start it next to `fuzz`
+    Dashboard(Contract),
+    /// Summarize a past or running campaign: execs, coverage, findings,
+    /// corpus composition and reproducers
+    Stats {
+        /// `output_dir` of the campaign, the configured one by default
+        output_dir: Option<PathBuf>,
+        /// Instrumented contract, to compute the coverage and name the
+        /// messages
+        #[clap(long)]
+        contract_path: Option<PathBuf>,
+    },
     /// Instrument the ink! contract, and compile it with Phink features
     Instrument(Contract),
     /// Run all the seeds
-    Run(Contract),
+    Run {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// Write the coverage to the disk every N inputs, overriding
+        /// `coverage_flush_every`
+        #[clap(long)]
+        flush_every: Option<usize>,
+        /// `json` prints the findings and the coverage once done
+        #[clap(long, value_enum, default_value_t = OutputMode::Text)]
+        output: OutputMode,
+        /// Exit with `1` when this campaign hit some finding. `false` exits
+        /// with `0` anyway, only a setup error exits with `2`
+        #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+        error_on_finding: bool,
+        /// Print one line per finding and a summary once done, instead of
+        /// the details of each bug, and don't stop at the first one
+        #[clap(long)]
+        terse: bool,
+    },
     /// Remove all the temporary files under /tmp/ink_fuzzed_*
     Clean,
     /// Generate a coverage report, only of the harness. You won't have your
     /// contract coverage here (mainly for debugging purposes only)
     HarnessCover(Contract),
     /// Generate a coverage report for your smart-contract
-    Coverage(Contract),
+    Coverage {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// `html` for a browsable report, `lcov` for an `lcov.info` export
+        #[clap(long, value_enum, default_value_t = CoverageFormat::Html)]
+        format: CoverageFormat,
+        /// `json` prints the coverage once the report is generated
+        #[clap(long, value_enum, default_value_t = OutputMode::Text)]
+        output: OutputMode,
+    },
     /// Execute one seed
     Execute {
-        /// Seed to be run
+        /// Seed to be run, `-` to read it from stdin
+        seed: String,
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// `seed` is the input itself, hex-encoded, e.g. pasted from a report
+        #[clap(long)]
+        hex: bool,
+        /// Only print the decoded messages, without executing them
+        #[clap(long)]
+        decode_only: bool,
+        /// `json` prints whether the seed triggered a bug, and which one. The
+        /// execution then doesn't halt on the bug.
+        #[clap(long, value_enum, default_value_t = OutputMode::Text)]
+        output: OutputMode,
+    },
+    /// Aggregate the coverage and every finding into a Markdown or HTML report
+    Report {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// Format of the report
+        #[clap(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+        /// Where to write the report. Defaults to `<output_dir>/report.<ext>`
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the messages of a seed, e.g. a file of the corpus or a crash of
+    /// AFL++, with their typed arguments, origin and value, without
+    /// executing anything
+    Decode {
+        /// Seed to be decoded, `-` to read it from stdin
+        seed: String,
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// `seed` is the input itself, hex-encoded, e.g. pasted from a report
+        #[clap(long)]
+        hex: bool,
+    },
+    /// Replay a seed and print what each of its messages did: the decoded
+    /// call, its caller and value, the events emitted, the storage keys
+    /// written, the gas used and the coverage points newly hit
+    Trace {
+        /// Seed to be traced, `-` to read it from stdin
+        seed: String,
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// `seed` is the input itself, hex-encoded, e.g. pasted from a report
+        #[clap(long)]
+        hex: bool,
+    },
+    /// Generate an `#[ink::test]` reproducing a seed, e.g. a crashing one
+    Reproduce {
+        /// Seed to be reproduced
         seed: PathBuf,
         /// Path where the contract is located. It must be the root directory
         /// of the contract
         contract_path: PathBuf,
     },
+    /// Encode a message into a seed of the corpus, to bootstrap the fuzzer
+    /// with known interesting states, e.g. `generate-seed . --message
+    /// transfer --args 5Gr... 80`
+    GenerateSeed {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// Label of the message to call
+        #[clap(long, add = ArgValueCompleter::new(complete_message))]
+        message: String,
+        /// Arguments of the message, as `cargo contract call` takes them
+        #[clap(long, num_args = 0.., allow_hyphen_values = true)]
+        args: Vec<String>,
+        /// Value transferred with the message
+        #[clap(long, default_value_t = 0)]
+        value: u32,
+        /// Origin of the message, only if `fuzz_origin` is enabled
+        #[clap(long, default_value_t = 1)]
+        origin: u8,
+        /// Existing seed to append the message to, instead of writing a new
+        /// seed to `<output_dir>/corpus`
+        #[clap(long)]
+        append: Option<PathBuf>,
+    },
+    /// Encode the calls listed in a YAML file, by message name and with
+    /// human-readable arguments, into one seed, e.g. a known exploit kept as
+    /// a regression seed
+    EncodeSeed {
+        /// YAML list of calls, each with a `message`, and optionally `args`,
+        /// `value` and `origin`
+        calls: PathBuf,
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// Where to write the seed, instead of a new seed of
+        /// `<output_dir>/corpus`
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import the corpus of another contract sharing the same interface, e.g.
+    /// another PSP22 token, remapping the selectors by message name
+    ImportCorpus {
+        /// Corpus to import, e.g. `output/token_a/corpus`
+        corpus: PathBuf,
+        /// Contract of this campaign
+        contract_path: PathBuf,
+        /// Contract the corpus was generated for. Both must be built
+        #[clap(long)]
+        from: PathBuf,
+    },
+    /// Move a campaign between machines: its corpus, dictionaries, coverage
+    /// and configuration, bundled into a `.tar.zst`
+    Corpus {
+        #[clap(subcommand)]
+        action: CorpusAction,
+    },
+    /// Replay every crash of AFL++, classify each one (the finding it raises,
+    /// a decode failure, or not reproducible), and summarize them, deduplicated,
+    /// in `<output_dir>/triage/summary.txt`
+    Triage {
+        /// Crash directory of AFL++, e.g.
+        /// `output/phink/afl/mainaflfuzzer/crashes`
+        crash_dir: PathBuf,
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+    },
+    /// Find the first commit of the contract history where a seed raises its
+    /// finding, instrumenting, building and replaying the seed on each tested
+    /// commit
+    Bisect {
+        /// Seed raising the finding, e.g. a crash of AFL++
+        #[clap(long)]
+        seed: PathBuf,
+        /// Git repository of the contract
+        #[clap(long)]
+        repo: PathBuf,
+        /// Root directory of the contract, relative to `repo`
+        #[clap(long, default_value = ".")]
+        contract_dir: PathBuf,
+        /// Last commit known not to raise the finding. Without it, the whole
+        /// history leading to `bad` is searched
+        #[clap(long)]
+        good: Option<String>,
+        /// Commit raising the finding
+        #[clap(long, default_value = "HEAD")]
+        bad: String,
+    },
+    /// Measure the calls per second the harness can execute on a contract
+    Benchmark {
+        /// Path where the contract is located. It must be the root directory
+        /// of the contract
+        contract_path: PathBuf,
+        /// How many times each message is called
+        #[clap(long, default_value_t = 100)]
+        iterations: u32,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CorpusAction {
+    /// Bundle the campaign of `output_dir` into `archive`
+    Export {
+        /// Archive to write, e.g. `campaign.tar.zst`
+        archive: PathBuf,
+        /// Instrumented contract the campaign fuzzes
+        contract_path: PathBuf,
+    },
+    /// Restore `archive` into `output_dir`, adding its inputs to the corpus
+    Import {
+        /// Archive written by `corpus export`
+        archive: PathBuf,
+        /// Instrumented contract, built from the same sources as the exported
+        /// one
+        contract_path: PathBuf,
+        /// Import even if the archive was made for another build of the
+        /// contract
+        #[clap(long)]
+        force: bool,
+    },
 }
 
 #[derive(clap::Args, Debug)]
@@ -91,7 +468,8 @@ struct Contract {
 
 fn main() {
     // We execute `handle_cli()` first, then re-enter into `main()`
-    if let Ok(config_str) = var("PHINK_START_FUZZING_WITH_CONFIG") {
+    if let Ok(config_str) = var(START_FUZZING_ENV) {
+        logging::init_harness();
         Fuzzer::execute_harness(Fuzz, ZiggyConfig::parse(config_str)).unwrap();
     } else {
         handle_cli();
@@ -99,8 +477,34 @@ fn main() {
 }
 
 fn handle_cli() {
+    // Called back by the completion script of `completions`
+    CompleteEnv::with_factory(Cli::command).complete();
+
     let cli = Cli::parse();
-    let config = Configuration::load_config(&cli.config);
+    logging::init(Verbosity::new(cli.verbose, cli.quiet));
+
+    // There is no configuration to load yet
+    match &cli.command {
+        Commands::Init { contract_path } => {
+            ProjectInit::new(contract_path.clone()).scaffold().unwrap();
+            return;
+        }
+        Commands::Doctor => {
+            if !Doctor::diagnose() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Commands::Completions { shell } => {
+            write_registration(*shell, &Cli::command(), &mut std::io::stdout())
+                .unwrap_or_else(setup_error);
+            return;
+        }
+        _ => {}
+    }
+
+    let config = Configuration::load_config(&cli.config, cli.profile.as_deref())
+        .unwrap_or_else(setup_error);
 
     match cli.command {
         Commands::Instrument(contract_path) => {
@@ -112,36 +516,389 @@ fn handle_cli() {
                 contract_path.contract_path.display()
             );
         }
-        Commands::Fuzz(contract_path) => {
-            ZiggyConfig::new(config, contract_path.contract_path)
-                .ziggy_fuzz()
-                .unwrap();
+        Commands::Fuzz {
+            contract_path,
+            code_hash,
+            rpc,
+            metadata,
+            save_coverage,
+            flush_every,
+            from_echidna,
+            resume,
+            output,
+            error_on_finding,
+        } => {
+            let mut config = config;
+            if let Some(echidna) = from_echidna {
+                EchidnaConfig::load(&echidna)
+                    .unwrap_or_else(setup_error)
+                    .apply(&mut config);
+            }
+            if save_coverage {
+                config.save_coverage_during_fuzzing = Some(true);
+            }
+            if flush_every.is_some() {
+                config.coverage_flush_every = flush_every;
+            }
+            if resume {
+                config.resume = Some(true);
+            }
+            let contract_path = match code_hash {
+                Some(code_hash) => {
+                    DeployedCode::new(rpc.unwrap(), code_hash, metadata)
+                        .download(&config.output_dir())
+                        .unwrap_or_else(setup_error)
+                }
+                None => contract_path.unwrap(),
+            };
+            let ziggy = ZiggyConfig::new(config, contract_path);
+            let before = FindingsDb::load(&ziggy.config.output_dir());
+            ziggy.ziggy_fuzz().unwrap_or_else(setup_error);
+            // Each AFL++ process only counted its own executions
+            if output == OutputMode::Text {
+                MessageStats::display_err_report(
+                    ziggy.config.err_ratio_threshold,
+                    &StatsTracker::campaign_results(&ziggy.config.output_dir()),
+                );
+            }
+            let summary = Summary::since("fuzz", &before, &ziggy);
+            let code = summary.exit_code(error_on_finding);
+            summarize(summary, output, &ziggy);
+            std::process::exit(code);
         }
-        Commands::Run(contract_path) => {
-            ZiggyConfig::new(config, contract_path.contract_path)
-                .ziggy_run()
-                .unwrap();
+        Commands::Run {
+            contract_path,
+            flush_every,
+            output,
+            error_on_finding,
+            terse,
+        } => {
+            let mut config = config;
+            if flush_every.is_some() {
+                config.coverage_flush_every = flush_every;
+            }
+            if terse {
+                config.terse_findings = Some(true);
+                config.halt_on_bug = Some(false);
+            }
+            let ziggy = ZiggyConfig::new(config, contract_path);
+            let before = FindingsDb::load(&ziggy.config.output_dir());
+            ziggy.ziggy_run().unwrap_or_else(setup_error);
+            if terse {
+                println!(
+                    "{}",
+                    replay_summary(
+                        &before,
+                        &FindingsDb::load(&ziggy.config.output_dir())
+                    )
+                );
+            }
+            let summary = Summary::since("run", &before, &ziggy);
+            let code = summary.exit_code(error_on_finding);
+            summarize(summary, output, &ziggy);
+            std::process::exit(code);
         }
         Commands::Execute {
             seed,
             contract_path,
+            hex,
+            decode_only,
+            output,
         } => {
+            let input = read_input(&seed, hex).unwrap();
+            let mut config = config;
+            if decode_only {
+                let reproducer =
+                    Reproducer::new(ZiggyConfig::new(config, contract_path)).unwrap();
+                println!("{}", reproducer.describe(&input).unwrap());
+                return;
+            }
+            if output == OutputMode::Json || GithubActions::enabled() {
+                // Otherwise the process would abort before printing anything
+                config.halt_on_bug = Some(false);
+            }
             let ziggy: ZiggyConfig = ZiggyConfig::new(config, contract_path);
-            Fuzzer::execute_harness(ExecuteOneInput(seed), ziggy).unwrap();
+            let before = FindingsDb::load(&ziggy.config.output_dir());
+            Fuzzer::execute_harness(ExecuteOneInput(input), ziggy.clone()).unwrap();
+            let seed = (!hex && seed != "-").then(|| PathBuf::from(seed));
+            summarize(Summary::seed(seed, &before, &ziggy), output, &ziggy);
+        }
+        Commands::Decode {
+            seed,
+            contract_path,
+            hex,
+        } => {
+            let input = read_input(&seed, hex).unwrap();
+            let reproducer =
+                Reproducer::new(ZiggyConfig::new(config, contract_path)).unwrap();
+            println!("{}", reproducer.describe(&input).unwrap());
+        }
+        Commands::Trace {
+            seed,
+            contract_path,
+            hex,
+        } => {
+            let input = read_input(&seed, hex).unwrap();
+            Fuzzer::execute_harness(
+                Trace(input),
+                ZiggyConfig::new(config, contract_path),
+            )
+            .unwrap();
+        }
+        Commands::Reproduce {
+            seed,
+            contract_path,
+        } => {
+            let reproducer =
+                Reproducer::new(ZiggyConfig::new(config, contract_path)).unwrap();
+            println!("{}", reproducer.generate(&seed).unwrap());
+        }
+        Commands::GenerateSeed {
+            contract_path,
+            message,
+            args,
+            value,
+            origin,
+            append,
+        } => {
+            let generator =
+                SeedGenerator::new(ZiggyConfig::new(config, contract_path)).unwrap();
+            let encoded = generator
+                .encode(&SeedMessage {
+                    message,
+                    args,
+                    value,
+                    origin,
+                })
+                .unwrap();
+            let path = generator.write(&encoded, append.as_deref()).unwrap();
+            println!("🌱 Seed written to {}", path.display());
+        }
+        Commands::EncodeSeed {
+            calls,
+            contract_path,
+            output,
+        } => {
+            let description = fs::read_to_string(&calls)
+                .unwrap_or_else(|e| panic!("🙅 Can't read {}: {}", calls.display(), e));
+            let generator =
+                SeedGenerator::new(ZiggyConfig::new(config, contract_path)).unwrap();
+            let encoded = generator.encode_yaml(&description).unwrap();
+            let path = generator.write_new(&encoded, output.as_deref()).unwrap();
+            println!("🌱 Seed written to {}", path.display());
+        }
+        Commands::ImportCorpus {
+            corpus,
+            contract_path,
+            from,
+        } => {
+            let (imported, dropped) = CorpusRecycler::new(&from, &contract_path, &config)
+                .and_then(|recycler| recycler.import(&corpus, &config.output_dir()))
+                .unwrap();
+            println!(
+                "♻️ {} inputs imported into {}, {} dropped without any known message",
+                imported,
+                config.output_dir().join(CORPUS_DIR).display(),
+                dropped
+            );
+        }
+        Commands::Corpus {
+            action:
+                CorpusAction::Export {
+                    archive,
+                    contract_path,
+                },
+        } => {
+            let inputs = CorpusArchive::new(ZiggyConfig::new(config, contract_path))
+                .export(&archive)
+                .unwrap_or_else(|e| panic!("{}", e));
+            println!(
+                "📦 {} inputs of the corpus archived into {}",
+                inputs,
+                archive.display()
+            );
+        }
+        Commands::Corpus {
+            action:
+                CorpusAction::Import {
+                    archive,
+                    contract_path,
+                    force,
+                },
+        } => {
+            let output_dir = config.output_dir();
+            let inputs = CorpusArchive::new(ZiggyConfig::new(config, contract_path))
+                .import(&archive, force)
+                .unwrap_or_else(|e| panic!("{}", e));
+            println!(
+                "📦 {} inputs imported into {}",
+                inputs,
+                output_dir.join(CORPUS_DIR).display()
+            );
+        }
+        Commands::Report {
+            contract_path,
+            format,
+            output,
+        } => {
+            let path = FindingsReport::generate(
+                ZiggyConfig::new(config, contract_path),
+                format,
+                output,
+            )
+            .unwrap();
+            println!("📜 Report generated at: {}", path.display());
+        }
+        Commands::Bisect {
+            seed,
+            repo,
+            contract_dir,
+            good,
+            bad,
+        } => {
+            let culprit = Bisector::new(
+                config,
+                global_args(&cli.config, cli.profile.as_deref()),
+                seed,
+                repo,
+                contract_dir,
+                good,
+                bad,
+            )
+            .run()
+            .unwrap_or_else(|e| panic!("{}", e));
+            println!(
+                "🔪 {} is the first commit raising `{}`: {}",
+                culprit.commit, culprit.finding, culprit.subject
+            );
+        }
+        Commands::Triage {
+            crash_dir,
+            contract_path,
+        } => {
+            let summary = Triage::new(
+                ZiggyConfig::new(config, contract_path),
+                global_args(&cli.config, cli.profile.as_deref()),
+                crash_dir,
+            )
+            .run()
+            .unwrap_or_else(|e| panic!("{}", e));
+            println!("🗂️ Triage summary written to {}", summary.display());
+        }
+        Commands::Watch {
+            contract_path,
+            duration_secs,
+        } => {
+            Watcher::new(
+                ZiggyConfig::new(config, contract_path),
+                global_args(&cli.config, cli.profile.as_deref()),
+                Duration::from_secs(duration_secs),
+            )
+            .run();
+        }
+        Commands::Mutate {
+            contract_path,
+            corpus,
+            max_mutants,
+        } => {
+            MutationTester::new(
+                config,
+                contract_path,
+                global_args(&cli.config, cli.profile.as_deref()),
+                corpus,
+                max_mutants,
+            )
+            .run()
+            .unwrap();
+        }
+        Commands::Docker {
+            action,
+            contract_path,
+            image,
+        } => {
+            DockerRunner::new(image, global_args(&cli.config, cli.profile.as_deref()))
+                .run(action, &contract_path)
+                .unwrap();
+        }
+        Commands::Dashboard(contract_path) => {
+            Dashboard::new(ZiggyConfig::new(config, contract_path.contract_path))
+                .run()
+                .unwrap();
+        }
+        Commands::Stats {
+            output_dir,
+            contract_path,
+        } => {
+            let mut config = config;
+            if output_dir.is_some() {
+                config.output_dir = output_dir;
+            }
+            let ziggy = ZiggyConfig::new(config, contract_path.unwrap_or_default());
+            CampaignStats::collect(&ziggy).display();
+            MessageStats::display_err_report(
+                ziggy.config.err_ratio_threshold,
+                &StatsTracker::campaign_results(&ziggy.config.output_dir()),
+            );
         }
         Commands::HarnessCover(contract_path) => {
             ZiggyConfig::new(config, contract_path.contract_path)
                 .ziggy_cover()
                 .unwrap();
         }
-        Commands::Coverage(contract_path) => {
-            CoverageTracker::generate(ZiggyConfig::new(
-                config,
-                contract_path.contract_path,
-            ));
+        Commands::Coverage {
+            contract_path,
+            format,
+            output,
+        } => {
+            let ziggy = ZiggyConfig::new(config, contract_path);
+            CoverageTracker::generate(ziggy.clone(), format);
+            let mut summary = Summary::campaign("coverage", &ziggy);
+            summary.report_path = ziggy.config.report_path.clone();
+            summarize(summary, output, &ziggy);
+        }
+        Commands::Benchmark {
+            contract_path,
+            iterations,
+        } => {
+            let benchmark =
+                Benchmark::new(ZiggyConfig::new(config, contract_path), iterations)
+                    .unwrap();
+            Benchmark::display(&benchmark.run());
         }
         Commands::Clean => {
             Instrumenter::clean().unwrap();
         }
+        Commands::Init { .. } | Commands::Doctor | Commands::Completions { .. } => {
+            unreachable!()
+        }
+    }
+}
+
+/// Arguments handed over to the `phink` processes started by `watch`,
+/// `mutate` and `docker`, so that they load the same configuration
+fn global_args(config: &Path, profile: Option<&str>) -> Vec<String> {
+    let mut args = vec!["--config".to_string(), config.display().to_string()];
+    if let Some(profile) = profile {
+        args.extend(["--profile".to_string(), profile.to_string()]);
+    }
+    args
+}
+
+/// Abort the command with `EXIT_SETUP_ERROR`, telling apart a campaign that
+/// couldn't start from one that found bugs. Generic over what it stands for,
+/// so that it fits `unwrap_or_else`.
+fn setup_error<T>(error: impl std::fmt::Display) -> T {
+    eprintln!("{}", error);
+    std::process::exit(EXIT_SETUP_ERROR)
+}
+
+/// Print `summary` with `--output json`, and publish it on GitHub Actions
+fn summarize(summary: Summary, output: OutputMode, ziggy: &ZiggyConfig) {
+    // The annotations go first, the JSON being the last line of stdout
+    if GithubActions::enabled() {
+        GithubActions::publish(&summary, ziggy);
+    }
+    if output == OutputMode::Json {
+        summary.print();
     }
 }